@@ -3,13 +3,17 @@ use crate::render::BlockRenderer;
 use crate::world::worldhandler::WorldHandler;
 use crate::*;
 use common::block::*;
+use common::claim::Claim;
 use common::comms::*;
 use common::daynight::DayNight;
 use common::inventory::Inventory;
 use common::player::PlayerData;
+use common::world_pos::WorldCoord;
+use common::world_preset::WorldPreset;
 use egui::TextureHandle;
 use gamework::video::*;
 use nalgebra_glm::*;
+use server::generator::BiomeMap;
 use server::YabServer;
 use std::collections::HashMap;
 use std::sync::mpsc::Receiver;
@@ -27,12 +31,23 @@ pub struct GameContext {
     pub player_target_handle: ParticlePositionHandle,
     pub block_registry: BlockRegistry,
     pub block_texture_atlas: Option<TextureAtlas>,
+    pub world_id: String,
     pub seed: u32,
     pub description: String,
     pub server: Option<YabServer>,
     pub server_address: Option<String>,
     pub connect_to_address: Option<String>,
+    pub spectator: bool,
+    pub op: bool,
+    /// Key to sign in as the host of the server started by this client
+    pub host_key: Option<String>,
+    pub username: String,
+    pub claims: Vec<Claim>,
     pub world_type: Option<GeneratorType>,
+    pub world_height_chunks: Option<usize>,
+    pub world_preset: Option<WorldPreset>,
+    /// Biomes of the world, for worlds with biomes
+    pub biome_map: Option<BiomeMap>,
     pub comms_client: Option<CommsClient>,
     pub col_receiver: Option<Receiver<ServerMessage>>,
     pub config: ClientConfig,
@@ -65,11 +80,20 @@ impl GameContext {
             dig_beam_emitter_handle: 0,
             player_position_handle: 0,
             player_target_handle: 0,
+            world_id: String::new(),
             seed: 0,
             description: "".to_string(),
             server_address: None,
             connect_to_address: None,
+            spectator: false,
+            op: false,
+            host_key: None,
+            username: String::new(),
+            claims: Vec::new(),
             world_type: None,
+            world_height_chunks: None,
+            world_preset: None,
+            biome_map: None,
             server: None,
             comms_client: None,
             col_receiver: None,
@@ -132,7 +156,12 @@ impl GameContext {
         self.physics.as_mut().unwrap().step(&world);
     }
 
-    pub fn is_occopied_by_body(&mut self, wbx: i16, wby: i16, wbz: i16) -> bool {
+    pub fn is_occopied_by_body(
+        &mut self,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
+    ) -> bool {
         let world = self.world.as_mut().unwrap();
         self.physics
             .as_mut()
@@ -140,6 +169,16 @@ impl GameContext {
             .is_occopied_by_body(wbx, wby, wbz, world)
     }
 
+    /// Can the player change the block at this position according to the known land claims?
+    pub fn may_build(&self, wbx: WorldCoord, wby: WorldCoord) -> bool {
+        self.op
+            || self
+                .claims
+                .iter()
+                .filter(|c| c.contains_block(wbx, wby))
+                .all(|c| c.is_member(&self.username))
+    }
+
     pub fn dig_effect(&mut self, block_position: Vec3) {
         let emitter_def = self.dig_common_emitter.as_ref().unwrap().clone();
         let player_position_handle = self.player_position_handle;
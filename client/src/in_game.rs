@@ -1,7 +1,9 @@
 use crate::block_button::*;
 use crate::{block_select::BlockSelectState, *};
 use common::block::*;
+use common::claim::*;
 use common::inventory::Inventory;
+use common::world_pos::WorldCoord;
 use common::{chunk::*, player::PlayerData};
 use egui::plot::{Line, PlotPoints};
 use egui::Align2;
@@ -31,6 +33,13 @@ pub struct InGameState {
     block_place_timer: f32,
     block_remove_timer: f32,
     show_debug_gui: bool,
+    admin_console_open: bool,
+    admin_command: String,
+    admin_log: Vec<String>,
+    claims_window_open: bool,
+    claim_radius: WorldCoord,
+    claim_member: String,
+    claim_denied: Option<String>,
 }
 
 impl InGameState {
@@ -54,6 +63,13 @@ impl InGameState {
             block_remove_timer: 0.0,
             block_place_timer: 0.0,
             show_debug_gui: false,
+            admin_console_open: false,
+            admin_command: String::new(),
+            admin_log: Vec::new(),
+            claims_window_open: false,
+            claim_radius: 1,
+            claim_member: String::new(),
+            claim_denied: None,
         }
     }
 
@@ -71,6 +87,18 @@ impl InGameState {
         context: &mut GameContext,
         system: &mut SystemContext,
     ) -> StateCommand<GameContext> {
+        // While a window is open the keys are not used for the game
+        if self.admin_console_open || self.claims_window_open {
+            if let InputEvent::KeyPress {
+                key: Key::Escape, ..
+            } = event
+            {
+                self.admin_console_open = false;
+                self.claims_window_open = false;
+                system.input_mut().set_mouse_captured(true);
+            }
+            return StateCommand::None;
+        }
         match event {
             InputEvent::MouseMove { dx, dy, .. } => {
                 if system.input().get_mouse_captured() {
@@ -88,7 +116,7 @@ impl InGameState {
                         system.audio_mut().play_sound("jump");
                     }
                 }
-                Key::Tab => {
+                Key::Tab if !context.spectator => {
                     system.input_mut().set_mouse_captured(false);
                     return StateCommand::OpenState {
                         state: Box::new(BlockSelectState::new(
@@ -112,7 +140,8 @@ impl InGameState {
                     self.rendering_mut().toggle_render_fog();
                 }
                 Key::F => {
-                    if *shift {
+                    // Spectators always fly through the world, only operators can toggle flying
+                    if *shift && context.op && !context.spectator {
                         self.player_flying = !self.player_flying;
                         context
                             .physics_mut()
@@ -128,6 +157,15 @@ impl InGameState {
                     return StateCommand::CloseState;
                 }
                 Key::G => self.show_debug_gui = !self.show_debug_gui,
+                Key::P if !context.spectator => {
+                    self.claims_window_open = true;
+                    self.claim_denied = None;
+                    system.input_mut().set_mouse_captured(false);
+                }
+                Key::C if context.op => {
+                    self.admin_console_open = true;
+                    system.input_mut().set_mouse_captured(false);
+                }
                 _ => (),
             },
             _ => {}
@@ -141,7 +179,7 @@ impl InGameState {
         context: &mut GameContext,
         delta: f32,
     ) {
-        if !system.input().get_mouse_captured() {
+        if !system.input().get_mouse_captured() || context.spectator {
             return;
         }
         if system.input().is_mouse_button_down(MouseButton::Right) {
@@ -154,14 +192,16 @@ impl InGameState {
                     false,
                 ) {
                     let (wbx, wby, wbz) = (
-                        (hit.hit_block_pos.x + hit.hit_norm.x).floor() as i16,
-                        (hit.hit_block_pos.y + hit.hit_norm.y).floor() as i16,
-                        (hit.hit_block_pos.z + hit.hit_norm.z).floor() as i16,
+                        (hit.hit_block_pos.x + hit.hit_norm.x).floor() as WorldCoord,
+                        (hit.hit_block_pos.y + hit.hit_norm.y).floor() as WorldCoord,
+                        (hit.hit_block_pos.z + hit.hit_norm.z).floor() as WorldCoord,
                     );
                     if context.world().chunks.are_all_neighbours_stored(
                         ChunkColumnPos::from_world_block_coords(wbx, wby),
                     ) {
-                        if !context.is_occopied_by_body(wbx, wby, wbz) {
+                        if !context.is_occopied_by_body(wbx, wby, wbz)
+                            && context.may_build(wbx, wby)
+                        {
                             // Do we have sufficient resources?
                             let selected_block = context.selected_block.kind();
                             if context.inventory.count(selected_block) > 0 {
@@ -222,9 +262,9 @@ impl InGameState {
                 if self.block_remove_timer > BLOCK_REMOVE_TIME_S {
                     // See if we need to remove a block
                     let (wbx, wby, wbz) = (
-                        (hit.hit_block_pos.x).floor() as i16,
-                        (hit.hit_block_pos.y).floor() as i16,
-                        (hit.hit_block_pos.z).floor() as i16,
+                        (hit.hit_block_pos.x).floor() as WorldCoord,
+                        (hit.hit_block_pos.y).floor() as WorldCoord,
+                        (hit.hit_block_pos.z).floor() as WorldCoord,
                     );
                     let block = context.world().chunks.get_block(wbx, wby, wbz);
                     context.dig_effect(Vec3::new(
@@ -234,7 +274,8 @@ impl InGameState {
                     ));
                     if context.world().chunks.are_all_neighbours_stored(
                         ChunkColumnPos::from_world_block_coords(wbx, wby),
-                    ) {
+                    ) && context.may_build(wbx, wby)
+                    {
                         // Add block to inventory
                         context.inventory.add(block.kind(), 1);
                         // Clear the block
@@ -297,6 +338,11 @@ impl State<GameContext> for InGameState {
             position.z,
             Vec3::new(0.6, 0.6, 1.5),
         );
+        if data.spectator {
+            self.player_flying = true;
+            data.physics_mut()
+                .set_object_colliding(self.player_body, false);
+        }
 
         self.rendering = Some(Rendering::new(data, context));
         self.rendering_mut().camera.yaw = data.starting_yaw;
@@ -318,10 +364,14 @@ impl State<GameContext> for InGameState {
             .get_object_position(self.player_body)
             .clone();
 
-        // Show the selected block
+        // Show the selected block, spectators cannot build
         egui::Area::new("selected_block")
             .anchor(Align2::LEFT_BOTTOM, [5.0, -5.0])
             .show(gui, |ui| {
+                if context.spectator {
+                    ui.label("Spectating");
+                    return;
+                }
                 let block = context.block_registry.get(context.selected_block);
                 let count = context.inventory.count(context.selected_block);
                 let preview_size = egui::Vec2::new(48.0, 48.0);
@@ -332,6 +382,124 @@ impl State<GameContext> for InGameState {
                 }
             });
 
+        // Show the admin console for operators
+        if self.admin_console_open {
+            let mut send_command = false;
+            egui::Window::new("Admin console")
+                .anchor(Align2::CENTER_TOP, [0.0, 5.0])
+                .show(gui, |ui| {
+                    for line in &self.admin_log {
+                        ui.label(line);
+                    }
+                    let response = ui.text_edit_singleline(&mut self.admin_command);
+                    if response.lost_focus() && ui.input().key_pressed(egui::Key::Enter) {
+                        send_command = true;
+                    }
+                    response.request_focus();
+                    if ui.button("Send").clicked() {
+                        send_command = true;
+                    }
+                });
+            if send_command && !self.admin_command.trim().is_empty() {
+                let command = self.admin_command.trim().to_string();
+                self.admin_log.push(format!("> {}", command));
+                self.admin_command.clear();
+                context
+                    .comms_client_mut()
+                    .send(ClientMessage::AdminCommand { command })
+                    .unwrap();
+            }
+        }
+
+        // Show the land claim at the player position
+        if self.claims_window_open {
+            let col = ChunkColumnPos::from_world_block_coords(
+                player_position.x.floor() as WorldCoord,
+                player_position.y.floor() as WorldCoord,
+            );
+            let mut claim_message = None;
+            egui::Window::new("Land claims")
+                .anchor(Align2::CENTER_TOP, [0.0, 5.0])
+                .show(gui, |ui| {
+                    if let Some(reason) = &self.claim_denied {
+                        ui.label(format!("Denied: {}", reason));
+                    }
+                    if let Some(claim) = context.claims.iter().find(|c| c.contains_column(col)) {
+                        let (width, length) = claim.size();
+                        ui.label(format!(
+                            "Claimed by {} ({}x{} columns)",
+                            claim.owner, width, length
+                        ));
+                        let may_edit = context.op || claim.owner == context.username;
+                        for member in &claim.members {
+                            ui.horizontal(|ui| {
+                                ui.label(member);
+                                if may_edit && ui.button("Remove").clicked() {
+                                    claim_message = Some(ClientMessage::SetClaimMember {
+                                        claim_id: claim.id,
+                                        username: member.clone(),
+                                        member: false,
+                                    });
+                                }
+                            });
+                        }
+                        if may_edit {
+                            ui.horizontal(|ui| {
+                                ui.text_edit_singleline(&mut self.claim_member);
+                                if ui.button("Add member").clicked()
+                                    && !self.claim_member.trim().is_empty()
+                                {
+                                    claim_message = Some(ClientMessage::SetClaimMember {
+                                        claim_id: claim.id,
+                                        username: self.claim_member.trim().to_string(),
+                                        member: true,
+                                    });
+                                    self.claim_member.clear();
+                                }
+                            });
+                            if ui.button("Remove claim").clicked() {
+                                claim_message = Some(ClientMessage::Unclaim { claim_id: claim.id });
+                            }
+                        }
+                    } else {
+                        ui.label(format!("Column {},{} is not claimed", col.x, col.y));
+                        ui.add(
+                            egui::Slider::new(
+                                &mut self.claim_radius,
+                                0..=(MAX_CLAIM_SIZE_COLUMNS - 1) / 2,
+                            )
+                            .text("Radius"),
+                        );
+                        let r = self.claim_radius;
+                        let claim = Claim::new(
+                            0,
+                            &context.username,
+                            ChunkColumnPos::new(col.x - r, col.y - r),
+                            ChunkColumnPos::new(col.x + r, col.y + r),
+                        );
+                        let owned_count = context
+                            .claims
+                            .iter()
+                            .filter(|c| c.owner == context.username)
+                            .count();
+                        if context.claims.iter().any(|c| c.overlaps(&claim)) {
+                            ui.label("This area overlaps an existing claim");
+                        } else if !context.op && owned_count >= MAX_CLAIMS_PER_PLAYER {
+                            ui.label("You own the maximum number of claims");
+                        } else if ui.button("Claim area").clicked() {
+                            claim_message = Some(ClientMessage::Claim {
+                                min: claim.min,
+                                max: claim.max,
+                            });
+                        }
+                    }
+                });
+            if let Some(message) = claim_message {
+                self.claim_denied = None;
+                context.comms_client_mut().send(message).unwrap();
+            }
+        }
+
         // Show the profiler
         if self.show_debug_gui {
             egui::Area::new("profiler")
@@ -377,6 +545,17 @@ impl State<GameContext> for InGameState {
                         player_position.y as i32,
                         player_position.z as i32
                     ));
+                    if let Some(biome_map) = &context.biome_map {
+                        let biome = biome_map.biome_at(
+                            player_position.x.floor() as WorldCoord,
+                            player_position.y.floor() as WorldCoord,
+                        );
+                        let [r, g, b] = biome.tint();
+                        ui.colored_label(
+                            egui::Rgba::from_rgb(r, g, b),
+                            format!("Biome {}", biome.name()),
+                        );
+                    }
                 });
         }
 
@@ -464,9 +643,9 @@ impl State<GameContext> for InGameState {
         let cam_cp = ChunkPos::from_world_pos(camera.position);
         // let text = format!(
         //     "Position {},{},{} / Chunk {},{},{}",
-        //     camera.position.x as i16,
-        //     camera.position.y as i16,
-        //     camera.position.z as i16,
+        //     camera.position.x as WorldCoord,
+        //     camera.position.y as WorldCoord,
+        //     camera.position.z as WorldCoord,
         //     cam_cp.x,
         //     cam_cp.y,
         //     cam_cp.z
@@ -563,8 +742,43 @@ impl State<GameContext> for InGameState {
                     }
                 }
 
-                ServerMessage::SignInConfirm { .. } => {
-                    panic!("unexpected in-game server sign in confirm");
+                ServerMessage::Claims { claims } => {
+                    debug!("Received {} land claims", claims.len());
+                    context.claims = claims;
+                }
+                ServerMessage::ClaimDenied { reason } => {
+                    warn!("Claim denied: {}", reason);
+                    self.claim_denied = Some(reason);
+                }
+                ServerMessage::AdminResponse { message } => {
+                    info!("Admin response: {}", message);
+                    self.admin_log.push(message);
+                }
+                ServerMessage::OpStatus { op } => {
+                    info!("Operator status changed to {}", op);
+                    context.op = op;
+                    if !op {
+                        self.admin_console_open = false;
+                        if self.player_flying && !context.spectator {
+                            self.player_flying = false;
+                            context
+                                .physics_mut()
+                                .set_object_colliding(self.player_body, true);
+                        }
+                    }
+                }
+                ServerMessage::MovePlayer { x, y, z } => {
+                    warn!("Moved back by the server to {},{},{}", x, y, z);
+                    let object = context.physics_mut().get_object_mut(self.player_body);
+                    // Positions sent to the server are camera positions
+                    object.position = Vec3::new(x, y, z - CAMERA_Z_OFFSET);
+                    object.velocity = Vec3::zeros();
+                }
+                ServerMessage::SignInConfirm { .. } | ServerMessage::SignInDenied { .. } => {
+                    panic!("unexpected in-game server sign in response");
+                }
+                ServerMessage::ServerStatus { .. } => {
+                    warn!("unexpected in-game server status");
                 }
             }
         }
@@ -1,18 +1,53 @@
 use crate::{start_game::StartGameState, GameContext};
-use common::comms::DEFAULT_TCP_PORT;
+use common::comms::{query_server_status, DEFAULT_TCP_PORT, PROTOCOL_VERSION};
+use common::server_status::ServerStatus;
+use crossbeam::channel::*;
+use crossbeam::unbounded;
 use gamework::*;
 use log::*;
+use std::net::ToSocketAddrs;
+use std::thread;
+use std::time::Duration;
+
+const STATUS_QUERY_TIMEOUT: Duration = Duration::from_secs(3);
 
 pub struct JoinGameState {
     address: String,
+    status: Option<Result<ServerStatus, String>>,
+    status_rx: Option<Receiver<Result<ServerStatus, String>>>,
 }
 
 impl JoinGameState {
     pub fn new() -> Self {
         JoinGameState {
             address: "".to_string(),
+            status: None,
+            status_rx: None,
         }
     }
+
+    /// Query the server status in the background so the gui does not block
+    fn start_status_query(&mut self, server_address: String) {
+        let (status_tx, status_rx) = unbounded();
+        thread::Builder::new()
+            .name("status_query".to_string())
+            .spawn(move || {
+                let result = match server_address.to_socket_addrs() {
+                    Ok(mut addresses) => match addresses.next() {
+                        Some(socket_addr) => query_server_status(socket_addr, STATUS_QUERY_TIMEOUT)
+                            .map_err(|e| format!("{}", e)),
+                        None => Err("Unknown server address".to_string()),
+                    },
+                    Err(e) => Err(format!("{}", e)),
+                };
+                if let Err(e) = status_tx.send(result) {
+                    debug!("Status query result not received: {}", e);
+                }
+            })
+            .unwrap();
+        self.status = None;
+        self.status_rx = Some(status_rx);
+    }
 }
 
 impl State<GameContext> for JoinGameState {
@@ -26,6 +61,12 @@ impl State<GameContext> for JoinGameState {
         _input_events: &Vec<InputEvent>,
         system: &mut SystemContext,
     ) -> StateCommand<GameContext> {
+        if let Some(status_rx) = &self.status_rx {
+            if let Ok(result) = status_rx.try_recv() {
+                self.status = Some(result);
+                self.status_rx = None;
+            }
+        }
         let mut state_command = StateCommand::None;
         egui::SidePanel::left("Join").show(gui, |ui| {
             ui.with_layout(
@@ -35,8 +76,60 @@ impl State<GameContext> for JoinGameState {
                     ui.separator();
                     ui.add(egui::Label::new("Server address"));
                     ui.add(egui::TextEdit::singleline(&mut self.address));
+                    if ui.button("Server details").clicked() {
+                        system.audio().play_sound("click");
+                        let server_address = format!("{}:{}", self.address, DEFAULT_TCP_PORT);
+                        self.start_status_query(server_address);
+                    }
+                    if self.status_rx.is_some() {
+                        ui.label("Contacting server...");
+                    }
+                    match &self.status {
+                        Some(Ok(status)) => {
+                            egui::Grid::new("server_status")
+                                .num_columns(2)
+                                .spacing([40.0, 4.0])
+                                .show(ui, |ui| {
+                                    ui.label("World");
+                                    ui.label(&status.description);
+                                    ui.end_row();
+                                    ui.label("Type");
+                                    ui.label(&status.world_type);
+                                    ui.end_row();
+                                    ui.label("Seed");
+                                    ui.label(status.seed.to_string());
+                                    ui.end_row();
+                                    ui.label("Players");
+                                    ui.label(format!(
+                                        "{}/{}",
+                                        status.players.len(),
+                                        status.max_players
+                                    ));
+                                    ui.end_row();
+                                    ui.label("Protocol");
+                                    ui.label(status.protocol_version.to_string());
+                                    ui.end_row();
+                                });
+                            ui.label(&status.motd);
+                            for username in &status.players {
+                                ui.label(format!("- {}", username));
+                            }
+                            if status.protocol_version != PROTOCOL_VERSION {
+                                ui.label(format!(
+                                    "Incompatible server: protocol {} expected",
+                                    PROTOCOL_VERSION
+                                ));
+                            }
+                        }
+                        Some(Err(e)) => {
+                            ui.label(format!("Server not available: {}", e));
+                        }
+                        None => {}
+                    }
                     ui.separator();
-                    if ui.button("Join").clicked() {
+                    let join_clicked = ui.button("Join").clicked();
+                    let spectate_clicked = ui.button("Spectate").clicked();
+                    if join_clicked || spectate_clicked {
                         system.audio().play_sound("click");
                         let server_address = format!("{}:{}", self.address, DEFAULT_TCP_PORT);
                         let server_address = if server_address == "localhost" {
@@ -46,6 +139,7 @@ impl State<GameContext> for JoinGameState {
                         };
                         debug!("Join server at address {}", server_address);
                         context.connect_to_address = Some(server_address);
+                        context.spectator = spectate_clicked;
                         state_command = StateCommand::OpenState {
                             state: Box::new(StartGameState::new()),
                         };
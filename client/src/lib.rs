@@ -19,6 +19,7 @@ extern crate nalgebra_glm as glm;
 use self::render::*;
 use common::comms::*;
 use common::world_definition::*;
+use common::world_preset::WorldPreset;
 use common::world_type::GeneratorType;
 use egui::Rounding;
 use game_context::GameContext;
@@ -61,13 +62,22 @@ pub enum StartMode {
 pub struct YabClient {
     start_mode: StartMode,
     world_type: GeneratorType,
+    height_chunks: usize,
+    preset: WorldPreset,
 }
 
 impl YabClient {
-    pub fn new(start_mode: StartMode, world_type: GeneratorType) -> YabClient {
+    pub fn new(
+        start_mode: StartMode,
+        world_type: GeneratorType,
+        height_chunks: usize,
+        preset: WorldPreset,
+    ) -> YabClient {
         YabClient {
             start_mode,
             world_type,
+            height_chunks,
+            preset,
         }
     }
 
@@ -81,8 +91,11 @@ impl YabClient {
                 data.server_address = Some(format!("0.0.0.0:{}", DEFAULT_TCP_PORT));
                 data.connect_to_address = Some(format!("127.0.0.1:{}", DEFAULT_TCP_PORT));
                 data.world_type = Some(self.world_type);
+                data.world_height_chunks = Some(self.height_chunks);
+                data.world_preset = Some(self.preset.clone());
                 data.seed = rand::thread_rng().gen::<u32>();
                 data.description = "Quick".to_string();
+                data.world_id = WorldsStore::new().new_world_id(&data.description);
                 Box::new(StartGameState::new())
             }
             StartMode::Continue => {
@@ -95,6 +108,7 @@ impl YabClient {
                     debug!("Continue game");
                     data.server_address = Some(format!("0.0.0.0:{}", DEFAULT_TCP_PORT));
                     data.connect_to_address = Some(format!("127.0.0.1:{}", DEFAULT_TCP_PORT));
+                    data.world_id = world.id.clone();
                     data.seed = world.seed;
                     data.description = world.description.clone();
                     Box::new(StartGameState::new())
@@ -1,18 +1,74 @@
 use crate::{start_game::StartGameState, GameContext};
+use common::block::BlockRegistry;
 use common::comms::DEFAULT_TCP_PORT;
 use common::world_definition::*;
 use egui::ScrollArea;
 use gamework::*;
+use server::archive::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Folder in which world archives are exported and from which they are imported
+const ARCHIVE_FOLDER: &str = "archives";
 
 pub struct LoadGameState {
     worlds: Vec<WorldDef>,
+    // Archives with the description of their world, if their manifest can be read
+    archives: Vec<(PathBuf, Option<String>)>,
+    message: Option<String>,
 }
 
 impl LoadGameState {
     pub fn new() -> Self {
+        let mut state = LoadGameState {
+            worlds: Vec::new(),
+            archives: Vec::new(),
+            message: None,
+        };
+        state.refresh();
+        state
+    }
+
+    fn refresh(&mut self) {
         let worlds_store = WorldsStore::new();
-        let worlds = worlds_store.list_worlds();
-        LoadGameState { worlds }
+        self.worlds = worlds_store.list_worlds();
+        self.archives = list_archives(Path::new(ARCHIVE_FOLDER))
+            .into_iter()
+            .map(|path| {
+                let description = read_manifest(&path).ok().map(|m| m.world.description);
+                (path, description)
+            })
+            .collect();
+    }
+
+    fn export(&mut self, world_id: &str, assets: &Assets) {
+        let world_folder = WorldsStore::new().get_world_path(world_id);
+        let archive_path = PathBuf::from(ARCHIVE_FOLDER).join(format!(
+            "{}.{}",
+            world_folder.file_name().unwrap().to_string_lossy(),
+            ARCHIVE_EXTENSION
+        ));
+        let result = fs::create_dir_all(ARCHIVE_FOLDER).and_then(|_| {
+            let registry = BlockRegistry::load_or_create(&assets.path("blocks.json"))
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            export_world(&world_folder, &registry, &archive_path)
+        });
+        self.message = Some(match result {
+            Ok(_) => format!("Exported to {}", archive_path.to_string_lossy()),
+            Err(e) => format!("Export failed: {}", e),
+        });
+        self.refresh();
+    }
+
+    fn import(&mut self, archive_path: &Path, assets: &Assets) {
+        let result = BlockRegistry::load_or_create(&assets.path("blocks.json"))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+            .and_then(|registry| import_world(archive_path, &registry));
+        self.message = Some(match result {
+            Ok(manifest) => format!("Imported {}", manifest.world.description),
+            Err(e) => format!("Import failed: {}", e),
+        });
+        self.refresh();
     }
 }
 
@@ -28,6 +84,8 @@ impl State<GameContext> for LoadGameState {
         system: &mut SystemContext,
     ) -> StateCommand<GameContext> {
         let mut state_command = StateCommand::None;
+        let mut export_id = None;
+        let mut import_path = None;
         egui::SidePanel::left("Load").show(gui, |ui| {
             ui.with_layout(
                 egui::Layout::top_down_justified(egui::Align::Center),
@@ -42,20 +100,33 @@ impl State<GameContext> for LoadGameState {
                                 egui::Layout::top_down_justified(egui::Align::Min),
                                 |ui| {
                                     for world in &self.worlds {
-                                        if ui
-                                            .button(format!(
-                                                "{}\n{}",
-                                                world.description,
-                                                world.timestamp.format("%Y-%m-%d %H:%M:%S")
-                                            ))
-                                            .clicked()
-                                        {
+                                        let (load, export) = ui
+                                            .horizontal(|ui| {
+                                                let export = ui.small_button("Export").clicked();
+                                                let load = ui
+                                                    .button(format!(
+                                                        "{}\n{}",
+                                                        world.description,
+                                                        world.timestamp.format("%Y-%m-%d %H:%M:%S")
+                                                    ))
+                                                    .clicked();
+                                                (load, export)
+                                            })
+                                            .inner;
+                                        if export {
+                                            system.audio().play_sound("click");
+                                            export_id = Some(world.id.clone());
+                                        }
+                                        if load {
                                             system.audio().play_sound("click");
                                             data.server_address =
                                                 Some(format!("0.0.0.0:{}", DEFAULT_TCP_PORT));
                                             data.connect_to_address =
                                                 Some(format!("127.0.0.1:{}", DEFAULT_TCP_PORT));
+                                            data.spectator = false;
+                                            data.world_id = world.id.clone();
                                             data.seed = world.seed;
+                                            data.description = world.description.clone();
                                             state_command = StateCommand::ReplaceState {
                                                 state: Box::new(StartGameState::new()),
                                             };
@@ -64,6 +135,25 @@ impl State<GameContext> for LoadGameState {
                                 },
                             );
                         });
+                    if !self.archives.is_empty() {
+                        ui.separator();
+                        ui.label("Import");
+                        for (archive, description) in &self.archives {
+                            let filename = archive.file_name().unwrap().to_string_lossy();
+                            let label = match description {
+                                Some(description) => format!("{}\n{}", description, filename),
+                                None => format!("{}\n(unreadable)", filename),
+                            };
+                            if ui.button(label).clicked() {
+                                system.audio().play_sound("click");
+                                import_path = Some(archive.clone());
+                            }
+                        }
+                    }
+                    if let Some(message) = &self.message {
+                        ui.separator();
+                        ui.label(message);
+                    }
                     ui.separator();
                     if ui.button("Back").clicked() {
                         system.audio().play_sound("click");
@@ -72,6 +162,12 @@ impl State<GameContext> for LoadGameState {
                 },
             );
         });
+        if let Some(world_id) = export_id {
+            self.export(&world_id, system.assets());
+        }
+        if let Some(archive_path) = import_path {
+            self.import(&archive_path, system.assets());
+        }
         state_command
     }
 
@@ -54,6 +54,8 @@ impl State<GameContext> for MainMenuState {
                             data.server_address = Some(format!("0.0.0.0:{}", DEFAULT_TCP_PORT));
                             data.connect_to_address =
                                 Some(format!("127.0.0.1:{}", DEFAULT_TCP_PORT));
+                            data.spectator = false;
+                            data.world_id = save.id.clone();
                             data.seed = save.seed;
                             data.description = save.description.clone();
                             state_command = StateCommand::OpenState {
@@ -1,5 +1,11 @@
 use crate::{start_game::StartGameState, GameContext};
-use common::{comms::DEFAULT_TCP_PORT, world_type::GeneratorType};
+use common::{
+    chunk::{DEFAULT_WORLD_HEIGHT_CHUNKS, MAX_WORLD_HEIGHT_CHUNKS, MIN_WORLD_HEIGHT_CHUNKS},
+    comms::DEFAULT_TCP_PORT,
+    world_definition::WorldsStore,
+    world_preset::{load_or_create_presets, WorldPreset, DEFAULT_PRESET_NAME, PRESETS_FOLDER},
+    world_type::GeneratorType,
+};
 use gamework::*;
 use log::*;
 use rand::{prelude::ThreadRng, RngCore};
@@ -9,6 +15,9 @@ pub struct NewGameState {
     seed: String,
     name: String,
     world_type: GeneratorType,
+    height_chunks: usize,
+    presets: Vec<WorldPreset>,
+    preset_index: usize,
 }
 
 impl NewGameState {
@@ -19,12 +28,22 @@ impl NewGameState {
             rng,
             name: "New World".to_string(),
             world_type: GeneratorType::Default,
+            height_chunks: DEFAULT_WORLD_HEIGHT_CHUNKS,
+            presets: Vec::new(),
+            preset_index: 0,
         }
     }
 }
 
 impl State<GameContext> for NewGameState {
-    fn initialize(&mut self, _data: &mut GameContext, _context: &mut SystemContext) {}
+    fn initialize(&mut self, _data: &mut GameContext, context: &mut SystemContext) {
+        self.presets = load_or_create_presets(&context.assets().path(PRESETS_FOLDER));
+        self.preset_index = self
+            .presets
+            .iter()
+            .position(|preset| preset.name == DEFAULT_PRESET_NAME)
+            .unwrap_or(0);
+    }
 
     fn update(
         &mut self,
@@ -83,6 +102,34 @@ impl State<GameContext> for NewGameState {
                                         GeneratorType::Water,
                                         "Water",
                                     );
+                                    ui.selectable_value(
+                                        &mut self.world_type,
+                                        GeneratorType::Biomes,
+                                        "Biomes",
+                                    );
+                                });
+                            ui.end_row();
+                            ui.add(egui::Label::new("Height (chunks)"));
+                            ui.add(
+                                egui::DragValue::new(&mut self.height_chunks)
+                                    .clamp_range(MIN_WORLD_HEIGHT_CHUNKS..=MAX_WORLD_HEIGHT_CHUNKS),
+                            );
+                            ui.end_row();
+                            ui.add(egui::Label::new("Preset"));
+                            let selected_name = self
+                                .presets
+                                .get(self.preset_index)
+                                .map_or(DEFAULT_PRESET_NAME, |preset| preset.name.as_str());
+                            egui::ComboBox::from_id_source("preset")
+                                .selected_text(selected_name)
+                                .show_ui(ui, |ui| {
+                                    for (index, preset) in self.presets.iter().enumerate() {
+                                        ui.selectable_value(
+                                            &mut self.preset_index,
+                                            index,
+                                            &preset.name,
+                                        );
+                                    }
                                 });
                             ui.end_row();
                         });
@@ -92,11 +139,15 @@ impl State<GameContext> for NewGameState {
                         context.server_address = Some(format!("0.0.0.0:{}", DEFAULT_TCP_PORT));
                         context.connect_to_address =
                             Some(format!("127.0.0.1:{}", DEFAULT_TCP_PORT));
+                        context.spectator = false;
                         context.world_type = Some(self.world_type);
+                        context.world_height_chunks = Some(self.height_chunks);
+                        context.world_preset = self.presets.get(self.preset_index).cloned();
                         match self.seed.parse() {
                             Ok(seed) => {
                                 context.seed = seed;
                                 context.description = self.name.clone();
+                                context.world_id = WorldsStore::new().new_world_id(&self.name);
                                 state_command = StateCommand::ReplaceState {
                                     state: Box::new(StartGameState::new()),
                                 };
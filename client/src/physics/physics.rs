@@ -2,6 +2,7 @@
 use crate::physics::physicsobject::*;
 use crate::world::worldhandler::{Direction, WorldHandler};
 use common::block::*;
+use common::world_pos::WorldCoord;
 use glm::*;
 use log::*;
 use std::collections::HashMap;
@@ -102,16 +103,20 @@ impl Physics {
         let pos = self.get_object_position(handle) + offset;
         world
             .chunks
-            .get_block(pos.x as i16, pos.y as i16, pos.z as i16)
+            .get_block(
+                pos.x as WorldCoord,
+                pos.y as WorldCoord,
+                pos.z as WorldCoord,
+            )
             .kind()
             == self.water_block
     }
 
     pub fn is_occopied_by_body(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         world: &mut WorldHandler,
     ) -> bool {
         // Temporarily set block to do detection
@@ -280,9 +285,9 @@ impl Physics {
                 object.in_water = world
                     .chunks
                     .get_block(
-                        object.position.x as i16,
-                        object.position.y as i16,
-                        object.position.z as i16,
+                        object.position.x as WorldCoord,
+                        object.position.y as WorldCoord,
+                        object.position.z as WorldCoord,
                     )
                     .kind()
                     == self.water_block;
@@ -2,6 +2,7 @@ use crate::render::*;
 use common::block::*;
 use common::chunk::chunk_buffer::ChunkBuffer;
 use common::chunk::*;
+use common::world_pos::WorldCoord;
 
 // Vertices of a cube
 const VERTICES: [[f32; 3]; 8] = [
@@ -65,9 +66,9 @@ impl BlockMesher {
     ) -> (Vec<BlockVertex>, Vec<BlockVertex>) {
         let mut vertices = Vec::new();
         let mut translucent_vertices = Vec::new();
-        let offset_x = cp.x * CHUNK_SIZE as i16;
-        let offset_y = cp.y * CHUNK_SIZE as i16;
-        let offset_z = cp.z * CHUNK_SIZE as i16;
+        let offset_x = cp.x * CHUNK_SIZE as WorldCoord;
+        let offset_y = cp.y * CHUNK_SIZE as WorldCoord;
+        let offset_z = cp.z * CHUNK_SIZE as WorldCoord;
         // Center
         let chunk = buffer.get_chunk_pos(cp).unwrap();
         for x in 0..CHUNK_SIZE {
@@ -89,9 +90,9 @@ impl BlockMesher {
                             if let Some(verts) = vertices_opt {
                                 self.add_face(
                                     verts,
-                                    x as i16 + offset_x,
-                                    y as i16 + offset_y,
-                                    z as i16 + offset_z,
+                                    x as WorldCoord + offset_x,
+                                    y as WorldCoord + offset_y,
+                                    z as WorldCoord + offset_z,
                                     self.texture(neighbour.kind(), FACE_XM),
                                     light,
                                     FACE_XP,
@@ -110,9 +111,9 @@ impl BlockMesher {
                             if let Some(verts) = vertices_opt {
                                 self.add_face(
                                     verts,
-                                    x as i16 + offset_x,
-                                    y as i16 + offset_y,
-                                    z as i16 + offset_z,
+                                    x as WorldCoord + offset_x,
+                                    y as WorldCoord + offset_y,
+                                    z as WorldCoord + offset_z,
                                     self.texture(neighbour.kind(), FACE_XP),
                                     light,
                                     FACE_XM,
@@ -131,9 +132,9 @@ impl BlockMesher {
                             if let Some(verts) = vertices_opt {
                                 self.add_face(
                                     verts,
-                                    x as i16 + offset_x,
-                                    y as i16 + offset_y,
-                                    z as i16 + offset_z,
+                                    x as WorldCoord + offset_x,
+                                    y as WorldCoord + offset_y,
+                                    z as WorldCoord + offset_z,
                                     self.texture(neighbour.kind(), FACE_YM),
                                     light,
                                     FACE_YP,
@@ -152,9 +153,9 @@ impl BlockMesher {
                             if let Some(verts) = vertices_opt {
                                 self.add_face(
                                     verts,
-                                    x as i16 + offset_x,
-                                    y as i16 + offset_y,
-                                    z as i16 + offset_z,
+                                    x as WorldCoord + offset_x,
+                                    y as WorldCoord + offset_y,
+                                    z as WorldCoord + offset_z,
                                     self.texture(neighbour.kind(), FACE_YP),
                                     light,
                                     FACE_YM,
@@ -173,9 +174,9 @@ impl BlockMesher {
                             if let Some(verts) = vertices_opt {
                                 self.add_face(
                                     verts,
-                                    x as i16 + offset_x,
-                                    y as i16 + offset_y,
-                                    z as i16 + offset_z,
+                                    x as WorldCoord + offset_x,
+                                    y as WorldCoord + offset_y,
+                                    z as WorldCoord + offset_z,
                                     self.texture(neighbour.kind(), FACE_ZM),
                                     light,
                                     FACE_ZP,
@@ -194,9 +195,9 @@ impl BlockMesher {
                             if let Some(verts) = vertices_opt {
                                 self.add_face(
                                     verts,
-                                    x as i16 + offset_x,
-                                    y as i16 + offset_y,
-                                    z as i16 + offset_z,
+                                    x as WorldCoord + offset_x,
+                                    y as WorldCoord + offset_y,
+                                    z as WorldCoord + offset_z,
                                     self.texture(neighbour.kind(), FACE_ZP),
                                     light,
                                     FACE_ZM,
@@ -228,9 +229,9 @@ impl BlockMesher {
                     if let Some(verts) = vertices_opt {
                         self.add_face(
                             verts,
-                            x as i16 + offset_x,
-                            y as i16 + offset_y,
-                            z as i16 + offset_z,
+                            x as WorldCoord + offset_x,
+                            y as WorldCoord + offset_y,
+                            z as WorldCoord + offset_z,
                             self.texture(neighbour.kind(), FACE_XM),
                             light,
                             FACE_XP,
@@ -260,9 +261,9 @@ impl BlockMesher {
                     if let Some(verts) = vertices_opt {
                         self.add_face(
                             verts,
-                            x as i16 + offset_x,
-                            y as i16 + offset_y,
-                            z as i16 + offset_z,
+                            x as WorldCoord + offset_x,
+                            y as WorldCoord + offset_y,
+                            z as WorldCoord + offset_z,
                             self.texture(neighbour.kind(), FACE_XP),
                             light,
                             FACE_XM,
@@ -292,9 +293,9 @@ impl BlockMesher {
                     if let Some(verts) = vertices_opt {
                         self.add_face(
                             verts,
-                            x as i16 + offset_x,
-                            y as i16 + offset_y,
-                            z as i16 + offset_z,
+                            x as WorldCoord + offset_x,
+                            y as WorldCoord + offset_y,
+                            z as WorldCoord + offset_z,
                             self.texture(neighbour.kind(), FACE_YM),
                             light,
                             FACE_YP,
@@ -324,9 +325,9 @@ impl BlockMesher {
                     if let Some(verts) = vertices_opt {
                         self.add_face(
                             verts,
-                            x as i16 + offset_x,
-                            y as i16 + offset_y,
-                            z as i16 + offset_z,
+                            x as WorldCoord + offset_x,
+                            y as WorldCoord + offset_y,
+                            z as WorldCoord + offset_z,
                             self.texture(neighbour.kind(), FACE_YP),
                             light,
                             FACE_YM,
@@ -360,9 +361,9 @@ impl BlockMesher {
                     if let Some(verts) = vertices_opt {
                         self.add_face(
                             verts,
-                            x as i16 + offset_x,
-                            y as i16 + offset_y,
-                            z as i16 + offset_z,
+                            x as WorldCoord + offset_x,
+                            y as WorldCoord + offset_y,
+                            z as WorldCoord + offset_z,
                             self.texture(neighbour.kind(), FACE_ZM),
                             light,
                             FACE_ZP,
@@ -396,9 +397,9 @@ impl BlockMesher {
                     if let Some(verts) = vertices_opt {
                         self.add_face(
                             verts,
-                            x as i16 + offset_x,
-                            y as i16 + offset_y,
-                            z as i16 + offset_z,
+                            x as WorldCoord + offset_x,
+                            y as WorldCoord + offset_y,
+                            z as WorldCoord + offset_z,
                             self.texture(neighbour.kind(), FACE_ZP),
                             light,
                             FACE_ZM,
@@ -414,9 +415,9 @@ impl BlockMesher {
     fn add_face(
         &self,
         vertices: &mut Vec<BlockVertex>,
-        x: i16,
-        y: i16,
-        z: i16,
+        x: WorldCoord,
+        y: WorldCoord,
+        z: WorldCoord,
         texture_layer: f32,
         light: u8,
         face: usize,
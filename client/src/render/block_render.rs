@@ -1,4 +1,5 @@
 use crate::render::*;
+use common::world_pos::WorldCoord;
 use common::{block::BlockRegistry, chunk::*};
 use failure;
 use gamework::glow::*;
@@ -147,7 +148,7 @@ impl BlockRenderer {
         gl: &glow::Context,
         model: Mat4,
         camera: &PerspectiveCamera,
-        max_range: i16,
+        max_range: WorldCoord,
         center_col: &ChunkColumnPos,
         render_lines: bool,
         out_of_range: &mut HashSet<ChunkColumnPos>,
@@ -173,7 +174,7 @@ impl BlockRenderer {
         let mut vertex_count = 0;
         let mut mesh_count = 0;
         let render_instant = Instant::now();
-        let dst_sq = max_range * max_range;
+        let dst_sq = max_range as i64 * max_range as i64;
         for (cp, mesh) in &mut self.meshes {
             let col = ChunkColumnPos::from_chunk_pos(*cp);
             if col.dist_squared_from(center_col) > dst_sq {
@@ -0,0 +1,132 @@
+use common::chunk::*;
+use common::claim::Claim;
+use common::world_pos::WorldCoord;
+use failure;
+use gamework::glow::*;
+use gamework::video::*;
+use gamework::*;
+use nalgebra_glm::*;
+
+// Number of chunk levels above and below the camera at which claim borders are drawn
+const OUTLINE_LEVELS: WorldCoord = 1;
+
+/// Renders the borders of land claims around the camera height
+pub struct ClaimOutline {
+    program: ShaderProgram,
+    model_uniform: Option<UniformLocation>,
+    view_uniform: Option<UniformLocation>,
+    projection_uniform: Option<UniformLocation>,
+    mesh: Option<Mesh>,
+    claims: Vec<Claim>,
+    level: WorldCoord,
+}
+
+impl ClaimOutline {
+    pub fn new(gl: &glow::Context, assets: &Assets) -> Result<ClaimOutline, failure::Error> {
+        let program = ShaderProgram::load(
+            gl,
+            assets,
+            "shaders/simple.vert",
+            "shaders/simple.frag",
+            "simple".to_string(),
+        )?;
+        program.set_used(gl);
+        let model_uniform = program.get_uniform(gl, "Model");
+        let view_uniform = program.get_uniform(gl, "View");
+        let projection_uniform = program.get_uniform(gl, "Projection");
+        Ok(ClaimOutline {
+            program,
+            model_uniform,
+            view_uniform,
+            projection_uniform,
+            mesh: None,
+            claims: Vec::new(),
+            level: 0,
+        })
+    }
+
+    pub fn render(
+        &mut self,
+        gl: &glow::Context,
+        camera: &PerspectiveCamera,
+        claims: &Vec<Claim>,
+        username: &str,
+        height_chunks: usize,
+    ) {
+        // Only rebuild the lines if the claims changed or the camera moved to another level
+        let level = (camera.position.z / CHUNK_SIZE as f32).floor() as WorldCoord;
+        if self.claims != *claims || self.level != level {
+            if let Some(mesh) = &self.mesh {
+                mesh.drop(gl);
+            }
+            self.mesh = build_mesh(gl, claims, username, level, height_chunks);
+            self.claims = claims.clone();
+            self.level = level;
+        }
+        if let Some(mesh) = &self.mesh {
+            self.program.set_used(gl);
+            if let Some(uniform) = &self.model_uniform {
+                self.program
+                    .set_uniform_matrix_4fv(gl, &uniform, &Mat4::identity());
+            }
+            if let Some(uniform) = &self.view_uniform {
+                self.program
+                    .set_uniform_matrix_4fv(gl, &uniform, camera.get_view());
+            }
+            if let Some(uniform) = &self.projection_uniform {
+                self.program
+                    .set_uniform_matrix_4fv(gl, &uniform, camera.get_projection());
+            }
+            unsafe {
+                gl.disable(glow::CULL_FACE);
+                gl.disable(glow::DEPTH_TEST);
+                gl.disable(glow::BLEND);
+            }
+            mesh.render_lines(gl);
+        }
+    }
+}
+
+fn build_mesh(
+    gl: &glow::Context,
+    claims: &Vec<Claim>,
+    username: &str,
+    level: WorldCoord,
+    height_chunks: usize,
+) -> Option<Mesh> {
+    if claims.is_empty() {
+        return None;
+    }
+    let size = CHUNK_SIZE as f32;
+    let z_min = ((level - OUTLINE_LEVELS).max(0) as f32) * size;
+    let z_max = ((level + OUTLINE_LEVELS + 1).min(height_chunks as WorldCoord) as f32) * size;
+    let mut vertices = Vec::new();
+    for claim in claims {
+        // Own claims are green, claims of others are red
+        let (r, g, b) = if claim.is_member(username) {
+            (0.2, 1.0, 0.2)
+        } else {
+            (1.0, 0.2, 0.2)
+        };
+        let x1 = claim.min.x as f32 * size;
+        let y1 = claim.min.y as f32 * size;
+        let x2 = (claim.max.x + 1) as f32 * size;
+        let y2 = (claim.max.y + 1) as f32 * size;
+        let corners = [(x1, y1), (x2, y1), (x2, y2), (x1, y2)];
+        for i in 0..corners.len() {
+            let (ax, ay) = corners[i];
+            let (bx, by) = corners[(i + 1) % corners.len()];
+            // Corner post
+            vertices.push(Vertex::new(ax, ay, z_min, r, g, b));
+            vertices.push(Vertex::new(ax, ay, z_max, r, g, b));
+            // Border at each chunk level
+            let mut z = z_min;
+            while z <= z_max {
+                vertices.push(Vertex::new(ax, ay, z, r, g, b));
+                vertices.push(Vertex::new(bx, by, z, r, g, b));
+                z += size;
+            }
+        }
+    }
+    Some(Mesh::new(gl, &vertices))
+}
@@ -1,6 +1,7 @@
 mod block_mesh;
 mod block_mesher;
 mod block_render;
+mod claim_outline;
 mod crosshair;
 mod deferred;
 mod skydome;
@@ -10,7 +11,9 @@ use crate::*;
 pub use block_mesh::{BlockMesh, BlockVertex};
 pub use block_mesher::BlockMesher;
 pub use block_render::BlockRenderer;
+use claim_outline::ClaimOutline;
 use common::chunk::*;
+use common::world_pos::WorldCoord;
 pub use crosshair::Crosshair;
 pub use deferred::DeferredPipeline;
 use gamework::video::*;
@@ -24,6 +27,7 @@ pub struct Rendering {
     pub render_lines: bool,
     fog_active: bool,
     crosshair: Crosshair,
+    claim_outline: ClaimOutline,
     skydome: SkyDome,
     pub world_mesher: WorldMesher,
     deferred: DeferredPipeline,
@@ -32,6 +36,7 @@ pub struct Rendering {
 impl Rendering {
     pub fn new(data: &mut GameContext, context: &mut SystemContext) -> Self {
         let crosshair = Crosshair::new(&context.video().gl(), &context.assets()).unwrap();
+        let claim_outline = ClaimOutline::new(&context.video().gl(), &context.assets()).unwrap();
         let skydome = SkyDome::new(&context.video().gl(), &context.assets());
         let deferred = DeferredPipeline::new(
             context.video().width(),
@@ -56,6 +61,7 @@ impl Rendering {
             render_lines: false,
             fog_active: true,
             crosshair,
+            claim_outline,
             skydome,
             world_mesher,
             deferred,
@@ -97,7 +103,7 @@ impl Rendering {
 
         context.video().clear_screen();
 
-        let max_range = data.world().render_range as i16;
+        let max_range = data.world().render_range as WorldCoord;
         let center_col = data.world().center_col.clone();
 
         data.block_renderer.as_mut().unwrap().render(
@@ -170,6 +176,14 @@ impl Rendering {
             fog_end,
         );
 
+        self.claim_outline.render(
+            context.video().gl(),
+            &self.camera,
+            &data.claims,
+            &data.username,
+            data.world().chunks.height_chunks(),
+        );
+
         // Render gui elements on top
         let half_width = context.video().width() as f32 / 2.0;
         let half_height = context.video().height() as f32 / 2.0;
@@ -2,6 +2,7 @@ use crate::render::*;
 use common::block::*;
 use common::chunk::chunk_buffer::ChunkBuffer;
 use common::chunk::*;
+use common::world_pos::WorldCoord;
 
 pub struct WorldMesher {
     block_mesher: BlockMesher,
@@ -20,8 +21,9 @@ impl WorldMesher {
         buffer: &ChunkBuffer,
     ) -> Vec<(Vec<BlockVertex>, Vec<BlockVertex>)> {
         let mut col_vertices: Vec<(Vec<BlockVertex>, Vec<BlockVertex>)> = Vec::new();
-        for z in 0..WORLD_HEIGHT_CHUNKS {
-            col_vertices.push(self.mesh_chunk(ChunkPos::new(col.x, col.y, z as i16), buffer));
+        for z in 0..buffer.height_chunks() {
+            col_vertices
+                .push(self.mesh_chunk(ChunkPos::new(col.x, col.y, z as WorldCoord), buffer));
         }
         col_vertices
     }
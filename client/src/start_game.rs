@@ -10,6 +10,8 @@ use gamework::*;
 use image::GenericImageView;
 use log::*;
 use nalgebra_glm::*;
+use rand::Rng;
+use server::generator::BiomeMap;
 use server::YabServer;
 use std::net::SocketAddr;
 use std::thread::sleep;
@@ -17,6 +19,7 @@ use std::time::Duration;
 
 const PRELOAD_COLUMN_COUNT: usize = 49; // Make sure this contains all chunks "nearby" the player
 const PRELOAD_SLEEP_DURATION: Duration = Duration::from_millis(10);
+const LOCAL_USERNAME: &str = "my user";
 
 #[derive(Copy, Clone, Debug)]
 pub enum StartGameStage {
@@ -32,6 +35,7 @@ pub struct StartGameState {
     preload_count: usize,
     player_chunk_stored: bool,
     stage: StartGameStage,
+    sign_in_denied: Option<String>,
 }
 
 impl StartGameState {
@@ -40,6 +44,7 @@ impl StartGameState {
             preload_count: 0,
             player_chunk_stored: false,
             stage: StartGameStage::StartingServer,
+            sign_in_denied: None,
         }
     }
 }
@@ -49,6 +54,7 @@ impl State<GameContext> for StartGameState {
         self.preload_count = 0;
         self.player_chunk_stored = false;
         self.stage = StartGameStage::StartingServer;
+        self.sign_in_denied = None;
     }
 
     fn update(
@@ -59,18 +65,43 @@ impl State<GameContext> for StartGameState {
         _input_events: &Vec<InputEvent>,
         context: &mut SystemContext,
     ) -> StateCommand<GameContext> {
+        if let Some(reason) = &self.sign_in_denied {
+            return show_sign_in_denied(gui, reason, context);
+        }
         let stage = self.stage;
         let (message, progress) = match stage {
             StartGameStage::StartingServer => {
                 // Start the server if needed
+                data.host_key = None;
                 if let Some(server_address) = &data.server_address {
                     let mut server = YabServer::new(server_address);
+                    // Only this client knows the key, so other players cannot sign in as the host
+                    let host_key = format!("{:032x}", rand::thread_rng().gen::<u128>());
+                    server.set_host(&host_key);
+                    data.host_key = Some(host_key);
                     let world_type = if let Some(world_type) = data.world_type {
                         world_type
                     } else {
                         GeneratorType::Default
                     };
-                    server.run(false, data.seed, data.description.clone(), world_type);
+                    let height_chunks = data
+                        .world_height_chunks
+                        .unwrap_or(DEFAULT_WORLD_HEIGHT_CHUNKS);
+                    let preset = data.world_preset.clone().unwrap_or_default();
+                    let started = server.run(
+                        false,
+                        data.world_id.clone(),
+                        data.seed,
+                        data.description.clone(),
+                        world_type,
+                        height_chunks,
+                        preset,
+                    );
+                    if let Err(e) = started {
+                        error!("Server cannot be started: {}", e);
+                        self.sign_in_denied = Some(e);
+                        return StateCommand::None;
+                    }
                     data.server = Some(server);
                 }
                 self.stage = StartGameStage::Connecting;
@@ -82,10 +113,14 @@ impl State<GameContext> for StartGameState {
                 let socket_addr: SocketAddr = connect_to_address
                     .parse()
                     .expect("Cannot parse server address");
+                data.username = LOCAL_USERNAME.to_string();
+                data.claims.clear();
                 let mut comms_client = CommsClient::new(socket_addr);
                 comms_client
                     .send(ClientMessage::SignIn {
-                        username: "my user".to_string(),
+                        username: data.username.clone(),
+                        spectator: data.spectator,
+                        host_key: data.host_key.clone(),
                     })
                     .unwrap();
                 data.comms_client = Some(comms_client);
@@ -105,6 +140,9 @@ impl State<GameContext> for StartGameState {
                             inventory,
                             gametime,
                             block_registry,
+                            op,
+                            height_chunks,
+                            biome_seed,
                         } => {
                             let starting_chunk_col = ChunkColumnPos::from_chunk_pos(
                                 ChunkPos::from_world_pos(data.starting_position),
@@ -117,7 +155,9 @@ impl State<GameContext> for StartGameState {
                             data.starting_yaw = yaw;
                             data.starting_pitch = pitch;
                             data.player_id = Some(player_id);
+                            data.op = op;
                             data.inventory = inventory;
+                            data.biome_map = biome_seed.map(BiomeMap::new);
                             debug!("Client gametime {}", gametime);
                             data.daynight.set_time(gametime);
                             let blocks: Vec<BlockDef> =
@@ -140,11 +180,17 @@ impl State<GameContext> for StartGameState {
                                     starting_chunk_col,
                                     data.comms_client.as_ref().unwrap().clone_col_receiver(),
                                     data.block_registry.clone(),
+                                    height_chunks as usize,
                                 )
                                 .unwrap(),
                             );
                             self.stage = StartGameStage::RequestingChunks;
                         }
+                        ServerMessage::SignInDenied { reason } => {
+                            warn!("Sign in denied: {}", reason);
+                            data.comms_client = None;
+                            self.sign_in_denied = Some(reason);
+                        }
                         _ => {
                             panic!("unexpected server response for sign in");
                         }
@@ -283,6 +329,27 @@ fn show_message(gui: &egui::Context, message: &str, progress: f32) {
     });
 }
 
+fn show_sign_in_denied(
+    gui: &egui::Context,
+    reason: &str,
+    context: &mut SystemContext,
+) -> StateCommand<GameContext> {
+    let mut state_command = StateCommand::None;
+    egui::CentralPanel::default().show(gui, |ui| {
+        ui.with_layout(
+            egui::Layout::top_down_justified(egui::Align::Center),
+            |ui| {
+                ui.label(format!("Cannot join the game: {}", reason));
+                if ui.button("Back").clicked() {
+                    context.audio().play_sound("click");
+                    state_command = StateCommand::CloseState;
+                }
+            },
+        );
+    });
+    state_command
+}
+
 fn load_image_from_path(path: &std::path::Path) -> Result<egui::ColorImage, image::ImageError> {
     let image = image::io::Reader::open(path)?.decode()?;
     let size = [image.width() as _, image.height() as _];
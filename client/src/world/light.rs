@@ -1,6 +1,7 @@
 use common::block::*;
 use common::chunk::chunk_buffer::ChunkBuffer;
 use common::chunk::*;
+use common::world_pos::WorldCoord;
 use std::collections::{HashSet, VecDeque};
 
 pub const MAX_LIGHT_LEVEL: u8 = 15;
@@ -14,24 +15,24 @@ pub trait LightHandler {
 
     fn propagate_light(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         dirty_chunks: &mut HashSet<ChunkPos>,
     );
 
     fn propagate_light_queue(
         &mut self,
-        start_queue: Vec<(i16, i16, i16)>,
+        start_queue: Vec<(WorldCoord, WorldCoord, WorldCoord)>,
         dirty_chunks: &mut HashSet<ChunkPos>,
     );
 
     // Remove light previously propagated from or through this block.
     fn remove_block_and_light(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         light_level: u8,
         new_block: Block,
         dirty_chunks: &mut HashSet<ChunkPos>,
@@ -39,20 +40,20 @@ pub trait LightHandler {
 
     fn light_removal_helper(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         light_level: u8,
         dirty_chunks: &mut HashSet<ChunkPos>,
-        queue: &mut VecDeque<(i16, i16, i16, u8)>,
-        propagate_queue: &mut Vec<(i16, i16, i16)>,
+        queue: &mut VecDeque<(WorldCoord, WorldCoord, WorldCoord, u8)>,
+        propagate_queue: &mut Vec<(WorldCoord, WorldCoord, WorldCoord)>,
     );
 
     fn propagate_light_after_removal(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         dirty_chunks: &mut HashSet<ChunkPos>,
     );
 }
@@ -72,9 +73,9 @@ impl LightHandler for ChunkBuffer {
             for lbx in 0..CHUNK_SIZE {
                 for lby in 0..CHUNK_SIZE {
                     for lbz in 0..CHUNK_SIZE {
-                        let wbx = cp.x * CHUNK_SIZE as i16 + lbx as i16;
-                        let wby = cp.y * CHUNK_SIZE as i16 + lby as i16;
-                        let wbz = cp.z * CHUNK_SIZE as i16 + lbz as i16;
+                        let wbx = cp.x * CHUNK_SIZE as WorldCoord + lbx as WorldCoord;
+                        let wby = cp.y * CHUNK_SIZE as WorldCoord + lby as WorldCoord;
+                        let wbz = cp.z * CHUNK_SIZE as WorldCoord + lbz as WorldCoord;
                         let mut block = chunk.get_block(lbx, lby, lbz);
                         let block_def = block_registry.get(block.kind());
                         if block_def.light > 0 {
@@ -93,9 +94,9 @@ impl LightHandler for ChunkBuffer {
 
     fn propagate_light(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         dirty_chunks: &mut HashSet<ChunkPos>,
     ) {
         let mut queue = Vec::new();
@@ -107,7 +108,7 @@ impl LightHandler for ChunkBuffer {
     // Based on https://www.seedofandromeda.com/blogs/29-fast-flood-fill-lighting-in-a-blocky-voxel-game-pt-1
     fn propagate_light_queue(
         &mut self,
-        start_queue: Vec<(i16, i16, i16)>,
+        start_queue: Vec<(WorldCoord, WorldCoord, WorldCoord)>,
         dirty_chunks: &mut HashSet<ChunkPos>,
     ) {
         let mut queue = Vec::new();
@@ -131,9 +132,9 @@ impl LightHandler for ChunkBuffer {
     // Remove light previously propagated from this block.
     fn remove_block_and_light(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         light_level: u8,
         new_block: Block,
         dirty_chunks: &mut HashSet<ChunkPos>,
@@ -223,13 +224,13 @@ impl LightHandler for ChunkBuffer {
 
     fn light_removal_helper(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         light_level: u8,
         dirty_chunks: &mut HashSet<ChunkPos>,
-        queue: &mut VecDeque<(i16, i16, i16, u8)>,
-        propagate_queue: &mut Vec<(i16, i16, i16)>,
+        queue: &mut VecDeque<(WorldCoord, WorldCoord, WorldCoord, u8)>,
+        propagate_queue: &mut Vec<(WorldCoord, WorldCoord, WorldCoord)>,
     ) {
         let cp = ChunkPos::from_world_block_coords(wbx, wby, wbz);
         let mut block = self.get_block(wbx, wby, wbz);
@@ -251,9 +252,9 @@ impl LightHandler for ChunkBuffer {
 
     fn propagate_light_after_removal(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         dirty_chunks: &mut HashSet<ChunkPos>,
     ) {
         let mut queue = Vec::new();
@@ -282,21 +283,21 @@ impl LightHandler for ChunkBuffer {
 // Helper for propagate_light that propagates light 1 block
 fn light_helper(
     chunks: &mut ChunkBuffer,
-    x: i16,
-    y: i16,
-    z: i16,
+    x: WorldCoord,
+    y: WorldCoord,
+    z: WorldCoord,
     light_level: u8,
     dirty_chunks: &mut HashSet<ChunkPos>,
-    queue: &mut Vec<(i16, i16, i16)>,
+    queue: &mut Vec<(WorldCoord, WorldCoord, WorldCoord)>,
 ) {
     let cp = ChunkPos::from_world_block_coords(x, y, z);
-    if cp.z < 0 || cp.z >= WORLD_HEIGHT_CHUNKS as i16 {
+    if cp.z < 0 || cp.z >= chunks.height_chunks() as WorldCoord {
         return;
     }
     let chunk = chunks.get_mut_chunk_pos(cp).unwrap();
-    let lbx = (x - cp.x * CHUNK_SIZE as i16) as usize;
-    let lby = (y - cp.y * CHUNK_SIZE as i16) as usize;
-    let lbz = (z - cp.z * CHUNK_SIZE as i16) as usize;
+    let lbx = (x - cp.x * CHUNK_SIZE as WorldCoord) as usize;
+    let lby = (y - cp.y * CHUNK_SIZE as WorldCoord) as usize;
+    let lbz = (z - cp.z * CHUNK_SIZE as WorldCoord) as usize;
     let mut block = chunk.get_block(lbx, lby, lbz);
     let block_light = block.get_light();
     if block.is_transparent() && block_light + 2 <= light_level {
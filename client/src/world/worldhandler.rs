@@ -4,6 +4,7 @@ use common::block::*;
 use common::chunk::chunk_buffer::ChunkBuffer;
 use common::chunk::*;
 use common::comms::*;
+use common::world_pos::WorldCoord;
 use crossbeam::channel::*;
 use crossbeam::unbounded;
 use failure;
@@ -20,7 +21,7 @@ use std::time::Instant;
 pub struct WorldHandler {
     pub chunks: ChunkBuffer,
     pub render_range: usize,
-    pub prio_render_range: i16,
+    pub prio_render_range: WorldCoord,
     pub center_col: ChunkColumnPos,
     column_rx: Receiver<ChunkColumn>,
     vertices_rx: Receiver<(ChunkPos, Vec<BlockVertex>, Vec<BlockVertex>)>,
@@ -36,6 +37,7 @@ impl WorldHandler {
         starting_column: ChunkColumnPos,
         col_receiver: Receiver<ServerMessage>,
         block_registry: BlockRegistry,
+        height_chunks: usize,
     ) -> Result<WorldHandler, failure::Error> {
         let (column_tx, column_rx): (Sender<ChunkColumn>, Receiver<ChunkColumn>) = unbounded();
         let (vertices_tx, vertices_rx) = unbounded();
@@ -43,7 +45,7 @@ impl WorldHandler {
 
         let water_block = block_registry.block_kind_from_code("water");
 
-        let mut buffer = ChunkBuffer::new();
+        let mut buffer = ChunkBuffer::new(height_chunks);
         let worldmesher = WorldMesher::new(block_registry.clone());
         let block_registry_clone = block_registry.clone();
 
@@ -72,6 +74,16 @@ impl WorldHandler {
                     match message {
                         // Store the received column and propagate sunlight
                         ServerMessage::ChunkColumn { col, block_data } => {
+                            // A column of another height would not fit in the buffer
+                            if block_data.len() != buffer.height_chunks() {
+                                warn!(
+                                    "Ignoring column {:?} with {} chunks, the world is {} chunks high",
+                                    col,
+                                    block_data.len(),
+                                    buffer.height_chunks()
+                                );
+                                continue 'handler;
+                            }
                             match status_tx.send((col, ColumnStatus::Received)) {
                                 Err(e) => {
                                     debug!("World handler shutting down: {}", e);
@@ -80,8 +92,8 @@ impl WorldHandler {
                                 _ => {}
                             };
                             let mut chunks = Vec::new();
-                            for z in 0..WORLD_HEIGHT_CHUNKS {
-                                let mut bytes_reader = Cursor::new(&block_data[z]);
+                            for (z, chunk_bytes) in block_data.iter().enumerate() {
+                                let mut bytes_reader = Cursor::new(chunk_bytes);
                                 let mut blocks: Vec<Block> =
                                     Vec::rle_decode_from(&mut bytes_reader).unwrap();
                                 // Set transparency and solidity bits
@@ -94,10 +106,10 @@ impl WorldHandler {
                                         block.toggle_solidity();
                                     }
                                 }
-                                let chunk = Chunk {
-                                    pos: ChunkPos::new(col.x, col.y, z as i16),
+                                let chunk = Chunk::from_block_vec(
+                                    ChunkPos::new(col.x, col.y, z as WorldCoord),
                                     blocks,
-                                };
+                                );
                                 assert!(chunk.is_initialized());
                                 chunks.push(chunk);
                             }
@@ -124,9 +136,9 @@ impl WorldHandler {
                     }
                     for col in propagation_columns {
                         // Propagate light in all chunks of this column
-                        for z in 0..WORLD_HEIGHT_CHUNKS {
+                        for z in 0..buffer.height_chunks() {
                             buffer.propagate_chunk_lights(
-                                ChunkPos::new(col.x, col.y, z as i16),
+                                ChunkPos::new(col.x, col.y, z as WorldCoord),
                                 &mut HashSet::new(),
                                 &block_registry_clone,
                             );
@@ -161,7 +173,7 @@ impl WorldHandler {
                         for (vertices, translucent_vertices) in col_vertices {
                             if vertices.len() > 0 || translucent_vertices.len() > 0 {
                                 match vertices_tx.send((
-                                    ChunkPos::new(col.x, col.y, z as i16),
+                                    ChunkPos::new(col.x, col.y, z as WorldCoord),
                                     vertices,
                                     translucent_vertices,
                                 )) {
@@ -209,7 +221,7 @@ impl WorldHandler {
             })
             .unwrap();
 
-        let chunks = ChunkBuffer::new();
+        let chunks = ChunkBuffer::new(height_chunks);
 
         Ok(WorldHandler {
             render_range,
@@ -255,7 +267,7 @@ impl WorldHandler {
             }
         }
         // Check the rest that are not too far and in the horizontal camera frustum
-        let dst_sq = (self.render_range * self.render_range) as i16;
+        let dst_sq = (self.render_range * self.render_range) as i64;
         let mut col_opt = None;
         for candidate in &self.request_candidates {
             if candidate.dist_squared_from(&center_col) <= dst_sq {
@@ -613,9 +625,9 @@ impl WorldHandler {
     /// Set a block in the buffer and add any chunks that need meshing to the dirty chunks set
     pub fn set_block_add_dirty(
         &mut self,
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         block: Block,
         dirty_chunks: &mut HashSet<ChunkPos>,
     ) {
@@ -650,9 +662,9 @@ impl WorldHandler {
         // Now set the block
         if let Some(chunk) = self.chunks.get_mut_chunk_pos(chunk_pos) {
             if chunk.is_initialized() {
-                let x_rel = (wbx - chunk_pos.x * CHUNK_SIZE as i16) as usize;
-                let y_rel = (wby - chunk_pos.y * CHUNK_SIZE as i16) as usize;
-                let z_rel = (wbz - chunk_pos.z * CHUNK_SIZE as i16) as usize;
+                let x_rel = (wbx - chunk_pos.x * CHUNK_SIZE as WorldCoord) as usize;
+                let y_rel = (wby - chunk_pos.y * CHUNK_SIZE as WorldCoord) as usize;
+                let z_rel = (wbz - chunk_pos.z * CHUNK_SIZE as WorldCoord) as usize;
                 // If a light emitting block is removed, the propagated light should also be removed.
                 let old_block_light = self.block_registry.get(old_block.kind()).light;
                 if old_block_light > 0 || old_block.get_light() > 0 {
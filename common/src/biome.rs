@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+pub const BIOME_COUNT: usize = 4;
+
+/// Biomes of worlds of the biomes type, chosen per position by temperature and humidity
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Biome {
+    Hills,
+    Alien,
+    Ocean,
+    Desert,
+}
+
+impl Biome {
+    pub fn all() -> [Biome; BIOME_COUNT] {
+        [Biome::Hills, Biome::Alien, Biome::Ocean, Biome::Desert]
+    }
+
+    /// ID of the biome, also its index in `Biome::all()`
+    pub fn id(&self) -> u8 {
+        match self {
+            Biome::Hills => 0,
+            Biome::Alien => 1,
+            Biome::Ocean => 2,
+            Biome::Desert => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Biome> {
+        Biome::all().get(id as usize).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Biome::Hills => "Hills",
+            Biome::Alien => "Alien",
+            Biome::Ocean => "Ocean",
+            Biome::Desert => "Desert",
+        }
+    }
+
+    /// Colour to tint the biome with, as linear RGB
+    pub fn tint(&self) -> [f32; 3] {
+        match self {
+            Biome::Hills => [0.45, 0.75, 0.35],
+            Biome::Alien => [0.7, 0.85, 1.0],
+            Biome::Ocean => [0.25, 0.45, 0.85],
+            Biome::Desert => [0.9, 0.8, 0.5],
+        }
+    }
+}
+
+#[cfg(test)]
+mod biome_test {
+
+    use crate::biome::*;
+
+    #[test]
+    fn biome_ids() {
+        for (index, biome) in Biome::all().iter().enumerate() {
+            assert_eq!(biome.id() as usize, index);
+            assert_eq!(Biome::from_id(biome.id()), Some(*biome));
+        }
+        assert_eq!(Biome::from_id(BIOME_COUNT as u8), None);
+    }
+}
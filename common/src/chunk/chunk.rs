@@ -1,10 +1,31 @@
 use crate::block::*;
 use crate::chunk::*;
+use crate::world_pos::WorldCoord;
 
-#[derive(Clone, Debug, PartialEq)]
+const CHUNK_BLOCKS: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+// Index sizes that are used for paletted chunks, these divide a 64 bit word evenly
+const PALETTE_BITS: [usize; 4] = [1, 2, 4, 8];
+
+/// The blocks of a chunk, stored in the most compact representation that fits
+#[derive(Clone, Debug)]
+enum ChunkBlocks {
+    Uninitialized,
+    /// Every block in the chunk is the same
+    Solid(Block),
+    /// A palette with the blocks in the chunk and a bit-packed palette index per block
+    Paletted {
+        palette: Vec<Block>,
+        bits: usize,
+        indices: Vec<u64>,
+    },
+    /// A block per position, for chunks with too many different blocks for a palette
+    Full(Vec<Block>),
+}
+
+#[derive(Clone, Debug)]
 pub struct Chunk {
     pub pos: ChunkPos,
-    pub blocks: Vec<Block>,
+    blocks: ChunkBlocks,
 }
 
 impl Chunk {
@@ -12,86 +33,349 @@ impl Chunk {
     pub fn new_uninitialized(pos: ChunkPos) -> Chunk {
         Chunk {
             pos,
-            blocks: Vec::new(),
+            blocks: ChunkBlocks::Uninitialized,
         }
     }
 
     /// Create a solid chunk (i.e. has 1 block type)
     pub fn new_solid(pos: ChunkPos, block: Block) -> Chunk {
-        let mut blocks = Vec::with_capacity(1);
-        blocks.push(block);
-        Chunk { pos, blocks }
+        Chunk {
+            pos,
+            blocks: ChunkBlocks::Solid(block),
+        }
     }
 
     /// Create a normal chunk
     pub fn new_normal(pos: ChunkPos, block: Block) -> Chunk {
-        let blocks = vec![block; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
-        Chunk { pos, blocks }
+        let mut chunk = Chunk::new_solid(pos, block);
+        chunk.make_normal();
+        chunk
+    }
+
+    /// Create a chunk from a single block (solid) or a block per position (normal),
+    /// in the order used by block_vec
+    pub fn from_block_vec(pos: ChunkPos, blocks: Vec<Block>) -> Chunk {
+        assert!(blocks.len() == 1 || blocks.len() == CHUNK_BLOCKS);
+        let mut chunk = Chunk::new_solid(pos, blocks[0]);
+        for (index, block) in blocks.into_iter().enumerate().skip(1) {
+            chunk.set_index(index, block);
+        }
+        chunk
+    }
+
+    /// A single block for solid chunks, otherwise a block per position
+    pub fn block_vec(&self) -> Vec<Block> {
+        match &self.blocks {
+            ChunkBlocks::Uninitialized => Vec::new(),
+            ChunkBlocks::Solid(block) => vec![*block],
+            ChunkBlocks::Paletted { .. } => (0..CHUNK_BLOCKS).map(|i| self.get_index(i)).collect(),
+            ChunkBlocks::Full(blocks) => blocks.clone(),
+        }
     }
 
     pub fn is_initialized(&self) -> bool {
-        self.blocks.len() > 0
+        !matches!(self.blocks, ChunkBlocks::Uninitialized)
     }
 
     pub fn is_normal(&self) -> bool {
-        self.blocks.len() > 1
+        matches!(
+            self.blocks,
+            ChunkBlocks::Paletted { .. } | ChunkBlocks::Full(_)
+        )
     }
 
     pub fn is_solid(&self) -> bool {
-        self.blocks.len() == 1
+        matches!(self.blocks, ChunkBlocks::Solid(_))
+    }
+
+    /// Approximate number of bytes used for the blocks of this chunk
+    pub fn memory_size(&self) -> usize {
+        let block_size = std::mem::size_of::<Block>();
+        std::mem::size_of::<Chunk>()
+            + match &self.blocks {
+                ChunkBlocks::Uninitialized | ChunkBlocks::Solid(_) => 0,
+                ChunkBlocks::Paletted {
+                    palette, indices, ..
+                } => palette.capacity() * block_size + indices.capacity() * 8,
+                ChunkBlocks::Full(blocks) => blocks.capacity() * block_size,
+            }
     }
 
     pub fn get_block(&self, x_rel: usize, y_rel: usize, z_rel: usize) -> Block {
         debug_assert!(self.is_initialized());
-        if self.is_solid() {
-            self.blocks[0]
-        } else {
-            debug_assert!(x_rel < CHUNK_SIZE);
-            debug_assert!(y_rel < CHUNK_SIZE);
-            debug_assert!(z_rel < CHUNK_SIZE);
-            let index = z_rel | y_rel << BIT_SHIFT_Y | x_rel << BIT_SHIFT_X;
-            self.blocks[index]
-        }
+        debug_assert!(x_rel < CHUNK_SIZE);
+        debug_assert!(y_rel < CHUNK_SIZE);
+        debug_assert!(z_rel < CHUNK_SIZE);
+        self.get_index(z_rel | y_rel << BIT_SHIFT_Y | x_rel << BIT_SHIFT_X)
     }
 
-    pub fn get_block_world(&self, wbx: i16, wby: i16, wbz: i16) -> Block {
-        let lbx = (wbx - self.pos.x * CHUNK_SIZE as i16) as usize;
-        let lby = (wby - self.pos.y * CHUNK_SIZE as i16) as usize;
-        let lbz = (wbz - self.pos.z * CHUNK_SIZE as i16) as usize;
+    pub fn get_block_world(&self, wbx: WorldCoord, wby: WorldCoord, wbz: WorldCoord) -> Block {
+        let lbx = (wbx - self.pos.x * CHUNK_SIZE as WorldCoord) as usize;
+        let lby = (wby - self.pos.y * CHUNK_SIZE as WorldCoord) as usize;
+        let lbz = (wbz - self.pos.z * CHUNK_SIZE as WorldCoord) as usize;
         self.get_block(lbx, lby, lbz)
     }
 
+    /// Store a block per position instead of a single block
     pub fn make_normal(&mut self) {
-        let solid_block = self.blocks[0];
-        self.blocks = vec![solid_block; CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE];
+        if let ChunkBlocks::Solid(block) = self.blocks {
+            self.blocks = ChunkBlocks::Paletted {
+                palette: vec![block],
+                bits: PALETTE_BITS[0],
+                indices: vec![0; CHUNK_BLOCKS * PALETTE_BITS[0] / 64],
+            };
+        }
     }
 
     pub fn set_block(&mut self, x_rel: usize, y_rel: usize, z_rel: usize, block: Block) {
-        let index = z_rel | y_rel << BIT_SHIFT_Y | x_rel << BIT_SHIFT_X;
-        if self.is_solid() {
-            if block != self.blocks[0] {
-                // Need to support different blocks in this chunk
-                self.make_normal();
-                self.blocks[index] = block;
-            }
-        } else {
-            self.blocks[index] = block;
-        }
+        self.set_index(z_rel | y_rel << BIT_SHIFT_Y | x_rel << BIT_SHIFT_X, block);
     }
 
-    pub fn set_block_world(&mut self, wbx: i16, wby: i16, wbz: i16, block: Block) {
-        let lbx = (wbx - self.pos.x * CHUNK_SIZE as i16) as usize;
-        let lby = (wby - self.pos.y * CHUNK_SIZE as i16) as usize;
-        let lbz = (wbz - self.pos.z * CHUNK_SIZE as i16) as usize;
+    pub fn set_block_world(
+        &mut self,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
+        block: Block,
+    ) {
+        let lbx = (wbx - self.pos.x * CHUNK_SIZE as WorldCoord) as usize;
+        let lby = (wby - self.pos.y * CHUNK_SIZE as WorldCoord) as usize;
+        let lbz = (wbz - self.pos.z * CHUNK_SIZE as WorldCoord) as usize;
         self.set_block(lbx, lby, lbz, block);
     }
 
     pub fn set_block_unchecked(&mut self, x_rel: usize, y_rel: usize, z_rel: usize, block: Block) {
-        let index = z_rel | y_rel << BIT_SHIFT_Y | x_rel << BIT_SHIFT_X;
-        self.blocks[index] = block;
+        self.set_block(x_rel, y_rel, z_rel, block);
     }
 
     pub fn clear(&mut self) {
-        self.blocks.clear();
+        self.blocks = ChunkBlocks::Uninitialized;
+    }
+
+    /// Switch to the most compact representation, e.g. a solid chunk when all blocks are equal.
+    /// Palette entries that are no longer used are only dropped by this.
+    pub fn optimize(&mut self) {
+        if !self.is_normal() {
+            return;
+        }
+        let blocks = self.block_vec();
+        if blocks.iter().all(|b| *b == blocks[0]) {
+            self.blocks = ChunkBlocks::Solid(blocks[0]);
+            return;
+        }
+        let mut palette: Vec<Block> = Vec::new();
+        for block in &blocks {
+            if !palette.contains(block) {
+                palette.push(*block);
+            }
+        }
+        self.blocks = match palette_bits(palette.len()) {
+            Some(bits) => {
+                let mut indices = vec![0; CHUNK_BLOCKS * bits / 64];
+                for (index, block) in blocks.iter().enumerate() {
+                    let palette_index = palette.iter().position(|b| b == block).unwrap();
+                    set_packed(&mut indices, bits, index, palette_index);
+                }
+                ChunkBlocks::Paletted {
+                    palette,
+                    bits,
+                    indices,
+                }
+            }
+            None => ChunkBlocks::Full(blocks),
+        };
+    }
+
+    fn get_index(&self, index: usize) -> Block {
+        match &self.blocks {
+            ChunkBlocks::Solid(block) => *block,
+            ChunkBlocks::Paletted {
+                palette,
+                bits,
+                indices,
+            } => palette[get_packed(indices, *bits, index)],
+            ChunkBlocks::Full(blocks) => blocks[index],
+            ChunkBlocks::Uninitialized => panic!("Chunk {:?} is not initialized", self.pos),
+        }
+    }
+
+    fn set_index(&mut self, index: usize, block: Block) {
+        if let ChunkBlocks::Solid(solid_block) = self.blocks {
+            if block == solid_block {
+                return;
+            }
+            // Need to support different blocks in this chunk
+            self.make_normal();
+        }
+        match &mut self.blocks {
+            ChunkBlocks::Paletted {
+                palette,
+                bits,
+                indices,
+            } => {
+                if let Some(palette_index) = palette.iter().position(|b| *b == block) {
+                    set_packed(indices, *bits, index, palette_index);
+                    return;
+                }
+                if palette.len() < 1 << *bits {
+                    palette.push(block);
+                    set_packed(indices, *bits, index, palette.len() - 1);
+                    return;
+                }
+            }
+            ChunkBlocks::Full(blocks) => {
+                blocks[index] = block;
+                return;
+            }
+            ChunkBlocks::Solid(_) => unreachable!(),
+            ChunkBlocks::Uninitialized => panic!("Chunk {:?} is not initialized", self.pos),
+        }
+        // The palette is full, drop unused entries or use larger indices
+        self.optimize();
+        self.grow_palette();
+        self.set_index(index, block);
+    }
+
+    /// Make room for one more palette entry, by using larger indices or no palette at all
+    fn grow_palette(&mut self) {
+        if let ChunkBlocks::Paletted {
+            palette,
+            bits,
+            indices,
+        } = &self.blocks
+        {
+            if palette.len() < 1 << *bits {
+                return;
+            }
+            self.blocks = match palette_bits(palette.len() + 1) {
+                Some(new_bits) => {
+                    let mut new_indices = vec![0; CHUNK_BLOCKS * new_bits / 64];
+                    for index in 0..CHUNK_BLOCKS {
+                        let palette_index = get_packed(indices, *bits, index);
+                        set_packed(&mut new_indices, new_bits, index, palette_index);
+                    }
+                    ChunkBlocks::Paletted {
+                        palette: palette.clone(),
+                        bits: new_bits,
+                        indices: new_indices,
+                    }
+                }
+                None => ChunkBlocks::Full(self.block_vec()),
+            };
+        }
+    }
+}
+
+/// Chunks are equal if they have the same position and blocks, in whatever representation
+impl PartialEq for Chunk {
+    fn eq(&self, other: &Self) -> bool {
+        if self.pos != other.pos || self.is_initialized() != other.is_initialized() {
+            return false;
+        }
+        match (&self.blocks, &other.blocks) {
+            (ChunkBlocks::Uninitialized, ChunkBlocks::Uninitialized) => true,
+            (ChunkBlocks::Solid(a), ChunkBlocks::Solid(b)) => a == b,
+            _ => (0..CHUNK_BLOCKS).all(|i| self.get_index(i) == other.get_index(i)),
+        }
+    }
+}
+
+/// Smallest index size for a palette of the given length, if any
+fn palette_bits(palette_len: usize) -> Option<usize> {
+    PALETTE_BITS
+        .iter()
+        .copied()
+        .find(|bits| palette_len <= 1 << bits)
+}
+
+fn get_packed(indices: &Vec<u64>, bits: usize, index: usize) -> usize {
+    let per_word = 64 / bits;
+    let shift = (index % per_word) * bits;
+    ((indices[index / per_word] >> shift) & ((1 << bits) - 1)) as usize
+}
+
+fn set_packed(indices: &mut Vec<u64>, bits: usize, index: usize, value: usize) {
+    let per_word = 64 / bits;
+    let shift = (index % per_word) * bits;
+    let mask = ((1u64 << bits) - 1) << shift;
+    let word = &mut indices[index / per_word];
+    *word = (*word & !mask) | ((value as u64) << shift);
+}
+
+#[cfg(test)]
+mod chunk_test {
+
+    use crate::block::{AIR_BLOCK, BEDROCK_BLOCK};
+    use crate::chunk::{Chunk, ChunkPos, CHUNK_SIZE};
+
+    fn pos() -> ChunkPos {
+        ChunkPos::new(1, 2, 3)
+    }
+
+    #[test]
+    fn solid_until_changed() {
+        let mut chunk = Chunk::new_solid(pos(), AIR_BLOCK);
+        chunk.set_block(1, 2, 3, AIR_BLOCK);
+        assert!(chunk.is_solid());
+        chunk.set_block(1, 2, 3, BEDROCK_BLOCK);
+        assert!(chunk.is_normal());
+        assert_eq!(chunk.get_block(1, 2, 3), BEDROCK_BLOCK);
+        assert_eq!(chunk.get_block(3, 2, 1), AIR_BLOCK);
+    }
+
+    #[test]
+    fn palette_grows_and_shrinks() {
+        let mut chunk = Chunk::new_solid(pos(), AIR_BLOCK);
+        let small = chunk.memory_size();
+        // More different blocks than fit in the largest palette
+        for i in 0..300u32 {
+            let x = i as usize % CHUNK_SIZE;
+            let y = i as usize / CHUNK_SIZE % CHUNK_SIZE;
+            let z = i as usize / (CHUNK_SIZE * CHUNK_SIZE);
+            chunk.set_block(x, y, z, 100 + i);
+        }
+        for i in 0..300u32 {
+            let x = i as usize % CHUNK_SIZE;
+            let y = i as usize / CHUNK_SIZE % CHUNK_SIZE;
+            let z = i as usize / (CHUNK_SIZE * CHUNK_SIZE);
+            assert_eq!(chunk.get_block(x, y, z), 100 + i);
+        }
+        let full = chunk.memory_size();
+        assert!(full > small);
+
+        // Back to two different blocks
+        for i in 0..300u32 {
+            let x = i as usize % CHUNK_SIZE;
+            let y = i as usize / CHUNK_SIZE % CHUNK_SIZE;
+            let z = i as usize / (CHUNK_SIZE * CHUNK_SIZE);
+            chunk.set_block(x, y, z, if i == 0 { BEDROCK_BLOCK } else { AIR_BLOCK });
+        }
+        chunk.optimize();
+        assert!(chunk.is_normal());
+        assert!(chunk.memory_size() < full);
+        assert_eq!(chunk.get_block(0, 0, 0), BEDROCK_BLOCK);
+        assert_eq!(chunk.get_block(1, 0, 0), AIR_BLOCK);
+
+        // And to a single block
+        chunk.set_block(0, 0, 0, AIR_BLOCK);
+        chunk.optimize();
+        assert!(chunk.is_solid());
+    }
+
+    #[test]
+    fn block_vec_round_trip() {
+        let mut chunk = Chunk::new_solid(pos(), AIR_BLOCK);
+        assert_eq!(chunk.block_vec(), vec![AIR_BLOCK]);
+        chunk.set_block(0, 1, 2, BEDROCK_BLOCK);
+        let blocks = chunk.block_vec();
+        assert_eq!(blocks.len(), CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+        let copy = Chunk::from_block_vec(pos(), blocks);
+        assert_eq!(copy, chunk);
+        assert_eq!(copy.get_block(0, 1, 2), BEDROCK_BLOCK);
+        // Equal chunks in different representations
+        assert_eq!(
+            Chunk::new_normal(pos(), AIR_BLOCK),
+            Chunk::new_solid(pos(), AIR_BLOCK)
+        );
     }
 }
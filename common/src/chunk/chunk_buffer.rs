@@ -1,20 +1,28 @@
 use crate::block::*;
 use crate::chunk::*;
+use crate::world_pos::WorldCoord;
 use std::collections::HashMap;
 
 pub struct ChunkBuffer {
     pub columns: HashMap<ChunkColumnPos, ChunkColumn>,
+    height_chunks: usize,
 }
 
 impl ChunkBuffer {
-    pub fn new() -> ChunkBuffer {
+    /// Buffer for the columns of a world with the given height
+    pub fn new(height_chunks: usize) -> ChunkBuffer {
         ChunkBuffer {
             columns: HashMap::new(),
+            height_chunks,
         }
     }
 
+    pub fn height_chunks(&self) -> usize {
+        self.height_chunks
+    }
+
     // Get a block in a chunk directly
-    pub fn get_block(&self, wbx: i16, wby: i16, wbz: i16) -> Block {
+    pub fn get_block(&self, wbx: WorldCoord, wby: WorldCoord, wbz: WorldCoord) -> Block {
         let cp = ChunkPos::from_world_block_coords(wbx, wby, wbz);
         if let Some(chunk) = self.get_chunk_pos(cp) {
             if chunk.is_initialized() {
@@ -27,14 +35,14 @@ impl ChunkBuffer {
     }
 
     // Set a block in a chunk directly, used for collision detection when building
-    pub fn set_block(&mut self, wbx: i16, wby: i16, wbz: i16, block: Block) {
+    pub fn set_block(&mut self, wbx: WorldCoord, wby: WorldCoord, wbz: WorldCoord, block: Block) {
         let cp = ChunkPos::from_world_block_coords(wbx, wby, wbz);
         if let Some(chunk) = self.get_mut_chunk_pos(cp) {
             chunk.set_block_world(wbx, wby, wbz, block);
         }
     }
 
-    pub fn get_chunk(&self, wcx: i16, wcy: i16, wcz: i16) -> Option<&Chunk> {
+    pub fn get_chunk(&self, wcx: WorldCoord, wcy: WorldCoord, wcz: WorldCoord) -> Option<&Chunk> {
         if let Some(column) = self.columns.get(&ChunkColumnPos::new(wcx, wcy)) {
             column.chunks.get(wcz as usize)
         } else {
@@ -46,7 +54,12 @@ impl ChunkBuffer {
         self.get_chunk(chunk_pos.x, chunk_pos.y, chunk_pos.z)
     }
 
-    pub fn get_mut_chunk(&mut self, wcx: i16, wcy: i16, wcz: i16) -> Option<&mut Chunk> {
+    pub fn get_mut_chunk(
+        &mut self,
+        wcx: WorldCoord,
+        wcy: WorldCoord,
+        wcz: WorldCoord,
+    ) -> Option<&mut Chunk> {
         if let Some(column) = self.columns.get_mut(&ChunkColumnPos::new(wcx, wcy)) {
             column.chunks.get_mut(wcz as usize)
         } else {
@@ -81,7 +94,7 @@ impl ChunkBuffer {
         }
     }
 
-    pub fn get_column(&self, wcx: i16, wcy: i16) -> Option<&ChunkColumn> {
+    pub fn get_column(&self, wcx: WorldCoord, wcy: WorldCoord) -> Option<&ChunkColumn> {
         self.columns.get(&ChunkColumnPos::new(wcx, wcy))
     }
 
@@ -89,7 +102,7 @@ impl ChunkBuffer {
         self.columns.get(col)
     }
 
-    pub fn get_column_clone(&self, wcx: i16, wcy: i16) -> Option<ChunkColumn> {
+    pub fn get_column_clone(&self, wcx: WorldCoord, wcy: WorldCoord) -> Option<ChunkColumn> {
         if let Some(column) = self.columns.get(&ChunkColumnPos::new(wcx, wcy)) {
             Some(column.clone())
         } else {
@@ -97,7 +110,7 @@ impl ChunkBuffer {
         }
     }
 
-    pub fn get_mut_column(&mut self, wcx: i16, wcy: i16) -> Option<&mut ChunkColumn> {
+    pub fn get_mut_column(&mut self, wcx: WorldCoord, wcy: WorldCoord) -> Option<&mut ChunkColumn> {
         self.columns.get_mut(&ChunkColumnPos::new(wcx, wcy))
     }
 
@@ -142,23 +155,23 @@ impl ChunkBuffer {
     }
 
     // Search from top of map to bottom at specified x,y to find first non-empty block z coordinate
-    pub fn get_top_z(&self, x: i16, y: i16) -> i16 {
+    pub fn get_top_z(&self, x: WorldCoord, y: WorldCoord) -> WorldCoord {
         let mut cp = ChunkPos::from_world_block_coords(x, y, 0);
-        let lbx = x.rem_euclid(CHUNK_SIZE as i16);
-        let lby = y.rem_euclid(CHUNK_SIZE as i16);
-        for cz in (0..WORLD_HEIGHT_CHUNKS as i16).rev() {
+        let lbx = x.rem_euclid(CHUNK_SIZE as WorldCoord);
+        let lby = y.rem_euclid(CHUNK_SIZE as WorldCoord);
+        for cz in (0..self.height_chunks as WorldCoord).rev() {
             cp.z = cz;
             if let Some(chunk) = self.get_chunk_pos(cp) {
                 if chunk.is_initialized() {
                     for lbz in (0..CHUNK_SIZE).rev() {
                         if chunk.get_block(lbx as usize, lby as usize, lbz).kind() != AIR_BLOCK_KIND
                         {
-                            return cz * CHUNK_SIZE as i16 + lbz as i16;
+                            return cz * CHUNK_SIZE as WorldCoord + lbz as WorldCoord;
                         }
                     }
                 }
             }
         }
-        WORLD_HEIGHT_BLOCKS
+        (self.height_chunks * CHUNK_SIZE) as WorldCoord
     }
 }
@@ -1,13 +1,15 @@
 use crate::chunk::*;
+use crate::world_pos::WorldCoord;
+use serde::{Deserialize, Serialize};
 
-#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
+#[derive(PartialEq, Eq, Hash, Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct ChunkColumnPos {
-    pub x: i16,
-    pub y: i16,
+    pub x: WorldCoord,
+    pub y: WorldCoord,
 }
 
 impl ChunkColumnPos {
-    pub fn new(x: i16, y: i16) -> ChunkColumnPos {
+    pub fn new(x: WorldCoord, y: WorldCoord) -> ChunkColumnPos {
         ChunkColumnPos { x, y }
     }
 
@@ -15,13 +17,14 @@ impl ChunkColumnPos {
         ChunkColumnPos { x: cp.x, y: cp.y }
     }
 
-    pub fn dist_squared_from(&self, other: &ChunkColumnPos) -> i16 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        dx * dx + dy * dy
+    /// Squared distance, saturates at i64::MAX for the far ends of the world
+    pub fn dist_squared_from(&self, other: &ChunkColumnPos) -> i64 {
+        let dx = other.x as i64 - self.x as i64;
+        let dy = other.y as i64 - self.y as i64;
+        dx.saturating_mul(dx).saturating_add(dy.saturating_mul(dy))
     }
 
-    pub fn from_world_block_coords(wbx: i16, wby: i16) -> ChunkColumnPos {
+    pub fn from_world_block_coords(wbx: WorldCoord, wby: WorldCoord) -> ChunkColumnPos {
         ChunkColumnPos {
             x: wbx >> 4,
             y: wby >> 4,
@@ -30,8 +33,8 @@ impl ChunkColumnPos {
 
     pub fn from_world_pos(wx: f32, wy: f32) -> ChunkColumnPos {
         ChunkColumnPos {
-            x: (wx / CHUNK_SIZE as f32).floor() as i16,
-            y: (wy / CHUNK_SIZE as f32).floor() as i16,
+            x: (wx / CHUNK_SIZE as f32).floor() as WorldCoord,
+            y: (wy / CHUNK_SIZE as f32).floor() as WorldCoord,
         }
     }
 
@@ -1,11 +1,12 @@
 use crate::chunk::*;
+use crate::world_pos::WorldCoord;
 use nalgebra_glm::Vec3;
 
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub struct ChunkPos {
-    pub x: i16,
-    pub y: i16,
-    pub z: i16,
+    pub x: WorldCoord,
+    pub y: WorldCoord,
+    pub z: WorldCoord,
 }
 
 impl ChunkPos {
@@ -13,7 +14,7 @@ impl ChunkPos {
         ChunkPos { x: 0, y: 0, z: 0 }
     }
 
-    pub fn new(x: i16, y: i16, z: i16) -> ChunkPos {
+    pub fn new(x: WorldCoord, y: WorldCoord, z: WorldCoord) -> ChunkPos {
         ChunkPos { x, y, z }
     }
 
@@ -23,7 +24,7 @@ impl ChunkPos {
         cp
     }
 
-    pub fn from_world_block_coords(wbx: i16, wby: i16, wbz: i16) -> ChunkPos {
+    pub fn from_world_block_coords(wbx: WorldCoord, wby: WorldCoord, wbz: WorldCoord) -> ChunkPos {
         ChunkPos {
             x: wbx >> 4,
             y: wby >> 4,
@@ -32,22 +33,25 @@ impl ChunkPos {
     }
 
     pub fn set_from_world_pos(&mut self, pos: Vec3) {
-        self.x = (pos.x / CHUNK_SIZE as f32).floor() as i16;
-        self.y = (pos.y / CHUNK_SIZE as f32).floor() as i16;
-        self.z = (pos.z / CHUNK_SIZE as f32).floor() as i16;
+        self.x = (pos.x / CHUNK_SIZE as f32).floor() as WorldCoord;
+        self.y = (pos.y / CHUNK_SIZE as f32).floor() as WorldCoord;
+        self.z = (pos.z / CHUNK_SIZE as f32).floor() as WorldCoord;
     }
 
     pub fn set_from_world_coords(&mut self, wx: f32, wy: f32, wz: f32) {
-        self.x = (wx / CHUNK_SIZE as f32).floor() as i16;
-        self.y = (wy / CHUNK_SIZE as f32).floor() as i16;
-        self.z = (wz / CHUNK_SIZE as f32).floor() as i16;
+        self.x = (wx / CHUNK_SIZE as f32).floor() as WorldCoord;
+        self.y = (wy / CHUNK_SIZE as f32).floor() as WorldCoord;
+        self.z = (wz / CHUNK_SIZE as f32).floor() as WorldCoord;
     }
 
-    pub fn dist_squared_from(&self, other: &ChunkPos) -> i16 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        let dz = other.z - self.z;
-        dx * dx + dy * dy + dz * dz
+    /// Squared distance, saturates at i64::MAX for the far ends of the world
+    pub fn dist_squared_from(&self, other: &ChunkPos) -> i64 {
+        let dx = other.x as i64 - self.x as i64;
+        let dy = other.y as i64 - self.y as i64;
+        let dz = other.z as i64 - self.z as i64;
+        dx.saturating_mul(dx)
+            .saturating_add(dy.saturating_mul(dy))
+            .saturating_add(dz.saturating_mul(dz))
     }
 
     pub fn xp(&self) -> ChunkPos {
@@ -96,31 +100,31 @@ impl ChunkPos {
 
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub struct ChunkRegionPos {
-    pub x: i16,
-    pub y: i16,
+    pub x: WorldCoord,
+    pub y: WorldCoord,
 }
 
 impl ChunkRegionPos {
-    pub fn new(x: i16, y: i16) -> Self {
+    pub fn new(x: WorldCoord, y: WorldCoord) -> Self {
         ChunkRegionPos { x, y }
     }
 
-    pub fn from_world_pos(wx: i16, wy: i16) -> Self {
+    pub fn from_world_pos(wx: WorldCoord, wy: WorldCoord) -> Self {
         ChunkRegionPos {
             x: wx.div_euclid(REGION_SIZE_BLOCKS),
             y: wy.div_euclid(REGION_SIZE_BLOCKS),
         }
     }
 
-    pub fn world_x(&self) -> i16 {
+    pub fn world_x(&self) -> WorldCoord {
         self.x * REGION_SIZE_BLOCKS
     }
 
-    pub fn world_y(&self) -> i16 {
+    pub fn world_y(&self) -> WorldCoord {
         self.y * REGION_SIZE_BLOCKS
     }
 
-    pub fn contains_world_pos(&self, wx: i16, wy: i16) -> bool {
+    pub fn contains_world_pos(&self, wx: WorldCoord, wy: WorldCoord) -> bool {
         let region = ChunkRegionPos::from_world_pos(wx, wy);
         *self == region
     }
@@ -158,4 +162,19 @@ mod chunk_pos_test {
             ChunkPos::new(1, 1, 1)
         );
     }
+
+    #[test]
+    fn far_coordinates() {
+        assert_eq!(
+            ChunkPos::from_world_block_coords(1_000_000, -1_000_001, 0),
+            ChunkPos::new(62_500, -62_501, 0)
+        );
+        let a = ChunkPos::new(-100_000, 0, 0);
+        let b = ChunkPos::new(100_000, 0, 1);
+        assert_eq!(a.dist_squared_from(&b), 40_000_000_001);
+        // Distances between the far ends of the world do not overflow
+        let min = ChunkPos::new(std::i32::MIN, std::i32::MIN, 0);
+        let max = ChunkPos::new(std::i32::MAX, std::i32::MAX, 0);
+        assert_eq!(min.dist_squared_from(&max), std::i64::MAX);
+    }
 }
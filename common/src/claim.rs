@@ -0,0 +1,190 @@
+use crate::chunk::ChunkColumnPos;
+use crate::comms::{read_from::ReadFrom, write_to::WriteTo, CommsError};
+use crate::world_pos::WorldCoord;
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+/// Maximum width and length of a claim in chunk columns
+pub const MAX_CLAIM_SIZE_COLUMNS: WorldCoord = 8;
+/// Maximum number of claims a player can own
+pub const MAX_CLAIMS_PER_PLAYER: usize = 3;
+
+/// A rectangular area of chunk columns in which only the owner and members can change blocks
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Claim {
+    pub id: u32,
+    pub owner: String,
+    pub members: Vec<String>,
+    /// Corner with the lowest coordinates, inclusive
+    pub min: ChunkColumnPos,
+    /// Corner with the highest coordinates, inclusive
+    pub max: ChunkColumnPos,
+}
+
+impl Claim {
+    /// Create a claim between two corners in any order
+    pub fn new(id: u32, owner: &str, a: ChunkColumnPos, b: ChunkColumnPos) -> Claim {
+        Claim {
+            id,
+            owner: owner.to_string(),
+            members: Vec::new(),
+            min: ChunkColumnPos::new(a.x.min(b.x), a.y.min(b.y)),
+            max: ChunkColumnPos::new(a.x.max(b.x), a.y.max(b.y)),
+        }
+    }
+
+    pub fn contains_column(&self, col: ChunkColumnPos) -> bool {
+        col.x >= self.min.x && col.x <= self.max.x && col.y >= self.min.y && col.y <= self.max.y
+    }
+
+    pub fn contains_block(&self, wbx: WorldCoord, wby: WorldCoord) -> bool {
+        self.contains_column(ChunkColumnPos::from_world_block_coords(wbx, wby))
+    }
+
+    pub fn overlaps(&self, other: &Claim) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    /// Width and length in chunk columns. The corners come from clients, so the size is
+    /// computed in i64 to not overflow, it is 0 or less if the corners are inverted.
+    pub fn size(&self) -> (i64, i64) {
+        (
+            self.max.x as i64 - self.min.x as i64 + 1,
+            self.max.y as i64 - self.min.y as i64 + 1,
+        )
+    }
+
+    pub fn is_member(&self, username: &str) -> bool {
+        self.owner == username || self.members.iter().any(|m| m == username)
+    }
+}
+
+impl<W> WriteTo<W> for Claim
+where
+    W: Write,
+{
+    fn write_to(&self, writer: &mut W) -> Result<(), CommsError> {
+        self.id.write_to(writer)?;
+        self.owner.write_to(writer)?;
+        assert!(self.members.len() < std::u8::MAX as usize);
+        (self.members.len() as u8).write_to(writer)?;
+        for member in &self.members {
+            member.write_to(writer)?;
+        }
+        self.min.x.write_to(writer)?;
+        self.min.y.write_to(writer)?;
+        self.max.x.write_to(writer)?;
+        self.max.y.write_to(writer)?;
+        Ok(())
+    }
+}
+
+impl<R> ReadFrom<R> for Claim
+where
+    R: Read,
+{
+    fn read_from(reader: &mut R) -> Result<Self, CommsError> {
+        let id = u32::read_from(reader)?;
+        let owner = String::read_from(reader)?;
+        let member_count = u8::read_from(reader)?;
+        let mut members = Vec::new();
+        for _ in 0..member_count {
+            members.push(String::read_from(reader)?);
+        }
+        let min_x = WorldCoord::read_from(reader)?;
+        let min_y = WorldCoord::read_from(reader)?;
+        let max_x = WorldCoord::read_from(reader)?;
+        let max_y = WorldCoord::read_from(reader)?;
+        Ok(Claim {
+            id,
+            owner,
+            members,
+            min: ChunkColumnPos::new(min_x, min_y),
+            max: ChunkColumnPos::new(max_x, max_y),
+        })
+    }
+}
+
+#[cfg(test)]
+mod claim_test {
+
+    use crate::chunk::ChunkColumnPos;
+    use crate::claim::Claim;
+
+    #[test]
+    fn corners_in_any_order() {
+        let claim = Claim::new(
+            1,
+            "owner",
+            ChunkColumnPos::new(3, -2),
+            ChunkColumnPos::new(-1, 4),
+        );
+        assert_eq!(claim.min, ChunkColumnPos::new(-1, -2));
+        assert_eq!(claim.max, ChunkColumnPos::new(3, 4));
+        assert_eq!(claim.size(), (5, 7));
+        let huge = Claim::new(
+            1,
+            "owner",
+            ChunkColumnPos::new(std::i32::MIN, std::i32::MIN),
+            ChunkColumnPos::new(std::i32::MAX, std::i32::MAX),
+        );
+        assert_eq!(huge.size(), (1 << 32, 1 << 32));
+    }
+
+    #[test]
+    fn contains_block() {
+        let claim = Claim::new(
+            1,
+            "owner",
+            ChunkColumnPos::new(-1, 0),
+            ChunkColumnPos::new(0, 0),
+        );
+        assert!(claim.contains_block(-16, 0));
+        assert!(claim.contains_block(15, 15));
+        assert!(!claim.contains_block(-17, 0));
+        assert!(!claim.contains_block(16, 0));
+        assert!(!claim.contains_block(0, -1));
+    }
+
+    #[test]
+    fn overlaps() {
+        let claim = Claim::new(
+            1,
+            "owner",
+            ChunkColumnPos::new(0, 0),
+            ChunkColumnPos::new(2, 2),
+        );
+        let touching = Claim::new(
+            2,
+            "other",
+            ChunkColumnPos::new(2, 2),
+            ChunkColumnPos::new(4, 4),
+        );
+        let next_to = Claim::new(
+            3,
+            "other",
+            ChunkColumnPos::new(3, 0),
+            ChunkColumnPos::new(4, 2),
+        );
+        assert!(claim.overlaps(&touching));
+        assert!(touching.overlaps(&claim));
+        assert!(!claim.overlaps(&next_to));
+    }
+
+    #[test]
+    fn members() {
+        let mut claim = Claim::new(
+            1,
+            "owner",
+            ChunkColumnPos::new(0, 0),
+            ChunkColumnPos::new(0, 0),
+        );
+        claim.members.push("friend".to_string());
+        assert!(claim.is_member("owner"));
+        assert!(claim.is_member("friend"));
+        assert!(!claim.is_member("stranger"));
+    }
+}
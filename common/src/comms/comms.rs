@@ -1,10 +1,11 @@
 use crate::comms::*;
+use crate::server_status::ServerStatus;
 use crossbeam::channel::*;
 use crossbeam::unbounded;
 use log::*;
-use std::{fmt, sync::atomic::AtomicBool, thread::sleep, time};
+use std::{fmt, sync::atomic::AtomicBool, thread::sleep, time, time::Duration};
 use std::{
-    net::{SocketAddr, TcpListener, TcpStream},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
     sync::Arc,
 };
 use std::{sync::atomic::Ordering, thread};
@@ -107,6 +108,28 @@ impl CommsClient {
     }
 }
 
+// Ask a server for its status without signing in, the connection is closed afterwards
+pub fn query_server_status(
+    server_address: SocketAddr,
+    timeout: Duration,
+) -> Result<ServerStatus, CommsError> {
+    debug!("Querying server status at {}", server_address);
+    let mut stream = TcpStream::connect_timeout(&server_address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    ClientMessage::StatusRequest {}.serialize_into_writer(&mut stream)?;
+    let result = match ServerMessage::deserialize_from_reader(&mut stream)? {
+        ServerMessage::ServerStatus { status } => Ok(status),
+        message => {
+            warn!("Unexpected response to status request: {:?}", message);
+            Err(CommsError::ProtocolError)
+        }
+    };
+    if let Err(e) = stream.shutdown(std::net::Shutdown::Both) {
+        debug!("Error closing status connection: {:?}", e);
+    }
+    result
+}
+
 // Communications server: accepts new connections, reads messages from clients
 pub struct CommsServer {
     channel_rx: Receiver<CommChannel>,
@@ -227,6 +250,11 @@ impl CommChannel {
         }
     }
 
+    // Address of the client, if still known
+    pub fn peer_ip(&self) -> Option<IpAddr> {
+        self.sender_stream.peer_addr().ok().map(|addr| addr.ip())
+    }
+
     // Shutdown the connection to the client
     pub fn disconnect(&mut self) {
         info!("Disconnecting client: {}", self.client_id);
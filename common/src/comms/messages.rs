@@ -2,7 +2,8 @@ use crate::block::*;
 use crate::comms::read_from::ReadFrom;
 use crate::comms::write_to::WriteTo;
 use crate::comms::*;
-use crate::{chunk::*, inventory::Inventory};
+use crate::world_pos::WorldCoord;
+use crate::{chunk::*, claim::Claim, inventory::Inventory, server_status::ServerStatus};
 use log::*;
 use std::collections::HashSet;
 use std::io::{Read, Write};
@@ -11,6 +12,9 @@ use std::io::{Read, Write};
 pub enum ClientMessage {
     SignIn {
         username: String,
+        spectator: bool,
+        /// The key of the host of a server started by a client, other clients have none
+        host_key: Option<String>,
     },
     PositionUpdate {
         x: f32,
@@ -26,15 +30,31 @@ pub enum ClientMessage {
         columns: HashSet<ChunkColumnPos>,
     },
     SetBlock {
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         block: Block,
     },
     // Message {
     //     text: String,
     // },
     SignOut {},
+    StatusRequest {},
+    AdminCommand {
+        command: String,
+    },
+    Claim {
+        min: ChunkColumnPos,
+        max: ChunkColumnPos,
+    },
+    Unclaim {
+        claim_id: u32,
+    },
+    SetClaimMember {
+        claim_id: u32,
+        username: String,
+        member: bool,
+    },
 }
 
 const CM_VARIANT_SIGN_IN: u8 = 0;
@@ -43,19 +63,59 @@ const CM_VARIANT_POSITION_UPDATE: u8 = 2;
 const CM_VARIANT_SUBSCRIBE: u8 = 3;
 const CM_VARIANT_UNSUBSCRIBE: u8 = 4;
 const CM_VARIANT_SET_BLOCK: u8 = 5;
+const CM_VARIANT_STATUS_REQUEST: u8 = 6;
+const CM_VARIANT_ADMIN_COMMAND: u8 = 7;
+const CM_VARIANT_CLAIM: u8 = 8;
+const CM_VARIANT_UNCLAIM: u8 = 9;
+const CM_VARIANT_SET_CLAIM_MEMBER: u8 = 10;
 
 /// Manual serialization because serde / bincode is bugged and to use RLE
 /// It's ugly but it works
 impl SerializeMessage<ClientMessage> for ClientMessage {
     fn serialize_into_writer<W: Write>(&self, writer: &mut W) -> Result<(), CommsError> {
         match self {
-            ClientMessage::SignIn { username } => {
+            ClientMessage::SignIn {
+                username,
+                spectator,
+                host_key,
+            } => {
                 CM_VARIANT_SIGN_IN.write_to(writer)?;
                 username.write_to(writer)?;
+                spectator.write_to(writer)?;
+                host_key.is_some().write_to(writer)?;
+                host_key.clone().unwrap_or_default().write_to(writer)?;
             }
             ClientMessage::SignOut {} => {
                 CM_VARIANT_SIGN_OUT.write_to(writer)?;
             }
+            ClientMessage::StatusRequest {} => {
+                CM_VARIANT_STATUS_REQUEST.write_to(writer)?;
+            }
+            ClientMessage::AdminCommand { command } => {
+                CM_VARIANT_ADMIN_COMMAND.write_to(writer)?;
+                command.write_to(writer)?;
+            }
+            ClientMessage::Claim { min, max } => {
+                CM_VARIANT_CLAIM.write_to(writer)?;
+                min.x.write_to(writer)?;
+                min.y.write_to(writer)?;
+                max.x.write_to(writer)?;
+                max.y.write_to(writer)?;
+            }
+            ClientMessage::Unclaim { claim_id } => {
+                CM_VARIANT_UNCLAIM.write_to(writer)?;
+                claim_id.write_to(writer)?;
+            }
+            ClientMessage::SetClaimMember {
+                claim_id,
+                username,
+                member,
+            } => {
+                CM_VARIANT_SET_CLAIM_MEMBER.write_to(writer)?;
+                claim_id.write_to(writer)?;
+                username.write_to(writer)?;
+                member.write_to(writer)?;
+            }
             ClientMessage::PositionUpdate {
                 x,
                 y,
@@ -109,10 +169,46 @@ impl SerializeMessage<ClientMessage> for ClientMessage {
         match enum_variant {
             CM_VARIANT_SIGN_IN => {
                 let username = String::read_from(reader)?;
-                let message = ClientMessage::SignIn { username };
+                let spectator = bool::read_from(reader)?;
+                let has_host_key = bool::read_from(reader)?;
+                let host_key = String::read_from(reader)?;
+                let message = ClientMessage::SignIn {
+                    username,
+                    spectator,
+                    host_key: if has_host_key { Some(host_key) } else { None },
+                };
                 Ok(message)
             }
             CM_VARIANT_SIGN_OUT => Ok(ClientMessage::SignOut {}),
+            CM_VARIANT_STATUS_REQUEST => Ok(ClientMessage::StatusRequest {}),
+            CM_VARIANT_ADMIN_COMMAND => {
+                let command = String::read_from(reader)?;
+                Ok(ClientMessage::AdminCommand { command })
+            }
+            CM_VARIANT_CLAIM => {
+                let min_x = WorldCoord::read_from(reader)?;
+                let min_y = WorldCoord::read_from(reader)?;
+                let max_x = WorldCoord::read_from(reader)?;
+                let max_y = WorldCoord::read_from(reader)?;
+                Ok(ClientMessage::Claim {
+                    min: ChunkColumnPos::new(min_x, min_y),
+                    max: ChunkColumnPos::new(max_x, max_y),
+                })
+            }
+            CM_VARIANT_UNCLAIM => {
+                let claim_id = u32::read_from(reader)?;
+                Ok(ClientMessage::Unclaim { claim_id })
+            }
+            CM_VARIANT_SET_CLAIM_MEMBER => {
+                let claim_id = u32::read_from(reader)?;
+                let username = String::read_from(reader)?;
+                let member = bool::read_from(reader)?;
+                Ok(ClientMessage::SetClaimMember {
+                    claim_id,
+                    username,
+                    member,
+                })
+            }
             CM_VARIANT_POSITION_UPDATE => {
                 let x = f32::read_from(reader)?;
                 let y = f32::read_from(reader)?;
@@ -132,8 +228,8 @@ impl SerializeMessage<ClientMessage> for ClientMessage {
                 let size = u16::read_from(reader)? as usize;
                 let mut columns = Vec::new();
                 for _ in 0..size {
-                    let x = i16::read_from(reader)?;
-                    let y = i16::read_from(reader)?;
+                    let x = WorldCoord::read_from(reader)?;
+                    let y = WorldCoord::read_from(reader)?;
                     columns.push(ChunkColumnPos { x, y });
                 }
                 assert!(size == columns.len());
@@ -143,17 +239,17 @@ impl SerializeMessage<ClientMessage> for ClientMessage {
                 let size = u16::read_from(reader)? as usize;
                 let mut columns = HashSet::new();
                 for _ in 0..size {
-                    let x = i16::read_from(reader)?;
-                    let y = i16::read_from(reader)?;
+                    let x = WorldCoord::read_from(reader)?;
+                    let y = WorldCoord::read_from(reader)?;
                     columns.insert(ChunkColumnPos { x, y });
                 }
                 assert!(size == columns.len());
                 Ok(ClientMessage::Unsubscribe { columns })
             }
             CM_VARIANT_SET_BLOCK => {
-                let wbx = i16::read_from(reader)?;
-                let wby = i16::read_from(reader)?;
-                let wbz = i16::read_from(reader)?;
+                let wbx = WorldCoord::read_from(reader)?;
+                let wby = WorldCoord::read_from(reader)?;
+                let wbz = WorldCoord::read_from(reader)?;
                 let block = Block::read_from(reader)?;
                 let message = ClientMessage::SetBlock {
                     wbx,
@@ -182,6 +278,17 @@ mod serialize_client_messages {
     fn sign_in() {
         test(ClientMessage::SignIn {
             username: "my user".to_string(),
+            spectator: false,
+            host_key: Some("0123456789abcdef".to_string()),
+        });
+    }
+
+    #[test]
+    fn sign_in_spectator() {
+        test(ClientMessage::SignIn {
+            username: "watcher".to_string(),
+            spectator: true,
+            host_key: None,
         });
     }
 
@@ -190,6 +297,40 @@ mod serialize_client_messages {
         test(ClientMessage::SignOut {});
     }
 
+    #[test]
+    fn status_request() {
+        test(ClientMessage::StatusRequest {});
+    }
+
+    #[test]
+    fn admin_command() {
+        test(ClientMessage::AdminCommand {
+            command: "ban some user".to_string(),
+        });
+    }
+
+    #[test]
+    fn claim() {
+        test(ClientMessage::Claim {
+            min: ChunkColumnPos::new(-3, 4),
+            max: ChunkColumnPos::new(2, 9),
+        });
+    }
+
+    #[test]
+    fn unclaim() {
+        test(ClientMessage::Unclaim { claim_id: 12345 });
+    }
+
+    #[test]
+    fn set_claim_member() {
+        test(ClientMessage::SetClaimMember {
+            claim_id: 3,
+            username: "friend".to_string(),
+            member: true,
+        });
+    }
+
     #[test]
     fn position_update() {
         test(ClientMessage::PositionUpdate {
@@ -256,6 +397,14 @@ pub enum ServerMessage {
         inventory: Inventory,
         gametime: f32,
         block_registry: String,
+        op: bool,
+        /// Height of the world in chunks
+        height_chunks: u16,
+        /// Seed of the biome map, derived from the world seed, only for worlds with biomes
+        biome_seed: Option<u32>,
+    },
+    SignInDenied {
+        reason: String,
     },
     ChunkColumn {
         col: ChunkColumnPos,
@@ -266,9 +415,9 @@ pub enum ServerMessage {
     //     text: String,
     // },
     SetBlock {
-        wbx: i16,
-        wby: i16,
-        wbz: i16,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
         block: Block,
     },
     PlayerSpawn {
@@ -291,6 +440,30 @@ pub enum ServerMessage {
     PlayerDespawn {
         player_id: u8,
     },
+    ServerStatus {
+        status: ServerStatus,
+    },
+    AdminResponse {
+        message: String,
+    },
+    Claims {
+        claims: Vec<Claim>,
+    },
+    /// A claim, unclaim or claim member change of the player was refused
+    ClaimDenied {
+        reason: String,
+    },
+    /// The operator status of the player changed
+    OpStatus {
+        op: bool,
+    },
+    /// The server refused the position of the player, e.g. because it flew without being an
+    /// operator, and moves it back
+    MovePlayer {
+        x: f32,
+        y: f32,
+        z: f32,
+    },
     // ClientDisconnect {},
 }
 
@@ -300,6 +473,13 @@ const SM_VARIANT_PLAYER_SPAWN: u8 = 2;
 const SM_VARIANT_PLAYER_DESPAWN: u8 = 3;
 const SM_VARIANT_POSITION_UPDATE: u8 = 4;
 const SM_VARIANT_SIGN_IN_CONFIRM: u8 = 5;
+const SM_VARIANT_SERVER_STATUS: u8 = 6;
+const SM_VARIANT_SIGN_IN_DENIED: u8 = 7;
+const SM_VARIANT_ADMIN_RESPONSE: u8 = 8;
+const SM_VARIANT_CLAIMS: u8 = 9;
+const SM_VARIANT_CLAIM_DENIED: u8 = 10;
+const SM_VARIANT_OP_STATUS: u8 = 11;
+const SM_VARIANT_MOVE_PLAYER: u8 = 12;
 
 /// Manual serialization because serde / bincode is bugged and to use RLE
 /// It's ugly but it works
@@ -316,6 +496,9 @@ impl SerializeMessage<ServerMessage> for ServerMessage {
                 inventory,
                 gametime,
                 block_registry,
+                op,
+                height_chunks,
+                biome_seed,
             } => {
                 SM_VARIANT_SIGN_IN_CONFIRM.write_to(writer)?;
                 player_id.write_to(writer)?;
@@ -327,13 +510,22 @@ impl SerializeMessage<ServerMessage> for ServerMessage {
                 inventory.write_to(writer)?;
                 gametime.write_to(writer)?;
                 block_registry.write_to(writer)?;
+                op.write_to(writer)?;
+                height_chunks.write_to(writer)?;
+                biome_seed.is_some().write_to(writer)?;
+                biome_seed.unwrap_or(0).write_to(writer)?;
+            }
+            ServerMessage::SignInDenied { reason } => {
+                SM_VARIANT_SIGN_IN_DENIED.write_to(writer)?;
+                reason.write_to(writer)?;
             }
             ServerMessage::ChunkColumn { col, block_data } => {
                 SM_VARIANT_CHUNK_COLUMN.write_to(writer)?;
                 col.x.write_to(writer)?;
                 col.y.write_to(writer)?;
-                for z in 0..WORLD_HEIGHT_CHUNKS {
-                    let blocks = &block_data[z];
+                assert!(block_data.len() < std::u16::MAX as usize);
+                (block_data.len() as u16).write_to(writer)?;
+                for blocks in block_data {
                     assert!(blocks.len() < std::u16::MAX as usize);
                     (blocks.len() as u16).write_to(writer)?;
                     if let Err(_) = writer.write_all(&blocks) {
@@ -391,6 +583,36 @@ impl SerializeMessage<ServerMessage> for ServerMessage {
                 pitch.write_to(writer)?;
                 player_id.write_to(writer)?;
             }
+            ServerMessage::ServerStatus { status } => {
+                SM_VARIANT_SERVER_STATUS.write_to(writer)?;
+                status.write_to(writer)?;
+            }
+            ServerMessage::AdminResponse { message } => {
+                SM_VARIANT_ADMIN_RESPONSE.write_to(writer)?;
+                message.write_to(writer)?;
+            }
+            ServerMessage::Claims { claims } => {
+                SM_VARIANT_CLAIMS.write_to(writer)?;
+                assert!(claims.len() < std::u16::MAX as usize);
+                (claims.len() as u16).write_to(writer)?;
+                for claim in claims {
+                    claim.write_to(writer)?;
+                }
+            }
+            ServerMessage::ClaimDenied { reason } => {
+                SM_VARIANT_CLAIM_DENIED.write_to(writer)?;
+                reason.write_to(writer)?;
+            }
+            ServerMessage::OpStatus { op } => {
+                SM_VARIANT_OP_STATUS.write_to(writer)?;
+                op.write_to(writer)?;
+            }
+            ServerMessage::MovePlayer { x, y, z } => {
+                SM_VARIANT_MOVE_PLAYER.write_to(writer)?;
+                x.write_to(writer)?;
+                y.write_to(writer)?;
+                z.write_to(writer)?;
+            }
         }
         Ok({})
     }
@@ -408,6 +630,10 @@ impl SerializeMessage<ServerMessage> for ServerMessage {
                 let inventory = Inventory::read_from(reader)?;
                 let gametime = f32::read_from(reader)?;
                 let block_registry = String::read_from(reader)?;
+                let op = bool::read_from(reader)?;
+                let height_chunks = u16::read_from(reader)?;
+                let has_biomes = bool::read_from(reader)?;
+                let biome_seed = u32::read_from(reader)?;
                 Ok(ServerMessage::SignInConfirm {
                     player_id,
                     x,
@@ -418,14 +644,22 @@ impl SerializeMessage<ServerMessage> for ServerMessage {
                     inventory,
                     gametime,
                     block_registry,
+                    op,
+                    height_chunks,
+                    biome_seed: if has_biomes { Some(biome_seed) } else { None },
                 })
             }
+            SM_VARIANT_SIGN_IN_DENIED => {
+                let reason = String::read_from(reader)?;
+                Ok(ServerMessage::SignInDenied { reason })
+            }
             SM_VARIANT_CHUNK_COLUMN => {
-                let x = i16::read_from(reader)?;
-                let y = i16::read_from(reader)?;
+                let x = WorldCoord::read_from(reader)?;
+                let y = WorldCoord::read_from(reader)?;
                 let col = ChunkColumnPos { x, y };
+                let chunk_count = u16::read_from(reader)?;
                 let mut block_data = Vec::new();
-                for _ in 0..WORLD_HEIGHT_CHUNKS {
+                for _ in 0..chunk_count {
                     let length = u16::read_from(reader)? as usize;
                     let mut blocks = vec![0; length];
                     if let Err(_) = reader.read_exact(&mut blocks) {
@@ -436,9 +670,9 @@ impl SerializeMessage<ServerMessage> for ServerMessage {
                 Ok(ServerMessage::ChunkColumn { col, block_data })
             }
             SM_VARIANT_SET_BLOCK => {
-                let wbx = i16::read_from(reader)?;
-                let wby = i16::read_from(reader)?;
-                let wbz = i16::read_from(reader)?;
+                let wbx = WorldCoord::read_from(reader)?;
+                let wby = WorldCoord::read_from(reader)?;
+                let wbz = WorldCoord::read_from(reader)?;
                 let block = Block::read_from(reader)?;
 
                 let message = ServerMessage::SetBlock {
@@ -492,6 +726,36 @@ impl SerializeMessage<ServerMessage> for ServerMessage {
                 };
                 Ok(message)
             }
+            SM_VARIANT_SERVER_STATUS => {
+                let status = ServerStatus::read_from(reader)?;
+                Ok(ServerMessage::ServerStatus { status })
+            }
+            SM_VARIANT_ADMIN_RESPONSE => {
+                let message = String::read_from(reader)?;
+                Ok(ServerMessage::AdminResponse { message })
+            }
+            SM_VARIANT_CLAIMS => {
+                let claim_count = u16::read_from(reader)?;
+                let mut claims = Vec::new();
+                for _ in 0..claim_count {
+                    claims.push(Claim::read_from(reader)?);
+                }
+                Ok(ServerMessage::Claims { claims })
+            }
+            SM_VARIANT_CLAIM_DENIED => {
+                let reason = String::read_from(reader)?;
+                Ok(ServerMessage::ClaimDenied { reason })
+            }
+            SM_VARIANT_OP_STATUS => {
+                let op = bool::read_from(reader)?;
+                Ok(ServerMessage::OpStatus { op })
+            }
+            SM_VARIANT_MOVE_PLAYER => {
+                let x = f32::read_from(reader)?;
+                let y = f32::read_from(reader)?;
+                let z = f32::read_from(reader)?;
+                Ok(ServerMessage::MovePlayer { x, y, z })
+            }
             _ => {
                 error!("Unknown enum variant {}", enum_variant);
                 Err(CommsError::ProtocolError)
@@ -503,7 +767,7 @@ impl SerializeMessage<ServerMessage> for ServerMessage {
 #[cfg(test)]
 mod serialize_server_messages {
 
-    use crate::{block::BlockRegistry, comms::*};
+    use crate::{block::BlockRegistry, claim::Claim, comms::*, server_status::ServerStatus};
     use crate::{chunk::*, inventory::Inventory};
 
     #[test]
@@ -522,13 +786,23 @@ mod serialize_server_messages {
             inventory,
             gametime: 1.23,
             block_registry: serde_json::to_string(&BlockRegistry::default()).unwrap(),
+            op: true,
+            height_chunks: 16,
+            biome_seed: Some(1234),
+        });
+    }
+
+    #[test]
+    fn sign_in_denied() {
+        test(ServerMessage::SignInDenied {
+            reason: String::from("You are banned from this server"),
         });
     }
 
     #[test]
     fn chunk() {
         let mut block_data = Vec::new();
-        for _ in 0..WORLD_HEIGHT_CHUNKS {
+        for _ in 0..DEFAULT_WORLD_HEIGHT_CHUNKS {
             let mut blocks = Vec::new();
             blocks.push(1);
             block_data.push(blocks);
@@ -579,6 +853,72 @@ mod serialize_server_messages {
         });
     }
 
+    #[test]
+    fn server_status() {
+        test(ServerMessage::ServerStatus {
+            status: ServerStatus {
+                protocol_version: PROTOCOL_VERSION,
+                description: String::from("My world"),
+                seed: 1234,
+                world_type: String::from("Default"),
+                motd: String::from("Welcome!"),
+                max_players: 16,
+                players: vec![String::from("some user"), String::from("other user")],
+            },
+        });
+    }
+
+    #[test]
+    fn admin_response() {
+        test(ServerMessage::AdminResponse {
+            message: String::from("Player some user is now an operator"),
+        });
+    }
+
+    #[test]
+    fn claims() {
+        let mut claim = Claim::new(
+            7,
+            "owner",
+            ChunkColumnPos::new(-2, 3),
+            ChunkColumnPos::new(1, 5),
+        );
+        claim.members.push(String::from("friend"));
+        test(ServerMessage::Claims {
+            claims: vec![
+                claim,
+                Claim::new(
+                    8,
+                    "other",
+                    ChunkColumnPos::new(10, 10),
+                    ChunkColumnPos::new(10, 10),
+                ),
+            ],
+        });
+    }
+
+    #[test]
+    fn claim_denied() {
+        test(ServerMessage::ClaimDenied {
+            reason: String::from("This area overlaps an existing claim"),
+        });
+    }
+
+    #[test]
+    fn op_status() {
+        test(ServerMessage::OpStatus { op: true });
+        test(ServerMessage::OpStatus { op: false });
+    }
+
+    #[test]
+    fn move_player() {
+        test(ServerMessage::MovePlayer {
+            x: 12.5,
+            y: -3.25,
+            z: 70.0,
+        });
+    }
+
     // Serialize and deserialize a message and compare it ith the original
     fn test(message: ServerMessage) {
         let mut buf: Vec<u8> = Vec::new();
@@ -4,6 +4,7 @@ pub mod read_from;
 pub mod rle;
 pub mod write_to;
 
+pub use comms::query_server_status;
 pub use comms::CommChannel;
 pub use comms::CommsClient;
 pub use comms::CommsError;
@@ -16,6 +17,8 @@ pub use rle::RleEncode;
 use std::io::{Read, Write};
 
 pub const DEFAULT_TCP_PORT: u32 = 34254;
+// Increase when the message format changes in an incompatible way
+pub const PROTOCOL_VERSION: u16 = 10;
 
 pub trait SerializeMessage<T> {
     fn serialize_into_writer<W: Write>(&self, writer: &mut W) -> Result<(), CommsError>;
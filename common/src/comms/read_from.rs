@@ -19,6 +19,15 @@ where
     }
 }
 
+impl<R> ReadFrom<R> for bool
+where
+    R: Read,
+{
+    fn read_from(reader: &mut R) -> Result<Self, CommsError> {
+        Ok(u8::read_from(reader)? > 0)
+    }
+}
+
 impl<R> ReadFrom<R> for u16
 where
     R: Read,
@@ -41,6 +50,17 @@ where
     }
 }
 
+impl<R> ReadFrom<R> for i32
+where
+    R: Read,
+{
+    fn read_from(reader: &mut R) -> Result<Self, CommsError> {
+        let mut bytes: [u8; 4] = [0; 4];
+        reader.read_exact(&mut bytes)?;
+        Ok(i32::from_le_bytes(bytes))
+    }
+}
+
 impl<R> ReadFrom<R> for u32
 where
     R: Read,
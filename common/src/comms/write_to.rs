@@ -17,6 +17,15 @@ where
     }
 }
 
+impl<W> WriteTo<W> for bool
+where
+    W: Write,
+{
+    fn write_to(&self, writer: &mut W) -> Result<(), CommsError> {
+        Ok(writer.write_all(&[*self as u8])?)
+    }
+}
+
 impl<W> WriteTo<W> for u16
 where
     W: Write,
@@ -35,6 +44,15 @@ where
     }
 }
 
+impl<W> WriteTo<W> for i32
+where
+    W: Write,
+{
+    fn write_to(&self, writer: &mut W) -> Result<(), CommsError> {
+        Ok(writer.write_all(&self.to_le_bytes())?)
+    }
+}
+
 impl<W> WriteTo<W> for u32
 where
     W: Write,
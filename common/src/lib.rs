@@ -1,13 +1,18 @@
+pub mod biome;
 pub mod block;
 pub mod chunk;
+pub mod claim;
 pub mod comms;
 pub mod daynight;
 pub mod error;
 pub mod interpolate;
 pub mod inventory;
+pub mod ore_definition;
 pub mod player;
+pub mod server_status;
 pub mod world_definition;
 pub mod world_pos;
+pub mod world_preset;
 pub mod world_type;
 
-pub use chrono;
\ No newline at end of file
+pub use chrono;
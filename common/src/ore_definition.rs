@@ -0,0 +1,52 @@
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// File in the assets with the ores that are placed in the terrain of new worlds
+pub const ORES_FILE: &str = "ores.json";
+
+/// An ore as listed in the ores file. The ores are copied into the world definition when a
+/// world is created, so changing the ores file does not change existing worlds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OreDefinition {
+    /// Block code of the ore
+    pub block: String,
+    /// Block code of the block the ore replaces, e.g. stone
+    pub host_block: String,
+    /// Lowest and highest z coordinate of ore blocks
+    pub min_z: usize,
+    pub max_z: usize,
+    /// Average number of blocks in a vein
+    pub vein_size: usize,
+    /// Average number of veins in a cube of 32x32x32 blocks within the depth range
+    pub frequency: f64,
+}
+
+/// Load the ores from a file, without a file there are no ores
+pub fn load_ore_definitions(path: &Path) -> Vec<OreDefinition> {
+    match fs::read_to_string(path)
+        .map_err(|e| e.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+    {
+        Ok(definitions) => definitions,
+        Err(e) => {
+            warn!("Ores cannot be loaded from {:?}: {}", path, e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod ore_definition_test {
+
+    use crate::ore_definition::*;
+
+    #[test]
+    fn load_ores_file() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets");
+        let ores = load_ore_definitions(&assets.join(ORES_FILE));
+        assert!(ores.iter().any(|ore| ore.block == "coal"));
+        assert!(load_ore_definitions(&assets.join("missing.json")).is_empty());
+    }
+}
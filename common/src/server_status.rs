@@ -0,0 +1,62 @@
+use crate::comms::{read_from::ReadFrom, write_to::WriteTo, CommsError};
+use std::io::{Read, Write};
+
+/// Public information about a running server, available without signing in
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServerStatus {
+    pub protocol_version: u16,
+    pub description: String,
+    pub seed: u32,
+    pub world_type: String,
+    pub motd: String,
+    pub max_players: u8,
+    pub players: Vec<String>,
+}
+
+impl<W> WriteTo<W> for ServerStatus
+where
+    W: Write,
+{
+    fn write_to(&self, writer: &mut W) -> Result<(), CommsError> {
+        self.protocol_version.write_to(writer)?;
+        self.description.write_to(writer)?;
+        self.seed.write_to(writer)?;
+        self.world_type.write_to(writer)?;
+        self.motd.write_to(writer)?;
+        self.max_players.write_to(writer)?;
+        assert!(self.players.len() < std::u8::MAX as usize);
+        (self.players.len() as u8).write_to(writer)?;
+        for username in &self.players {
+            username.write_to(writer)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R> ReadFrom<R> for ServerStatus
+where
+    R: Read,
+{
+    fn read_from(reader: &mut R) -> Result<Self, CommsError> {
+        let protocol_version = u16::read_from(reader)?;
+        let description = String::read_from(reader)?;
+        let seed = u32::read_from(reader)?;
+        let world_type = String::read_from(reader)?;
+        let motd = String::read_from(reader)?;
+        let max_players = u8::read_from(reader)?;
+        let player_count = u8::read_from(reader)?;
+        let mut players = Vec::new();
+        for _ in 0..player_count {
+            players.push(String::read_from(reader)?);
+        }
+        Ok(ServerStatus {
+            protocol_version,
+            description,
+            seed,
+            world_type,
+            motd,
+            max_players,
+            players,
+        })
+    }
+}
@@ -2,12 +2,19 @@ use chrono::serde::ts_milliseconds;
 use chrono::{DateTime, Utc};
 use log::*;
 use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{self, Write};
 use std::path::PathBuf;
 use std::{fs, path::Path};
 
+use crate::chunk::{DEFAULT_WORLD_HEIGHT_CHUNKS, MAX_WORLD_HEIGHT_CHUNKS, MIN_WORLD_HEIGHT_CHUNKS};
+use crate::ore_definition::OreDefinition;
+use crate::world_preset::WorldPreset;
 use crate::world_type::GeneratorType;
 
 pub const WORLD_DEF_FILE: &str = "world.json";
+/// Version of the storage format of worlds, older worlds are migrated when loaded
+pub const WORLD_FORMAT_VERSION: u32 = 3;
 
 pub struct WorldsStore {
     store_path: PathBuf,
@@ -32,6 +39,8 @@ impl WorldsStore {
         let paths = fs::read_dir(&self.store_path).unwrap();
         for path in paths {
             match path {
+                // Folders of a world that is being restored are temporary
+                Ok(dir_entry) if dir_entry.file_name().to_string_lossy().ends_with(".tmp") => {}
                 Ok(dir_entry) => {
                     let world_info_file = dir_entry.path().join("world.json");
                     if let Some(world_def) = WorldDef::load(&world_info_file) {
@@ -46,18 +55,38 @@ impl WorldsStore {
         worlds
     }
 
-    pub fn get_world_path(&self, seed: u32) -> PathBuf {
-        self.store_path
-            .join(PathBuf::from(format!("world_{}", seed)))
+    /// Folder that holds the folders of all worlds
+    pub fn path(&self) -> &Path {
+        &self.store_path
+    }
+
+    pub fn get_world_path(&self, id: &str) -> PathBuf {
+        self.store_path.join(id)
+    }
+
+    /// Pick an ID for a new world based on its description that no other world uses yet
+    pub fn new_world_id(&self, description: &str) -> String {
+        let base = world_id_from_description(description);
+        let mut id = base.clone();
+        let mut count = 1;
+        while self.get_world_path(&id).exists() {
+            count += 1;
+            id = format!("{}_{}", base, count);
+        }
+        id
     }
 
     pub fn create_new_world(
         &self,
+        id: &str,
         seed: u32,
         description: &str,
         world_type: GeneratorType,
+        height_chunks: usize,
+        preset: WorldPreset,
+        ores: Vec<OreDefinition>,
     ) -> WorldDef {
-        let world_path = self.get_world_path(seed);
+        let world_path = self.get_world_path(id);
         if world_path.exists() {
             panic!("World already exists at {}", world_path.to_str().unwrap());
         }
@@ -65,47 +94,149 @@ impl WorldsStore {
         const VERSION: &'static str = env!("CARGO_PKG_VERSION");
         let world_info_file = world_path.join(WORLD_DEF_FILE);
         let world = WorldDef {
+            id: id.to_string(),
             seed,
             world_type,
             description: description.to_string(),
             version: VERSION.to_string(),
+            format_version: WORLD_FORMAT_VERSION,
+            height_chunks: height_chunks.clamp(MIN_WORLD_HEIGHT_CHUNKS, MAX_WORLD_HEIGHT_CHUNKS),
+            caves: Some(CaveSettings::default()),
+            ores,
+            structures: true,
+            preset,
+            generator: None,
             timestamp: Utc::now(),
             gametime: 0.3, // Early morning
         };
-        world.save(&world_info_file);
+        if let Err(e) = world.save(&world_info_file) {
+            error!(
+                "Error saving world definition to {}: {}",
+                world_info_file.to_string_lossy(),
+                e
+            );
+        }
         world
     }
 
-    pub fn try_load_world(&self, seed: u32) -> Option<WorldDef> {
-        let world_path = self.get_world_path(seed);
+    pub fn try_load_world(&self, id: &str) -> Option<WorldDef> {
+        let world_path = self.get_world_path(id);
         if !world_path.exists() {
             warn!("World not found at: {}", world_path.to_str().unwrap());
             return None;
         }
-        let world_info_file = world_path.join(WORLD_DEF_FILE);
-        let mut world = WorldDef::load(&world_info_file).unwrap();
-        world.version = env!("CARGO_PKG_VERSION").to_string();
-        world.save(&world_info_file);
-        Some(world)
+        WorldDef::load(&world_path.join(WORLD_DEF_FILE))
+    }
+}
+
+/// ID of a world created before worlds had their own ID, its folder is named after the seed
+pub fn seed_world_id(seed: u32) -> String {
+    format!("world_{}", seed)
+}
+
+/// Turn a description into a world ID that can be used as a folder name
+fn world_id_from_description(description: &str) -> String {
+    let mut id = String::new();
+    for c in description.trim().chars() {
+        if c.is_ascii_alphanumeric() {
+            id.push(c.to_ascii_lowercase());
+        } else if !id.ends_with('_') {
+            id.push('_');
+        }
+    }
+    let id = id.trim_matches('_');
+    if id.is_empty() {
+        "world".to_string()
+    } else {
+        id.to_string()
     }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct WorldDef {
+    /// Identifies the world, it is the name of the world folder so worlds can share a seed
+    #[serde(default)]
+    pub id: String,
     pub seed: u32,
     pub world_type: GeneratorType,
     pub description: String,
+    /// Version of the game that created the world
     pub version: String,
+    /// Storage format version, worlds from before format versions were introduced have zero
+    #[serde(default)]
+    pub format_version: u32,
+    /// Height of the world in chunks, older worlds have the default height
+    #[serde(default = "default_height_chunks")]
+    pub height_chunks: usize,
+    /// Caves carved into the terrain, older worlds have no caves
+    #[serde(default)]
+    pub caves: Option<CaveSettings>,
+    /// Ores placed in the terrain, copied from the ores file when the world is created. Older
+    /// worlds have no ores.
+    #[serde(default)]
+    pub ores: Vec<OreDefinition>,
+    /// Whether villages and dungeons are planned in the terrain, older worlds have none
+    #[serde(default)]
+    pub structures: bool,
+    /// Parameters of the generator, older worlds have the default preset
+    #[serde(default)]
+    pub preset: WorldPreset,
+    /// What the generated terrain depends on besides this definition, recorded when the world
+    /// is first opened
+    #[serde(default)]
+    pub generator: Option<GeneratorFingerprint>,
     #[serde(with = "ts_milliseconds")]
     pub timestamp: DateTime<Utc>,
     pub gametime: f32,
 }
 
+/// Version of the terrain generators and hash of the model files they place. Changed columns
+/// are stored as diffs against the generated terrain, so worlds are not opened when it changes.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GeneratorFingerprint {
+    pub version: u32,
+    pub models_hash: u64,
+}
+
+/// Settings of the caves carved into the terrain of a world
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CaveSettings {
+    /// Lowest z coordinate of caves
+    pub min_z: usize,
+    /// Highest z coordinate of caves, they never reach above the terrain
+    pub max_z: usize,
+    /// How much of the rock is carved out, from 0 (no caves) to 1
+    pub density: f64,
+    /// How often caves open up to the surface, from 0 (never) to 1
+    pub surface_openings: f64,
+    /// How much of the ground just below the surface is carved out into overhangs, from 0 to 1.
+    /// Worlds created before overhangs existed have none.
+    #[serde(default)]
+    pub overhangs: f64,
+}
+
+impl Default for CaveSettings {
+    fn default() -> Self {
+        CaveSettings {
+            min_z: 8,
+            max_z: 192,
+            density: 0.3,
+            surface_openings: 0.15,
+            overhangs: 0.3,
+        }
+    }
+}
+
 impl WorldDef {
+    /// Load a world definition, the ID is taken from the folder it is in
     pub fn load(path: &Path) -> Option<WorldDef> {
         match fs::read_to_string(path) {
-            Ok(def_string) => match serde_json::from_str(&def_string) {
-                Ok(world_def) => {
+            Ok(def_string) => match serde_json::from_str::<WorldDef>(&def_string) {
+                Ok(mut world_def) => {
+                    // Folders can be copied or renamed, and older worlds have no ID
+                    if let Some(folder_name) = path.parent().and_then(|p| p.file_name()) {
+                        world_def.id = folder_name.to_string_lossy().to_string();
+                    }
                     return Some(world_def);
                 }
                 Err(e) => {
@@ -119,8 +250,80 @@ impl WorldDef {
         None
     }
 
-    pub fn save(&self, path: &Path) {
-        let def_string = serde_json::to_string_pretty(self).unwrap();
-        fs::write(path, def_string).unwrap();
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let def_string = serde_json::to_string_pretty(self)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // Write to a temporary file first, so a crash never leaves a partial definition
+        let tmp_path = path.with_extension("json.tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(def_string.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)
+    }
+}
+
+fn default_height_chunks() -> usize {
+    DEFAULT_WORLD_HEIGHT_CHUNKS
+}
+
+#[cfg(test)]
+mod world_definition_test {
+
+    use crate::world_definition::*;
+
+    #[test]
+    fn world_id_from_description_is_folder_safe() {
+        assert_eq!(world_id_from_description("My World"), "my_world");
+        assert_eq!(
+            world_id_from_description("  ../Test: copy #2 "),
+            "test_copy_2"
+        );
+        assert_eq!(world_id_from_description("???"), "world");
+    }
+
+    #[test]
+    fn worlds_with_same_seed() {
+        let store_path = std::env::temp_dir().join("yab_world_definition_test");
+        if store_path.exists() {
+            fs::remove_dir_all(&store_path).unwrap();
+        }
+        let store = WorldsStore {
+            store_path: store_path.clone(),
+        };
+        let first_id = store.new_world_id("Survival");
+        store.create_new_world(
+            &first_id,
+            42,
+            "Survival",
+            GeneratorType::Flat,
+            4,
+            WorldPreset::default(),
+            Vec::new(),
+        );
+        let second_id = store.new_world_id("Survival");
+        assert_ne!(first_id, second_id);
+        store.create_new_world(
+            &second_id,
+            42,
+            "Survival",
+            GeneratorType::Flat,
+            4,
+            WorldPreset::default(),
+            Vec::new(),
+        );
+
+        // A world from before world IDs is identified by its folder
+        let legacy_path = store.get_world_path(&seed_world_id(7));
+        fs::create_dir_all(&legacy_path).unwrap();
+        let mut legacy = store.try_load_world(&first_id).unwrap();
+        legacy.id = String::new();
+        legacy.seed = 7;
+        legacy.save(&legacy_path.join(WORLD_DEF_FILE)).unwrap();
+
+        let mut ids: Vec<String> = store.list_worlds().into_iter().map(|w| w.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["survival", "survival_2", "world_7"]);
+        assert_eq!(store.try_load_world("world_7").unwrap().seed, 7);
+        fs::remove_dir_all(&store_path).unwrap();
     }
 }
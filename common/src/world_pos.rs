@@ -1,8 +1,12 @@
+/// Coordinate of a block or chunk in the world. It is wide enough that the world is
+/// effectively unbounded, distances between coordinates are calculated as i64.
+pub type WorldCoord = i32;
+
 #[derive(PartialEq, Eq, Hash, Debug, Copy, Clone)]
 pub struct WorldPos {
-    pub x: i16,
-    pub y: i16,
-    pub z: i16,
+    pub x: WorldCoord,
+    pub y: WorldCoord,
+    pub z: WorldCoord,
 }
 
 impl WorldPos {
@@ -10,14 +14,17 @@ impl WorldPos {
         WorldPos { x: 0, y: 0, z: 0 }
     }
 
-    pub fn new(x: i16, y: i16, z: i16) -> WorldPos {
+    pub fn new(x: WorldCoord, y: WorldCoord, z: WorldCoord) -> WorldPos {
         WorldPos { x, y, z }
     }
 
-    pub fn dist_squared_from(&self, other: &WorldPos) -> i16 {
-        let dx = other.x - self.x;
-        let dy = other.y - self.y;
-        let dz = other.z - self.z;
-        dx * dx + dy * dy + dz * dz
+    /// Squared distance, saturates at i64::MAX for the far ends of the world
+    pub fn dist_squared_from(&self, other: &WorldPos) -> i64 {
+        let dx = other.x as i64 - self.x as i64;
+        let dy = other.y as i64 - self.y as i64;
+        let dz = other.z as i64 - self.z as i64;
+        dx.saturating_mul(dx)
+            .saturating_add(dy.saturating_mul(dy))
+            .saturating_add(dz.saturating_mul(dz))
     }
 }
@@ -0,0 +1,264 @@
+use crate::chunk::{CHUNK_SIZE, MAX_WORLD_HEIGHT_CHUNKS};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Folder in the assets with the world presets, one JSON file per preset
+pub const PRESETS_FOLDER: &str = "presets";
+pub const DEFAULT_PRESET_NAME: &str = "Default";
+/// Largest width of the generated towers and trees, grid cells must fit them
+pub const MAX_TOWER_SIZE: i32 = 15;
+pub const MAX_TREE_SIZE: i32 = 9;
+
+/// Parameters of the world generator. The preset is stored in the world definition when a
+/// world is created, so changing a preset file does not change existing worlds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldPreset {
+    pub name: String,
+    pub flat: FlatPreset,
+    pub hills: HillsPreset,
+    pub objects: ObjectPreset,
+}
+
+impl Default for WorldPreset {
+    fn default() -> Self {
+        WorldPreset {
+            name: DEFAULT_PRESET_NAME.to_string(),
+            flat: FlatPreset::default(),
+            hills: HillsPreset::default(),
+            objects: ObjectPreset::default(),
+        }
+    }
+}
+
+impl WorldPreset {
+    /// Check that the generator can use the parameters, e.g. that objects fit in the grid cells
+    pub fn validate(&self) -> Result<(), String> {
+        let objects = &self.objects;
+        let max_z = MAX_WORLD_HEIGHT_CHUNKS * CHUNK_SIZE;
+        if objects.poi_grid_margin < 0 || objects.poi_grid_margin * 2 >= objects.poi_grid_size {
+            return Err("poi_grid_margin must be less than half the grid size".to_string());
+        }
+        if objects.poi_grid_size < MAX_TOWER_SIZE + 2 * objects.poi_grid_margin {
+            return Err(format!(
+                "poi_grid_size must be at least {} plus twice the margin",
+                MAX_TOWER_SIZE
+            ));
+        }
+        if objects.tree_grid_margin < 0 || objects.tree_grid_margin * 2 >= objects.tree_grid_size {
+            return Err("tree_grid_margin must be less than half the grid size".to_string());
+        }
+        if objects.tree_grid_size < MAX_TREE_SIZE {
+            return Err(format!("tree_grid_size must be at least {}", MAX_TREE_SIZE));
+        }
+        if !(0.0..=1.0).contains(&objects.poi_density)
+            || !(0.0..=1.0).contains(&objects.tree_density)
+        {
+            return Err("densities must be from 0 to 1".to_string());
+        }
+        if self.hills.min_roughness > self.hills.max_roughness {
+            return Err("min_roughness must not be more than max_roughness".to_string());
+        }
+        if self.hills.water_z >= max_z || self.hills.terrain_min_z >= max_z {
+            return Err(format!(
+                "water_z and terrain_min_z must be below the world height of {}",
+                max_z
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Terrain of flat worlds
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FlatPreset {
+    /// The z coordinate of the grass, lowered in worlds that are not high enough for it
+    pub terrain_top_z: usize,
+    /// Thickness of the dirt and grass on top of the stone
+    pub soil_thickness: usize,
+}
+
+impl Default for FlatPreset {
+    fn default() -> Self {
+        FlatPreset {
+            terrain_top_z: 36,
+            soil_thickness: 4,
+        }
+    }
+}
+
+/// Terrain of default (hills) worlds
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HillsPreset {
+    pub water_z: usize,
+    pub soil_thickness: usize,
+    /// Below this z coordinate the terrain is solid stone
+    pub terrain_min_z: usize,
+    /// Height range of the hills above the solid stone
+    pub terrain_z_range: usize,
+    /// Scale of the terrain noise, it varies between these values from place to place
+    pub min_roughness: f64,
+    pub max_roughness: f64,
+    /// Scale of the noise that decides the roughness
+    pub roughness_scale: f64,
+}
+
+impl Default for HillsPreset {
+    fn default() -> Self {
+        HillsPreset {
+            water_z: 80,
+            soil_thickness: 3,
+            terrain_min_z: 64,
+            terrain_z_range: 128,
+            min_roughness: 0.0025,
+            max_roughness: 0.025,
+            roughness_scale: 0.0001,
+        }
+    }
+}
+
+/// Generated points of interest and trees, and how they are placed
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ObjectPreset {
+    /// Number of different towers and trees that are generated
+    pub tower_count: usize,
+    pub tree_count: usize,
+    /// Points of interest are placed at most once in each grid cell, away from its edges
+    pub poi_grid_size: i32,
+    pub poi_grid_margin: i32,
+    /// Chance of a point of interest in a grid cell, from 0 to 1
+    pub poi_density: f64,
+    pub tree_grid_size: i32,
+    pub tree_grid_margin: i32,
+    pub tree_density: f64,
+    /// Names of the models in the objects folder that are placed, e.g. `hut` for `hut.vox`.
+    /// Only these are placed, in this order, so adding models does not change existing worlds.
+    pub models: Vec<String>,
+}
+
+impl Default for ObjectPreset {
+    fn default() -> Self {
+        ObjectPreset {
+            tower_count: 10,
+            tree_count: 10,
+            poi_grid_size: 32,
+            poi_grid_margin: 1,
+            poi_density: 0.2,
+            tree_grid_size: 13,
+            tree_grid_margin: 0,
+            tree_density: 0.35,
+            models: Vec::new(),
+        }
+    }
+}
+
+/// Load the presets in a folder, sorted by file name. Presets that cannot be loaded or are
+/// invalid are skipped, without presets the default preset is saved in the folder.
+pub fn load_or_create_presets(folder: &Path) -> Vec<WorldPreset> {
+    let mut paths: Vec<_> = match fs::read_dir(folder) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    let mut presets = Vec::new();
+    for path in paths {
+        match fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str::<WorldPreset>(&json).map_err(|e| e.to_string()))
+        {
+            Ok(preset) => match preset.validate() {
+                Ok(_) => presets.push(preset),
+                Err(e) => warn!("Skipping invalid preset {:?}: {}", path, e),
+            },
+            Err(e) => warn!("Error loading preset {:?}: {}", path, e),
+        }
+    }
+    if presets.is_empty() {
+        let preset = WorldPreset::default();
+        let path = folder.join("default.json");
+        match fs::create_dir_all(folder)
+            .and_then(|_| fs::write(&path, serde_json::to_string_pretty(&preset).unwrap()))
+        {
+            Ok(_) => info!("File {:?} created", path),
+            Err(e) => warn!("Could not save preset at {:?}: {}", path, e),
+        }
+        presets.push(preset);
+    }
+    presets
+}
+
+/// Find a preset by name, ignoring case
+pub fn find_preset(presets: &Vec<WorldPreset>, name: &str) -> Option<WorldPreset> {
+    presets
+        .iter()
+        .find(|preset| preset.name.eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+#[cfg(test)]
+mod world_preset_test {
+
+    use crate::world_preset::*;
+
+    #[test]
+    fn load_presets() {
+        let folder = std::env::temp_dir().join("yab_preset_test");
+        if folder.exists() {
+            fs::remove_dir_all(&folder).unwrap();
+        }
+        // Without presets the default preset is created
+        let presets = load_or_create_presets(&folder);
+        assert_eq!(presets, vec![WorldPreset::default()]);
+        assert!(folder.join("default.json").exists());
+
+        // Missing values are taken from the default preset
+        fs::write(
+            folder.join("tall.json"),
+            r#"{ "name": "Tall", "hills": { "terrain_z_range": 256 } }"#,
+        )
+        .unwrap();
+        fs::write(folder.join("broken.json"), "{").unwrap();
+        fs::write(
+            folder.join("crowded.json"),
+            r#"{ "name": "Crowded", "objects": { "tree_grid_size": 5 } }"#,
+        )
+        .unwrap();
+        let presets = load_or_create_presets(&folder);
+        assert_eq!(presets.len(), 2);
+        let tall = find_preset(&presets, "tall").unwrap();
+        assert_eq!(tall.hills.terrain_z_range, 256);
+        assert_eq!(tall.hills.water_z, HillsPreset::default().water_z);
+        assert_eq!(tall.objects, ObjectPreset::default());
+        assert!(find_preset(&presets, "missing").is_none());
+        assert!(find_preset(&presets, "crowded").is_none());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn validate_presets() {
+        assert!(WorldPreset::default().validate().is_ok());
+        let invalid = |change: fn(&mut WorldPreset)| {
+            let mut preset = WorldPreset::default();
+            change(&mut preset);
+            preset.validate().is_err()
+        };
+        assert!(invalid(|p| p.objects.poi_grid_size = MAX_TOWER_SIZE + 1));
+        assert!(invalid(|p| p.objects.poi_grid_margin = 16));
+        assert!(invalid(|p| p.objects.tree_grid_size = MAX_TREE_SIZE - 1));
+        assert!(invalid(|p| p.objects.tree_grid_margin = -1));
+        assert!(invalid(|p| p.objects.poi_density = 1.5));
+        assert!(invalid(|p| p.objects.tree_density = -0.1));
+        assert!(invalid(|p| p.hills.min_roughness = 1.0));
+        assert!(invalid(|p| p.hills.water_z = 100_000));
+        assert!(invalid(|p| p.hills.terrain_min_z = 100_000));
+    }
+}
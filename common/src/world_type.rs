@@ -6,4 +6,6 @@ pub enum GeneratorType {
     Water,
     Alien,
     Default,
+    /// Terrain of several biomes, chosen by temperature and humidity
+    Biomes,
 }
@@ -7,7 +7,7 @@ pub struct Position {
 impl Position {
     pub fn new(x: f32, y: f32) -> Self {
         Position { x, y }
-    } 
+    }
 
     pub fn zero() -> Self {
         Position { x: 0.0, y: 0.0 }
@@ -0,0 +1,68 @@
+//! Compares the memory used by generated chunk columns with palette-compressed chunks to the
+//! memory a block per position would use. Run with `cargo bench -p server --bench chunk_memory`.
+
+use common::block::{Block, BlockRegistry};
+use common::chunk::*;
+use common::world_pos::WorldCoord;
+use common::world_type::GeneratorType;
+use server::generator::ColumnGenerator;
+use std::time::Instant;
+
+const SEED: u32 = 1234;
+// Columns in a square around the origin
+const COLUMN_RANGE: WorldCoord = 8;
+
+fn main() {
+    let block_registry = BlockRegistry::default();
+    let mut generator =
+        ColumnGenerator::for_seed(SEED, DEFAULT_WORLD_HEIGHT_CHUNKS, &block_registry);
+
+    let chunk_size = std::mem::size_of::<Chunk>();
+    let full_chunk_size =
+        chunk_size + CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * std::mem::size_of::<Block>();
+    let mut columns = 0;
+    let (mut solid, mut paletted, mut full) = (0, 0, 0);
+    let mut full_bytes = 0;
+    let mut paletted_bytes = 0;
+    let start = Instant::now();
+    for x in -COLUMN_RANGE..COLUMN_RANGE {
+        for y in -COLUMN_RANGE..COLUMN_RANGE {
+            let column =
+                generator.generate_column(GeneratorType::Default, ChunkColumnPos::new(x, y));
+            for chunk in &column {
+                let size = chunk.memory_size();
+                if chunk.is_solid() {
+                    solid += 1;
+                    full_bytes += chunk_size + std::mem::size_of::<Block>();
+                } else {
+                    if size >= full_chunk_size {
+                        full += 1;
+                    } else {
+                        paletted += 1;
+                    }
+                    full_bytes += full_chunk_size;
+                }
+                paletted_bytes += size;
+            }
+            columns += 1;
+        }
+    }
+    println!(
+        "Generated {} hills columns in {:.1} s",
+        columns,
+        start.elapsed().as_secs_f32()
+    );
+    println!(
+        "Chunks: {} solid, {} paletted, {} full",
+        solid, paletted, full
+    );
+    println!(
+        "Block per position: {} bytes per column",
+        full_bytes / columns
+    );
+    println!(
+        "Palette compressed: {} bytes per column ({:.1}% saved)",
+        paletted_bytes / columns,
+        100.0 - 100.0 * paletted_bytes as f64 / full_bytes as f64
+    );
+}
@@ -0,0 +1,203 @@
+use log::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BanList {
+    pub usernames: Vec<String>,
+    pub ips: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Whitelist {
+    pub enabled: bool,
+    pub usernames: Vec<String>,
+}
+
+/// Operators, bans and the whitelist of a world, each stored in its own file in the world folder
+pub struct AccessStore {
+    ops: Vec<String>,
+    bans: BanList,
+    whitelist: Whitelist,
+    folder_path: PathBuf,
+    saving_paused: bool,
+    unsaved_changes: bool,
+}
+
+impl AccessStore {
+    pub fn load(folder_path: &Path) -> AccessStore {
+        let store = AccessStore {
+            ops: load_list(&folder_path.join("ops.json")),
+            bans: load_list(&folder_path.join("bans.json")),
+            whitelist: load_list(&folder_path.join("whitelist.json")),
+            folder_path: folder_path.to_path_buf(),
+            saving_paused: false,
+            unsaved_changes: false,
+        };
+        // Write the files so they can be edited by hand
+        store.save();
+        store
+    }
+
+    pub fn save(&self) {
+        save_list(&self.folder_path, "ops.json", &self.ops);
+        save_list(&self.folder_path, "bans.json", &self.bans);
+        save_list(&self.folder_path, "whitelist.json", &self.whitelist);
+    }
+
+    /// While saving is paused, e.g. during a backup, changes are saved when it is resumed
+    pub fn set_saving_paused(&mut self, paused: bool) {
+        self.saving_paused = paused;
+        if !paused && self.unsaved_changes {
+            self.save();
+            self.unsaved_changes = false;
+        }
+    }
+
+    /// Check if a user may sign in, returns the reason if not
+    pub fn check_sign_in(&self, username: &str, ip: Option<IpAddr>) -> Result<(), String> {
+        if self.bans.usernames.iter().any(|u| u == username) {
+            return Err("You are banned from this server".to_string());
+        }
+        if let Some(ip) = ip {
+            if self.bans.ips.iter().any(|i| *i == ip.to_string()) {
+                return Err("Your address is banned from this server".to_string());
+            }
+        }
+        if self.whitelist.enabled && !self.is_op(username) && !self.is_whitelisted(username) {
+            return Err("You are not on the whitelist of this server".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn is_op(&self, username: &str) -> bool {
+        self.ops.iter().any(|u| u == username)
+    }
+
+    pub fn is_whitelisted(&self, username: &str) -> bool {
+        self.whitelist.usernames.iter().any(|u| u == username)
+    }
+
+    pub fn set_op(&mut self, username: &str, op: bool) {
+        set_in_list(&mut self.ops, username, op);
+        self.changed();
+    }
+
+    pub fn set_banned(&mut self, username: &str, banned: bool) {
+        set_in_list(&mut self.bans.usernames, username, banned);
+        self.changed();
+    }
+
+    pub fn set_ip_banned(&mut self, ip: &str, banned: bool) {
+        set_in_list(&mut self.bans.ips, ip, banned);
+        self.changed();
+    }
+
+    pub fn set_whitelisted(&mut self, username: &str, whitelisted: bool) {
+        set_in_list(&mut self.whitelist.usernames, username, whitelisted);
+        self.changed();
+    }
+
+    pub fn set_whitelist_enabled(&mut self, enabled: bool) {
+        self.whitelist.enabled = enabled;
+        self.changed();
+    }
+
+    fn changed(&mut self) {
+        if self.saving_paused {
+            self.unsaved_changes = true;
+        } else {
+            self.save();
+        }
+    }
+}
+
+fn set_in_list(list: &mut Vec<String>, value: &str, present: bool) {
+    list.retain(|v| v != value);
+    if present {
+        list.push(value.to_string());
+    }
+}
+
+fn load_list<T: DeserializeOwned + Default>(path: &Path) -> T {
+    match fs::read_to_string(path) {
+        Ok(data_string) => match serde_json::from_str(&data_string) {
+            Ok(data) => {
+                return data;
+            }
+            Err(e) => {
+                // Keep a copy, so the list is not lost when the file is saved again
+                let copy_path = path.with_extension("json.invalid");
+                error!(
+                    "Error parsing {}, it is copied to {}: {}",
+                    path.to_string_lossy(),
+                    copy_path.to_string_lossy(),
+                    e
+                );
+                if let Err(e) = fs::copy(path, &copy_path) {
+                    error!("Error copying {}: {}", path.to_string_lossy(), e);
+                }
+            }
+        },
+        Err(e) => {
+            debug!("Cannot load {}: {}", path.to_string_lossy(), e);
+        }
+    }
+    T::default()
+}
+
+fn save_list<T: Serialize>(folder_path: &Path, filename: &str, data: &T) {
+    let path = folder_path.join(filename);
+    let tmp_path = folder_path.join(format!("{}.tmp", filename));
+    let data_string = serde_json::to_string_pretty(data).unwrap();
+    if let Err(e) = fs::write(&tmp_path, data_string) {
+        error!("Error writing {}: {}", tmp_path.to_string_lossy(), e);
+        return;
+    }
+    if let Err(e) = std::fs::rename(&tmp_path, &path) {
+        error!(
+            "Error renaming {} to {}: {}",
+            tmp_path.to_string_lossy(),
+            path.to_string_lossy(),
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod access_store_test {
+
+    use crate::access_store::*;
+
+    #[test]
+    fn lists_are_kept() {
+        let folder = std::env::temp_dir().join("yab_access_store_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let mut store = AccessStore::load(&folder);
+        store.set_banned("griefer", true);
+        store.set_op("admin", true);
+        let store = AccessStore::load(&folder);
+        assert!(store.check_sign_in("griefer", None).is_err());
+        assert!(store.is_op("admin"));
+        // A list with an error is kept aside before it is replaced
+        let broken = r#"{ "usernames": ["griefer"], "ips": "#;
+        fs::write(folder.join("bans.json"), broken).unwrap();
+        AccessStore::load(&folder);
+        let copy = fs::read_to_string(folder.join("bans.json.invalid")).unwrap();
+        assert_eq!(copy, broken);
+        // Changes made while saving is paused are saved when it resumes
+        let mut store = AccessStore::load(&folder);
+        store.set_saving_paused(true);
+        store.set_op("helper", true);
+        assert!(!AccessStore::load(&folder).is_op("helper"));
+        store.set_saving_paused(false);
+        assert!(AccessStore::load(&folder).is_op("helper"));
+        // Saving fails without a folder, but does not panic
+        fs::remove_dir_all(&folder).unwrap();
+        store.save();
+    }
+}
@@ -0,0 +1,313 @@
+use crate::access_store::AccessStore;
+use crate::backup::{self, Backups};
+use crate::block_log::{self, BlockChange, BlockLog};
+use crate::client::Client;
+use crate::schematic::{self, Schematic};
+use crate::server_world_handler::ServerWorldHandler;
+use common::block::BlockRegistry;
+use common::comms::ServerMessage;
+use common::world_pos::WorldCoord;
+use log::*;
+
+const HELP: &str = "Commands: op <user>, deop <user>, kick <user>, ban <user>, unban <user>, \
+    ban-ip <ip>, unban-ip <ip>, whitelist on|off, whitelist add|remove <user>, \
+    log here <range> [<minutes>], log player <minutes> <user>, \
+    rollback here <range> <minutes>, rollback player <minutes> <user>, \
+    backup, backup list, backup restore <name>, \
+    schematic copy <x1> <y1> <z1> <x2> <y2> <z2> <name>, \
+    schematic paste <name> [<quarter turns>] [mirror], schematic list";
+// Maximum number of block changes listed in a response
+const MAX_LISTED_CHANGES: usize = 10;
+
+/// Execute an administrative command of an operator, returns the response for the operator.
+/// Block changes that result from the command are added to the broadcast messages.
+pub fn execute_admin_command(
+    command: &str,
+    position: (WorldCoord, WorldCoord, WorldCoord),
+    access: &mut AccessStore,
+    world: &mut ServerWorldHandler,
+    backups: &mut Backups,
+    clients: &mut Vec<Client>,
+    block_registry: &BlockRegistry,
+    broadcast: &mut Vec<ServerMessage>,
+) -> String {
+    info!("Executing admin command '{}'", command);
+    let words: Vec<&str> = command.split_whitespace().collect();
+    if words.is_empty() {
+        return HELP.to_string();
+    }
+    // Usernames may contain spaces, so take the rest of the command
+    let argument = |skip: usize| words[skip..].join(" ");
+    match (words[0], words.len()) {
+        ("op", n) if n > 1 => {
+            let username = argument(1);
+            access.set_op(&username, true);
+            set_client_op(clients, &username, true);
+            format!("{} is now an operator", username)
+        }
+        ("deop", n) if n > 1 => {
+            let username = argument(1);
+            access.set_op(&username, false);
+            set_client_op(clients, &username, false);
+            format!("{} is no longer an operator", username)
+        }
+        ("kick", n) if n > 1 => {
+            let username = argument(1);
+            let count = disconnect_matching(clients, |c| c.data.username == username);
+            format!("Kicked {} connection(s) of {}", count, username)
+        }
+        ("ban", n) if n > 1 => {
+            let username = argument(1);
+            access.set_banned(&username, true);
+            disconnect_matching(clients, |c| c.data.username == username);
+            format!("{} is banned", username)
+        }
+        ("unban", n) if n > 1 => {
+            let username = argument(1);
+            access.set_banned(&username, false);
+            format!("{} is no longer banned", username)
+        }
+        ("ban-ip", 2) => {
+            let ip = words[1];
+            access.set_ip_banned(ip, true);
+            disconnect_matching(clients, |c| {
+                c.connection.peer_ip().map(|i| i.to_string()) == Some(ip.to_string())
+            });
+            format!("Address {} is banned", ip)
+        }
+        ("unban-ip", 2) => {
+            access.set_ip_banned(words[1], false);
+            format!("Address {} is no longer banned", words[1])
+        }
+        ("whitelist", 2) if words[1] == "on" || words[1] == "off" => {
+            access.set_whitelist_enabled(words[1] == "on");
+            format!("Whitelist is {}", words[1])
+        }
+        ("whitelist", n) if n > 2 && (words[1] == "add" || words[1] == "remove") => {
+            let username = argument(2);
+            access.set_whitelisted(&username, words[1] == "add");
+            if words[1] == "add" {
+                format!("{} is added to the whitelist", username)
+            } else {
+                format!("{} is removed from the whitelist", username)
+            }
+        }
+        ("log", 3) | ("log", 4) if words[1] == "here" => {
+            // Without a time limit all logged changes are listed
+            let minutes = words
+                .get(3)
+                .map_or(Ok(None), |m| m.parse::<u32>().map(Some));
+            match (words[2].parse::<WorldCoord>(), minutes) {
+                (Ok(range), Ok(minutes)) => {
+                    let (x, y, z) = position;
+                    let since = minutes.map_or(0, |m| block_log::now().saturating_sub(m * 60));
+                    let changes =
+                        changes_in_log(world, |log| log.changes_near(x, y, z, range, since));
+                    describe_changes(world, &changes)
+                }
+                _ => HELP.to_string(),
+            }
+        }
+        ("log", n) if n > 3 && words[1] == "player" => match words[2].parse::<u32>() {
+            Ok(minutes) => {
+                let username = argument(3);
+                let since = block_log::now().saturating_sub(minutes * 60);
+                let changes = changes_in_log(world, |log| log.changes_by(&username, since));
+                describe_changes(world, &changes)
+            }
+            Err(_) => HELP.to_string(),
+        },
+        ("rollback", 4) if words[1] == "here" => {
+            match (words[2].parse::<WorldCoord>(), words[3].parse::<u32>()) {
+                (Ok(range), Ok(minutes)) => {
+                    let (x, y, z) = position;
+                    let since = block_log::now().saturating_sub(minutes * 60);
+                    let changes =
+                        changes_in_log(world, |log| log.changes_near(x, y, z, range, since));
+                    rollback(world, &changes, block_registry, broadcast)
+                }
+                _ => HELP.to_string(),
+            }
+        }
+        ("rollback", n) if n > 3 && words[1] == "player" => match words[2].parse::<u32>() {
+            Ok(minutes) => {
+                let username = argument(3);
+                let since = block_log::now().saturating_sub(minutes * 60);
+                let changes = changes_in_log(world, |log| log.changes_by(&username, since));
+                rollback(world, &changes, block_registry, broadcast)
+            }
+            Err(_) => HELP.to_string(),
+        },
+        ("backup", _) if world.world_folder().is_none() => {
+            "Backups are only available for worlds stored in a folder".to_string()
+        }
+        ("backup", 1) => {
+            backups.request();
+            "World backup requested".to_string()
+        }
+        ("backup", 2) if words[1] == "list" => {
+            let names = backup::list_backups(world.world_folder().unwrap());
+            if names.is_empty() {
+                "No backups found".to_string()
+            } else {
+                format!("Backups: {}", names.join(", "))
+            }
+        }
+        ("backup", 3) if words[1] == "restore" => {
+            match backup::request_restore(world.world_folder().unwrap(), words[2]) {
+                Ok(_) => format!(
+                    "Backup {} will be restored when the world is loaded again",
+                    words[2]
+                ),
+                Err(e) => format!("Cannot restore backup {}: {}", words[2], e),
+            }
+        }
+        ("schematic", 2) if words[1] == "list" => {
+            let names = schematic::list_schematics();
+            if names.is_empty() {
+                "No schematics found".to_string()
+            } else {
+                format!("Schematics: {}", names.join(", "))
+            }
+        }
+        ("schematic", 9) if words[1] == "copy" => {
+            let coords: Vec<WorldCoord> = words[2..8].iter().flat_map(|w| w.parse()).collect();
+            match (coords.len(), schematic::schematic_path(words[8])) {
+                (6, Some(path)) => {
+                    let from = (coords[0], coords[1], coords[2]);
+                    let to = (coords[3], coords[4], coords[5]);
+                    match world.copy_schematic(from, to, block_registry) {
+                        Ok(copy) => match copy.save(&path) {
+                            Ok(_) => format!(
+                                "Copied {}x{}x{} blocks to schematic {}",
+                                copy.size_x, copy.size_y, copy.size_z, words[8]
+                            ),
+                            Err(e) => format!("Cannot save schematic {}: {}", words[8], e),
+                        },
+                        Err(e) => e,
+                    }
+                }
+                _ => HELP.to_string(),
+            }
+        }
+        ("schematic", n) if n >= 3 && n <= 5 && words[1] == "paste" => {
+            let mirror = words[n - 1] == "mirror";
+            let quarter_turns = match (n, mirror) {
+                (3, _) | (4, true) => Ok(0),
+                _ => words[3].parse::<usize>(),
+            };
+            match (schematic::schematic_path(words[2]), quarter_turns) {
+                (Some(path), Ok(quarter_turns)) if n < 5 || mirror => {
+                    match Schematic::load(&path) {
+                        Ok(loaded) => {
+                            let pasted = loaded.transformed(quarter_turns, mirror);
+                            paste(world, &pasted, position, block_registry, broadcast)
+                        }
+                        Err(e) => format!("Cannot load schematic {}: {}", words[2], e),
+                    }
+                }
+                _ => HELP.to_string(),
+            }
+        }
+        _ => HELP.to_string(),
+    }
+}
+
+/// Paste a schematic and send the changed columns to the clients
+fn paste(
+    world: &mut ServerWorldHandler,
+    schematic: &Schematic,
+    position: (WorldCoord, WorldCoord, WorldCoord),
+    block_registry: &BlockRegistry,
+    broadcast: &mut Vec<ServerMessage>,
+) -> String {
+    match world.paste_schematic(schematic, position, block_registry, "schematic") {
+        Ok((changed, columns)) => {
+            for col in columns {
+                if let Some(block_data) = world.try_clone_existing_column(col) {
+                    broadcast.push(ServerMessage::ChunkColumn { col, block_data });
+                }
+            }
+            format!("Pasted schematic, {} blocks changed", changed)
+        }
+        Err(e) => e,
+    }
+}
+
+/// Query the block log of the world, worlds without a block log have no changes
+fn changes_in_log<F>(world: &mut ServerWorldHandler, query: F) -> Vec<BlockChange>
+where
+    F: FnOnce(&mut BlockLog) -> Vec<BlockChange>,
+{
+    world.block_log_mut().map(query).unwrap_or_default()
+}
+
+/// List the most recent changes
+fn describe_changes(world: &mut ServerWorldHandler, changes: &Vec<BlockChange>) -> String {
+    let now = block_log::now();
+    let mut lines = vec![format!("{} block changes found", changes.len())];
+    for change in changes.iter().rev().take(MAX_LISTED_CHANGES) {
+        lines.push(format!(
+            "{} min ago {} at {},{},{}: {} to {}",
+            now.saturating_sub(change.timestamp) / 60,
+            world
+                .block_log_mut()
+                .and_then(|log| log.username(change.user))
+                .unwrap_or("?")
+                .to_string(),
+            change.wbx,
+            change.wby,
+            change.wbz,
+            change.old_block,
+            change.new_block
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Undo the changes and send the restored blocks to the clients
+fn rollback(
+    world: &mut ServerWorldHandler,
+    changes: &Vec<BlockChange>,
+    block_registry: &BlockRegistry,
+    broadcast: &mut Vec<ServerMessage>,
+) -> String {
+    let restored = world.rollback(changes, block_registry, "rollback");
+    for (wbx, wby, wbz, block) in &restored {
+        broadcast.push(ServerMessage::SetBlock {
+            wbx: *wbx,
+            wby: *wby,
+            wbz: *wbz,
+            block: *block,
+        });
+    }
+    format!(
+        "Rolled back {} of {} block changes",
+        restored.len(),
+        changes.len()
+    )
+}
+
+fn set_client_op(clients: &mut Vec<Client>, username: &str, op: bool) {
+    for client in clients.iter_mut() {
+        if client.data.username == username {
+            client.set_op(op);
+            client.connection.send(ServerMessage::OpStatus { op });
+        }
+    }
+}
+
+/// Disconnect all signed in clients matching the filter, returns the number of disconnected clients
+fn disconnect_matching<F>(clients: &mut Vec<Client>, filter: F) -> usize
+where
+    F: Fn(&Client) -> bool,
+{
+    let mut count = 0;
+    for client in clients.iter_mut() {
+        if client.is_signed_in() && filter(client) {
+            client.connection.disconnect();
+            count += 1;
+        }
+    }
+    count
+}
@@ -0,0 +1,376 @@
+use crate::backup::world_files;
+use crate::region::sync_parent_dir;
+use common::block::{BlockDef, BlockRegistry};
+use common::comms::read_from::ReadFrom;
+use common::comms::write_to::WriteTo;
+use common::world_definition::{seed_world_id, WorldDef, WorldsStore, WORLD_DEF_FILE};
+use log::*;
+use serde::{Deserialize, Serialize};
+use snap::read::FrameDecoder;
+use snap::write::FrameEncoder;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// File extension of world archives
+pub const ARCHIVE_EXTENSION: &str = "yabw";
+/// Version of the archive layout, archives with another version cannot be imported
+pub const ARCHIVE_FORMAT_VERSION: u16 = 1;
+const MAGIC: &[u8; 4] = b"YABW";
+
+/// Describes the world in an archive
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorldManifest {
+    pub format_version: u16,
+    pub world: WorldDef,
+    /// The block registry the world was saved with, block kinds in the world refer to it
+    pub blocks: Vec<BlockDef>,
+}
+
+/// Export a world folder to a single archive file. The archive holds a manifest followed by
+/// all world files, compressed as a single stream.
+pub fn export_world(
+    world_folder: &Path,
+    registry: &BlockRegistry,
+    archive_path: &Path,
+) -> io::Result<WorldManifest> {
+    let world = match WorldDef::load(&world_folder.join(WORLD_DEF_FILE)) {
+        Some(world) => world,
+        None => return Err(invalid_data("world definition cannot be loaded")),
+    };
+    let manifest = WorldManifest {
+        format_version: ARCHIVE_FORMAT_VERSION,
+        world,
+        blocks: registry.all_blocks().clone(),
+    };
+    let files = world_files(world_folder)?;
+
+    // Write to a temporary file first, so a failed export never leaves a partial archive
+    let tmp_path = archive_path.with_extension(format!("{}.tmp", ARCHIVE_EXTENSION));
+    {
+        let mut encoder = FrameEncoder::new(BufWriter::new(File::create(&tmp_path)?));
+        encoder.write_all(MAGIC)?;
+        write_to(&mut encoder, &ARCHIVE_FORMAT_VERSION)?;
+        let manifest_bytes = serde_json::to_vec_pretty(&manifest).unwrap();
+        write_bytes(&mut encoder, &manifest_bytes)?;
+        write_to(&mut encoder, &(files.len() as u32))?;
+        for path in &files {
+            let name = path.file_name().unwrap().to_string_lossy().to_string();
+            write_to(&mut encoder, &name)?;
+            write_bytes(&mut encoder, &fs::read(path)?)?;
+        }
+        encoder.flush()?;
+        encoder
+            .into_inner()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+            .into_inner()
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?
+            .sync_all()?;
+    }
+    fs::rename(&tmp_path, archive_path)?;
+    info!(
+        "Exported world {} with {} files to {:?}",
+        manifest.world.id,
+        files.len(),
+        archive_path
+    );
+    Ok(manifest)
+}
+
+/// Read only the manifest of an archive
+pub fn read_manifest(archive_path: &Path) -> io::Result<WorldManifest> {
+    let mut decoder = FrameDecoder::new(BufReader::new(File::open(archive_path)?));
+    read_header(&mut decoder)
+}
+
+/// Import a world from an archive into the worlds folder. Fails if a world with the same
+/// ID exists or if the blocks of the archive do not match the given registry.
+pub fn import_world(archive_path: &Path, registry: &BlockRegistry) -> io::Result<WorldManifest> {
+    import_world_into(archive_path, registry, WorldsStore::new().path())
+}
+
+fn import_world_into(
+    archive_path: &Path,
+    registry: &BlockRegistry,
+    worlds_folder: &Path,
+) -> io::Result<WorldManifest> {
+    let mut decoder = FrameDecoder::new(BufReader::new(File::open(archive_path)?));
+    let mut manifest = read_header(&mut decoder)?;
+    check_blocks(&manifest.blocks, registry.all_blocks())?;
+    // Archives of worlds from before world IDs get the ID of their old folder
+    if manifest.world.id.is_empty() {
+        manifest.world.id = seed_world_id(manifest.world.seed);
+    }
+    if manifest.world.id.contains(|c| c == '/' || c == '\\') || manifest.world.id.starts_with('.') {
+        return Err(invalid_data(&format!(
+            "invalid world ID {} in archive",
+            manifest.world.id
+        )));
+    }
+    let world_folder = worlds_folder.join(&manifest.world.id);
+    if world_folder.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("world {} already exists", manifest.world.id),
+        ));
+    }
+
+    // Extract to a temporary folder that only gets the world folder name when complete
+    let tmp_folder = PathBuf::from(format!("{}.tmp", world_folder.to_string_lossy()));
+    if tmp_folder.exists() {
+        fs::remove_dir_all(&tmp_folder)?;
+    }
+    fs::create_dir_all(&tmp_folder)?;
+    let file_count = match extract_files(&mut decoder, &tmp_folder) {
+        Ok(file_count) => file_count,
+        Err(e) => {
+            if let Err(e) = fs::remove_dir_all(&tmp_folder) {
+                warn!(
+                    "Cannot remove partially imported world {:?}: {}",
+                    tmp_folder, e
+                );
+            }
+            return Err(e);
+        }
+    };
+    fs::rename(&tmp_folder, &world_folder)?;
+    sync_parent_dir(&world_folder)?;
+    info!(
+        "Imported world {} with {} files from {:?}",
+        manifest.world.id, file_count, archive_path
+    );
+    Ok(manifest)
+}
+
+/// List the archives in a folder
+pub fn list_archives(folder: &Path) -> Vec<PathBuf> {
+    let mut archives: Vec<PathBuf> = match fs::read_dir(folder) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |ext| ext == ARCHIVE_EXTENSION)
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    archives.sort();
+    archives
+}
+
+fn extract_files<R: Read>(reader: &mut R, folder: &Path) -> io::Result<u32> {
+    let file_count = read_u32(reader)?;
+    for _ in 0..file_count {
+        let name = String::read_from(reader).map_err(|e| invalid_data(&e.to_string()))?;
+        if name.is_empty() || name.contains(|c| c == '/' || c == '\\') || name.starts_with('.') {
+            return Err(invalid_data(&format!(
+                "invalid file name {} in archive",
+                name
+            )));
+        }
+        let bytes = read_bytes(reader)?;
+        let mut file = File::create(folder.join(&name))?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+    }
+    Ok(file_count)
+}
+
+fn read_header<R: Read>(reader: &mut R) -> io::Result<WorldManifest> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a world archive"));
+    }
+    let version = u16::read_from(reader).map_err(|e| invalid_data(&e.to_string()))?;
+    if version != ARCHIVE_FORMAT_VERSION {
+        return Err(invalid_data(&format!(
+            "archive format version {} is not supported",
+            version
+        )));
+    }
+    let manifest: WorldManifest = serde_json::from_slice(&read_bytes(reader)?)
+        .map_err(|e| invalid_data(&format!("invalid manifest: {}", e)))?;
+    Ok(manifest)
+}
+
+/// The world refers to blocks by index, so all blocks of the archive must be known by code
+/// at the same index
+fn check_blocks(archive_blocks: &Vec<BlockDef>, blocks: &Vec<BlockDef>) -> io::Result<()> {
+    if archive_blocks.len() > blocks.len() {
+        return Err(invalid_data(&format!(
+            "archive uses {} block types, only {} are known",
+            archive_blocks.len(),
+            blocks.len()
+        )));
+    }
+    for (archive_block, block) in archive_blocks.iter().zip(blocks.iter()) {
+        if archive_block.code != block.code {
+            return Err(invalid_data(&format!(
+                "archive block {} does not match block {}",
+                archive_block.code, block.code
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> io::Result<()> {
+    assert!(bytes.len() < std::u32::MAX as usize);
+    write_to(writer, &(bytes.len() as u32))?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    // The length is not trusted, the bytes are only allocated as they are read
+    let length = read_u32(reader)? as u64;
+    let mut bytes = Vec::new();
+    reader.take(length).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < length {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "archive is truncated",
+        ));
+    }
+    Ok(bytes)
+}
+
+fn write_to<W: Write, T: WriteTo<W>>(writer: &mut W, value: &T) -> io::Result<()> {
+    value
+        .write_to(writer)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    u32::read_from(reader).map_err(|e| invalid_data(&e.to_string()))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod archive_test {
+
+    use crate::archive::*;
+    use crate::region::Region;
+    use crate::world_store::test_world_def;
+    use common::chunk::ChunkColumnPos;
+    use common::world_type::GeneratorType;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    fn world_folder(root: &Path) -> PathBuf {
+        let folder = root.join("export").join("test");
+        fs::create_dir_all(&folder).unwrap();
+        test_world_def(GeneratorType::Flat, 8)
+            .save(&folder.join(WORLD_DEF_FILE))
+            .unwrap();
+        let mut region = Region::create(&folder.join("r0_0.reg")).unwrap();
+        region
+            .write_column(
+                ChunkColumnPos::new(1, 2),
+                &vec![vec![1, 2, 3], vec![4; 300]],
+            )
+            .unwrap();
+        drop(region);
+        fs::write(folder.join("block_log.dat"), vec![7u8; 26 * 3]).unwrap();
+        fs::write(folder.join("block_log_users.txt"), "builder\n").unwrap();
+        fs::write(folder.join("ops.json"), r#"{ "usernames": ["admin"] }"#).unwrap();
+        fs::write(
+            folder.join("claims.json"),
+            r#"{ "next_id": 1, "claims": [] }"#,
+        )
+        .unwrap();
+        folder
+    }
+
+    #[test]
+    fn export_and_import() {
+        let root = temp_folder("yab_archive_test");
+        let folder = world_folder(&root);
+        let registry = BlockRegistry::default();
+        let archive_path = root.join("test.yabw");
+        export_world(&folder, &registry, &archive_path).unwrap();
+        assert_eq!(list_archives(&root), vec![archive_path.clone()]);
+        assert_eq!(read_manifest(&archive_path).unwrap().world.id, "test");
+
+        // All world files are imported unchanged
+        let worlds_folder = root.join("worlds");
+        let manifest = import_world_into(&archive_path, &registry, &worlds_folder).unwrap();
+        assert_eq!(manifest.world.id, "test");
+        let imported = worlds_folder.join("test");
+        let mut names: Vec<String> = world_files(&imported)
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "block_log.dat",
+                "block_log_users.txt",
+                "claims.json",
+                "ops.json",
+                "r0_0.reg",
+                WORLD_DEF_FILE
+            ]
+        );
+        for name in &names {
+            assert_eq!(
+                fs::read(imported.join(name)).unwrap(),
+                fs::read(folder.join(name)).unwrap()
+            );
+        }
+        let mut region = Region::open(&imported.join("r0_0.reg")).unwrap();
+        assert_eq!(
+            region.read_column(ChunkColumnPos::new(1, 2)).unwrap(),
+            Some(vec![vec![1, 2, 3], vec![4; 300]])
+        );
+
+        // A world is not imported over an existing one
+        let e = import_world_into(&archive_path, &registry, &worlds_folder).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn truncated_archive_is_refused() {
+        let root = temp_folder("yab_archive_truncated_test");
+        let folder = world_folder(&root);
+        let registry = BlockRegistry::default();
+        let archive_path = root.join("test.yabw");
+        export_world(&folder, &registry, &archive_path).unwrap();
+
+        // The last file of the archive is cut off
+        let mut decoder = FrameDecoder::new(File::open(&archive_path).unwrap());
+        let mut bytes = Vec::new();
+        decoder.read_to_end(&mut bytes).unwrap();
+        let mut encoder = FrameEncoder::new(File::create(&archive_path).unwrap());
+        encoder.write_all(&bytes[..bytes.len() - 1]).unwrap();
+        drop(encoder);
+        let worlds_folder = root.join("worlds");
+        let e = import_world_into(&archive_path, &registry, &worlds_folder).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(fs::read_dir(&worlds_folder).unwrap().count(), 0);
+
+        // A length far beyond the end of the archive is not allocated
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        write_to(&mut header, &ARCHIVE_FORMAT_VERSION).unwrap();
+        write_to(&mut header, &std::u32::MAX).unwrap();
+        let mut encoder = FrameEncoder::new(File::create(&archive_path).unwrap());
+        encoder.write_all(&header).unwrap();
+        drop(encoder);
+        let e = read_manifest(&archive_path).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
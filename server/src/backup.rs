@@ -0,0 +1,383 @@
+use crate::region::sync_parent_dir;
+use common::chrono::Utc;
+use crossbeam::channel::{unbounded, Receiver, TryRecvError};
+use log::*;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread::Builder;
+use std::time::{Duration, Instant};
+
+// A restore requested while the world is running is applied when the world is loaded again
+const RESTORE_FILE: &str = "restore.txt";
+
+/// Takes rotating snapshots of a world folder in a background thread.
+/// Snapshots are stored in `backups/<world folder name>/<timestamp in milliseconds>`.
+pub struct Backups {
+    world_folder: PathBuf,
+    backup_folder: PathBuf,
+    interval: Option<Duration>,
+    retention: usize,
+    last_backup: Instant,
+    requested: bool,
+    running: Option<Receiver<io::Result<String>>>,
+}
+
+impl Backups {
+    /// An interval of zero minutes disables scheduled backups, the retention is the number of
+    /// snapshots that is kept
+    pub fn new(world_folder: &Path, interval_minutes: u64, retention: usize) -> Backups {
+        let interval = if interval_minutes > 0 {
+            Some(Duration::from_secs(interval_minutes * 60))
+        } else {
+            None
+        };
+        Backups {
+            world_folder: world_folder.to_path_buf(),
+            backup_folder: backup_folder(world_folder),
+            interval,
+            retention: retention.max(1),
+            last_backup: Instant::now(),
+            requested: false,
+            running: None,
+        }
+    }
+
+    /// Request a backup at the next opportunity
+    pub fn request(&mut self) {
+        self.requested = true;
+    }
+
+    /// Should a backup be started? The world must be saved before starting it.
+    pub fn is_due(&self) -> bool {
+        if self.is_running() {
+            return false;
+        }
+        self.requested
+            || self
+                .interval
+                .map_or(false, |interval| self.last_backup.elapsed() >= interval)
+    }
+
+    /// While a backup is running the world files must not be written
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+
+    /// Start copying the saved world files in the background
+    pub fn start(&mut self) {
+        let (sender, receiver) = unbounded();
+        let world_folder = self.world_folder.clone();
+        let backup_folder = self.backup_folder.clone();
+        let retention = self.retention;
+        Builder::new()
+            .name("yab-world-backup".to_string())
+            .spawn(move || {
+                let result = create_backup(&world_folder, &backup_folder);
+                if result.is_ok() {
+                    remove_old_backups(&backup_folder, retention);
+                }
+                sender.send(result).unwrap();
+            })
+            .unwrap();
+        self.running = Some(receiver);
+        self.requested = false;
+        self.last_backup = Instant::now();
+    }
+
+    /// Check whether a running backup has finished
+    pub fn update(&mut self) {
+        if let Some(receiver) = &self.running {
+            match receiver.try_recv() {
+                Ok(Ok(name)) => info!("World backup {} completed", name),
+                Ok(Err(e)) => error!("World backup failed: {}", e),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => error!("World backup thread stopped"),
+            }
+            self.running = None;
+        }
+    }
+
+    /// Block until a running backup has finished, e.g. before shutdown
+    pub fn wait(&mut self) {
+        if let Some(receiver) = self.running.take() {
+            match receiver.recv() {
+                Ok(Ok(name)) => info!("World backup {} completed", name),
+                Ok(Err(e)) => error!("World backup failed: {}", e),
+                Err(e) => error!("World backup thread stopped: {}", e),
+            }
+        }
+    }
+}
+
+/// Folder containing the backups of a world
+pub fn backup_folder(world_folder: &Path) -> PathBuf {
+    let world_name = world_folder.file_name().unwrap_or_default();
+    PathBuf::from("backups").join(world_name)
+}
+
+/// Names of the available backups of a world, oldest first
+pub fn list_backups(world_folder: &Path) -> Vec<String> {
+    list_backups_in(&backup_folder(world_folder))
+}
+
+/// Replace the files of a world that is not running with those of a backup
+pub fn restore_backup(world_folder: &Path, name: &str) -> io::Result<()> {
+    restore_backup_from(world_folder, &backup_folder(world_folder), name)
+}
+
+/// The backup is copied next to the world folder and then renamed into place, so the world is
+/// left as it was if the copy fails
+fn restore_backup_from(world_folder: &Path, backup_folder: &Path, name: &str) -> io::Result<()> {
+    let source = backup_folder.join(name);
+    if name.is_empty() || name.contains(|c| c == '/' || c == '\\') || !source.is_dir() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("backup {} not found", name),
+        ));
+    }
+    let restore_folder = sibling_folder(world_folder, "restore.tmp");
+    let replaced_folder = sibling_folder(world_folder, "replaced.tmp");
+    for folder in [&restore_folder, &replaced_folder].iter() {
+        if folder.exists() {
+            fs::remove_dir_all(folder)?;
+        }
+    }
+    fs::create_dir_all(&restore_folder)?;
+    for path in world_files(&source)? {
+        let target = restore_folder.join(path.file_name().unwrap());
+        fs::copy(&path, &target)?;
+        File::open(&target)?.sync_all()?;
+    }
+    // Damaged files are kept, so they can still be inspected
+    for entry in fs::read_dir(world_folder)? {
+        let path = entry?.path();
+        if path.to_string_lossy().ends_with(".damaged") {
+            fs::copy(&path, restore_folder.join(path.file_name().unwrap()))?;
+        }
+    }
+    fs::rename(world_folder, &replaced_folder)?;
+    if let Err(e) = fs::rename(&restore_folder, world_folder) {
+        fs::rename(&replaced_folder, world_folder)?;
+        return Err(e);
+    }
+    sync_parent_dir(world_folder)?;
+    if let Err(e) = fs::remove_dir_all(&replaced_folder) {
+        warn!("Cannot remove replaced world {:?}: {}", replaced_folder, e);
+    }
+    info!("Restored world {:?} from backup {}", world_folder, name);
+    Ok(())
+}
+
+/// Restore a backup the next time the world is loaded
+pub fn request_restore(world_folder: &Path, name: &str) -> io::Result<()> {
+    if !list_backups(world_folder).iter().any(|b| b == name) {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("backup {} not found", name),
+        ));
+    }
+    fs::write(world_folder.join(RESTORE_FILE), name)
+}
+
+/// Apply a restore that was requested while the world was running
+pub fn apply_requested_restore(world_folder: &Path) {
+    let restore_path = world_folder.join(RESTORE_FILE);
+    if let Ok(name) = fs::read_to_string(&restore_path) {
+        // The request is removed first, so a failed restore is not tried on every load
+        if let Err(e) = fs::remove_file(&restore_path) {
+            warn!("Cannot remove restore request {:?}: {}", restore_path, e);
+        }
+        if let Err(e) = restore_backup(world_folder, name.trim()) {
+            error!("Cannot restore backup {}: {}", name.trim(), e);
+        }
+    }
+}
+
+fn create_backup(world_folder: &Path, backup_folder: &Path) -> io::Result<String> {
+    fs::create_dir_all(backup_folder)?;
+    // Backups started in the same millisecond get a counter, an existing folder is never reused
+    let timestamp = Utc::now().format("%Y%m%d-%H%M%S-%3f").to_string();
+    let mut name = timestamp.clone();
+    let mut counter = 1;
+    let tmp_folder = loop {
+        let tmp_folder = backup_folder.join(format!("{}.tmp", name));
+        if !backup_folder.join(&name).exists() {
+            match fs::create_dir(&tmp_folder) {
+                Ok(()) => break tmp_folder,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+        name = format!("{}-{}", timestamp, counter);
+        counter += 1;
+    };
+    for path in world_files(world_folder)? {
+        let target = tmp_folder.join(path.file_name().unwrap());
+        fs::copy(&path, &target)?;
+        File::open(&target)?.sync_all()?;
+    }
+    // The backup only gets its final name when it is complete
+    let target_folder = backup_folder.join(&name);
+    if target_folder.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("backup {} already exists", name),
+        ));
+    }
+    fs::rename(&tmp_folder, &target_folder)?;
+    sync_parent_dir(&target_folder)?;
+    Ok(name)
+}
+
+/// Folder next to a world folder, named after the world with a suffix
+fn sibling_folder(world_folder: &Path, suffix: &str) -> PathBuf {
+    let world_name = world_folder.file_name().unwrap_or_default();
+    world_folder.with_file_name(format!("{}.{}", world_name.to_string_lossy(), suffix))
+}
+
+fn remove_old_backups(backup_folder: &Path, retention: usize) {
+    let backups = list_backups_in(backup_folder);
+    if backups.len() <= retention {
+        return;
+    }
+    for name in &backups[..backups.len() - retention] {
+        info!("Removing old world backup {}", name);
+        if let Err(e) = fs::remove_dir_all(backup_folder.join(name)) {
+            warn!("Cannot remove old world backup {}: {}", name, e);
+        }
+    }
+}
+
+fn list_backups_in(backup_folder: &Path) -> Vec<String> {
+    let mut backups: Vec<String> = match fs::read_dir(backup_folder) {
+        Ok(entries) => entries
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| !name.ends_with(".tmp"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    // Names are timestamps, so sorting them sorts by age
+    backups.sort();
+    backups
+}
+
+/// The saved files of a world, without temporary and damaged files and restore requests
+pub fn world_files(folder: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        if path.is_file()
+            && !name.ends_with(".tmp")
+            && !name.ends_with(".damaged")
+            && name != RESTORE_FILE
+        {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod backup_test {
+
+    use crate::backup::*;
+
+    fn temp_folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn backup_and_restore() {
+        let root = temp_folder("yab_backup_test");
+        let world_folder = root.join("world");
+        let backup_folder = root.join("backups").join("world");
+        fs::create_dir_all(&world_folder).unwrap();
+        fs::write(world_folder.join("world.json"), "saved").unwrap();
+        fs::write(world_folder.join("r0_0.reg"), "region").unwrap();
+        fs::write(world_folder.join("claims.json.tmp"), "partial").unwrap();
+        fs::write(world_folder.join("r1_0.reg.damaged"), "damaged").unwrap();
+        fs::write(world_folder.join(RESTORE_FILE), "name").unwrap();
+
+        // Only the saved world files are copied
+        let name = create_backup(&world_folder, &backup_folder).unwrap();
+        assert_eq!(list_backups_in(&backup_folder), vec![name.clone()]);
+        let mut files: Vec<String> = world_files(&backup_folder.join(&name))
+            .unwrap()
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["r0_0.reg", "world.json"]);
+
+        // Restoring removes files created after the backup and keeps damaged files
+        fs::write(world_folder.join("world.json"), "changed").unwrap();
+        fs::write(world_folder.join("r2_0.reg"), "new region").unwrap();
+        restore_backup_from(&world_folder, &backup_folder, &name).unwrap();
+        let read = |name: &str| fs::read_to_string(world_folder.join(name)).ok();
+        assert_eq!(read("world.json").as_deref(), Some("saved"));
+        assert_eq!(read("r0_0.reg").as_deref(), Some("region"));
+        assert_eq!(read("r1_0.reg.damaged").as_deref(), Some("damaged"));
+        assert_eq!(read("r2_0.reg"), None);
+        assert_eq!(read(RESTORE_FILE), None);
+        let mut folders: Vec<String> = fs::read_dir(&root)
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect();
+        folders.sort();
+        assert_eq!(folders, vec!["backups", "world"]);
+
+        // A failed restore leaves the world alone
+        fs::write(world_folder.join("world.json"), "changed").unwrap();
+        for name in &["missing", "", "../world"] {
+            let e = restore_backup_from(&world_folder, &backup_folder, name).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        }
+        assert_eq!(read("world.json").as_deref(), Some("changed"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn old_backups_are_removed() {
+        let backup_folder = temp_folder("yab_backup_prune_test");
+        let names = ["20240102-000000", "20240101-120000", "20240103-000000"];
+        for name in names.iter() {
+            fs::create_dir(backup_folder.join(name)).unwrap();
+        }
+        fs::create_dir(backup_folder.join("20240104-000000.tmp")).unwrap();
+        remove_old_backups(&backup_folder, 3);
+        assert_eq!(list_backups_in(&backup_folder).len(), 3);
+        remove_old_backups(&backup_folder, 2);
+        assert_eq!(
+            list_backups_in(&backup_folder),
+            vec!["20240102-000000", "20240103-000000"]
+        );
+        fs::remove_dir_all(&backup_folder).unwrap();
+    }
+
+    #[test]
+    fn backups_get_their_own_folder() {
+        let root = temp_folder("yab_backup_names_test");
+        let world_folder = root.join("world");
+        let backup_folder = root.join("backups").join("world");
+        fs::create_dir_all(&world_folder).unwrap();
+        fs::write(world_folder.join("world.json"), "saved").unwrap();
+        let mut names = Vec::new();
+        for _ in 0..5 {
+            names.push(create_backup(&world_folder, &backup_folder).unwrap());
+        }
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(unique.len(), names.len());
+        assert_eq!(list_backups_in(&backup_folder), unique);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,433 @@
+use common::block::Block;
+use common::comms::read_from::ReadFrom;
+use common::comms::write_to::WriteTo;
+use common::comms::CommsError;
+use common::world_pos::WorldCoord;
+use log::*;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Size in bytes of a single change record on disk
+const RECORD_SIZE: u64 = 26;
+// Size of the records of world format version 2 and older, with 16-bit coordinates
+const OLD_RECORD_SIZE: u64 = 20;
+const LOG_FILE: &str = "block_log.dat";
+
+/// A single logged block change
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockChange {
+    /// Seconds since the unix epoch
+    pub timestamp: u32,
+    /// Index of the user in the user list of the log
+    pub user: u16,
+    pub wbx: WorldCoord,
+    pub wby: WorldCoord,
+    pub wbz: WorldCoord,
+    pub old_block: Block,
+    pub new_block: Block,
+}
+
+/// Append-only log of all block changes in a world. Changes are stored as fixed size
+/// records in one file, the usernames they refer to are stored one per line in another, with
+/// line breaks and backslashes in names escaped.
+pub struct BlockLog {
+    usernames: Vec<String>,
+    user_index: HashMap<String, u16>,
+    log_path: PathBuf,
+    users_path: PathBuf,
+    // Number of usernames in the users file
+    saved_users: usize,
+    writer: BufWriter<File>,
+    // Changes that are written when saving is resumed
+    pending: Vec<BlockChange>,
+    saving_paused: bool,
+}
+
+impl BlockLog {
+    pub fn open(folder_path: &Path) -> io::Result<BlockLog> {
+        let log_path = folder_path.join(LOG_FILE);
+        let users_path = folder_path.join("block_log_users.txt");
+        let usernames: Vec<String> = match fs::read_to_string(&users_path) {
+            Ok(users) => users.lines().map(unescape_username).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e),
+        };
+        let mut user_index = HashMap::new();
+        for (index, username) in usernames.iter().enumerate() {
+            user_index.insert(username.clone(), index as u16);
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&log_path)?;
+        // Drop a partially written record, e.g. after a crash, so all records stay aligned
+        let length = file.metadata()?.len();
+        if length % RECORD_SIZE != 0 {
+            warn!("Block log ends with a partial record, truncating it");
+            file.set_len(length - length % RECORD_SIZE)?;
+        }
+        Ok(BlockLog {
+            saved_users: usernames.len(),
+            usernames,
+            user_index,
+            log_path,
+            users_path,
+            writer: BufWriter::new(file),
+            pending: Vec::new(),
+            saving_paused: false,
+        })
+    }
+
+    /// Record a block change by a user
+    pub fn log(
+        &mut self,
+        username: &str,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
+        old_block: Block,
+        new_block: Block,
+    ) {
+        let change = BlockChange {
+            timestamp: now(),
+            user: self.get_user(username),
+            wbx,
+            wby,
+            wbz,
+            old_block,
+            new_block,
+        };
+        if self.saving_paused {
+            self.pending.push(change);
+        } else {
+            self.write(&change);
+        }
+    }
+
+    /// While saving is paused, e.g. during a backup, changes are kept in memory
+    pub fn set_saving_paused(&mut self, paused: bool) {
+        self.saving_paused = paused;
+        if !paused {
+            for change in std::mem::take(&mut self.pending) {
+                self.write(&change);
+            }
+        }
+    }
+
+    /// Write buffered changes to disk
+    pub fn flush(&mut self) {
+        if let Err(e) = self.writer.flush() {
+            error!("Error flushing block log: {}", e);
+        }
+    }
+
+    /// Name of a user by index, None if the index is not in the users file
+    pub fn username(&self, user: u16) -> Option<&str> {
+        self.usernames.get(user as usize).map(|u| u.as_str())
+    }
+
+    /// Find the logged changes matching the filter, oldest first
+    pub fn query<F>(&mut self, filter: F) -> Vec<BlockChange>
+    where
+        F: Fn(&BlockChange) -> bool,
+    {
+        self.flush();
+        let mut changes = Vec::new();
+        let file = match File::open(&self.log_path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Cannot read block log: {}", e);
+                return changes;
+            }
+        };
+        let mut reader = BufReader::new(file);
+        while let Ok(change) = read_change(&mut reader) {
+            if filter(&change) {
+                changes.push(change);
+            }
+        }
+        changes.extend(self.pending.iter().filter(|c| filter(c)).cloned());
+        changes
+    }
+
+    /// Changes by a user since the given time
+    pub fn changes_by(&mut self, username: &str, since: u32) -> Vec<BlockChange> {
+        match self.user_index.get(username) {
+            Some(&user) => self.query(|c| c.user == user && c.timestamp >= since),
+            None => Vec::new(),
+        }
+    }
+
+    /// Changes within a cubic area around a position since the given time
+    pub fn changes_near(
+        &mut self,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
+        range: WorldCoord,
+        since: u32,
+    ) -> Vec<BlockChange> {
+        self.query(|c| {
+            c.timestamp >= since
+                && (c.wbx as i64 - wbx as i64).abs() <= range as i64
+                && (c.wby as i64 - wby as i64).abs() <= range as i64
+                && (c.wbz as i64 - wbz as i64).abs() <= range as i64
+        })
+    }
+
+    fn get_user(&mut self, username: &str) -> u16 {
+        if let Some(user) = self.user_index.get(username) {
+            return *user;
+        }
+        assert!(self.usernames.len() < std::u16::MAX as usize);
+        let user = self.usernames.len() as u16;
+        self.usernames.push(username.to_string());
+        self.user_index.insert(username.to_string(), user);
+        user
+    }
+
+    fn write(&mut self, change: &BlockChange) {
+        // New users are appended before the changes that refer to them, so existing indices
+        // never change
+        if self.saved_users < self.usernames.len() {
+            let new_users: Vec<String> = self.usernames[self.saved_users..]
+                .iter()
+                .map(|u| escape_username(u))
+                .collect();
+            let appended = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.users_path)
+                .and_then(|mut file| writeln!(file, "{}", new_users.join("\n")));
+            match appended {
+                Ok(_) => self.saved_users = self.usernames.len(),
+                Err(e) => error!("Error writing block log users: {}", e),
+            }
+        }
+        if let Err(e) = write_change(&mut self.writer, change) {
+            error!("Error writing to block log: {}", e);
+        }
+    }
+}
+
+/// Convert the block log in a world folder from 16-bit to 32-bit coordinates
+pub fn convert_block_log_coords(world_folder: &Path) -> io::Result<()> {
+    let log_path = world_folder.join(LOG_FILE);
+    if !log_path.exists() {
+        return Ok(());
+    }
+    let bytes = fs::read(&log_path)?;
+    let record_count = bytes.len() / OLD_RECORD_SIZE as usize;
+    let mut reader = Cursor::new(bytes);
+    let mut converted = Vec::with_capacity(record_count * RECORD_SIZE as usize);
+    for _ in 0..record_count {
+        let change = read_old_change(&mut reader).map_err(to_io_error)?;
+        write_change(&mut converted, &change).map_err(to_io_error)?;
+    }
+    // Replace the log only when the converted log is complete
+    let tmp_path = log_path.with_extension("dat.tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&converted)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, &log_path)?;
+    info!("Converted {} block log records", record_count);
+    Ok(())
+}
+
+/// Usernames come from clients, line breaks must not split them over several lines
+fn escape_username(username: &str) -> String {
+    username
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+fn unescape_username(line: &str) -> String {
+    let mut username = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            username.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => username.push('\n'),
+            Some('r') => username.push('\r'),
+            Some(other) => username.push(other),
+            None => username.push('\\'),
+        }
+    }
+    username
+}
+
+/// Current time in seconds since the unix epoch
+pub fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as u32
+}
+
+fn write_change<W: Write>(writer: &mut W, change: &BlockChange) -> Result<(), CommsError> {
+    change.timestamp.write_to(writer)?;
+    change.user.write_to(writer)?;
+    change.wbx.write_to(writer)?;
+    change.wby.write_to(writer)?;
+    change.wbz.write_to(writer)?;
+    change.old_block.write_to(writer)?;
+    change.new_block.write_to(writer)?;
+    Ok(())
+}
+
+fn read_change<R: std::io::Read>(reader: &mut R) -> Result<BlockChange, CommsError> {
+    Ok(BlockChange {
+        timestamp: u32::read_from(reader)?,
+        user: u16::read_from(reader)?,
+        wbx: WorldCoord::read_from(reader)?,
+        wby: WorldCoord::read_from(reader)?,
+        wbz: WorldCoord::read_from(reader)?,
+        old_block: Block::read_from(reader)?,
+        new_block: Block::read_from(reader)?,
+    })
+}
+
+fn read_old_change<R: std::io::Read>(reader: &mut R) -> Result<BlockChange, CommsError> {
+    Ok(BlockChange {
+        timestamp: u32::read_from(reader)?,
+        user: u16::read_from(reader)?,
+        wbx: i16::read_from(reader)? as WorldCoord,
+        wby: i16::read_from(reader)? as WorldCoord,
+        wbz: i16::read_from(reader)? as WorldCoord,
+        old_block: Block::read_from(reader)?,
+        new_block: Block::read_from(reader)?,
+    })
+}
+
+fn to_io_error(e: CommsError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod block_log_test {
+
+    use crate::block_log::*;
+
+    #[test]
+    fn changes_are_logged() {
+        let folder = std::env::temp_dir().join("yab_block_log_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let mut block_log = BlockLog::open(&folder).unwrap();
+        block_log.log("alice", 10, -20, 30, 0, 3);
+        block_log.log("bob", 12, -20, 30, 3, 0);
+        block_log.log("alice", 100, 100, 30, 0, 4);
+        drop(block_log);
+
+        // Changes and users are read back after reopening the log
+        let mut block_log = BlockLog::open(&folder).unwrap();
+        let changes = block_log.query(|_| true);
+        assert_eq!(changes.len(), 3);
+        assert_eq!(block_log.username(changes[1].user), Some("bob"));
+        assert_eq!(
+            (changes[0].wbx, changes[0].wby, changes[0].wbz),
+            (10, -20, 30)
+        );
+        assert_eq!((changes[1].old_block, changes[1].new_block), (3, 0));
+
+        assert_eq!(block_log.changes_by("alice", 0).len(), 2);
+        assert!(block_log.changes_by("carol", 0).is_empty());
+        assert_eq!(block_log.changes_near(11, -20, 30, 1, 0).len(), 2);
+        assert_eq!(block_log.changes_near(11, -20, 30, 0, 0).len(), 0);
+        assert_eq!(block_log.changes_near(100, 100, 31, 1, 0).len(), 1);
+        assert!(block_log
+            .changes_near(11, -20, 30, 1, now() + 60)
+            .is_empty());
+
+        // A new user gets the next index
+        block_log.log("carol", 0, 0, 0, 0, 1);
+        assert_eq!(block_log.changes_by("carol", 0)[0].user, 2);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn usernames_with_line_breaks() {
+        let folder = std::env::temp_dir().join("yab_block_log_names_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let names = ["evil\nbob", "back\\slash\\n", "\r", "", "bob"];
+        let mut block_log = BlockLog::open(&folder).unwrap();
+        for (i, name) in names.iter().enumerate() {
+            block_log.log(name, i as WorldCoord, 0, 0, 0, 1);
+        }
+        drop(block_log);
+
+        // Every change is still attributed to the user that made it
+        let mut block_log = BlockLog::open(&folder).unwrap();
+        for (i, name) in names.iter().enumerate() {
+            let changes = block_log.changes_by(name, 0);
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].wbx, i as WorldCoord);
+            assert_eq!(block_log.username(changes[0].user), Some(*name));
+        }
+        assert_eq!(block_log.username(names.len() as u16), None);
+        fs::remove_dir_all(&folder).unwrap();
+
+        // Without a folder the log cannot be opened
+        assert!(BlockLog::open(&folder).is_err());
+    }
+
+    #[test]
+    fn paused_changes_are_written_later() {
+        let folder = std::env::temp_dir().join("yab_block_log_pause_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let mut block_log = BlockLog::open(&folder).unwrap();
+        block_log.log("alice", 1, 2, 3, 0, 3);
+        block_log.flush();
+        let log_length = fs::metadata(folder.join(LOG_FILE)).unwrap().len();
+        block_log.set_saving_paused(true);
+        block_log.log("bob", 4, 5, 6, 0, 3);
+        block_log.flush();
+        assert_eq!(
+            fs::metadata(folder.join(LOG_FILE)).unwrap().len(),
+            log_length
+        );
+        let users = fs::read_to_string(folder.join("block_log_users.txt")).unwrap();
+        assert_eq!(users, "alice\n");
+        // Paused changes can be queried
+        assert_eq!(block_log.changes_by("bob", 0).len(), 1);
+
+        block_log.set_saving_paused(false);
+        drop(block_log);
+        let mut block_log = BlockLog::open(&folder).unwrap();
+        let changes = block_log.query(|_| true);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(block_log.username(changes[1].user), Some("bob"));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn partial_record_is_dropped() {
+        let folder = std::env::temp_dir().join("yab_block_log_partial_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let mut block_log = BlockLog::open(&folder).unwrap();
+        block_log.log("alice", 1, 2, 3, 0, 3);
+        drop(block_log);
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(folder.join(LOG_FILE))
+            .unwrap();
+        file.write_all(&[1, 2, 3]).unwrap();
+        drop(file);
+        let mut block_log = BlockLog::open(&folder).unwrap();
+        block_log.log("alice", 4, 5, 6, 0, 3);
+        let changes = block_log.query(|_| true);
+        assert_eq!(changes.len(), 2);
+        assert_eq!((changes[1].wbx, changes[1].wby, changes[1].wbz), (4, 5, 6));
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
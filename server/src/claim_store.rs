@@ -0,0 +1,263 @@
+use common::chunk::ChunkColumnPos;
+use common::claim::*;
+use common::world_pos::WorldCoord;
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ClaimList {
+    next_id: u32,
+    claims: Vec<Claim>,
+}
+
+/// Land claims of a world, stored in the world folder
+pub struct ClaimStore {
+    list: ClaimList,
+    tmp_path: PathBuf,
+    path: PathBuf,
+    saving_paused: bool,
+    unsaved_changes: bool,
+}
+
+impl ClaimStore {
+    pub fn load(folder_path: &Path) -> ClaimStore {
+        let path = folder_path.join("claims.json");
+        let tmp_path = folder_path.join("claims.json.tmp");
+        let list = match fs::read_to_string(&path) {
+            Ok(data_string) => match serde_json::from_str(&data_string) {
+                Ok(list) => Some(list),
+                Err(e) => {
+                    // Keep a copy, so the claims are not lost when the file is saved again
+                    let copy_path = path.with_extension("json.invalid");
+                    error!(
+                        "Error parsing {}, it is copied to {}: {}",
+                        path.to_string_lossy(),
+                        copy_path.to_string_lossy(),
+                        e
+                    );
+                    if let Err(e) = fs::copy(&path, &copy_path) {
+                        error!("Error copying {}: {}", path.to_string_lossy(), e);
+                    }
+                    None
+                }
+            },
+            Err(e) => {
+                debug!("Cannot load claims file: {}", e);
+                None
+            }
+        };
+        ClaimStore {
+            list: list.unwrap_or(ClaimList {
+                next_id: 1,
+                claims: Vec::new(),
+            }),
+            tmp_path,
+            path,
+            saving_paused: false,
+            unsaved_changes: false,
+        }
+    }
+
+    /// While saving is paused, e.g. during a backup, changes are saved when it is resumed
+    pub fn set_saving_paused(&mut self, paused: bool) {
+        self.saving_paused = paused;
+        if !paused && self.unsaved_changes {
+            self.save();
+        }
+    }
+
+    pub fn claims(&self) -> &Vec<Claim> {
+        &self.list.claims
+    }
+
+    /// Can the user change the block at this position?
+    pub fn may_build(&self, username: &str, wbx: WorldCoord, wby: WorldCoord) -> bool {
+        self.list
+            .claims
+            .iter()
+            .filter(|c| c.contains_block(wbx, wby))
+            .all(|c| c.is_member(username))
+    }
+
+    /// Claim an area for a player, returns the reason if this is not allowed
+    pub fn add(
+        &mut self,
+        owner: &str,
+        a: ChunkColumnPos,
+        b: ChunkColumnPos,
+        op: bool,
+    ) -> Result<u32, String> {
+        let claim = Claim::new(self.list.next_id, owner, a, b);
+        let (width, length) = claim.size();
+        if width < 1 || length < 1 {
+            return Err("claim corners are inverted".to_string());
+        }
+        let max_size = MAX_CLAIM_SIZE_COLUMNS as i64;
+        if !op && (width > max_size || length > max_size) {
+            return Err(format!(
+                "claim of {}x{} columns is too large",
+                width, length
+            ));
+        }
+        if !op
+            && self.list.claims.iter().filter(|c| c.owner == owner).count() >= MAX_CLAIMS_PER_PLAYER
+        {
+            return Err("maximum number of claims reached".to_string());
+        }
+        if self.list.claims.iter().any(|c| c.overlaps(&claim)) {
+            return Err("claim overlaps an existing claim".to_string());
+        }
+        let id = claim.id;
+        self.list.next_id += 1;
+        self.list.claims.push(claim);
+        self.changed();
+        Ok(id)
+    }
+
+    /// Remove a claim, only the owner or an operator can do this
+    pub fn remove(&mut self, claim_id: u32, username: &str, op: bool) -> Result<(), String> {
+        self.get_owned_claim(claim_id, username, op)?;
+        self.list.claims.retain(|c| c.id != claim_id);
+        self.changed();
+        Ok(())
+    }
+
+    /// Add or remove a member of a claim, only the owner or an operator can do this
+    pub fn set_member(
+        &mut self,
+        claim_id: u32,
+        username: &str,
+        op: bool,
+        member_username: &str,
+        member: bool,
+    ) -> Result<(), String> {
+        let claim = self.get_owned_claim(claim_id, username, op)?;
+        claim.members.retain(|m| m != member_username);
+        if member {
+            claim.members.push(member_username.to_string());
+        }
+        self.changed();
+        Ok(())
+    }
+
+    fn get_owned_claim(
+        &mut self,
+        claim_id: u32,
+        username: &str,
+        op: bool,
+    ) -> Result<&mut Claim, String> {
+        match self.list.claims.iter_mut().find(|c| c.id == claim_id) {
+            Some(claim) if op || claim.owner == username => Ok(claim),
+            Some(_) => Err(format!("claim {} is not owned by {}", claim_id, username)),
+            None => Err(format!("claim {} does not exist", claim_id)),
+        }
+    }
+
+    fn changed(&mut self) {
+        if self.saving_paused {
+            self.unsaved_changes = true;
+        } else {
+            self.save();
+        }
+    }
+
+    fn save(&mut self) {
+        self.unsaved_changes = false;
+        let data_string = serde_json::to_string_pretty(&self.list).unwrap();
+        if let Err(e) = fs::write(&self.tmp_path, data_string) {
+            error!(
+                "Error writing claims file {}: {}",
+                self.tmp_path.to_string_lossy(),
+                e
+            );
+            return;
+        }
+        if let Err(e) = std::fs::rename(&self.tmp_path, &self.path) {
+            error!(
+                "Error renaming claims file from {} to {}: {}",
+                self.tmp_path.to_string_lossy(),
+                self.path.to_string_lossy(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod claim_store_test {
+
+    use crate::claim_store::*;
+
+    #[test]
+    fn claims_are_kept() {
+        let folder = std::env::temp_dir().join("yab_claim_store_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let mut store = ClaimStore::load(&folder);
+        let id = store
+            .add(
+                "owner",
+                ChunkColumnPos::new(0, 0),
+                ChunkColumnPos::new(1, 1),
+                false,
+            )
+            .unwrap();
+        let store = ClaimStore::load(&folder);
+        assert_eq!(store.claims().len(), 1);
+        assert_eq!(store.claims()[0].id, id);
+        assert!(!store.may_build("stranger", 0, 0));
+        // A claims file with an error is kept aside before it is replaced
+        let broken = r#"{ "next_id": 2, "claims": [ "#;
+        fs::write(folder.join("claims.json"), broken).unwrap();
+        let mut store = ClaimStore::load(&folder);
+        assert!(store.claims().is_empty());
+        let copy = fs::read_to_string(folder.join("claims.json.invalid")).unwrap();
+        assert_eq!(copy, broken);
+        store
+            .add(
+                "owner",
+                ChunkColumnPos::new(0, 0),
+                ChunkColumnPos::new(0, 0),
+                false,
+            )
+            .unwrap();
+        let copy = fs::read_to_string(folder.join("claims.json.invalid")).unwrap();
+        assert_eq!(copy, broken);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn extreme_corners_are_refused() {
+        let folder = std::env::temp_dir().join("yab_claim_store_extreme_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let mut store = ClaimStore::load(&folder);
+        let min = ChunkColumnPos::new(std::i32::MIN, std::i32::MIN);
+        let max = ChunkColumnPos::new(std::i32::MAX, std::i32::MAX);
+        assert!(store.add("griefer", min, max, false).is_err());
+        assert!(store
+            .add(
+                "griefer",
+                ChunkColumnPos::new(std::i32::MIN, 0),
+                ChunkColumnPos::new(std::i32::MAX, 0),
+                false
+            )
+            .is_err());
+        assert!(store
+            .add(
+                "griefer",
+                ChunkColumnPos::new(std::i32::MAX, std::i32::MAX),
+                max,
+                false
+            )
+            .is_ok());
+        assert!(store.claims().iter().all(|c| c.size() == (1, 1)));
+        // Operators can claim areas of any size
+        assert!(store
+            .add("admin", min, ChunkColumnPos::new(0, 0), true)
+            .is_ok());
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1,27 +1,124 @@
 use common::comms::*;
 use common::{chunk::*, player::PlayerData};
 use std::collections::HashSet;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+// Time a player that may not fly can stay in the air without falling, a jump takes far less
+const MAX_HOVER_TIME: Duration = Duration::from_secs(2);
+// Height a player can jump above the last position where it was supported
+const MAX_JUMP_HEIGHT: f32 = 3.0;
+
+/// Position of a player in the air, to check that it falls
+struct Airborne {
+    lowest_z: f32,
+    lowest_since: Instant,
+}
+
+/// Checks that a player that may not fly falls when it is in the air
+#[derive(Default)]
+pub struct FlightCheck {
+    // Last position where the player was supported by blocks, None until the first position
+    // after signing in or an operator status change
+    last_supported: Option<(f32, f32, f32)>,
+    airborne: Option<Airborne>,
+}
+
+impl FlightCheck {
+    /// Check a new position of the player, given whether there are blocks next to or below
+    /// it. Returns the last position where the player was supported if it rose too high or
+    /// stayed in the air too long without falling.
+    pub fn check(
+        &mut self,
+        x: f32,
+        y: f32,
+        z: f32,
+        supported: bool,
+        now: Instant,
+    ) -> Option<(f32, f32, f32)> {
+        let last_supported = match self.last_supported {
+            Some(last_supported) => last_supported,
+            None => {
+                self.last_supported = Some((x, y, z));
+                (x, y, z)
+            }
+        };
+        if supported {
+            self.last_supported = Some((x, y, z));
+            self.airborne = None;
+            return None;
+        }
+        let airborne = self.airborne.get_or_insert(Airborne {
+            lowest_z: z,
+            lowest_since: now,
+        });
+        if z < airborne.lowest_z {
+            airborne.lowest_z = z;
+            airborne.lowest_since = now;
+        }
+        if z > last_supported.2 + MAX_JUMP_HEIGHT
+            || now.duration_since(airborne.lowest_since) > MAX_HOVER_TIME
+        {
+            self.airborne = None;
+            return Some(last_supported);
+        }
+        None
+    }
+
+    /// Start again from the next position
+    pub fn reset(&mut self) {
+        self.last_supported = None;
+        self.airborne = None;
+    }
+}
 
 // A single client connected to the server
 pub struct Client {
     pub player_id: u8,
+    pub spectator: bool,
     pub connection: CommChannel,
     _connect_time: Instant,
     pub authenticated: bool,
+    pub op: bool,
     pub data: PlayerData,
     chunk_subscriptions: HashSet<ChunkColumnPos>,
+    queued_message: Option<ClientMessage>,
+    pub flight_check: FlightCheck,
 }
 
 impl Client {
-    pub fn new(connection: CommChannel, player_id: u8) -> Client {
+    pub fn new(connection: CommChannel, player_id: u8, spectator: bool) -> Client {
         Client {
             player_id,
+            spectator,
             connection,
             _connect_time: Instant::now(),
             authenticated: false,
+            op: false,
             data: PlayerData::new(player_id, &format!("player-{}", player_id)),
             chunk_subscriptions: HashSet::new(),
+            queued_message: None,
+            flight_check: FlightCheck::default(),
+        }
+    }
+
+    /// Create a client for a connection of which the first message was already received
+    pub fn new_with_message(
+        connection: CommChannel,
+        player_id: u8,
+        spectator: bool,
+        first_message: ClientMessage,
+    ) -> Client {
+        let mut client = Client::new(connection, player_id, spectator);
+        client.queued_message = Some(first_message);
+        client
+    }
+
+    /// Receive the next message from this client, if available
+    pub fn try_receive(&mut self) -> Option<ClientMessage> {
+        if self.queued_message.is_some() {
+            self.queued_message.take()
+        } else {
+            self.connection.try_receive()
         }
     }
 
@@ -33,6 +130,13 @@ impl Client {
         self.data.username = username.clone();
         self.authenticated = true;
         self.chunk_subscriptions.clear();
+        self.flight_check.reset();
+    }
+
+    /// Change the operator status, flying is checked from the next position on
+    pub fn set_op(&mut self, op: bool) {
+        self.op = op;
+        self.flight_check.reset();
     }
 
     /// Update client position
@@ -59,3 +163,47 @@ impl Client {
         self.chunk_subscriptions.contains(&col)
     }
 }
+
+#[cfg(test)]
+mod client_test {
+
+    use crate::client::*;
+
+    #[test]
+    fn flying_is_detected() {
+        let mut check = FlightCheck::default();
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        assert_eq!(check.check(0.0, 0.0, 10.0, true, at(0)), None);
+
+        // Jumping and falling is fine
+        for (i, z) in [11.0, 12.0, 12.5, 12.0, 10.5].iter().enumerate() {
+            assert_eq!(check.check(0.0, 0.0, *z, false, at(i as u64 * 200)), None);
+        }
+        for i in 0..20 {
+            let z = 10.0 - i as f32;
+            assert_eq!(check.check(1.0, 0.0, z, false, at(1000 + i * 500)), None);
+        }
+        assert_eq!(check.check(1.0, 0.0, -10.0, true, at(11000)), None);
+
+        // Hovering is not
+        assert_eq!(check.check(2.0, 0.0, -9.0, false, at(11100)), None);
+        assert_eq!(check.check(3.0, 0.0, -9.0, false, at(12000)), None);
+        assert_eq!(
+            check.check(4.0, 0.0, -9.0, false, at(13200)),
+            Some((1.0, 0.0, -10.0))
+        );
+
+        // Neither is rising far above the ground
+        assert_eq!(check.check(0.0, 0.0, 0.0, true, at(14000)), None);
+        assert_eq!(
+            check.check(0.0, 0.0, 3.5, false, at(14100)),
+            Some((0.0, 0.0, 0.0))
+        );
+
+        // The first position after a reset is the new reference
+        check.reset();
+        assert_eq!(check.check(0.0, 0.0, 50.0, false, at(15000)), None);
+        assert_eq!(check.check(0.0, 0.0, 45.0, false, at(15500)), None);
+    }
+}
@@ -0,0 +1,134 @@
+use common::biome::*;
+use common::world_pos::WorldCoord;
+use noise::Perlin;
+
+use crate::generator::NoiseSource2D;
+
+// Scale of the temperature and humidity noise, biomes span a few hundred blocks
+const CLIMATE_SCALE: f64 = 0.002;
+// Climate distance over which neighbouring biomes blend
+const BLEND_DISTANCE: f64 = 0.15;
+
+/// Seed of the biome map of a world. Clients get this seed instead of the world seed, so they
+/// cannot rebuild the terrain noise from it.
+pub fn biome_seed(world_seed: u32) -> u32 {
+    let mut hash = (world_seed as u64 ^ 0x9e37_79b9_7f4a_7c15).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash ^= hash >> 31;
+    hash = hash.wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^= hash >> 29;
+    (hash >> 32) as u32
+}
+
+/// Chooses biomes from temperature and humidity noise fields. The map only depends on the
+/// seed, so clients can query the biome of any position as well. The seed comes from
+/// `biome_seed`.
+#[derive(Clone)]
+pub struct BiomeMap {
+    temperature_noise: NoiseSource2D<Perlin>,
+    humidity_noise: NoiseSource2D<Perlin>,
+}
+
+impl BiomeMap {
+    pub fn new(seed: u32) -> Self {
+        BiomeMap {
+            // Perlin noise is rarely near its extremes, so it is stretched to reach every biome
+            temperature_noise: NoiseSource2D::<Perlin>::new_perlin(seed.wrapping_add(1), -0.5, 1.5),
+            humidity_noise: NoiseSource2D::<Perlin>::new_perlin(seed.wrapping_add(2), -0.5, 1.5),
+        }
+    }
+
+    /// Temperature and humidity at a position, both from 0 to 1
+    pub fn climate(&self, x: WorldCoord, y: WorldCoord) -> (f64, f64) {
+        (
+            self.temperature_noise
+                .get(x as f64, y as f64, CLIMATE_SCALE)
+                .clamp(0.0, 1.0),
+            self.humidity_noise
+                .get(x as f64, y as f64, CLIMATE_SCALE)
+                .clamp(0.0, 1.0),
+        )
+    }
+
+    /// The biome with the climate closest to the climate at a position
+    pub fn biome_at(&self, x: WorldCoord, y: WorldCoord) -> Biome {
+        let weights = self.weights(x, y);
+        let mut biome = Biome::Hills;
+        for candidate in Biome::all().iter() {
+            if weights[candidate.id() as usize] > weights[biome.id() as usize] {
+                biome = *candidate;
+            }
+        }
+        biome
+    }
+
+    /// Weight of each biome at a position, indexed by biome ID and adding up to 1. Away from
+    /// biome borders a single biome has all the weight, near borders the weights change smoothly.
+    pub fn weights(&self, x: WorldCoord, y: WorldCoord) -> [f64; BIOME_COUNT] {
+        let (temperature, humidity) = self.climate(x, y);
+        let mut distances = [0.0; BIOME_COUNT];
+        for biome in Biome::all().iter() {
+            let (biome_temperature, biome_humidity) = biome_climate(*biome);
+            distances[biome.id() as usize] = ((temperature - biome_temperature).powi(2)
+                + (humidity - biome_humidity).powi(2))
+            .sqrt();
+        }
+        let closest = distances.iter().cloned().fold(f64::MAX, f64::min);
+        let mut weights = [0.0; BIOME_COUNT];
+        let mut total = 0.0;
+        for (weight, distance) in weights.iter_mut().zip(distances.iter()) {
+            *weight = (1.0 - (distance - closest) / BLEND_DISTANCE).max(0.0);
+            total += *weight;
+        }
+        for weight in weights.iter_mut() {
+            *weight /= total;
+        }
+        weights
+    }
+}
+
+/// The temperature and humidity at which a biome is most likely
+fn biome_climate(biome: Biome) -> (f64, f64) {
+    match biome {
+        Biome::Hills => (0.5, 0.45),
+        Biome::Alien => (0.2, 0.5),
+        Biome::Ocean => (0.5, 0.75),
+        Biome::Desert => (0.8, 0.25),
+    }
+}
+
+#[cfg(test)]
+mod biome_map_test {
+
+    use crate::generator::biome_map::*;
+
+    #[test]
+    fn weights_blend_biomes() {
+        let map = BiomeMap::new(1234);
+        let mut found = Vec::new();
+        for x in (-4096..4096).step_by(16) {
+            let weights = map.weights(x, 0);
+            assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+            // The chosen biome always has the largest weight
+            let biome = map.biome_at(x, 0);
+            assert!(weights.iter().all(|w| *w <= weights[biome.id() as usize]));
+            if !found.contains(&biome) {
+                found.push(biome);
+            }
+            // Weights change smoothly between neighbouring positions
+            let next = map.weights(x + 1, 0);
+            for (weight, next_weight) in weights.iter().zip(next.iter()) {
+                assert!((weight - next_weight).abs() < 0.1);
+            }
+        }
+        assert!(found.len() > 1);
+    }
+
+    #[test]
+    fn biome_seed_hides_world_seed() {
+        for seed in [0, 1, 1234, u32::MAX].iter() {
+            assert_ne!(biome_seed(*seed), *seed);
+            assert_eq!(biome_seed(*seed), biome_seed(*seed));
+        }
+        assert_ne!(biome_seed(1234), biome_seed(1235));
+    }
+}
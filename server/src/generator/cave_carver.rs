@@ -0,0 +1,220 @@
+use common::block::*;
+use common::world_definition::CaveSettings;
+use common::world_pos::WorldCoord;
+use noise::{Fbm, Perlin};
+
+use crate::generator::{NoiseSource2D, NoiseSource3D};
+
+// Rock kept above caves, except where they open up to the surface
+const MIN_ROOF_THICKNESS: usize = 6;
+// Half the width of tunnels in noise units at full density
+const TUNNEL_WIDTH: f64 = 0.06;
+// Part of the cavern noise range that is carved out at full density
+const CAVERN_SIZE: f64 = 0.3;
+// Depth below the surface that overhangs are carved in
+const OVERHANG_DEPTH: usize = 8;
+// Solid blocks kept at the top of the terrain above overhangs
+const OVERHANG_ROOF: usize = 2;
+// Part of the overhang noise range that is carved out at full strength
+const OVERHANG_SIZE: f64 = 0.4;
+
+/// Carves tunnels and caverns into generated terrain using 3D noise. Tunnels follow the
+/// positions where two noise fields are both near their middle value, caverns are carved where
+/// a third noise field is high. Overhangs are pockets carved by a fourth noise field just below
+/// the roof of the terrain, they undercut slopes and cliffs. The caves only depend on the seed
+/// and the position, so every generator worker carves the same caves.
+pub struct CaveCarver {
+    settings: CaveSettings,
+    tunnel_noise_a: NoiseSource3D<Perlin>,
+    tunnel_noise_b: NoiseSource3D<Perlin>,
+    cavern_noise: NoiseSource3D<Fbm>,
+    opening_noise: NoiseSource2D<Perlin>,
+    overhang_noise: NoiseSource3D<Fbm>,
+    water_block: Block,
+}
+
+impl CaveCarver {
+    pub fn new(seed: u32, settings: CaveSettings, block_registry: &BlockRegistry) -> Self {
+        CaveCarver {
+            settings,
+            tunnel_noise_a: NoiseSource3D::<Perlin>::new_perlin(seed.wrapping_add(10), 0.0, 1.0),
+            tunnel_noise_b: NoiseSource3D::<Perlin>::new_perlin(seed.wrapping_add(11), 0.0, 1.0),
+            cavern_noise: NoiseSource3D::<Fbm>::new_fbm(seed.wrapping_add(12), 0.0, 1.0),
+            opening_noise: NoiseSource2D::<Perlin>::new_perlin(seed.wrapping_add(13), 0.0, 1.0),
+            // Fbm noise stays close to its middle value, so it is stretched to reach the threshold
+            overhang_noise: NoiseSource3D::<Fbm>::new_fbm(seed.wrapping_add(14), -1.5, 2.5),
+            water_block: block_registry.block_kind_from_code("water"),
+        }
+    }
+
+    /// Carve the caves and overhangs of a 1x1 column of terrain. Caves stay below water and
+    /// below the roof of the terrain, unless they open up to the surface here.
+    pub fn carve(&self, x: WorldCoord, y: WorldCoord, blocks: &mut Vec<Block>) {
+        if self.settings.density <= 0.0 && self.settings.overhangs <= 0.0 {
+            return;
+        }
+        let top_z = match blocks.iter().rposition(|b| b.kind() != AIR_BLOCK_KIND) {
+            Some(top_z) => top_z,
+            None => return,
+        };
+        let opening = blocks[top_z].kind() != self.water_block
+            && self.opening_noise.get(x as f64, y as f64, 0.02)
+                > 1.0 - self.settings.surface_openings;
+        let roof_z = if opening {
+            top_z
+        } else {
+            top_z.saturating_sub(MIN_ROOF_THICKNESS)
+        };
+        if self.settings.overhangs > 0.0 {
+            self.carve_overhangs(x, y, top_z, blocks);
+        }
+        if self.settings.density <= 0.0 {
+            return;
+        }
+        let max_z = self.settings.max_z.min(roof_z);
+        for z in self.settings.min_z..=max_z {
+            let kind = blocks[z].kind();
+            if kind == AIR_BLOCK_KIND || kind == self.water_block {
+                continue;
+            }
+            // Water is not carried over into caves, it would hang in the air
+            if blocks
+                .get(z + 1)
+                .map_or(false, |b| b.kind() == self.water_block)
+            {
+                continue;
+            }
+            if self.is_cave(x, y, z) {
+                blocks[z] = AIR_BLOCK;
+            }
+        }
+    }
+
+    /// Carve pockets just below the surface. Where they reach the side of a slope the
+    /// ground above them is left hanging over.
+    fn carve_overhangs(&self, x: WorldCoord, y: WorldCoord, top_z: usize, blocks: &mut [Block]) {
+        // No overhangs under water, the water would hang in the air
+        if blocks[top_z].kind() == self.water_block || top_z < OVERHANG_DEPTH {
+            return;
+        }
+        let min_z = (top_z - OVERHANG_DEPTH).max(self.settings.min_z);
+        for z in min_z..=top_z - OVERHANG_ROOF {
+            if blocks[z].kind() == AIR_BLOCK_KIND {
+                continue;
+            }
+            // Overhangs are flattened, so they form ledges
+            let overhang = self
+                .overhang_noise
+                .get(x as f64, y as f64, z as f64 * 3.0, 0.03);
+            if overhang > 1.0 - OVERHANG_SIZE * self.settings.overhangs {
+                blocks[z] = AIR_BLOCK;
+            }
+        }
+    }
+
+    fn is_cave(&self, x: WorldCoord, y: WorldCoord, z: usize) -> bool {
+        let (x, y, z) = (x as f64, y as f64, z as f64);
+        // Tunnels are stretched horizontally
+        let width = TUNNEL_WIDTH * self.settings.density.sqrt();
+        let tunnel_a = self.tunnel_noise_a.get(x, y, z * 2.0, 0.015);
+        if (tunnel_a - 0.5).abs() < width {
+            let tunnel_b = self.tunnel_noise_b.get(x, y, z * 2.0, 0.015);
+            if (tunnel_b - 0.5).abs() < width {
+                return true;
+            }
+        }
+        let cavern = self.cavern_noise.get(x, y, z * 1.5, 0.01);
+        cavern > 1.0 - CAVERN_SIZE * self.settings.density
+    }
+}
+
+#[cfg(test)]
+mod cave_carver_test {
+
+    use crate::generator::cave_carver::*;
+
+    fn solid_column() -> Vec<Block> {
+        let mut blocks = vec![BEDROCK_BLOCK_KIND; 128];
+        for z in 100..128 {
+            blocks[z] = AIR_BLOCK;
+        }
+        blocks
+    }
+
+    #[test]
+    fn carve_caves() {
+        let registry = BlockRegistry::default();
+        let settings = CaveSettings {
+            min_z: 8,
+            max_z: 120,
+            density: 0.5,
+            surface_openings: 0.0,
+            overhangs: 0.0,
+        };
+        let carver = CaveCarver::new(1234, settings, &registry);
+        let mut carved = 0;
+        for x in 0..64 {
+            for y in 0..64 {
+                let mut blocks = solid_column();
+                carver.carve(x, y, &mut blocks);
+                // Caves stay within their depth range and below the roof
+                assert!(blocks[..8].iter().all(|b| *b == BEDROCK_BLOCK_KIND));
+                assert!(blocks[94..100].iter().all(|b| *b == BEDROCK_BLOCK_KIND));
+                carved += blocks.iter().filter(|b| **b == AIR_BLOCK).count() - 28;
+            }
+        }
+        let fraction = carved as f64 / (64.0 * 64.0 * 86.0);
+        assert!(fraction > 0.01 && fraction < 0.3);
+
+        let no_caves = CaveCarver::new(
+            1234,
+            CaveSettings {
+                density: 0.0,
+                overhangs: 0.0,
+                ..settings
+            },
+            &registry,
+        );
+        let mut blocks = solid_column();
+        no_caves.carve(0, 0, &mut blocks);
+        assert_eq!(blocks, solid_column());
+    }
+
+    #[test]
+    fn carve_overhangs() {
+        let registry = BlockRegistry::default();
+        let carver = CaveCarver::new(
+            1234,
+            CaveSettings {
+                min_z: 8,
+                max_z: 120,
+                density: 0.0,
+                surface_openings: 0.0,
+                overhangs: 1.0,
+            },
+            &registry,
+        );
+        let mut carved = 0;
+        for x in 0..64 {
+            for y in 0..64 {
+                let mut blocks = solid_column();
+                carver.carve(x, y, &mut blocks);
+                // Overhangs stay just below the surface and keep the top of the terrain
+                assert!(blocks[..91].iter().all(|b| *b == BEDROCK_BLOCK_KIND));
+                assert!(blocks[98..100].iter().all(|b| *b == BEDROCK_BLOCK_KIND));
+                carved += blocks.iter().filter(|b| **b == AIR_BLOCK).count() - 28;
+            }
+        }
+        let fraction = carved as f64 / (64.0 * 64.0 * 7.0);
+        assert!(fraction > 0.01 && fraction < 0.5);
+
+        // Nothing is carved under water
+        let mut blocks = solid_column();
+        blocks[99] = registry.block_kind_from_code("water");
+        let flooded = blocks.clone();
+        for x in 0..64 {
+            carver.carve(x, 0, &mut blocks);
+            assert_eq!(blocks, flooded);
+        }
+    }
+}
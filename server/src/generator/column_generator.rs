@@ -1,12 +1,27 @@
 use crate::generator::generators::*;
+use common::biome::*;
 use common::block::*;
 use common::chunk::*;
+use common::world_definition::{CaveSettings, GeneratorFingerprint, WorldDef};
+use common::world_pos::WorldCoord;
+use common::world_preset::{ObjectPreset, WorldPreset};
 use common::world_type::GeneratorType;
-use std::i16;
+use std::path::Path;
 use std::sync::Arc;
 
+use super::CaveCarver;
+use super::ObjectGenerator;
 use super::ObjectPlacer;
 use super::PregeneratedObject;
+use super::StructurePlanner;
+use super::TowerGenerator;
+use super::TreeGenerator;
+use super::{load_vox_objects, models_hash, VoxPlacement, OBJECTS_FOLDER};
+
+/// Version of the terrain generators, it must be raised when a change to the generators
+/// changes the terrain of existing worlds
+pub const GENERATOR_VERSION: u32 = 2;
+use super::{lookup_ores, Ore, OrePlacer};
 
 /// Generate complete columns of chunks of a given world type, based on a seed
 pub struct ColumnGenerator {
@@ -14,25 +29,178 @@ pub struct ColumnGenerator {
     flat_generator: FlatGenerator,
     water_generator: WaterWorldGenerator,
     alien_generator: AlienGenerator,
+    biome_generator: BiomeGenerator,
     poi_objects: ObjectPlacer,
     tree_objects: ObjectPlacer,
+    biome_poi_objects: ObjectPlacer,
+    biome_tree_objects: ObjectPlacer,
+    cave_carver: Option<CaveCarver>,
+    ore_placer: Option<OrePlacer>,
+    structure_planner: Option<StructurePlanner>,
+    poi_object_list: Arc<Vec<PregeneratedObject>>,
+    height_chunks: usize,
 }
 
 impl ColumnGenerator {
     pub fn new(
         seed: u32,
+        height_chunks: usize,
+        preset: &WorldPreset,
         poi_object_list: Arc<Vec<PregeneratedObject>>,
         tree_object_list: Arc<Vec<PregeneratedObject>>,
         block_registry: &BlockRegistry,
     ) -> Self {
+        let height_blocks = height_chunks * CHUNK_SIZE;
+        // Flat terrain is lowered in worlds that are not high enough for it
+        let flat_top_z = preset.flat.terrain_top_z.min(height_blocks / 2);
+        let objects = &preset.objects;
+        // Objects are placed once per grid cell, so cells cannot be empty
+        let poi_grid_size = objects.poi_grid_size.max(1);
+        let tree_grid_size = objects.tree_grid_size.max(1);
+        let biome_generator = BiomeGenerator::new(seed, height_blocks, block_registry);
+        let mut poi_densities = [0.0; BIOME_COUNT];
+        let mut tree_densities = [0.0; BIOME_COUNT];
+        for biome in Biome::all().iter() {
+            let settings = biome_generator.settings(*biome);
+            poi_densities[biome.id() as usize] = settings.poi_density;
+            tree_densities[biome.id() as usize] = settings.tree_density;
+        }
+        // Biome worlds place the same objects, with a density that depends on the biome
+        let biome_map = biome_generator.biome_map();
+        let biome_poi_objects = ObjectPlacer::new(
+            seed,
+            Arc::clone(&poi_object_list),
+            poi_grid_size,
+            objects.poi_grid_margin,
+            0.0,
+            false,
+            false,
+        )
+        .with_biome_densities(biome_map.clone(), poi_densities);
+        let biome_tree_objects = ObjectPlacer::new(
+            seed,
+            Arc::clone(&tree_object_list),
+            tree_grid_size,
+            objects.tree_grid_margin,
+            0.0,
+            true,
+            true,
+        )
+        .with_biome_densities(biome_map.clone(), tree_densities);
         ColumnGenerator {
-            hills_generator: HillsGenerator::new(seed, block_registry),
-            flat_generator: FlatGenerator::new(32, 36, block_registry),
-            water_generator: WaterWorldGenerator::new(seed, block_registry),
-            alien_generator: AlienGenerator::new(seed, block_registry),
-            poi_objects: ObjectPlacer::new(seed, poi_object_list, 32, 1, 0.2, false, false),
-            tree_objects: ObjectPlacer::new(seed, tree_object_list, 13, 0, 0.35, true, true),
+            hills_generator: HillsGenerator::new(
+                seed,
+                height_blocks,
+                &preset.hills,
+                block_registry,
+            ),
+            flat_generator: FlatGenerator::new(
+                flat_top_z.saturating_sub(preset.flat.soil_thickness),
+                flat_top_z,
+                height_blocks,
+                block_registry,
+            ),
+            water_generator: WaterWorldGenerator::new(seed, height_blocks, block_registry),
+            alien_generator: AlienGenerator::new(seed, height_blocks, block_registry),
+            biome_generator,
+            poi_objects: ObjectPlacer::new(
+                seed,
+                Arc::clone(&poi_object_list),
+                poi_grid_size,
+                objects.poi_grid_margin,
+                objects.poi_density,
+                false,
+                false,
+            ),
+            tree_objects: ObjectPlacer::new(
+                seed,
+                tree_object_list,
+                tree_grid_size,
+                objects.tree_grid_margin,
+                objects.tree_density,
+                true,
+                true,
+            ),
+            biome_poi_objects,
+            biome_tree_objects,
+            cave_carver: None,
+            ore_placer: None,
+            structure_planner: None,
+            poi_object_list,
+            height_chunks,
+        }
+    }
+
+    /// Create a generator with its own pregenerated objects, using the default preset
+    pub fn for_seed(seed: u32, height_chunks: usize, block_registry: &BlockRegistry) -> Self {
+        ColumnGenerator::for_preset(seed, height_chunks, &WorldPreset::default(), block_registry)
+    }
+
+    /// Create a generator with its own pregenerated objects
+    pub fn for_preset(
+        seed: u32,
+        height_chunks: usize,
+        preset: &WorldPreset,
+        block_registry: &BlockRegistry,
+    ) -> Self {
+        let (poi_object_list, tree_object_list) =
+            pregenerate_objects(seed, &preset.objects, block_registry);
+        ColumnGenerator::new(
+            seed,
+            height_chunks,
+            preset,
+            poi_object_list,
+            tree_object_list,
+            block_registry,
+        )
+    }
+
+    /// Create a generator for the terrain of a world, including its caves, ores and structures
+    pub fn for_world(world_def: &WorldDef, block_registry: &BlockRegistry) -> Self {
+        let mut generator = ColumnGenerator::for_preset(
+            world_def.seed,
+            world_def.height_chunks,
+            &world_def.preset,
+            block_registry,
+        );
+        if let Some(caves) = world_def.caves {
+            generator = generator.with_caves(world_def.seed, caves, block_registry);
         }
+        if !world_def.ores.is_empty() {
+            let ores = lookup_ores(&world_def.ores, block_registry);
+            generator = generator.with_ores(world_def.seed, Arc::new(ores));
+        }
+        if world_def.structures {
+            generator = generator.with_structures(world_def.seed, block_registry);
+        }
+        generator
+    }
+
+    /// Carve caves into the terrain of all world types
+    pub fn with_caves(
+        mut self,
+        seed: u32,
+        caves: CaveSettings,
+        block_registry: &BlockRegistry,
+    ) -> Self {
+        self.cave_carver = Some(CaveCarver::new(seed, caves, block_registry));
+        self
+    }
+
+    /// Place veins of ore in the terrain of all world types
+    pub fn with_ores(mut self, seed: u32, ores: Arc<Vec<Ore>>) -> Self {
+        self.ore_placer = Some(OrePlacer::new(seed, ores));
+        self
+    }
+
+    /// Plan villages of the points of interest and dungeons in the terrain of all world types
+    pub fn with_structures(mut self, seed: u32, block_registry: &BlockRegistry) -> Self {
+        self.structure_planner = Some(StructurePlanner::new(
+            seed,
+            Arc::clone(&self.poi_object_list),
+            block_registry,
+        ));
+        self
     }
 
     pub fn generate_column(
@@ -40,34 +208,60 @@ impl ColumnGenerator {
         world_type: GeneratorType,
         col: ChunkColumnPos,
     ) -> Vec<Chunk> {
-        let cwx = col.x * CHUNK_SIZE as i16;
-        let cwy = col.y * CHUNK_SIZE as i16;
+        let cwx = col.x * CHUNK_SIZE as WorldCoord;
+        let cwy = col.y * CHUNK_SIZE as WorldCoord;
         // Create empty column first
         let mut column = Vec::new();
-        for z in 0..WORLD_HEIGHT_CHUNKS {
+        for z in 0..self.height_chunks {
             column.push(Chunk::new_solid(
-                ChunkPos::new(col.x, col.y, z as i16),
+                ChunkPos::new(col.x, col.y, z as WorldCoord),
                 AIR_BLOCK,
             ));
         }
+        // Veins of ore may reach into this column from its neighbours
+        let last = CHUNK_SIZE as WorldCoord - 1;
+        let veins = match &self.ore_placer {
+            Some(ore_placer) => ore_placer.veins(cwx, cwy, cwx + last, cwy + last),
+            None => Vec::new(),
+        };
         // Generate the column in 1x1 columns of world height
         for rel_x in 0..CHUNK_SIZE {
             for rel_y in 0..CHUNK_SIZE {
-                let x = rel_x as i16 + cwx;
-                let y = rel_y as i16 + cwy;
+                let x = rel_x as WorldCoord + cwx;
+                let y = rel_y as WorldCoord + cwy;
                 // Generate the terrain
                 let generator: &mut dyn Generator = match world_type {
                     GeneratorType::Flat => &mut self.flat_generator,
                     GeneratorType::Water => &mut self.water_generator,
                     GeneratorType::Alien => &mut self.alien_generator,
                     GeneratorType::Default => &mut self.hills_generator,
+                    GeneratorType::Biomes => &mut self.biome_generator,
+                };
+                let (tree_objects, poi_objects) = match world_type {
+                    GeneratorType::Biomes => {
+                        (&mut self.biome_tree_objects, &mut self.biome_poi_objects)
+                    }
+                    _ => (&mut self.tree_objects, &mut self.poi_objects),
                 };
                 let mut blocks = generator.generate(x, y);
-                // Place trees and points of interest
-                self.tree_objects.place(x, y, &mut blocks, generator);
-                self.poi_objects.place(x, y, &mut blocks, generator);
+                if let Some(ore_placer) = &self.ore_placer {
+                    ore_placer.place(x, y, &veins, &mut blocks);
+                }
+                // Carve caves before placing objects, so objects are not hollowed out
+                if let Some(cave_carver) = &self.cave_carver {
+                    cave_carver.carve(x, y, &mut blocks);
+                }
+                // Stamp this column's slice of the villages and dungeons around it
+                if let Some(structure_planner) = &mut self.structure_planner {
+                    structure_planner.stamp(x, y, &mut blocks, generator);
+                }
+                // Place trees and points of interest, outside of the villages
+                let structures = self.structure_planner.as_mut();
+                tree_objects.place(x, y, &mut blocks, generator, structures);
+                let structures = self.structure_planner.as_mut();
+                poi_objects.place(x, y, &mut blocks, generator, structures);
                 // Copy the results into the chunk column
-                for cz in 0..WORLD_HEIGHT_CHUNKS {
+                for cz in 0..self.height_chunks {
                     let chunk = column.get_mut(cz).unwrap();
                     let chunk_bottom_z = cz * CHUNK_SIZE;
                     for rel_z in 0..CHUNK_SIZE {
@@ -76,7 +270,212 @@ impl ColumnGenerator {
                 }
             }
         }
-
+        // Use the most compact block storage, e.g. solid chunks below the surface
+        for chunk in column.iter_mut() {
+            chunk.optimize();
+        }
         column
     }
 }
+
+/// Fingerprint of the terrain generated for a preset, besides the world definition
+pub fn generator_fingerprint(preset: &WorldPreset) -> GeneratorFingerprint {
+    GeneratorFingerprint {
+        version: GENERATOR_VERSION,
+        models_hash: models_hash(Path::new(OBJECTS_FOLDER), &preset.objects),
+    }
+}
+
+/// Generate the points of interest and the trees that are placed in the world.
+/// These only depend on the seed, so generators can share them.
+pub fn pregenerate_objects(
+    seed: u32,
+    preset: &ObjectPreset,
+    block_registry: &BlockRegistry,
+) -> (Arc<Vec<PregeneratedObject>>, Arc<Vec<PregeneratedObject>>) {
+    let mut poi_object_list = {
+        let mut result = Vec::new();
+        let mut gen = TowerGenerator::new(seed, block_registry);
+        // Placers pick from the objects, so there is at least one of each
+        for _ in 0..preset.tower_count.max(1) {
+            result.push(gen.generate());
+        }
+        result
+    };
+    let mut tree_object_list = {
+        let mut result = Vec::new();
+        let mut gen = TreeGenerator::new(seed, block_registry);
+        for _ in 0..preset.tree_count.max(1) {
+            result.push(gen.generate());
+        }
+        result
+    };
+    // Models from .vox files that the preset lists are placed alongside the generated objects
+    for (placement, object) in load_vox_objects(Path::new(OBJECTS_FOLDER), preset, block_registry) {
+        match placement {
+            VoxPlacement::PointOfInterest => poi_object_list.push(object),
+            VoxPlacement::Tree => tree_object_list.push(object),
+        }
+    }
+    (Arc::new(poi_object_list), Arc::new(tree_object_list))
+}
+
+#[cfg(test)]
+mod column_generator_test {
+
+    use crate::generator::{lookup_ores, ColumnGenerator};
+    use common::block::*;
+    use common::chunk::*;
+    use common::ore_definition::{load_ore_definitions, ORES_FILE};
+    use common::world_definition::CaveSettings;
+    use common::world_preset::*;
+    use common::world_type::GeneratorType;
+    use std::path::Path;
+    use std::sync::Arc;
+
+    #[test]
+    fn caves_are_deterministic() {
+        let registry = BlockRegistry::default();
+        let new_generator = || {
+            ColumnGenerator::for_seed(1, DEFAULT_WORLD_HEIGHT_CHUNKS, &registry).with_caves(
+                1,
+                CaveSettings::default(),
+                &registry,
+            )
+        };
+        // Separate generators, like the generator workers, carve the same caves
+        let mut generator_a = new_generator();
+        let mut generator_b = new_generator();
+        let mut plain = ColumnGenerator::for_seed(1, DEFAULT_WORLD_HEIGHT_CHUNKS, &registry);
+        let mut carved = false;
+        for x in 0..4 {
+            let col = ChunkColumnPos::new(x, 0);
+            let column_a = generator_a.generate_column(GeneratorType::Default, col);
+            let column_b = generator_b.generate_column(GeneratorType::Default, col);
+            let column_plain = plain.generate_column(GeneratorType::Default, col);
+            for ((chunk_a, chunk_b), chunk_plain) in column_a
+                .iter()
+                .zip(column_b.iter())
+                .zip(column_plain.iter())
+            {
+                for rel_x in 0..CHUNK_SIZE {
+                    for rel_y in 0..CHUNK_SIZE {
+                        for rel_z in 0..CHUNK_SIZE {
+                            let block = chunk_a.get_block(rel_x, rel_y, rel_z);
+                            assert_eq!(block, chunk_b.get_block(rel_x, rel_y, rel_z));
+                            if block != chunk_plain.get_block(rel_x, rel_y, rel_z) {
+                                assert_eq!(block, AIR_BLOCK);
+                                carved = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert!(carved);
+    }
+
+    #[test]
+    fn ore_frequencies() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets");
+        let registry = BlockRegistry::load_or_create(&assets.join("blocks.json")).unwrap();
+        let ores = lookup_ores(&load_ore_definitions(&assets.join(ORES_FILE)), &registry);
+        let mut generator = ColumnGenerator::for_seed(1, DEFAULT_WORLD_HEIGHT_CHUNKS, &registry)
+            .with_ores(1, Arc::new(ores.clone()));
+        let stone = registry.block_kind_from_code("stone");
+        let mut ore_counts = vec![0; ores.len()];
+        let mut stone_counts = vec![0; ores.len()];
+        for x in 0..8 {
+            for y in 0..8 {
+                let col = ChunkColumnPos::new(x, y);
+                // Biome terrain has no ores of its own
+                let column = generator.generate_column(GeneratorType::Biomes, col);
+                for (cz, chunk) in column.iter().enumerate() {
+                    for rel_x in 0..CHUNK_SIZE {
+                        for rel_y in 0..CHUNK_SIZE {
+                            for rel_z in 0..CHUNK_SIZE {
+                                let z = cz * CHUNK_SIZE + rel_z;
+                                let block = chunk.get_block(rel_x, rel_y, rel_z);
+                                for (index, ore) in ores.iter().enumerate() {
+                                    if block == ore.block {
+                                        assert!(z >= ore.min_z && z <= ore.max_z);
+                                        ore_counts[index] += 1;
+                                    }
+                                    if (block == stone || block == ore.block)
+                                        && z >= ore.min_z
+                                        && z <= ore.max_z
+                                    {
+                                        stone_counts[index] += 1;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        // The share of the stone taken by each ore is close to its frequency times its vein size
+        for (index, ore) in ores.iter().enumerate() {
+            let expected = ore.frequency * ore.vein_size as f64 / 32768.0;
+            let share = ore_counts[index] as f64 / stone_counts[index] as f64;
+            assert!(share > expected * 0.5 && share < expected * 1.5);
+        }
+    }
+
+    #[test]
+    fn terrain_fits_low_worlds() {
+        let registry = BlockRegistry::default();
+        let water = registry.block_kind_from_code("water");
+        let mut generator = ColumnGenerator::for_seed(1, MIN_WORLD_HEIGHT_CHUNKS, &registry);
+        let top_z = MIN_WORLD_HEIGHT_CHUNKS * CHUNK_SIZE - 1;
+        for world_type in [
+            GeneratorType::Default,
+            GeneratorType::Water,
+            GeneratorType::Alien,
+        ] {
+            let mut has_water = false;
+            for x in 0..4 {
+                let column = generator.generate_column(world_type, ChunkColumnPos::new(x, 0));
+                let block = |z: usize| column[z / CHUNK_SIZE].get_block(0, 0, z % CHUNK_SIZE);
+                // The terrain is lower than the world, with air above it
+                assert_ne!(block(0), AIR_BLOCK);
+                assert_eq!(block(top_z).kind(), AIR_BLOCK_KIND);
+                has_water |= (0..=top_z).any(|z| block(z) == water);
+            }
+            if world_type == GeneratorType::Water {
+                assert!(has_water);
+            }
+        }
+    }
+
+    #[test]
+    fn presets_change_terrain() {
+        let registry = BlockRegistry::default();
+        let preset = WorldPreset {
+            flat: FlatPreset {
+                terrain_top_z: 20,
+                soil_thickness: 2,
+            },
+            ..WorldPreset::default()
+        };
+        let grass = registry.block_kind_from_code("grass");
+        // Trees may stand on the grass, so look for the grass from below
+        let grass_z = |generator: &mut ColumnGenerator| {
+            let column = generator.generate_column(GeneratorType::Flat, ChunkColumnPos::new(0, 0));
+            (0..column.len() * CHUNK_SIZE)
+                .find(|z| column[z / CHUNK_SIZE].get_block(0, 0, z % CHUNK_SIZE) == grass)
+                .unwrap()
+        };
+        let mut default_generator = ColumnGenerator::for_seed(1, 8, &registry);
+        assert_eq!(grass_z(&mut default_generator), 36);
+        let mut generator = ColumnGenerator::for_preset(1, 8, &preset, &registry);
+        assert_eq!(grass_z(&mut generator), 20);
+        // The preset files in the assets can be loaded
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets");
+        let presets = load_or_create_presets(&assets.join(PRESETS_FOLDER));
+        assert_eq!(
+            find_preset(&presets, DEFAULT_PRESET_NAME),
+            Some(WorldPreset::default())
+        );
+    }
+}
@@ -1,15 +1,14 @@
-use common::{
-    block::*,
-    chunk::{CHUNK_SIZE, WORLD_HEIGHT_CHUNKS},
-};
+use common::block::*;
+use common::world_pos::WorldCoord;
 use noise::{Fbm, Perlin};
 
 use crate::generator::{NoiseSource2D, NoiseSource3D};
 
-use super::Generator;
+use super::{scale_terrain_z, Generator};
 
 /// Generates an extreme alien landscape covered in ice, without any water but with resource placement
 pub struct AlienGenerator {
+    height_blocks: usize,
     terrain_noise: NoiseSource3D<Fbm>,
     resource_type_noise: NoiseSource2D<Perlin>,
     resource_density_noise: NoiseSource3D<Perlin>,
@@ -23,7 +22,7 @@ pub struct AlienGenerator {
 }
 
 impl AlienGenerator {
-    pub fn new(seed: u32, block_registry: &BlockRegistry) -> Self {
+    pub fn new(seed: u32, height_blocks: usize, block_registry: &BlockRegistry) -> Self {
         let stone_block = block_registry.block_kind_from_code("stone");
         let gold_block = block_registry.block_kind_from_code("gold_ore");
         let iron_block = block_registry.block_kind_from_code("iron_ore");
@@ -31,11 +30,12 @@ impl AlienGenerator {
         let ice_block = block_registry.block_kind_from_code("ice");
 
         AlienGenerator {
+            height_blocks,
             terrain_noise: NoiseSource3D::<Fbm>::new_fbm(seed, 0.0, 1.0),
             resource_type_noise: NoiseSource2D::<Perlin>::new_perlin(seed, 0.0, 2.0),
             resource_density_noise: NoiseSource3D::<Perlin>::new_perlin(seed, 0.0, 1.0),
-            terrain_min_z: 16,
-            terrain_z_range: 230,
+            terrain_min_z: scale_terrain_z(16, height_blocks),
+            terrain_z_range: scale_terrain_z(230, height_blocks).max(1),
             stone_block,
             gold_block,
             iron_block,
@@ -44,7 +44,7 @@ impl AlienGenerator {
         }
     }
 
-    fn determine_block(&mut self, z: usize, x: i16, y: i16) -> u32 {
+    fn determine_block(&mut self, z: usize, x: WorldCoord, y: WorldCoord) -> u32 {
         let block = if z < self.terrain_min_z {
             self.stone_block
         } else if z > self.terrain_min_z + self.terrain_z_range + 16 {
@@ -78,7 +78,7 @@ impl AlienGenerator {
 
     fn add_soil(&self, blocks: &mut Vec<Block>, soil_thickness: usize) {
         let mut soil_added = 0;
-        for z in 0..WORLD_HEIGHT_CHUNKS * CHUNK_SIZE {
+        for z in 0..self.height_blocks {
             let block = blocks[z].kind();
             if block == AIR_BLOCK_KIND || block == self.water_block {
                 if soil_added < soil_thickness {
@@ -93,17 +93,17 @@ impl AlienGenerator {
 }
 
 impl Generator for AlienGenerator {
-    fn generate(&mut self, x: i16, y: i16) -> Vec<Block> {
-        let mut blocks = vec![AIR_BLOCK; WORLD_HEIGHT_CHUNKS * CHUNK_SIZE];
+    fn generate(&mut self, x: WorldCoord, y: WorldCoord) -> Vec<Block> {
+        let mut blocks = vec![AIR_BLOCK; self.height_blocks];
         // Generate rocks and water
-        for z in 0..WORLD_HEIGHT_CHUNKS * CHUNK_SIZE {
+        for z in 0..self.height_blocks {
             blocks[z] = self.determine_block(z, x, y);
         }
         self.add_soil(&mut blocks, 2);
         blocks
     }
 
-    fn determine_rock_water_top(&mut self, x: i16, y: i16) -> (usize, usize, usize) {
+    fn determine_rock_water_top(&mut self, x: WorldCoord, y: WorldCoord) -> (usize, usize, usize) {
         let mut rock_top_z = 0;
         let water_top_z = 0;
         let mut top_z = 0;
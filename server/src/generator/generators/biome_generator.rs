@@ -0,0 +1,216 @@
+use common::biome::*;
+use common::block::*;
+use common::world_pos::WorldCoord;
+use noise::{Fbm, Perlin};
+
+use super::Generator;
+use crate::generator::{biome_seed, BiomeMap, NoiseSource2D};
+
+/// Terrain and object settings of a biome
+pub struct BiomeSettings {
+    /// Terrain height relative to the water level, at the lowest and highest terrain noise
+    pub min_height: f64,
+    pub max_height: f64,
+    /// Height of the small-scale variation added to the terrain
+    pub detail_height: f64,
+    pub surface_block: Block,
+    pub soil_block: Block,
+    /// Thickness of the soil, including the surface block
+    pub soil_thickness: usize,
+    pub tree_density: f64,
+    pub poi_density: f64,
+}
+
+/// Generates terrain of several biomes. Each biome has its own terrain height and surface,
+/// heights are blended by the biome weights so there are no cliffs at biome borders.
+pub struct BiomeGenerator {
+    height_blocks: usize,
+    water_z: usize,
+    biome_map: BiomeMap,
+    terrain_noise: NoiseSource2D<Fbm>,
+    detail_noise: NoiseSource2D<Perlin>,
+    biomes: Vec<BiomeSettings>,
+    stone_block: Block,
+    sand_block: Block,
+    water_block: Block,
+}
+
+impl BiomeGenerator {
+    pub fn new(seed: u32, height_blocks: usize, block_registry: &BlockRegistry) -> Self {
+        let stone_block = block_registry.block_kind_from_code("stone");
+        let dirt_block = block_registry.block_kind_from_code("dirt");
+        let grass_block = block_registry.block_kind_from_code("grass");
+        let sand_block = block_registry.block_kind_from_code("sand");
+        let sandstone_block = block_registry.block_kind_from_code("sandstone");
+        let ice_block = block_registry.block_kind_from_code("ice");
+        let water_block = block_registry.block_kind_from_code("water");
+
+        // Settings are indexed by biome ID
+        let biomes = Biome::all()
+            .iter()
+            .map(|biome| match biome {
+                Biome::Hills => BiomeSettings {
+                    min_height: -4.0,
+                    max_height: 48.0,
+                    detail_height: 4.0,
+                    surface_block: grass_block,
+                    soil_block: dirt_block,
+                    soil_thickness: 3,
+                    tree_density: 0.35,
+                    poi_density: 0.2,
+                },
+                Biome::Alien => BiomeSettings {
+                    min_height: 8.0,
+                    max_height: 110.0,
+                    detail_height: 12.0,
+                    surface_block: ice_block,
+                    soil_block: ice_block,
+                    soil_thickness: 2,
+                    tree_density: 0.0,
+                    poi_density: 0.05,
+                },
+                Biome::Ocean => BiomeSettings {
+                    min_height: -40.0,
+                    max_height: -8.0,
+                    detail_height: 2.0,
+                    surface_block: sand_block,
+                    soil_block: sand_block,
+                    soil_thickness: 3,
+                    tree_density: 0.0,
+                    poi_density: 0.0,
+                },
+                Biome::Desert => BiomeSettings {
+                    min_height: 1.0,
+                    max_height: 14.0,
+                    detail_height: 3.0,
+                    surface_block: sand_block,
+                    soil_block: sandstone_block,
+                    soil_thickness: 6,
+                    tree_density: 0.02,
+                    poi_density: 0.1,
+                },
+            })
+            .collect();
+
+        BiomeGenerator {
+            height_blocks,
+            // The water level is lowered in worlds that are not high enough for it
+            water_z: 80.min(height_blocks / 3),
+            biome_map: BiomeMap::new(biome_seed(seed)),
+            terrain_noise: NoiseSource2D::<Fbm>::new_fbm(seed, -0.5, 1.5),
+            detail_noise: NoiseSource2D::<Perlin>::new_perlin(seed, 0.0, 1.0),
+            biomes,
+            stone_block,
+            sand_block,
+            water_block,
+        }
+    }
+
+    pub fn biome_map(&self) -> &BiomeMap {
+        &self.biome_map
+    }
+
+    pub fn settings(&self, biome: Biome) -> &BiomeSettings {
+        &self.biomes[biome.id() as usize]
+    }
+
+    /// The z coordinate of the top block of the terrain and the biome with the largest weight
+    fn terrain_top(&self, x: WorldCoord, y: WorldCoord) -> (usize, &BiomeSettings) {
+        let weights = self.biome_map.weights(x, y);
+        let terrain = self
+            .terrain_noise
+            .get(x as f64, y as f64, 0.005)
+            .clamp(0.0, 1.0);
+        let detail = self.detail_noise.get(x as f64, y as f64, 0.05);
+        let mut height = 0.0;
+        let mut dominant = 0;
+        for (id, (weight, settings)) in weights.iter().zip(self.biomes.iter()).enumerate() {
+            height += weight
+                * (settings.min_height
+                    + (settings.max_height - settings.min_height) * terrain
+                    + settings.detail_height * detail);
+            if *weight > weights[dominant] {
+                dominant = id;
+            }
+        }
+        let top_z = (self.water_z as f64 + height).clamp(1.0, self.height_blocks as f64 - 1.0);
+        (top_z as usize, &self.biomes[dominant])
+    }
+}
+
+impl Generator for BiomeGenerator {
+    fn generate(&mut self, x: WorldCoord, y: WorldCoord) -> Vec<Block> {
+        let (top_z, settings) = self.terrain_top(x, y);
+        // Surfaces at the water level are beaches
+        let beach = top_z <= self.water_z + 1;
+        let rock_top_z = top_z.saturating_sub(settings.soil_thickness);
+        let mut blocks = vec![AIR_BLOCK; self.height_blocks];
+        for z in 0..self.height_blocks {
+            blocks[z] = if z <= rock_top_z {
+                self.stone_block
+            } else if z <= top_z {
+                if beach {
+                    self.sand_block
+                } else if z == top_z {
+                    settings.surface_block
+                } else {
+                    settings.soil_block
+                }
+            } else if z <= self.water_z {
+                self.water_block
+            } else {
+                AIR_BLOCK
+            };
+        }
+        blocks
+    }
+
+    fn determine_rock_water_top(&mut self, x: WorldCoord, y: WorldCoord) -> (usize, usize, usize) {
+        let (top_z, settings) = self.terrain_top(x, y);
+        let rock_top_z = top_z.saturating_sub(settings.soil_thickness);
+        let water_top_z = if top_z < self.water_z {
+            self.water_z
+        } else {
+            0
+        };
+        (rock_top_z, water_top_z, top_z)
+    }
+}
+
+#[cfg(test)]
+mod biome_generator_test {
+
+    use crate::generator::generators::*;
+    use common::block::*;
+    use std::path::Path;
+
+    #[test]
+    fn biome_borders_are_smooth() {
+        // The default registry lacks the ice and sandstone of some biomes
+        let blocks_path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets/blocks.json");
+        let registry = BlockRegistry::load_or_create(&blocks_path).unwrap();
+        let water_block = registry.block_kind_from_code("water");
+        let mut generator = BiomeGenerator::new(1234, 1024, &registry);
+        let mut last = None;
+        let mut borders = 0;
+        for x in -4096..4096 {
+            let (_, _, top_z) = generator.determine_rock_water_top(x, 0);
+            let biome = generator.biome_map().biome_at(x, 0);
+            if let Some((last_top_z, last_biome)) = last {
+                if last_biome != biome {
+                    borders += 1;
+                    // Heights are blended, so borders are no steeper than the terrain itself
+                    assert!((top_z as i64 - last_top_z as i64).abs() <= 4);
+                }
+            }
+            let blocks = generator.generate(x, 0);
+            assert_ne!(blocks[top_z], AIR_BLOCK);
+            assert_ne!(blocks[top_z], water_block);
+            assert!(blocks[top_z + 1..]
+                .iter()
+                .all(|b| *b == AIR_BLOCK || *b == water_block));
+            last = Some((top_z, biome));
+        }
+        assert!(borders > 0);
+    }
+}
@@ -1,12 +1,11 @@
-use common::{
-    block::*,
-    chunk::{CHUNK_SIZE, WORLD_HEIGHT_CHUNKS},
-};
+use common::block::*;
+use common::world_pos::WorldCoord;
 
 use super::Generator;
 
 /// Generates a completely flat world covered with grass with no water or resources
 pub struct FlatGenerator {
+    height_blocks: usize,
     dirt_bottom_z: usize,
     terrain_top_z: usize,
     stone_block: Block,
@@ -15,11 +14,17 @@ pub struct FlatGenerator {
 }
 
 impl FlatGenerator {
-    pub fn new(dirt_bottom_z: usize, terrain_top_z: usize, block_registry: &BlockRegistry) -> Self {
+    pub fn new(
+        dirt_bottom_z: usize,
+        terrain_top_z: usize,
+        height_blocks: usize,
+        block_registry: &BlockRegistry,
+    ) -> Self {
         let stone_block = block_registry.block_kind_from_code("stone");
         let dirt_block = block_registry.block_kind_from_code("dirt");
         let grass_block = block_registry.block_kind_from_code("grass");
         FlatGenerator {
+            height_blocks,
             dirt_bottom_z,
             terrain_top_z,
             stone_block,
@@ -30,9 +35,9 @@ impl FlatGenerator {
 }
 
 impl Generator for FlatGenerator {
-    fn generate(&mut self, _x: i16, _y: i16) -> Vec<Block> {
+    fn generate(&mut self, _x: WorldCoord, _y: WorldCoord) -> Vec<Block> {
         let mut blocks = Vec::new();
-        for z in 0..WORLD_HEIGHT_CHUNKS * CHUNK_SIZE {
+        for z in 0..self.height_blocks {
             let block = if z <= self.dirt_bottom_z {
                 self.stone_block
             } else if z < self.terrain_top_z {
@@ -47,7 +52,11 @@ impl Generator for FlatGenerator {
         blocks
     }
 
-    fn determine_rock_water_top(&mut self, _x: i16, _y: i16) -> (usize, usize, usize) {
+    fn determine_rock_water_top(
+        &mut self,
+        _x: WorldCoord,
+        _y: WorldCoord,
+    ) -> (usize, usize, usize) {
         let rock_top_z = self.dirt_bottom_z - 1;
         let water_top_z = 0;
         let top_z = self.terrain_top_z;
@@ -1,13 +1,13 @@
-use super::Generator;
+use super::{scale_terrain_z, Generator};
 use crate::generator::{NoiseSource2D, NoiseSource3D};
-use common::{
-    block::*,
-    chunk::{CHUNK_SIZE, WORLD_HEIGHT_CHUNKS},
-};
+use common::block::*;
+use common::world_pos::WorldCoord;
+use common::world_preset::HillsPreset;
 use noise::{Fbm, Perlin};
 
 /// Generates rough hills covered in grass, with a fixed water level and resource placement
 pub struct HillsGenerator {
+    height_blocks: usize,
     roughness_noise: NoiseSource2D<Perlin>,
     terrain_noise: NoiseSource3D<Fbm>,
     water_z: usize,
@@ -16,6 +16,7 @@ pub struct HillsGenerator {
     soil_thickness: usize,
     terrain_min_z: usize,
     terrain_z_range: usize,
+    roughness_scale: f64,
     stone_block: Block,
     dirt_block: Block,
     grass_block: Block,
@@ -26,7 +27,12 @@ pub struct HillsGenerator {
 }
 
 impl HillsGenerator {
-    pub fn new(seed: u32, block_registry: &BlockRegistry) -> Self {
+    pub fn new(
+        seed: u32,
+        height_blocks: usize,
+        preset: &HillsPreset,
+        block_registry: &BlockRegistry,
+    ) -> Self {
         let stone_block = block_registry.block_kind_from_code("stone");
         let dirt_block = block_registry.block_kind_from_code("dirt");
         let grass_block = block_registry.block_kind_from_code("grass");
@@ -36,14 +42,21 @@ impl HillsGenerator {
         let water_block = block_registry.block_kind_from_code("water");
 
         HillsGenerator {
-            roughness_noise: NoiseSource2D::<Perlin>::new_perlin(seed, 0.0025, 0.025),
+            height_blocks,
+            roughness_noise: NoiseSource2D::<Perlin>::new_perlin(
+                seed,
+                preset.min_roughness,
+                preset.max_roughness,
+            ),
             terrain_noise: NoiseSource3D::<Fbm>::new_fbm(seed, 0.0, 1.0),
             resource_type_noise: NoiseSource2D::<Perlin>::new_perlin(seed, 0.0, 1.0),
             resource_density_noise: NoiseSource3D::<Perlin>::new_perlin(seed, 0.0, 1.0),
-            water_z: 80,
-            soil_thickness: 3,
-            terrain_min_z: 64,
-            terrain_z_range: 128,
+            water_z: scale_terrain_z(preset.water_z, height_blocks),
+            soil_thickness: preset.soil_thickness,
+            terrain_min_z: scale_terrain_z(preset.terrain_min_z, height_blocks),
+            // The range divides heights, so it cannot be zero
+            terrain_z_range: scale_terrain_z(preset.terrain_z_range, height_blocks).max(1),
+            roughness_scale: preset.roughness_scale,
             stone_block,
             dirt_block,
             grass_block,
@@ -54,7 +67,7 @@ impl HillsGenerator {
         }
     }
 
-    fn determine_block(&mut self, z: usize, x: i16, y: i16, roughness: f64) -> Block {
+    fn determine_block(&mut self, z: usize, x: WorldCoord, y: WorldCoord, roughness: f64) -> Block {
         let block = if z < self.terrain_min_z {
             self.stone_block
         } else if z > self.terrain_min_z + self.terrain_z_range + 16 {
@@ -90,13 +103,14 @@ impl HillsGenerator {
         block
     }
 
-    fn get_terrain_roughness(&mut self, x: i16, y: i16) -> f64 {
-        self.roughness_noise.get(x as f64, y as f64, 0.0001)
+    fn get_terrain_roughness(&mut self, x: WorldCoord, y: WorldCoord) -> f64 {
+        self.roughness_noise
+            .get(x as f64, y as f64, self.roughness_scale)
     }
 
     fn add_soil(&self, blocks: &mut Vec<Block>, soil_thickness: usize, water_z: usize) {
         let mut soil_added = 0;
-        for z in 0..WORLD_HEIGHT_CHUNKS * CHUNK_SIZE {
+        for z in 0..self.height_blocks {
             let block_kind = blocks[z].kind();
             if block_kind == AIR_BLOCK_KIND || block_kind == self.water_block {
                 if soil_added < soil_thickness {
@@ -117,18 +131,18 @@ impl HillsGenerator {
 }
 
 impl Generator for HillsGenerator {
-    fn generate(&mut self, x: i16, y: i16) -> Vec<Block> {
+    fn generate(&mut self, x: WorldCoord, y: WorldCoord) -> Vec<Block> {
         let roughness = self.get_terrain_roughness(x, y);
-        let mut blocks = vec![AIR_BLOCK; WORLD_HEIGHT_CHUNKS * CHUNK_SIZE];
+        let mut blocks = vec![AIR_BLOCK; self.height_blocks];
         // Generate rocks and water
-        for z in 0..WORLD_HEIGHT_CHUNKS * CHUNK_SIZE {
+        for z in 0..self.height_blocks {
             blocks[z] = self.determine_block(z, x, y, roughness);
         }
         self.add_soil(&mut blocks, self.soil_thickness, self.water_z);
         blocks
     }
 
-    fn determine_rock_water_top(&mut self, x: i16, y: i16) -> (usize, usize, usize) {
+    fn determine_rock_water_top(&mut self, x: WorldCoord, y: WorldCoord) -> (usize, usize, usize) {
         let roughness = self.get_terrain_roughness(x, y);
         let mut rock_top_z = 0;
         let mut water_top_z = 0;
@@ -1,18 +1,34 @@
 mod alien_generator;
+mod biome_generator;
 mod flat_generator;
 mod hills_generator;
 mod water_generator;
 
 pub use alien_generator::AlienGenerator;
+pub use biome_generator::BiomeGenerator;
 use common::block::Block;
+use common::world_pos::WorldCoord;
 pub use flat_generator::FlatGenerator;
 pub use hills_generator::HillsGenerator;
 pub use water_generator::WaterWorldGenerator;
 
+// Terrain heights are chosen for worlds at least this many blocks high
+const TERRAIN_DESIGN_HEIGHT: usize = 256;
+
 pub trait Generator {
     /// Generate a single 1x1 column of terrain
-    fn generate(&mut self, x: i16, y: i16) -> Vec<Block>;
+    fn generate(&mut self, x: WorldCoord, y: WorldCoord) -> Vec<Block>;
 
     /// Determine the top Z coordinates for rock, water and any non-air block for a given x,y
-    fn determine_rock_water_top(&mut self, x: i16, y: i16) -> (usize, usize, usize);
+    fn determine_rock_water_top(&mut self, x: WorldCoord, y: WorldCoord) -> (usize, usize, usize);
+}
+
+/// Scale a terrain height down for worlds that are not high enough for it. Heights in higher
+/// worlds are unchanged, so their terrain does not depend on the world height.
+pub fn scale_terrain_z(z: usize, height_blocks: usize) -> usize {
+    if height_blocks >= TERRAIN_DESIGN_HEIGHT {
+        z
+    } else {
+        z * height_blocks / TERRAIN_DESIGN_HEIGHT
+    }
 }
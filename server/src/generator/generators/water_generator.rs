@@ -1,11 +1,12 @@
-use super::Generator;
+use super::{scale_terrain_z, Generator};
 use crate::generator::NoiseSource2D;
 use common::block::*;
-use common::chunk::{CHUNK_SIZE, WORLD_HEIGHT_CHUNKS};
+use common::world_pos::WorldCoord;
 use noise::*;
 
 // Generates a world covered by an ocean with a rolling sand floor, without resources
 pub struct WaterWorldGenerator {
+    height_blocks: usize,
     soil_thickness: usize,
     water_z: usize,
     ocean_floor_noise: NoiseSource2D<Perlin>,
@@ -15,16 +16,17 @@ pub struct WaterWorldGenerator {
 }
 
 impl WaterWorldGenerator {
-    pub fn new(seed: u32, block_registry: &BlockRegistry) -> Self {
+    pub fn new(seed: u32, height_blocks: usize, block_registry: &BlockRegistry) -> Self {
         let stone_block = block_registry.block_kind_from_code("stone");
         let sand_block = block_registry.block_kind_from_code("sand");
         let water_block = block_registry.block_kind_from_code("water");
 
-        let floor_min_z = 16;
-        let floor_max_z = 64;
+        let floor_min_z = scale_terrain_z(16, height_blocks);
+        let floor_max_z = scale_terrain_z(64, height_blocks);
         WaterWorldGenerator {
+            height_blocks,
             soil_thickness: 3,
-            water_z: 128,
+            water_z: scale_terrain_z(128, height_blocks),
             ocean_floor_noise: NoiseSource2D::<Perlin>::new_perlin(
                 seed,
                 floor_min_z as f64,
@@ -36,21 +38,21 @@ impl WaterWorldGenerator {
         }
     }
 
-    fn get_ocean_floor_z(&mut self, x: i16, y: i16) -> usize {
+    fn get_ocean_floor_z(&mut self, x: WorldCoord, y: WorldCoord) -> usize {
         self.ocean_floor_noise.get(x as f64, y as f64, 0.01) as usize
     }
 }
 
 impl Generator for WaterWorldGenerator {
-    fn generate(&mut self, x: i16, y: i16) -> Vec<Block> {
+    fn generate(&mut self, x: WorldCoord, y: WorldCoord) -> Vec<Block> {
         let mut blocks = Vec::new();
-        for z in 0..WORLD_HEIGHT_CHUNKS * CHUNK_SIZE {
+        for z in 0..self.height_blocks {
             let floor_z = self.get_ocean_floor_z(x, y);
             let block = if z <= floor_z {
                 self.stone_block
             } else if z < floor_z + self.soil_thickness {
                 self.sand_block
-            } else if z < 128 {
+            } else if z < self.water_z {
                 self.water_block
             } else {
                 AIR_BLOCK
@@ -61,7 +63,7 @@ impl Generator for WaterWorldGenerator {
         blocks
     }
 
-    fn determine_rock_water_top(&mut self, x: i16, y: i16) -> (usize, usize, usize) {
+    fn determine_rock_water_top(&mut self, x: WorldCoord, y: WorldCoord) -> (usize, usize, usize) {
         let floor_z = self.get_ocean_floor_z(x, y);
         let rock_top_z = floor_z;
         let water_top_z = self.water_z;
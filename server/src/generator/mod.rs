@@ -1,12 +1,21 @@
+mod biome_map;
+mod cave_carver;
 mod column_generator;
 mod generators;
 mod noise_source;
 mod object_generator;
 mod object_placer;
+mod ore_placer;
+mod structure_planner;
 mod world_generator;
 
+pub use biome_map::{biome_seed, BiomeMap};
+pub use cave_carver::CaveCarver;
+pub use column_generator::{generator_fingerprint, ColumnGenerator, GENERATOR_VERSION};
 pub use generators::Generator;
 pub use noise_source::*;
 pub use object_generator::*;
 pub use object_placer::*;
+pub use ore_placer::*;
+pub use structure_planner::*;
 pub use world_generator::WorldGenerator;
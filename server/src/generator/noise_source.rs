@@ -1,5 +1,6 @@
 use noise::*;
 
+#[derive(Clone)]
 pub struct NoiseSource2D<N>
 where
     N: NoiseFn<[f64; 2]>,
@@ -55,6 +56,7 @@ where
     }
 }
 
+#[derive(Clone)]
 pub struct NoiseSource3D<N>
 where
     N: NoiseFn<[f64; 3]>,
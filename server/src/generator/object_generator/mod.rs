@@ -1,10 +1,12 @@
 mod tower_generator;
 mod tree_generator;
+mod vox_loader;
 
 use common::block::*;
 use rand::prelude::*;
 pub use tower_generator::TowerGenerator;
 pub use tree_generator::TreeGenerator;
+pub use vox_loader::*;
 
 pub trait ObjectGenerator {
     /// Pre-generate an object to be placed in the world
@@ -0,0 +1,433 @@
+use common::block::*;
+use common::comms::read_from::ReadFrom;
+use common::world_preset::ObjectPreset;
+use log::*;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use crate::generator::PregeneratedObject;
+
+/// Folder with the MagicaVoxel models that are placed in the world, each with a mapping file
+pub const OBJECTS_FOLDER: &str = "assets/objects";
+const MAGIC: &[u8; 4] = b"VOX ";
+
+/// Where models are placed, they are added to the objects of the same kind
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VoxPlacement {
+    PointOfInterest,
+    Tree,
+}
+
+impl Default for VoxPlacement {
+    fn default() -> Self {
+        VoxPlacement::PointOfInterest
+    }
+}
+
+/// Mapping file of a model, e.g. `tower.json` for `tower.vox`
+#[derive(Debug, Deserialize)]
+pub struct VoxMapping {
+    /// Block code of each palette index, voxels without a block keep the terrain
+    pub palette: HashMap<u8, String>,
+    /// Anchor as x, y and z in the model, by default the center of the bottom
+    #[serde(default)]
+    pub anchor: Option<[usize; 3]>,
+    /// Block code of the foundation below the model
+    #[serde(default)]
+    pub foundation_block: Option<String>,
+    #[serde(default)]
+    pub place_on_soil: bool,
+    #[serde(default)]
+    pub overwrite_non_empty: bool,
+    #[serde(default)]
+    pub placement: VoxPlacement,
+}
+
+/// A model of a .vox file, with palette indices and zero for empty voxels
+struct VoxModel {
+    size_x: usize,
+    size_y: usize,
+    size_z: usize,
+    voxels: Vec<(usize, usize, usize, u8)>,
+}
+
+/// Load the models of a preset from a folder, each with its mapping file. Models that cannot
+/// be loaded are skipped, so a missing folder simply has no models.
+pub fn load_vox_objects(
+    folder: &Path,
+    preset: &ObjectPreset,
+    block_registry: &BlockRegistry,
+) -> Vec<(VoxPlacement, PregeneratedObject)> {
+    let mut objects = Vec::new();
+    for name in &preset.models {
+        let path = folder.join(name).with_extension("vox");
+        let mapping_path = path.with_extension("json");
+        let mapping: VoxMapping = match fs::read_to_string(&mapping_path)
+            .map_err(|e| e.to_string())
+            .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+        {
+            Ok(mapping) => mapping,
+            Err(e) => {
+                error!(
+                    "Cannot load mapping {:?} of model {:?}: {}",
+                    mapping_path, path, e
+                );
+                continue;
+            }
+        };
+        let max_size = max_model_size(mapping.placement, preset);
+        match fs::read(&path)
+            .and_then(|bytes| vox_objects(&bytes, &mapping, max_size, block_registry))
+        {
+            Ok(models) => {
+                info!("Loaded {} models from {:?}", models.len(), path);
+                for object in models {
+                    objects.push((mapping.placement, object));
+                }
+            }
+            Err(e) => error!("Cannot load model {:?}: {}", path, e),
+        }
+    }
+    objects
+}
+
+/// Hash of the model and mapping files of a preset, missing files included
+pub fn models_hash(folder: &Path, preset: &ObjectPreset) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64;
+    let mut add = |bytes: &[u8]| {
+        for byte in bytes {
+            hash = (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    for name in &preset.models {
+        add(name.as_bytes());
+        for extension in ["vox", "json"].iter() {
+            match fs::read(folder.join(name).with_extension(extension)) {
+                Ok(bytes) => {
+                    add(&(bytes.len() as u64).to_le_bytes());
+                    add(&bytes);
+                }
+                Err(_) => add(b"missing"),
+            }
+        }
+    }
+    hash
+}
+
+/// Largest width of a model that fits in a grid cell of its placement
+fn max_model_size(placement: VoxPlacement, preset: &ObjectPreset) -> usize {
+    let size = match placement {
+        VoxPlacement::PointOfInterest => preset.poi_grid_size - 2 * preset.poi_grid_margin,
+        // Trees may cross into the neighbouring cells
+        VoxPlacement::Tree => preset.tree_grid_size,
+    };
+    size.max(0) as usize
+}
+
+/// Convert the models in the bytes of a .vox file to objects, using the mapping for the blocks.
+/// Models that are wider or longer than the maximum size are refused.
+pub fn vox_objects(
+    bytes: &[u8],
+    mapping: &VoxMapping,
+    max_size: usize,
+    block_registry: &BlockRegistry,
+) -> io::Result<Vec<PregeneratedObject>> {
+    let mut palette = HashMap::new();
+    for (index, code) in &mapping.palette {
+        let block = block_kind(code, block_registry)
+            .map_err(|e| invalid_data(&format!("{} for color {}", e, index)))?;
+        palette.insert(*index, block);
+    }
+    let foundation_block = match &mapping.foundation_block {
+        Some(code) => Some(
+            block_kind(code, block_registry)
+                .map_err(|e| invalid_data(&format!("{} for the foundation", e)))?,
+        ),
+        None => None,
+    };
+    let mut objects = Vec::new();
+    for model in read_models(bytes)? {
+        if model.size_x > max_size || model.size_y > max_size {
+            return Err(invalid_data(&format!(
+                "model of {}x{} does not fit in {}x{}",
+                model.size_x, model.size_y, max_size, max_size
+            )));
+        }
+        let mut object = PregeneratedObject::new(model.size_x, model.size_y, model.size_z);
+        object.blocks = vec![IGNORE_BLOCK; model.size_x * model.size_y * model.size_z];
+        for (x, y, z, index) in model.voxels {
+            match palette.get(&index) {
+                Some(block) => object.set(x, y, z, *block),
+                None => return Err(invalid_data(&format!("color {} has no block", index))),
+            }
+        }
+        if let Some([x, y, z]) = mapping.anchor {
+            if x >= model.size_x || y >= model.size_y || z >= model.size_z {
+                return Err(invalid_data("anchor is outside the model"));
+            }
+            object.anchor_x = x;
+            object.anchor_y = y;
+            object.anchor_z = z;
+        }
+        object.foundation_block = foundation_block;
+        object.place_on_soil = mapping.place_on_soil;
+        object.overwrite_non_empty = mapping.overwrite_non_empty;
+        objects.push(object);
+    }
+    Ok(objects)
+}
+
+/// Block kind of a block code, which must be in the registry
+fn block_kind(code: &str, block_registry: &BlockRegistry) -> io::Result<Block> {
+    if !block_registry.all_blocks().iter().any(|b| b.code == code) {
+        return Err(invalid_data(&format!("unknown block {}", code)));
+    }
+    Ok(block_registry.block_kind_from_code(code))
+}
+
+/// Read the models of a .vox file. The file holds chunks with an ID, content size and children
+/// size; each model is a SIZE chunk followed by an XYZI chunk, other chunks are skipped.
+fn read_models(bytes: &[u8]) -> io::Result<Vec<VoxModel>> {
+    let mut reader = Cursor::new(bytes);
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a .vox file"));
+    }
+    let _version = read_i32(&mut reader)?;
+    let mut models = Vec::new();
+    let mut size = None;
+    while (reader.position() as usize) < bytes.len() {
+        let mut id = [0u8; 4];
+        reader.read_exact(&mut id)?;
+        let content_size = read_size(&mut reader)?;
+        let _children_size = read_size(&mut reader)?;
+        match &id {
+            // The main chunk has no content, its children follow directly
+            b"MAIN" => {}
+            b"SIZE" => {
+                let x = read_size(&mut reader)?;
+                let y = read_size(&mut reader)?;
+                let z = read_size(&mut reader)?;
+                if x == 0 || y == 0 || z == 0 {
+                    return Err(invalid_data("model has no size"));
+                }
+                size = Some((x, y, z));
+                let rest = content_size
+                    .checked_sub(12)
+                    .ok_or_else(|| invalid_data("size chunk is too small"))?;
+                skip(&mut reader, rest)?;
+            }
+            b"XYZI" => {
+                let (size_x, size_y, size_z) = match size.take() {
+                    Some(size) => size,
+                    None => return Err(invalid_data("voxels without model size")),
+                };
+                let end = reader.position() + content_size as u64;
+                if end > bytes.len() as u64 {
+                    return Err(invalid_data("chunk is larger than the file"));
+                }
+                // The count is not trusted, the voxels must fit in the chunk
+                let count = read_size(&mut reader)?;
+                if content_size < 4 || count > (content_size - 4) / 4 {
+                    return Err(invalid_data("voxel count does not fit in its chunk"));
+                }
+                let mut voxels = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let mut voxel = [0u8; 4];
+                    reader.read_exact(&mut voxel)?;
+                    let (x, y, z) = (voxel[0] as usize, voxel[1] as usize, voxel[2] as usize);
+                    if x >= size_x || y >= size_y || z >= size_z {
+                        return Err(invalid_data("voxel is outside the model"));
+                    }
+                    voxels.push((x, y, z, voxel[3]));
+                }
+                // Bytes after the voxels are not chunk headers
+                reader.set_position(end);
+                models.push(VoxModel {
+                    size_x,
+                    size_y,
+                    size_z,
+                    voxels,
+                });
+            }
+            _ => skip(&mut reader, content_size)?,
+        }
+    }
+    Ok(models)
+}
+
+fn read_i32(reader: &mut Cursor<&[u8]>) -> io::Result<i32> {
+    i32::read_from(reader).map_err(|e| invalid_data(&e.to_string()))
+}
+
+fn read_size(reader: &mut Cursor<&[u8]>) -> io::Result<usize> {
+    let size = read_i32(reader)?;
+    if size < 0 {
+        return Err(invalid_data("negative size"));
+    }
+    Ok(size as usize)
+}
+
+fn skip(reader: &mut Cursor<&[u8]>, length: usize) -> io::Result<()> {
+    let position = reader.position() + length as u64;
+    if position > reader.get_ref().len() as u64 {
+        return Err(invalid_data("chunk is larger than the file"));
+    }
+    reader.set_position(position);
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod vox_loader_test {
+
+    use crate::generator::object_generator::vox_loader::*;
+
+    fn chunk(id: &[u8; 4], content: &[i32], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(content.len() as i32 * 4).to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as i32).to_le_bytes());
+        for value in content {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(children);
+        bytes
+    }
+
+    /// A 2x3x4 model with two voxels, preceded by a chunk that is skipped
+    fn vox_file() -> Vec<u8> {
+        let mut children = chunk(b"PACK", &[1], &[]);
+        children.extend(chunk(b"SIZE", &[2, 3, 4], &[]));
+        let mut xyzi = chunk(b"XYZI", &[2], &[]);
+        // The content size includes the voxels
+        xyzi[4..8].copy_from_slice(&12i32.to_le_bytes());
+        xyzi.extend_from_slice(&[0, 0, 0, 1, 1, 2, 3, 7]);
+        children.extend(xyzi);
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend_from_slice(&150i32.to_le_bytes());
+        bytes.extend(chunk(b"MAIN", &[], &children));
+        bytes
+    }
+
+    fn mapping(json: &str) -> VoxMapping {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn load_model() {
+        let registry = BlockRegistry::default();
+        let mapping = mapping(
+            r#"{
+                "palette": { "1": "bedrock", "7": "air" },
+                "anchor": [1, 1, 0],
+                "foundation_block": "bedrock",
+                "place_on_soil": true,
+                "placement": "tree"
+            }"#,
+        );
+        assert_eq!(mapping.placement, VoxPlacement::Tree);
+        let objects = vox_objects(&vox_file(), &mapping, 3, &registry).unwrap();
+        assert_eq!(objects.len(), 1);
+        let object = &objects[0];
+        assert_eq!((object.size_x, object.size_y, object.size_z), (2, 3, 4));
+        assert_eq!(
+            (object.anchor_x, object.anchor_y, object.anchor_z),
+            (1, 1, 0)
+        );
+        assert_eq!(object.get(0, 0, 0), BEDROCK_BLOCK_KIND);
+        assert_eq!(object.get(1, 2, 3), AIR_BLOCK_KIND);
+        assert_eq!(object.get(1, 1, 1), IGNORE_BLOCK);
+        assert_eq!(object.foundation_block, Some(BEDROCK_BLOCK_KIND));
+        assert!(object.place_on_soil);
+        assert!(!object.overwrite_non_empty);
+    }
+
+    #[test]
+    fn refuse_bad_models() {
+        let registry = BlockRegistry::default();
+        // Color 7 has no block
+        let partial = mapping(r#"{ "palette": { "1": "bedrock" } }"#);
+        assert!(vox_objects(&vox_file(), &partial, 3, &registry).is_err());
+        let unknown = mapping(r#"{ "palette": { "1": "bedrock", "7": "unobtainium" } }"#);
+        assert!(vox_objects(&vox_file(), &unknown, 3, &registry).is_err());
+        let foundation = mapping(
+            r#"{ "palette": { "1": "bedrock", "7": "air" }, "foundation_block": "unobtainium" }"#,
+        );
+        assert!(vox_objects(&vox_file(), &foundation, 3, &registry).is_err());
+        let complete = mapping(r#"{ "palette": { "1": "bedrock", "7": "air" } }"#);
+        // The model is 3 blocks long
+        assert!(vox_objects(&vox_file(), &complete, 2, &registry).is_err());
+        let mut truncated = vox_file();
+        truncated.truncate(truncated.len() - 3);
+        assert!(vox_objects(&truncated, &complete, 3, &registry).is_err());
+        // A size chunk that claims to be smaller than its size values
+        let mut small_size = vox_file();
+        let size_chunk = small_size.windows(4).position(|w| w == b"SIZE").unwrap();
+        small_size[size_chunk + 4..size_chunk + 8].copy_from_slice(&4i32.to_le_bytes());
+        assert!(vox_objects(&small_size, &complete, 3, &registry).is_err());
+        assert!(vox_objects(b"PNG file", &complete, 3, &registry).is_err());
+        // A voxel count that does not fit in its chunk is not allocated
+        let mut huge_count = vox_file();
+        let xyzi_chunk = huge_count.windows(4).position(|w| w == b"XYZI").unwrap();
+        huge_count[xyzi_chunk + 12..xyzi_chunk + 16].copy_from_slice(&std::i32::MAX.to_le_bytes());
+        assert!(vox_objects(&huge_count, &complete, 3, &registry).is_err());
+    }
+
+    #[test]
+    fn skip_rest_of_voxel_chunk() {
+        // Bytes after the voxels that look like a chunk header are part of the voxel chunk
+        let mut padded = vox_file();
+        let xyzi_chunk = padded.windows(4).position(|w| w == b"XYZI").unwrap();
+        padded[xyzi_chunk + 4..xyzi_chunk + 8].copy_from_slice(&16i32.to_le_bytes());
+        padded.extend_from_slice(b"XYZI");
+        let complete = mapping(r#"{ "palette": { "1": "bedrock", "7": "air" } }"#);
+        let objects = vox_objects(&padded, &complete, 3, &BlockRegistry::default()).unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].get(0, 0, 0), BEDROCK_BLOCK_KIND);
+    }
+
+    #[test]
+    fn load_listed_models() {
+        let folder = std::env::temp_dir().join("yab_vox_test");
+        if folder.exists() {
+            fs::remove_dir_all(&folder).unwrap();
+        }
+        fs::create_dir_all(&folder).unwrap();
+        let json = r#"{ "palette": { "1": "bedrock", "7": "air" }, "placement": "tree" }"#;
+        for name in ["hut", "well"].iter() {
+            fs::write(folder.join(name).with_extension("vox"), vox_file()).unwrap();
+            fs::write(folder.join(name).with_extension("json"), json).unwrap();
+        }
+        let registry = BlockRegistry::default();
+        let mut preset = ObjectPreset::default();
+        assert!(load_vox_objects(&folder, &preset, &registry).is_empty());
+        // Models that are not in the folder are skipped
+        preset.models = vec!["well".to_string(), "missing".to_string()];
+        let objects = load_vox_objects(&folder, &preset, &registry);
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].0, VoxPlacement::Tree);
+        // Trees must fit in a grid cell
+        let mut small_grid = preset.clone();
+        small_grid.tree_grid_size = 2;
+        assert!(load_vox_objects(&folder, &small_grid, &registry).is_empty());
+        // Changing a model changes the hash, other models in the folder do not
+        let hash = models_hash(&folder, &preset);
+        fs::remove_file(folder.join("hut.vox")).unwrap();
+        assert_eq!(models_hash(&folder, &preset), hash);
+        fs::write(
+            folder.join("well.json"),
+            json.replace("tree", "point_of_interest"),
+        )
+        .unwrap();
+        assert_ne!(models_hash(&folder, &preset), hash);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1,14 +1,16 @@
 use std::sync::Arc;
 
-use common::{block::*, chunk::WORLD_HEIGHT_BLOCKS};
+use common::biome::BIOME_COUNT;
+use common::block::*;
+use common::world_pos::WorldCoord;
 use noise::*;
 
-use super::{Generator, NoiseSource2D, PregeneratedObject};
+use super::{BiomeMap, Generator, NoiseSource2D, PregeneratedObject, StructurePlanner};
 
 /// Grid-based object placer for an infinite block world
 pub struct ObjectPlacer {
-    grid_size: i16,
-    grid_margin: i16,
+    grid_size: WorldCoord,
+    grid_margin: WorldCoord,
     object_density: f64,
     clustered_objects: bool,
     grid_x_noise: NoiseSource2D<Value>,
@@ -18,14 +20,15 @@ pub struct ObjectPlacer {
     randomizer_noise: NoiseSource2D<Value>,
     pregenerated: Arc<Vec<PregeneratedObject>>,
     overlapping: bool,
+    biome_densities: Option<(BiomeMap, [f64; BIOME_COUNT])>,
 }
 
 impl ObjectPlacer {
     pub fn new(
         seed: u32,
         pregenerated: Arc<Vec<PregeneratedObject>>,
-        grid_size: i16,
-        grid_margin: i16,
+        grid_size: WorldCoord,
+        grid_margin: WorldCoord,
         object_density: f64,
         clustered_objects: bool,
         overlapping: bool,
@@ -42,15 +45,30 @@ impl ObjectPlacer {
             pregenerated,
             clustered_objects,
             overlapping,
+            biome_densities: None,
         }
     }
 
+    /// Use an object density per biome instead, indexed by biome ID. The biome at the anchor of
+    /// an object decides, so objects are not cut off at biome borders.
+    pub fn with_biome_densities(
+        mut self,
+        biome_map: BiomeMap,
+        densities: [f64; BIOME_COUNT],
+    ) -> Self {
+        self.biome_densities = Some((biome_map, densities));
+        self
+    }
+
+    /// Place the objects in a 1x1 column of terrain. Objects that would cover a part of the
+    /// planned structures are left out.
     pub fn place(
         &mut self,
-        x: i16,
-        y: i16,
+        x: WorldCoord,
+        y: WorldCoord,
         blocks: &mut Vec<Block>,
         generator: &mut dyn Generator,
+        mut structures: Option<&mut StructurePlanner>,
     ) {
         let grid_x = (x / self.grid_size) * self.grid_size;
         let grid_y = (y / self.grid_size) * self.grid_size;
@@ -65,23 +83,25 @@ impl ObjectPlacer {
                         y,
                         generator,
                         blocks,
+                        structures.as_deref_mut(),
                     );
                 }
             }
         } else {
-            self.place_grid_object(grid_x, grid_y, x, y, generator, blocks);
+            self.place_grid_object(grid_x, grid_y, x, y, generator, blocks, structures);
         }
     }
 
     /// Place pregenerated object(s) at the given coordinate
     fn place_grid_object(
         &mut self,
-        grid_x: i16,
-        grid_y: i16,
-        x: i16,
-        y: i16,
+        grid_x: WorldCoord,
+        grid_y: WorldCoord,
+        x: WorldCoord,
+        y: WorldCoord,
         generator: &mut dyn Generator,
         blocks: &mut Vec<u32>,
+        structures: Option<&mut StructurePlanner>,
     ) {
         // Determine pregenerated object for this location
         let random = self.randomizer_noise.get(grid_x as f64, grid_y as f64, 1.0);
@@ -98,8 +118,8 @@ impl ObjectPlacer {
             )
         } else {
             (
-                grid_x + self.grid_margin + pregenerated.anchor_x as i16,
-                grid_y + self.grid_margin + pregenerated.anchor_y as i16,
+                grid_x + self.grid_margin + pregenerated.anchor_x as WorldCoord,
+                grid_y + self.grid_margin + pregenerated.anchor_y as WorldCoord,
                 self.grid_size as f64 - pregenerated.size_x as f64 - self.grid_margin as f64 * 2.0,
                 self.grid_size as f64 - pregenerated.size_y as f64 - self.grid_margin as f64 * 2.0,
             )
@@ -108,16 +128,16 @@ impl ObjectPlacer {
             + (self
                 .grid_x_noise
                 .get(grid_x as f64 + 0.123, grid_y as f64 + 50.665, 1.0)
-                * grid_range_x) as i16;
+                * grid_range_x) as WorldCoord;
         let anchor_world_y = grid_start_y
             + (self
                 .grid_y_noise
                 .get(grid_x as f64 - 102.4, grid_y as f64 + 553.1, 1.0)
-                * grid_range_y) as i16;
-        let x1 = anchor_world_x - pregenerated.anchor_x as i16;
-        let y1 = anchor_world_y - pregenerated.anchor_y as i16;
-        let x2 = x1 + pregenerated.size_x as i16;
-        let y2 = y1 + pregenerated.size_y as i16;
+                * grid_range_y) as WorldCoord;
+        let x1 = anchor_world_x - pregenerated.anchor_x as WorldCoord;
+        let y1 = anchor_world_y - pregenerated.anchor_y as WorldCoord;
+        let x2 = x1 + pregenerated.size_x as WorldCoord;
+        let y2 = y1 + pregenerated.size_y as WorldCoord;
         if x < x1 || x >= x2 || y < y1 || y >= y2 {
             // We are entirely outside of this objects area
             return;
@@ -131,7 +151,13 @@ impl ObjectPlacer {
             self.value_density_noise
                 .get(anchor_world_x as f64, anchor_world_y as f64, 1.0)
         };
-        if density_noise > self.object_density {
+        let object_density = match &self.biome_densities {
+            Some((biome_map, densities)) => {
+                densities[biome_map.biome_at(anchor_world_x, anchor_world_y).id() as usize]
+            }
+            None => self.object_density,
+        };
+        if density_noise > object_density {
             // No object here
             return;
         }
@@ -143,6 +169,12 @@ impl ObjectPlacer {
             // No objects below water
             return;
         }
+        // No objects in villages, the whole object is left out so it is not cut off
+        if let Some(structures) = structures {
+            if structures.covers_surface(x1, y1, x2 - 1, y2 - 1, generator) {
+                return;
+            }
+        }
 
         // Determine anchor and object position
         let anchor_world_z = if pregenerated.place_on_soil {
@@ -168,6 +200,8 @@ impl ObjectPlacer {
         // Now place the object
         let bottom_block = pregenerated.get(x_rel, y_rel, 0).kind();
         let place_foundation = bottom_block != AIR_BLOCK_KIND && bottom_block != IGNORE_BLOCK;
+        // Objects are cut off at the top of the world
+        let z2 = z2.min(blocks.len());
         for z in from_z..z2 {
             if pregenerated.overwrite_non_empty || blocks[z].kind() == AIR_BLOCK_KIND {
                 if z < z1 {
@@ -187,7 +221,7 @@ impl ObjectPlacer {
         }
         if pregenerated.overwrite_non_empty {
             // Clear any blocks above the object
-            for z in z2..WORLD_HEIGHT_BLOCKS as usize {
+            for z in z2..blocks.len() {
                 blocks[z] = AIR_BLOCK;
             }
         }
@@ -0,0 +1,244 @@
+use common::block::*;
+use common::ore_definition::OreDefinition;
+use common::world_pos::WorldCoord;
+use log::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::sync::Arc;
+
+// Size of the cubic cells that veins are generated in
+const CELL_SIZE: WorldCoord = 32;
+// Veins must stay within the neighbouring cells
+const MAX_VEIN_SIZE: usize = 1024;
+
+/// An ore with its block kinds looked up
+#[derive(Clone, Debug)]
+pub struct Ore {
+    pub block: Block,
+    pub host_block: Block,
+    pub min_z: usize,
+    pub max_z: usize,
+    pub vein_size: usize,
+    pub frequency: f64,
+}
+
+/// An ellipsoid of ore blocks
+#[derive(Clone, Debug)]
+pub struct Vein {
+    ore: usize,
+    center: [f64; 3],
+    radius: [f64; 3],
+}
+
+/// Look up the block kinds of ores. Ores with unknown blocks are skipped.
+pub fn lookup_ores(definitions: &[OreDefinition], block_registry: &BlockRegistry) -> Vec<Ore> {
+    let known = |code: &str| block_registry.all_blocks().iter().any(|b| b.code == code);
+    let mut ores = Vec::new();
+    for definition in definitions {
+        if !known(&definition.block) || !known(&definition.host_block) {
+            warn!(
+                "Skipping ore {} in {}, its blocks are not in the registry",
+                definition.block, definition.host_block
+            );
+            continue;
+        }
+        if definition.vein_size > MAX_VEIN_SIZE {
+            warn!(
+                "Ore {} has veins of {} blocks, they are limited to {}",
+                definition.block, definition.vein_size, MAX_VEIN_SIZE
+            );
+        }
+        ores.push(Ore {
+            block: block_registry.block_kind_from_code(&definition.block),
+            host_block: block_registry.block_kind_from_code(&definition.host_block),
+            min_z: definition.min_z,
+            max_z: definition.max_z,
+            vein_size: definition.vein_size.min(MAX_VEIN_SIZE),
+            frequency: definition.frequency.max(0.0),
+        });
+    }
+    ores
+}
+
+/// Places veins of ore in the terrain. Veins are generated per cell, from the seed and the cell
+/// coordinates only, so veins that cross column borders are placed in both columns.
+pub struct OrePlacer {
+    seed: u32,
+    ores: Arc<Vec<Ore>>,
+}
+
+impl OrePlacer {
+    pub fn new(seed: u32, ores: Arc<Vec<Ore>>) -> Self {
+        OrePlacer { seed, ores }
+    }
+
+    /// The veins that may reach the blocks from x1, y1 up to and including x2, y2
+    pub fn veins(
+        &self,
+        x1: WorldCoord,
+        y1: WorldCoord,
+        x2: WorldCoord,
+        y2: WorldCoord,
+    ) -> Vec<Vein> {
+        let mut veins = Vec::new();
+        // Ores come first, so overlapping veins are placed in the same order everywhere
+        for (index, ore) in self.ores.iter().enumerate() {
+            if ore.frequency <= 0.0 || ore.vein_size == 0 || ore.min_z > ore.max_z {
+                continue;
+            }
+            for cell_x in (x1.div_euclid(CELL_SIZE) - 1)..=(x2.div_euclid(CELL_SIZE) + 1) {
+                for cell_y in (y1.div_euclid(CELL_SIZE) - 1)..=(y2.div_euclid(CELL_SIZE) + 1) {
+                    let min_cell_z = ore.min_z as WorldCoord / CELL_SIZE;
+                    let max_cell_z = ore.max_z as WorldCoord / CELL_SIZE;
+                    for cell_z in min_cell_z..=max_cell_z {
+                        self.cell_veins(index, ore, [cell_x, cell_y, cell_z], &mut veins);
+                    }
+                }
+            }
+        }
+        veins
+    }
+
+    /// Place the ore blocks of veins in a 1x1 column of terrain
+    pub fn place(&self, x: WorldCoord, y: WorldCoord, veins: &Vec<Vein>, blocks: &mut Vec<Block>) {
+        for vein in veins {
+            let dx = (x as f64 + 0.5 - vein.center[0]) / vein.radius[0];
+            let dy = (y as f64 + 0.5 - vein.center[1]) / vein.radius[1];
+            let horizontal = dx * dx + dy * dy;
+            if horizontal >= 1.0 {
+                continue;
+            }
+            let ore = &self.ores[vein.ore];
+            let half_height = (1.0 - horizontal).sqrt() * vein.radius[2];
+            let from_z = (vein.center[2] - half_height - 0.5)
+                .ceil()
+                .max(ore.min_z as f64) as usize;
+            let to_z = ((vein.center[2] + half_height - 0.5).floor() as i64)
+                .min(ore.max_z as i64)
+                .min(blocks.len() as i64 - 1);
+            for z in from_z as i64..=to_z {
+                let block = &mut blocks[z as usize];
+                if block.kind() == ore.host_block {
+                    *block = ore.block;
+                }
+            }
+        }
+    }
+
+    fn cell_veins(&self, index: usize, ore: &Ore, cell: [WorldCoord; 3], veins: &mut Vec<Vein>) {
+        let mut random = StdRng::seed_from_u64(cell_seed(self.seed, index, cell));
+        // The fraction of the frequency is the chance of an extra vein
+        let mut count = ore.frequency.floor() as usize;
+        if random.gen::<f64>() < ore.frequency.fract() {
+            count += 1;
+        }
+        // Radius of a sphere with the volume of the vein
+        let radius = (3.0 * ore.vein_size as f64 / (4.0 * std::f64::consts::PI)).cbrt();
+        for _ in 0..count {
+            let center = [
+                (cell[0] * CELL_SIZE) as f64 + random.gen::<f64>() * CELL_SIZE as f64,
+                (cell[1] * CELL_SIZE) as f64 + random.gen::<f64>() * CELL_SIZE as f64,
+                (cell[2] * CELL_SIZE) as f64 + random.gen::<f64>() * CELL_SIZE as f64,
+            ];
+            if center[2] < ore.min_z as f64 || center[2] > ore.max_z as f64 + 1.0 {
+                continue;
+            }
+            // Stretch the vein in a random direction, keeping its volume
+            let stretch_x = random.gen_range(0.7, 1.4);
+            let stretch_y = random.gen_range(0.7, 1.4);
+            let stretch_z = 1.0 / (stretch_x * stretch_y);
+            veins.push(Vein {
+                ore: index,
+                center,
+                radius: [radius * stretch_x, radius * stretch_y, radius * stretch_z],
+            });
+        }
+    }
+}
+
+/// Seed of the random veins of an ore in a cell
+fn cell_seed(seed: u32, ore: usize, cell: [WorldCoord; 3]) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64 ^ seed as u64;
+    for value in [ore as i64, cell[0] as i64, cell[1] as i64, cell[2] as i64].iter() {
+        hash = (hash ^ *value as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        hash ^= hash >> 29;
+    }
+    hash
+}
+
+#[cfg(test)]
+mod ore_placer_test {
+
+    use crate::generator::ore_placer::*;
+    use common::ore_definition::{load_ore_definitions, ORES_FILE};
+    use std::path::Path;
+
+    const HEIGHT: usize = 128;
+
+    fn test_ores(registry: &BlockRegistry) -> Arc<Vec<Ore>> {
+        Arc::new(vec![Ore {
+            block: registry.block_kind_from_code("sand"),
+            host_block: registry.block_kind_from_code("stone"),
+            min_z: 16,
+            max_z: 111,
+            vein_size: 10,
+            frequency: 4.0,
+        }])
+    }
+
+    fn stone_column(registry: &BlockRegistry) -> Vec<Block> {
+        vec![registry.block_kind_from_code("stone"); HEIGHT]
+    }
+
+    #[test]
+    fn ore_frequency() {
+        let registry = BlockRegistry::default();
+        let sand = registry.block_kind_from_code("sand");
+        let placer = OrePlacer::new(1234, test_ores(&registry));
+        let veins = placer.veins(0, 0, 127, 127);
+        let mut count = 0;
+        for x in 0..128 {
+            for y in 0..128 {
+                let mut blocks = stone_column(&registry);
+                placer.place(x, y, &veins, &mut blocks);
+                for (z, block) in blocks.iter().enumerate() {
+                    if *block == sand {
+                        assert!(z >= 16 && z <= 111);
+                        count += 1;
+                    }
+                }
+            }
+        }
+        // 4 veins of 10 blocks in each of the 48 cells in the sample
+        let expected = 4.0 * 48.0 * 10.0;
+        assert!((count as f64 - expected).abs() < expected * 0.25);
+    }
+
+    #[test]
+    fn veins_cross_column_borders() {
+        let registry = BlockRegistry::default();
+        let placer = OrePlacer::new(1234, test_ores(&registry));
+        let veins_a = placer.veins(0, 0, 63, 31);
+        let veins_b = placer.veins(32, 0, 95, 31);
+        // Blocks are the same, whichever chunk column the veins are taken from
+        for x in 32..64 {
+            for y in 0..32 {
+                let mut blocks_a = stone_column(&registry);
+                let mut blocks_b = stone_column(&registry);
+                placer.place(x, y, &veins_a, &mut blocks_a);
+                placer.place(x, y, &veins_b, &mut blocks_b);
+                assert_eq!(blocks_a, blocks_b);
+            }
+        }
+    }
+
+    #[test]
+    fn lookup_ores_file() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets");
+        let registry = BlockRegistry::load_or_create(&assets.join("blocks.json")).unwrap();
+        let definitions = load_ore_definitions(&assets.join(ORES_FILE));
+        let ores = lookup_ores(&definitions, &registry);
+        assert!(!ores.is_empty());
+        assert!(ores.iter().all(|ore| ore.block != AIR_BLOCK_KIND));
+        assert!(lookup_ores(&definitions, &BlockRegistry::default()).is_empty());
+    }
+}
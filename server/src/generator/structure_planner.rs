@@ -0,0 +1,603 @@
+use common::block::*;
+use common::world_pos::WorldCoord;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use super::{Generator, PregeneratedObject};
+
+/// Size of the square regions that each hold at most one village and one dungeon
+pub const REGION_SIZE: WorldCoord = 256;
+// Structures stay this far from the region border, so they never reach into another region
+const REGION_MARGIN: WorldCoord = 16;
+const VILLAGE_CHANCE: f64 = 0.3;
+const DUNGEON_CHANCE: f64 = 0.4;
+const PATH_HALF_WIDTH: WorldCoord = 1;
+const CORRIDOR_HALF_WIDTH: WorldCoord = 2;
+const CORRIDOR_HEIGHT: usize = 3;
+// Dungeons are not planned closer to the bottom of the world
+const MIN_DUNGEON_Z: usize = 8;
+// Plans are cached per generator, this limits the memory they use
+const MAX_CACHED_PLANS: usize = 64;
+
+/// A part of a structure, coordinates are inclusive
+#[derive(Clone, Debug, PartialEq)]
+pub enum StructurePart {
+    /// A path on the terrain surface
+    Path {
+        x1: WorldCoord,
+        y1: WorldCoord,
+        x2: WorldCoord,
+        y2: WorldCoord,
+    },
+    /// A room or corridor: walls around an empty interior
+    Room {
+        x1: WorldCoord,
+        y1: WorldCoord,
+        z1: usize,
+        x2: WorldCoord,
+        y2: WorldCoord,
+        z2: usize,
+    },
+    Lamp {
+        x: WorldCoord,
+        y: WorldCoord,
+        z: usize,
+    },
+    /// A pregenerated object with its lowest corner at a position
+    Object {
+        x: WorldCoord,
+        y: WorldCoord,
+        z: usize,
+        object: usize,
+    },
+}
+
+/// The structures in a region
+#[derive(Debug, Default, PartialEq)]
+pub struct StructurePlan {
+    pub villages: usize,
+    pub dungeons: usize,
+    pub parts: Vec<StructurePart>,
+}
+
+/// Plans villages and dungeons that span many columns. Plans only depend on the seed and the
+/// terrain, so every column stamps its own slice of the same plan, whichever generator worker
+/// generates it.
+pub struct StructurePlanner {
+    seed: u32,
+    objects: Arc<Vec<PregeneratedObject>>,
+    plans: HashMap<(WorldCoord, WorldCoord), Arc<StructurePlan>>,
+    path_block: Block,
+    wall_block: Block,
+    lamp_block: Block,
+    water_block: Block,
+}
+
+impl StructurePlanner {
+    /// Create a planner that builds villages from the given objects, e.g. towers
+    pub fn new(
+        seed: u32,
+        objects: Arc<Vec<PregeneratedObject>>,
+        block_registry: &BlockRegistry,
+    ) -> Self {
+        StructurePlanner {
+            seed,
+            objects,
+            plans: HashMap::new(),
+            path_block: block_registry.block_kind_from_code("sandstone"),
+            wall_block: block_registry.block_kind_from_code("bricks"),
+            lamp_block: block_registry.block_kind_from_code("lamp"),
+            water_block: block_registry.block_kind_from_code("water"),
+        }
+    }
+
+    /// The plan of the region that holds a position
+    pub fn plan(
+        &mut self,
+        x: WorldCoord,
+        y: WorldCoord,
+        generator: &mut dyn Generator,
+    ) -> Arc<StructurePlan> {
+        let region = (x.div_euclid(REGION_SIZE), y.div_euclid(REGION_SIZE));
+        if let Some(plan) = self.plans.get(&region) {
+            return Arc::clone(plan);
+        }
+        if self.plans.len() >= MAX_CACHED_PLANS {
+            self.plans.clear();
+        }
+        let plan = Arc::new(self.plan_region(region.0, region.1, generator));
+        self.plans.insert(region, Arc::clone(&plan));
+        plan
+    }
+
+    /// Whether the structures on the terrain surface, i.e. village paths and objects, cover any
+    /// column from x1, y1 up to and including x2, y2
+    pub fn covers_surface(
+        &mut self,
+        x1: WorldCoord,
+        y1: WorldCoord,
+        x2: WorldCoord,
+        y2: WorldCoord,
+        generator: &mut dyn Generator,
+    ) -> bool {
+        for region_x in x1.div_euclid(REGION_SIZE)..=x2.div_euclid(REGION_SIZE) {
+            for region_y in y1.div_euclid(REGION_SIZE)..=y2.div_euclid(REGION_SIZE) {
+                let plan = self.plan(region_x * REGION_SIZE, region_y * REGION_SIZE, generator);
+                for part in &plan.parts {
+                    let (part_x1, part_y1, part_x2, part_y2) = match *part {
+                        StructurePart::Path { x1, y1, x2, y2 } => (x1, y1, x2, y2),
+                        StructurePart::Object { x, y, object, .. } => {
+                            let object = &self.objects[object];
+                            (
+                                x,
+                                y,
+                                x + object.size_x as WorldCoord - 1,
+                                y + object.size_y as WorldCoord - 1,
+                            )
+                        }
+                        _ => continue,
+                    };
+                    if x1 <= part_x2 && x2 >= part_x1 && y1 <= part_y2 && y2 >= part_y1 {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    /// Stamp the structures of the plan onto a 1x1 column of terrain
+    pub fn stamp(
+        &mut self,
+        x: WorldCoord,
+        y: WorldCoord,
+        blocks: &mut Vec<Block>,
+        generator: &mut dyn Generator,
+    ) {
+        let plan = self.plan(x, y, generator);
+        let top = blocks.len() - 1;
+        // Parts are stamped in passes, so the walls of one room do not divide another and lamps
+        // are not cleared by the rooms after them
+        for part in &plan.parts {
+            if let StructurePart::Path { x1, y1, x2, y2 } = *part {
+                if x >= x1 && x <= x2 && y >= y1 && y <= y2 {
+                    let (rock_top_z, water_top_z, top_z) = generator.determine_rock_water_top(x, y);
+                    let kind = blocks[top_z].kind();
+                    if water_top_z <= rock_top_z
+                        && kind != AIR_BLOCK_KIND
+                        && kind != self.water_block
+                    {
+                        blocks[top_z] = self.path_block;
+                    }
+                }
+            }
+        }
+        for part in &plan.parts {
+            if let StructurePart::Room {
+                x1,
+                y1,
+                z1,
+                x2,
+                y2,
+                z2,
+            } = *part
+            {
+                if x >= x1 && x <= x2 && y >= y1 && y <= y2 {
+                    for z in z1..=z2.min(top) {
+                        blocks[z] = self.wall_block;
+                    }
+                }
+            }
+        }
+        for part in &plan.parts {
+            if let StructurePart::Room {
+                x1,
+                y1,
+                z1,
+                x2,
+                y2,
+                z2,
+            } = *part
+            {
+                if x > x1 && x < x2 && y > y1 && y < y2 {
+                    for z in z1 + 1..z2.min(top + 1) {
+                        blocks[z] = AIR_BLOCK;
+                    }
+                }
+            }
+        }
+        for part in &plan.parts {
+            match *part {
+                StructurePart::Lamp {
+                    x: lamp_x,
+                    y: lamp_y,
+                    z,
+                } => {
+                    if x == lamp_x && y == lamp_y && z <= top {
+                        blocks[z] = self.lamp_block;
+                    }
+                }
+                StructurePart::Object {
+                    x: x1,
+                    y: y1,
+                    z: z1,
+                    object,
+                } => {
+                    let object = &self.objects[object];
+                    let x2 = x1 + object.size_x as WorldCoord;
+                    let y2 = y1 + object.size_y as WorldCoord;
+                    if x >= x1 && x < x2 && y >= y1 && y < y2 {
+                        self.stamp_object(object, (x - x1) as usize, (y - y1) as usize, z1, blocks);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn stamp_object(
+        &self,
+        object: &PregeneratedObject,
+        x_rel: usize,
+        y_rel: usize,
+        z1: usize,
+        blocks: &mut Vec<Block>,
+    ) {
+        for z_rel in 0..object.size_z.min(blocks.len().saturating_sub(z1)) {
+            let block = object.get(x_rel, y_rel, z_rel);
+            let z = z1 + z_rel;
+            if block != IGNORE_BLOCK
+                && (object.overwrite_non_empty || blocks[z].kind() == AIR_BLOCK_KIND)
+            {
+                blocks[z] = block;
+            }
+        }
+        // Fill the space below the object down to the terrain
+        let bottom_block = object.get(x_rel, y_rel, 0).kind();
+        if bottom_block == AIR_BLOCK_KIND || bottom_block == IGNORE_BLOCK {
+            return;
+        }
+        if let Some(foundation_block) = object.foundation_block {
+            for z in (0..z1.min(blocks.len())).rev() {
+                let kind = blocks[z].kind();
+                if kind != AIR_BLOCK_KIND && kind != self.water_block {
+                    break;
+                }
+                blocks[z] = foundation_block;
+            }
+        }
+    }
+
+    fn plan_region(
+        &self,
+        region_x: WorldCoord,
+        region_y: WorldCoord,
+        generator: &mut dyn Generator,
+    ) -> StructurePlan {
+        let mut random = StdRng::seed_from_u64(region_seed(self.seed, region_x, region_y));
+        let bounds = (
+            region_x * REGION_SIZE + REGION_MARGIN,
+            region_y * REGION_SIZE + REGION_MARGIN,
+            (region_x + 1) * REGION_SIZE - REGION_MARGIN - 1,
+            (region_y + 1) * REGION_SIZE - REGION_MARGIN - 1,
+        );
+        let mut plan = StructurePlan::default();
+        // Both chances are always drawn, so a village does not change the dungeon
+        let village = random.gen::<f64>() < VILLAGE_CHANCE;
+        let dungeon = random.gen::<f64>() < DUNGEON_CHANCE;
+        if village && !self.objects.is_empty() {
+            if self.plan_village(&mut random, bounds, generator, &mut plan.parts) {
+                plan.villages += 1;
+            }
+        }
+        if dungeon {
+            if self.plan_dungeon(&mut random, bounds, generator, &mut plan.parts) {
+                plan.dungeons += 1;
+            }
+        }
+        plan
+    }
+
+    /// Plan objects around a square, joined to it by paths
+    fn plan_village(
+        &self,
+        random: &mut StdRng,
+        bounds: (WorldCoord, WorldCoord, WorldCoord, WorldCoord),
+        generator: &mut dyn Generator,
+        parts: &mut Vec<StructurePart>,
+    ) -> bool {
+        let (min_x, min_y, max_x, max_y) = bounds;
+        let center_x = random.gen_range(min_x + 48, max_x - 48);
+        let center_y = random.gen_range(min_y + 48, max_y - 48);
+        let (rock_top_z, water_top_z, _) = generator.determine_rock_water_top(center_x, center_y);
+        if water_top_z > rock_top_z {
+            return false;
+        }
+        let mut paths = vec![StructurePart::Path {
+            x1: center_x - 3,
+            y1: center_y - 3,
+            x2: center_x + 3,
+            y2: center_y + 3,
+        }];
+        let mut objects = Vec::new();
+        let count = random.gen_range(3, 7);
+        for i in 0..count {
+            let angle = (i as f64 + random.gen_range(0.0, 0.5)) * 2.0 * PI / count as f64;
+            let distance = random.gen_range(16.0, 40.0);
+            let index = random.gen_range(0, self.objects.len());
+            let object = &self.objects[index];
+            let anchor_x = center_x + (angle.cos() * distance) as WorldCoord;
+            let anchor_y = center_y + (angle.sin() * distance) as WorldCoord;
+            let (rock_top_z, water_top_z, top_z) =
+                generator.determine_rock_water_top(anchor_x, anchor_y);
+            if water_top_z > rock_top_z {
+                continue;
+            }
+            let anchor_z = if object.place_on_soil {
+                top_z
+            } else {
+                rock_top_z
+            } + 1;
+            let x1 = anchor_x - object.anchor_x as WorldCoord;
+            let y1 = anchor_y - object.anchor_y as WorldCoord;
+            // Columns of other regions do not stamp this plan, so large objects may not fit
+            if anchor_z < object.anchor_z
+                || x1 < min_x - REGION_MARGIN
+                || y1 < min_y - REGION_MARGIN
+                || x1 + object.size_x as WorldCoord > max_x + REGION_MARGIN + 1
+                || y1 + object.size_y as WorldCoord > max_y + REGION_MARGIN + 1
+            {
+                continue;
+            }
+            objects.push(StructurePart::Object {
+                x: x1,
+                y: y1,
+                z: anchor_z - object.anchor_z,
+                object: index,
+            });
+            // Paths go along x first and then along y, ending at the object
+            paths.push(StructurePart::Path {
+                x1: center_x.min(anchor_x),
+                y1: center_y - PATH_HALF_WIDTH,
+                x2: center_x.max(anchor_x),
+                y2: center_y + PATH_HALF_WIDTH,
+            });
+            paths.push(StructurePart::Path {
+                x1: anchor_x - PATH_HALF_WIDTH,
+                y1: center_y.min(anchor_y),
+                x2: anchor_x + PATH_HALF_WIDTH,
+                y2: center_y.max(anchor_y),
+            });
+        }
+        if objects.is_empty() {
+            return false;
+        }
+        parts.extend(paths);
+        parts.extend(objects);
+        true
+    }
+
+    /// Plan rooms below the terrain, joined in a chain by corridors
+    fn plan_dungeon(
+        &self,
+        random: &mut StdRng,
+        bounds: (WorldCoord, WorldCoord, WorldCoord, WorldCoord),
+        generator: &mut dyn Generator,
+        parts: &mut Vec<StructurePart>,
+    ) -> bool {
+        let (min_x, min_y, max_x, max_y) = bounds;
+        let mut room_x = random.gen_range(min_x + 32, max_x - 32);
+        let mut room_y = random.gen_range(min_y + 32, max_y - 32);
+        let depth = random.gen_range(20, 40);
+        let (rock_top_z, _, _) = generator.determine_rock_water_top(room_x, room_y);
+        if rock_top_z < MIN_DUNGEON_Z + depth {
+            return false;
+        }
+        let floor_z = rock_top_z - depth;
+        // Rooms overlap, with the same height their lamps hang from the ceiling
+        let height = random.gen_range(4, 7);
+        let count = random.gen_range(4, 9);
+        for i in 0..count {
+            let half_x = random.gen_range(3, 6);
+            let half_y = random.gen_range(3, 6);
+            parts.push(StructurePart::Room {
+                x1: room_x - half_x - 1,
+                y1: room_y - half_y - 1,
+                z1: floor_z - 1,
+                x2: room_x + half_x + 1,
+                y2: room_y + half_y + 1,
+                z2: floor_z + height,
+            });
+            parts.push(StructurePart::Lamp {
+                x: room_x,
+                y: room_y,
+                z: floor_z + height - 1,
+            });
+            if i + 1 == count {
+                break;
+            }
+            // The next room, with a corridor along x and then along y. The corridors overlap at
+            // the corner, so their walls do not close it off.
+            let next_x = (room_x + random.gen_range(-24, 25)).clamp(min_x + 8, max_x - 8);
+            let next_y = (room_y + random.gen_range(-24, 25)).clamp(min_y + 8, max_y - 8);
+            parts.push(StructurePart::Room {
+                x1: room_x.min(next_x) - CORRIDOR_HALF_WIDTH,
+                y1: room_y - CORRIDOR_HALF_WIDTH,
+                z1: floor_z - 1,
+                x2: room_x.max(next_x) + CORRIDOR_HALF_WIDTH,
+                y2: room_y + CORRIDOR_HALF_WIDTH,
+                z2: floor_z + CORRIDOR_HEIGHT,
+            });
+            parts.push(StructurePart::Room {
+                x1: next_x - CORRIDOR_HALF_WIDTH,
+                y1: room_y.min(next_y) - CORRIDOR_HALF_WIDTH,
+                z1: floor_z - 1,
+                x2: next_x + CORRIDOR_HALF_WIDTH,
+                y2: room_y.max(next_y) + CORRIDOR_HALF_WIDTH,
+                z2: floor_z + CORRIDOR_HEIGHT,
+            });
+            room_x = next_x;
+            room_y = next_y;
+        }
+        true
+    }
+}
+
+/// Seed of the random structures of a region
+fn region_seed(seed: u32, region_x: WorldCoord, region_y: WorldCoord) -> u64 {
+    let mut hash = 0x9e37_79b9_7f4a_7c15u64 ^ seed as u64;
+    for value in [region_x as i64, region_y as i64].iter() {
+        hash = (hash ^ *value as u64).wrapping_mul(0x0000_0100_0000_01b3);
+        hash ^= hash >> 29;
+    }
+    hash
+}
+
+#[cfg(test)]
+mod structure_planner_test {
+
+    use crate::generator::column_generator::pregenerate_objects;
+    use crate::generator::generators::FlatGenerator;
+    use crate::generator::structure_planner::*;
+    use crate::generator::ObjectPlacer;
+    use common::chunk::CHUNK_SIZE;
+    use common::world_preset::ObjectPreset;
+    use std::path::Path;
+
+    const SEED: u32 = 1234;
+
+    fn registry() -> BlockRegistry {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../assets");
+        BlockRegistry::load_or_create(&assets.join("blocks.json")).unwrap()
+    }
+
+    fn new_planner(registry: &BlockRegistry) -> StructurePlanner {
+        let (towers, _) = pregenerate_objects(SEED, &ObjectPreset::default(), registry);
+        StructurePlanner::new(SEED, towers, registry)
+    }
+
+    #[test]
+    fn structures_line_up_across_columns() {
+        let registry = registry();
+        let mut generator = FlatGenerator::new(60, 64, 128, &registry);
+        let mut planner = new_planner(&registry);
+        // Find a region with a village and a dungeon
+        let (region_x, region_y) = (0..8)
+            .flat_map(|rx| (0..8).map(move |ry| (rx, ry)))
+            .find(|(rx, ry)| {
+                let plan = planner.plan(rx * REGION_SIZE, ry * REGION_SIZE, &mut generator);
+                plan.villages > 0 && plan.dungeons > 0
+            })
+            .unwrap();
+        let (min_x, min_y) = (region_x * REGION_SIZE, region_y * REGION_SIZE);
+        // A new planner for each chunk column, like the generator workers, stamps the same blocks
+        let mut stamped = 0;
+        for cx in 0..REGION_SIZE / CHUNK_SIZE as WorldCoord {
+            for cy in 0..REGION_SIZE / CHUNK_SIZE as WorldCoord {
+                let mut worker_planner = new_planner(&registry);
+                for rel_x in 0..CHUNK_SIZE as WorldCoord {
+                    for rel_y in 0..CHUNK_SIZE as WorldCoord {
+                        let x = min_x + cx * CHUNK_SIZE as WorldCoord + rel_x;
+                        let y = min_y + cy * CHUNK_SIZE as WorldCoord + rel_y;
+                        let mut blocks_a = generator.generate(x, y);
+                        let mut blocks_b = blocks_a.clone();
+                        let terrain = blocks_a.clone();
+                        planner.stamp(x, y, &mut blocks_a, &mut generator);
+                        worker_planner.stamp(x, y, &mut blocks_b, &mut generator);
+                        assert_eq!(blocks_a, blocks_b);
+                        if blocks_a != terrain {
+                            stamped += 1;
+                        }
+                    }
+                }
+            }
+        }
+        assert!(stamped > 0);
+        // Columns outside the region are left alone by its plan
+        let plan = planner.plan(min_x, min_y, &mut generator);
+        let outside = |x: WorldCoord, y: WorldCoord| {
+            x < min_x || y < min_y || x >= min_x + REGION_SIZE || y >= min_y + REGION_SIZE
+        };
+        for part in &plan.parts {
+            match *part {
+                StructurePart::Path { x1, y1, x2, y2 }
+                | StructurePart::Room { x1, y1, x2, y2, .. } => {
+                    assert!(!outside(x1, y1) && !outside(x2, y2));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn dungeons_are_hollow() {
+        let registry = registry();
+        let bricks = registry.block_kind_from_code("bricks");
+        let lamp = registry.block_kind_from_code("lamp");
+        let mut generator = FlatGenerator::new(60, 64, 128, &registry);
+        let mut planner = new_planner(&registry);
+        let mut rooms = 0;
+        for rx in 0..8 {
+            for ry in 0..8 {
+                let plan = planner.plan(rx * REGION_SIZE, ry * REGION_SIZE, &mut generator);
+                for part in &plan.parts {
+                    if let StructurePart::Lamp { x, y, z } = *part {
+                        // Lamps hang from the ceiling in the middle of a room
+                        let mut blocks = generator.generate(x, y);
+                        planner.stamp(x, y, &mut blocks, &mut generator);
+                        assert_eq!(blocks[z], lamp);
+                        assert_eq!(blocks[z - 1], AIR_BLOCK);
+                        let floor_z = (0..z).rev().find(|z| blocks[*z] != AIR_BLOCK).unwrap();
+                        assert_eq!(blocks[floor_z], bricks);
+                        assert_eq!(blocks[z + 1], bricks);
+                        rooms += 1;
+                    }
+                }
+            }
+        }
+        assert!(rooms > 0);
+    }
+
+    #[test]
+    fn objects_stay_out_of_villages() {
+        let registry = registry();
+        let mut generator = FlatGenerator::new(60, 64, 128, &registry);
+        let mut planner = new_planner(&registry);
+        let (_, trees) = pregenerate_objects(SEED, &ObjectPreset::default(), &registry);
+        // Trees everywhere
+        let mut placer = ObjectPlacer::new(SEED, trees, 13, 0, 1.0, false, true);
+        let (region_x, region_y) = (0..8)
+            .flat_map(|rx| (0..8).map(move |ry| (rx, ry)))
+            .find(|(rx, ry)| {
+                let plan = planner.plan(rx * REGION_SIZE, ry * REGION_SIZE, &mut generator);
+                plan.villages > 0
+            })
+            .unwrap();
+        let (mut covered, mut planted, mut planted_in_village) = (0, 0, 0);
+        for x in region_x * REGION_SIZE..(region_x + 1) * REGION_SIZE {
+            for y in region_y * REGION_SIZE..(region_y + 1) * REGION_SIZE {
+                let mut blocks = generator.generate(x, y);
+                planner.stamp(x, y, &mut blocks, &mut generator);
+                let stamped = blocks.clone();
+                let mut without_village = blocks.clone();
+                placer.place(x, y, &mut blocks, &mut generator, Some(&mut planner));
+                placer.place(x, y, &mut without_village, &mut generator, None);
+                if planner.covers_surface(x, y, x, y, &mut generator) {
+                    covered += 1;
+                    assert_eq!(blocks, stamped);
+                    if without_village != stamped {
+                        planted_in_village += 1;
+                    }
+                } else if blocks != stamped {
+                    planted += 1;
+                }
+            }
+        }
+        assert!(covered > 0);
+        assert!(planted > 0);
+        // Without the planner trees would grow in the village
+        assert!(planted_in_village > 0);
+    }
+}
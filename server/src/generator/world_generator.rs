@@ -1,16 +1,12 @@
 use common::block::BlockRegistry;
 use common::chunk::*;
-use common::world_type::GeneratorType;
+use common::world_definition::WorldDef;
 use crossbeam::channel::*;
 use crossbeam::unbounded;
 use log::*;
-use std::sync::Arc;
 use std::thread;
 
 use crate::generator::column_generator::ColumnGenerator;
-use crate::generator::ObjectGenerator;
-use crate::generator::TowerGenerator;
-use crate::generator::TreeGenerator;
 
 /// Multi-threaded terrain generator
 pub struct WorldGenerator {
@@ -20,28 +16,8 @@ pub struct WorldGenerator {
 }
 
 impl WorldGenerator {
-    pub fn new(
-        seed: u32,
-        world_type: GeneratorType,
-        block_resistry: &BlockRegistry,
-    ) -> WorldGenerator {
-        // let seed = 1234;
-        let poi_object_list = {
-            let mut result = Vec::new();
-            let mut gen = TowerGenerator::new(seed, block_resistry);
-            for _ in 0..10 {
-                result.push(gen.generate());
-            }
-            Arc::new(result)
-        };
-        let tree_object_list = {
-            let mut result = Vec::new();
-            let mut gen = TreeGenerator::new(seed, block_resistry);
-            for _ in 0..10 {
-                result.push(gen.generate());
-            }
-            Arc::new(result)
-        };
+    pub fn new(world_def: &WorldDef, block_resistry: &BlockRegistry) -> WorldGenerator {
+        let world_type = world_def.world_type;
         let worker_count = num_cpus::get() - 1;
         info!("Initializing {} generator workers", worker_count);
         // Start chunk column generator threads
@@ -50,18 +26,12 @@ impl WorldGenerator {
         for id in 0..worker_count {
             let colpos_rx = colpos_rx.clone();
             let column_tx = column_tx.clone();
-            let poi_object_list = Arc::clone(&poi_object_list);
-            let tree_object_list = Arc::clone(&tree_object_list);
+            let world_def = world_def.clone();
             let block_resistry = block_resistry.clone();
             thread::Builder::new()
                 .name(format!("generator{}", id).to_string())
                 .spawn(move || {
-                    let mut generator = ColumnGenerator::new(
-                        seed,
-                        poi_object_list,
-                        tree_object_list,
-                        &block_resistry,
-                    );
+                    let mut generator = ColumnGenerator::for_world(&world_def, &block_resistry);
                     info!("Starting generator {}", id);
                     loop {
                         // Wait for a new column position (i.e. a generator request)
@@ -0,0 +1,407 @@
+use crate::generator::ColumnGenerator;
+use crate::region::{region_position, Region};
+use crate::superchunk::SuperChunk;
+use crate::world_store::apply_diff;
+use common::block::{Block, BlockRegistry, BlockTrait};
+use common::chunk::{ChunkColumnPos, CHUNK_SIZE};
+use common::world_definition::{WorldDef, WORLD_DEF_FILE};
+use common::world_pos::WorldCoord;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Report on the files and stored columns of a world folder
+#[derive(Debug, Serialize)]
+pub struct WorldReport {
+    pub world: WorldDef,
+    pub disk_usage: DiskUsage,
+    pub superchunks: Vec<FileReport>,
+    pub regions: Vec<FileReport>,
+    pub stored_columns: usize,
+    /// Stored columns with blocks that differ from the generated terrain
+    pub modified_columns: usize,
+    pub changed_blocks: u64,
+    /// Block count of each kind in the stored columns, by block name
+    pub blocks: BTreeMap<String, u64>,
+    /// Changed block count of each kind in the stored columns, by block name
+    pub changed_blocks_by_kind: BTreeMap<String, u64>,
+    /// Files and columns that cannot be decoded
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct DiskUsage {
+    pub total_bytes: u64,
+    pub region_bytes: u64,
+    pub superchunk_bytes: u64,
+    pub other_bytes: u64,
+}
+
+/// A region or superchunk file and the columns stored in it
+#[derive(Debug, Serialize)]
+pub struct FileReport {
+    pub name: String,
+    pub bytes: u64,
+    pub columns: Vec<ColumnReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ColumnReport {
+    pub x: WorldCoord,
+    pub y: WorldCoord,
+    pub changed_chunks: usize,
+    pub changed_blocks: u64,
+}
+
+/// Block counts of a column by block kind
+struct ColumnBlocks {
+    blocks: HashMap<Block, u64>,
+    changed: HashMap<Block, u64>,
+    changed_chunks: usize,
+}
+
+/// Inspect a world folder without changing it. Worlds in an older format are not migrated,
+/// so their superchunks are inspected as they are.
+pub fn inspect_world(world_folder: &Path, registry: &BlockRegistry) -> io::Result<WorldReport> {
+    let world = match WorldDef::load(&world_folder.join(WORLD_DEF_FILE)) {
+        Some(world) => world,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "world definition cannot be loaded",
+            ))
+        }
+    };
+    let mut generator = ColumnGenerator::for_world(&world, registry);
+    let mut report = WorldReport {
+        world,
+        disk_usage: DiskUsage::default(),
+        superchunks: Vec::new(),
+        regions: Vec::new(),
+        stored_columns: 0,
+        modified_columns: 0,
+        changed_blocks: 0,
+        blocks: BTreeMap::new(),
+        changed_blocks_by_kind: BTreeMap::new(),
+        errors: Vec::new(),
+    };
+    let mut block_counts = HashMap::new();
+    let mut changed_counts = HashMap::new();
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(world_folder)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    paths.sort();
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let bytes = fs::metadata(&path)?.len();
+        report.disk_usage.total_bytes += bytes;
+        let columns = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("reg") => {
+                report.disk_usage.region_bytes += bytes;
+                read_region(&path, &name)
+            }
+            Some("chk") => {
+                report.disk_usage.superchunk_bytes += bytes;
+                SuperChunk::load(&path)
+                    .map(|sc| {
+                        sc.chunk_data
+                            .into_iter()
+                            .map(|(col, column_bytes)| (col, Ok(column_bytes)))
+                            .collect()
+                    })
+                    .map_err(|e| format!("superchunk {} cannot be loaded: {}", name, e))
+            }
+            _ => {
+                report.disk_usage.other_bytes += bytes;
+                continue;
+            }
+        };
+        let mut file_report = FileReport {
+            name: name.clone(),
+            bytes,
+            columns: Vec::new(),
+        };
+        let mut columns: Vec<(ChunkColumnPos, io::Result<Vec<Vec<u8>>>)> = match columns {
+            Ok(columns) => columns,
+            Err(e) => {
+                report.errors.push(e);
+                Vec::new()
+            }
+        };
+        columns.sort_by_key(|(col, _)| (col.x, col.y));
+        for (col, column_bytes) in columns {
+            let column = column_bytes
+                .map_err(|e| e.to_string())
+                .and_then(|column_bytes| {
+                    inspect_column(&report.world, &mut generator, col, &column_bytes)
+                });
+            let column = match column {
+                Ok(column) => column,
+                Err(e) => {
+                    report
+                        .errors
+                        .push(format!("column {},{} in {}: {}", col.x, col.y, name, e));
+                    continue;
+                }
+            };
+            let changed_blocks: u64 = column.changed.values().sum();
+            report.stored_columns += 1;
+            if changed_blocks > 0 {
+                report.modified_columns += 1;
+            }
+            report.changed_blocks += changed_blocks;
+            add_counts(&mut block_counts, &column.blocks);
+            add_counts(&mut changed_counts, &column.changed);
+            file_report.columns.push(ColumnReport {
+                x: col.x,
+                y: col.y,
+                changed_chunks: column.changed_chunks,
+                changed_blocks,
+            });
+        }
+        if path.extension().map_or(false, |ext| ext == "reg") {
+            report.regions.push(file_report);
+        } else {
+            report.superchunks.push(file_report);
+        }
+    }
+    report.blocks = named_counts(&block_counts, registry);
+    report.changed_blocks_by_kind = named_counts(&changed_counts, registry);
+    Ok(report)
+}
+
+/// Read all columns of a region, each column read can fail on its own
+fn read_region(
+    path: &Path,
+    name: &str,
+) -> Result<Vec<(ChunkColumnPos, io::Result<Vec<Vec<u8>>>)>, String> {
+    let (region_x, region_y) = match region_position(name) {
+        Some(position) => position,
+        None => return Err(format!("region {} has an invalid name", name)),
+    };
+    let mut region = Region::open_read_only(path)
+        .map_err(|e| format!("region {} cannot be opened: {}", name, e))?;
+    let columns = region.stored_columns(region_x, region_y);
+    Ok(columns
+        .into_iter()
+        .map(|col| {
+            let column_bytes = region
+                .read_column(col)
+                .map(|bytes| bytes.unwrap_or_default());
+            (col, column_bytes)
+        })
+        .collect())
+}
+
+/// Decode a stored column by applying it to the generated column, and count its blocks
+fn inspect_column(
+    world: &WorldDef,
+    generator: &mut ColumnGenerator,
+    col: ChunkColumnPos,
+    column_bytes: &Vec<Vec<u8>>,
+) -> Result<ColumnBlocks, String> {
+    let generated = generator.generate_column(world.world_type, col);
+    if column_bytes.len() != generated.len() {
+        return Err(format!(
+            "{} chunks stored instead of {}",
+            column_bytes.len(),
+            generated.len()
+        ));
+    }
+    let mut column = ColumnBlocks {
+        blocks: HashMap::new(),
+        changed: HashMap::new(),
+        changed_chunks: 0,
+    };
+    for (generated_chunk, diff_bytes) in generated.iter().zip(column_bytes.iter()) {
+        let mut chunk = generated_chunk.clone();
+        apply_diff(&mut chunk, diff_bytes)
+            .map_err(|e| format!("chunk {} cannot be decoded: {}", chunk.pos.z, e))?;
+        if chunk.is_solid() && generated_chunk.is_solid() {
+            // Solid chunks are counted at once
+            let block = chunk.get_block(0, 0, 0).kind();
+            let count = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as u64;
+            *column.blocks.entry(block).or_insert(0) += count;
+            if block != generated_chunk.get_block(0, 0, 0).kind() {
+                *column.changed.entry(block).or_insert(0) += count;
+                column.changed_chunks += 1;
+            }
+            continue;
+        }
+        let mut chunk_changed = false;
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let block = chunk.get_block(x, y, z).kind();
+                    *column.blocks.entry(block).or_insert(0) += 1;
+                    if block != generated_chunk.get_block(x, y, z).kind() {
+                        *column.changed.entry(block).or_insert(0) += 1;
+                        chunk_changed = true;
+                    }
+                }
+            }
+        }
+        if chunk_changed {
+            column.changed_chunks += 1;
+        }
+    }
+    Ok(column)
+}
+
+fn add_counts(total: &mut HashMap<Block, u64>, counts: &HashMap<Block, u64>) {
+    for (block, count) in counts {
+        *total.entry(*block).or_insert(0) += count;
+    }
+}
+
+/// Block counts by the name of the block in the registry
+fn named_counts(counts: &HashMap<Block, u64>, registry: &BlockRegistry) -> BTreeMap<String, u64> {
+    let mut named = BTreeMap::new();
+    for (block, count) in counts {
+        let name = match registry.all_blocks().get(*block as usize) {
+            Some(block_def) => block_def.name.clone(),
+            None => format!("Unknown block {}", block),
+        };
+        *named.entry(name).or_insert(0) += count;
+    }
+    named
+}
+
+impl WorldReport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl fmt::Display for WorldReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "World {} ({}), seed {}, type {:?}, height {} chunks, format version {}",
+            self.world.id,
+            self.world.description,
+            self.world.seed,
+            self.world.world_type,
+            self.world.height_chunks,
+            self.world.format_version
+        )?;
+        writeln!(
+            f,
+            "Disk usage: {} bytes ({} in regions, {} in superchunks, {} in other files)",
+            self.disk_usage.total_bytes,
+            self.disk_usage.region_bytes,
+            self.disk_usage.superchunk_bytes,
+            self.disk_usage.other_bytes
+        )?;
+        for (kind, files) in [("Superchunk", &self.superchunks), ("Region", &self.regions)].iter() {
+            for file in files.iter() {
+                writeln!(
+                    f,
+                    "{} {}: {} columns, {} bytes",
+                    kind,
+                    file.name,
+                    file.columns.len(),
+                    file.bytes
+                )?;
+                for column in &file.columns {
+                    writeln!(
+                        f,
+                        "  column {},{}: {} chunks changed, {} blocks changed",
+                        column.x, column.y, column.changed_chunks, column.changed_blocks
+                    )?;
+                }
+            }
+        }
+        writeln!(
+            f,
+            "Stored columns: {} ({} modified, {} the same as generated)",
+            self.stored_columns,
+            self.modified_columns,
+            self.stored_columns - self.modified_columns
+        )?;
+        writeln!(f, "Changed blocks: {}", self.changed_blocks)?;
+        writeln!(f, "Blocks in stored columns:")?;
+        for (name, count) in &self.blocks {
+            writeln!(f, "  {:<20} {}", name, count)?;
+        }
+        writeln!(f, "Changed blocks by kind:")?;
+        for (name, count) in &self.changed_blocks_by_kind {
+            writeln!(f, "  {:<20} {}", name, count)?;
+        }
+        if self.errors.is_empty() {
+            writeln!(f, "All stored columns can be decoded")
+        } else {
+            writeln!(f, "Errors:")?;
+            for error in &self.errors {
+                writeln!(f, "  {}", error)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod inspect_test {
+
+    use crate::generator::ColumnGenerator;
+    use crate::inspect::inspect_world;
+    use crate::region::{region_filename, Region};
+    use crate::storage::FileStorage;
+    use crate::world_store::{test_world_def, WorldStore};
+    use common::block::{BlockRegistry, BEDROCK_BLOCK};
+    use common::chunk::ChunkColumnPos;
+    use common::world_definition::WORLD_DEF_FILE;
+    use common::world_type::GeneratorType;
+    use std::fs;
+
+    #[test]
+    fn inspect_changed_world() {
+        let world_folder = std::env::temp_dir().join("yab_inspect_test");
+        if world_folder.exists() {
+            fs::remove_dir_all(&world_folder).unwrap();
+        }
+        fs::create_dir_all(&world_folder).unwrap();
+        let world_def = test_world_def(GeneratorType::Flat, 4);
+        world_def.save(&world_folder.join(WORLD_DEF_FILE)).unwrap();
+        let registry = BlockRegistry::default();
+
+        // Change a single block in a column
+        let mut generator = ColumnGenerator::for_seed(1, 4, &registry);
+        let mut column = generator.generate_column(GeneratorType::Flat, ChunkColumnPos::new(0, 0));
+        column[3].set_block(1, 2, 3, BEDROCK_BLOCK);
+        let storage = FileStorage::open(&world_folder).unwrap();
+        let mut store = WorldStore::new(Box::new(storage), &registry).unwrap();
+        store.enqueue_chunk_save(&column[3]);
+        store.save_world_if_needed(true, 0.0);
+
+        let report = inspect_world(&world_folder, &registry).unwrap();
+        assert_eq!(report.regions.len(), 1);
+        assert_eq!(report.stored_columns, 1);
+        assert_eq!(report.modified_columns, 1);
+        assert_eq!(report.changed_blocks, 1);
+        let bedrock_name = &registry.get(BEDROCK_BLOCK).name;
+        assert_eq!(report.changed_blocks_by_kind.get(bedrock_name), Some(&1));
+        assert_eq!(report.blocks.values().sum::<u64>(), 4 * 16 * 16 * 16);
+        assert!(report.errors.is_empty());
+        assert!(report.to_json().contains("\"modified_columns\": 1"));
+        assert!(report
+            .to_string()
+            .contains("All stored columns can be decoded"));
+
+        // A column with a chunk that cannot be decoded is reported
+        let col = ChunkColumnPos::new(1, 0);
+        let mut region = Region::open(&world_folder.join(region_filename(col))).unwrap();
+        region.write_column(col, &vec![vec![1, 2, 3]; 4]).unwrap();
+        region.sync().unwrap();
+        let report = inspect_world(&world_folder, &registry).unwrap();
+        assert_eq!(report.stored_columns, 1);
+        assert_eq!(report.errors.len(), 1);
+        fs::remove_dir_all(&world_folder).unwrap();
+    }
+}
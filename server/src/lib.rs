@@ -1,14 +1,35 @@
+mod access_store;
+mod admin;
+pub mod archive;
+pub mod backup;
+mod block_log;
+mod claim_store;
 mod client;
 pub mod generator;
+pub mod inspect;
+pub mod map;
+pub mod migration;
 mod player_store;
+pub mod region;
+pub mod schematic;
+mod server_config;
 mod server_world_handler;
+pub mod storage;
 pub mod superchunk;
 pub mod world_store;
 
 extern crate nalgebra_glm as glm;
 
+use access_store::AccessStore;
+use admin::execute_admin_command;
+use backup::Backups;
+use claim_store::ClaimStore;
 use client::*;
-use common::world_definition::WorldsStore;
+use common::ore_definition::{load_ore_definitions, ORES_FILE};
+use common::server_status::ServerStatus;
+use common::world_definition::{WorldDef, WorldsStore};
+use common::world_pos::WorldCoord;
+use common::world_preset::WorldPreset;
 use common::world_type::GeneratorType;
 use common::{block::BlockRegistry, comms::*};
 use common::{block::*, daynight::DayNight};
@@ -22,17 +43,27 @@ use glm::Vec3;
 use log::*;
 use player_store::PlayerStore;
 use rand::Rng;
+use server_config::ServerConfig;
+use std::net::IpAddr;
+use std::path::PathBuf;
 use std::thread::{sleep, Builder};
 use std::time::{Duration, Instant};
+use storage::FileStorage;
 
+use crate::generator::biome_seed;
 use crate::server_world_handler::ServerWorldHandler;
 
 const SLEEP_DURATION: Duration = Duration::from_millis(10);
 const MAX_PLAYERS: usize = 16;
+const MAX_SPECTATORS: usize = 16;
+// Spectators do not occupy a player slot, real player IDs start at 1
+const SPECTATOR_PLAYER_ID: u8 = 0;
 const UPDATE_TIME_STEP: f32 = 0.5;
+const PENDING_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct YabServer {
     address: String,
+    host_key: Option<String>,
     shutdown_sender: Option<Sender<String>>,
 }
 
@@ -40,29 +71,54 @@ impl YabServer {
     pub fn new(address: &String) -> YabServer {
         YabServer {
             address: address.clone(),
+            host_key: None,
             shutdown_sender: None,
         }
     }
 
-    pub fn run(&mut self, wait: bool, seed: u32, description: String, world_type: GeneratorType) {
+    /// The host of a server started by a client is always allowed in and is an operator. The
+    /// host is the player that signs in with this key from the same machine, the client that
+    /// started the server should generate a new random key for every run.
+    pub fn set_host(&mut self, host_key: &str) {
+        self.host_key = Some(host_key.to_string());
+    }
+
+    /// Run the server on the world with the given ID, a new world is created with the given seed,
+    /// type, height and preset if it does not exist. Returns once the world is loaded, or when
+    /// the server stops if waiting. Fails if the world cannot be loaded.
+    pub fn run(
+        &mut self,
+        wait: bool,
+        world_id: String,
+        seed: u32,
+        description: String,
+        world_type: GeneratorType,
+        height_chunks: usize,
+        preset: WorldPreset,
+    ) -> Result<(), String> {
         let (shutdown_tx, shutdown_rx) = unbounded();
+        let (started_tx, started_rx) = bounded(1);
         self.shutdown_sender = Some(shutdown_tx);
         let mut server_comms: CommsServer = CommsServer::new(self.address.as_str());
+        let server_host_key = self.host_key.clone();
         let handle = Builder::new()
             .name("yab-world-server".to_string())
             .spawn(move || {
-                let world_list = WorldsStore::new();
-                let world_folder = world_list.get_world_path(seed);
-                let assets = Assets::new("assets");
-                let block_registry =  BlockRegistry::load_or_create(&assets.path("blocks.json") ).unwrap();
-                debug!("Server block registry contains {} blocks", block_registry.all_blocks().len());
-
-                let mut world = if world_folder.exists() {
-                    ServerWorldHandler::load(seed, &block_registry)
-                } else {
-                    ServerWorldHandler::new(seed, description.as_str(), world_type, &block_registry)
+                let config = ServerConfig::load();
+                let (block_registry, world_folder, mut world) = match load_world(&world_id, seed, &description, world_type, height_chunks, preset) {
+                    Ok(loaded) => loaded,
+                    Err(e) => {
+                        server_comms.shutdown();
+                        let _ = started_tx.send(Err(e));
+                        return;
+                    }
                 };
+                let _ = started_tx.send(Ok(()));
                 let mut player_store = PlayerStore::load(&world_folder);
+                let mut access_store = AccessStore::load(&world_folder);
+                let mut claim_store = ClaimStore::load(&world_folder);
+                let mut backups = Backups::new(&world_folder, config.backup_interval_minutes, config.backup_count);
+                let mut pending_connections = Vec::new();
                 let mut clients = Vec::new();
                 let mut broadcast_to_all = Vec::new();
                 let mut loop_profile = Profile::new(1);
@@ -78,7 +134,7 @@ impl YabServer {
                 debug!("World time is {}", daynight.get_time());
 
                 // Preload a starting area of the world
-                let starting_chunk_col = ChunkColumnPos::new(REGION_SIZE_BLOCKS / CHUNK_SIZE as i16 / 2, REGION_SIZE_BLOCKS / CHUNK_SIZE as i16 / 2);
+                let starting_chunk_col = ChunkColumnPos::new(REGION_SIZE_BLOCKS / CHUNK_SIZE as WorldCoord / 2, REGION_SIZE_BLOCKS / CHUNK_SIZE as WorldCoord / 2);
                 let startup_chunk_range = 4;
                 info!(
                     "Preparing spawn area with {} radius around {:?}",
@@ -92,27 +148,102 @@ impl YabServer {
                     let delta = time.elapsed().as_fractional_secs() as f32;
                     time = Instant::now();
 
-                    // Receive incoming clients
+                    // Receive incoming connections, these only become players when signing in
                     client_profile.start();
-                    if let Some(mut connection) = server_comms.try_get_channel() {
-                        if clients.len() < MAX_PLAYERS {
-                            let player_id = get_free_player_id(&clients);
-                            clients.push(Client::new(connection, player_id));
-                            info!("Player {} connected", player_id);
-                        } else {
-                            info!("Max clients reached, connection denied");
-                            connection.disconnect();
+                    if let Some(connection) = server_comms.try_get_channel() {
+                        pending_connections.push((connection, Instant::now()));
+                    }
+
+                    // The first message of a new connection is either a sign in or a status request
+                    let mut still_pending = Vec::new();
+                    for (mut connection, connect_time) in pending_connections.drain(..) {
+                        match connection.try_receive() {
+                            Some(ClientMessage::StatusRequest {}) => {
+                                debug!("Status requested by connection {}", connection.client_id);
+                                connection.send(ServerMessage::ServerStatus {
+                                    status: server_status(&clients, world.world_def(), &config),
+                                });
+                                connection.disconnect();
+                            }
+                            Some(message @ ClientMessage::SignIn { spectator: true, .. }) => {
+                                if clients.iter().filter(|c| c.spectator).count() < MAX_SPECTATORS {
+                                    clients.push(Client::new_with_message(connection, SPECTATOR_PLAYER_ID, true, message));
+                                    info!("Spectator connected");
+                                } else {
+                                    info!("Max spectators reached, connection denied");
+                                    connection.disconnect();
+                                }
+                            }
+                            Some(message @ ClientMessage::SignIn { .. }) => {
+                                if clients.iter().filter(|c| !c.spectator).count() < MAX_PLAYERS {
+                                    let player_id = get_free_player_id(&clients);
+                                    clients.push(Client::new_with_message(connection, player_id, false, message));
+                                    info!("Player {} connected", player_id);
+                                } else {
+                                    info!("Max clients reached, connection denied");
+                                    connection.disconnect();
+                                }
+                            }
+                            Some(message) => {
+                                warn!(
+                                    "Connection {} sent {:?} before signing in",
+                                    connection.client_id, message
+                                );
+                                connection.disconnect();
+                            }
+                            None => {
+                                if connect_time.elapsed() > PENDING_CONNECTION_TIMEOUT {
+                                    info!("Connection {} timed out before signing in", connection.client_id);
+                                    connection.disconnect();
+                                } else {
+                                    still_pending.push((connection, connect_time));
+                                }
+                            }
                         }
                     }
+                    pending_connections = still_pending;
 
                     // Handle client messages
-                    let mut signed_in_player_ids = Vec::new();
+                    let mut signed_in_client_ids = Vec::new();
+                    let mut admin_commands = Vec::new();
                     for client in &mut clients {
-                        if let Some(message) = client.connection.try_receive() {
+                        if let Some(message) = client.try_receive() {
                             match message {
-                                ClientMessage::SignIn { username } => {
+                                ClientMessage::SignIn { username, host_key, .. } => {
+                                    let is_host = is_host(
+                                        server_host_key.as_ref(),
+                                        host_key.as_ref(),
+                                        client.connection.peer_ip(),
+                                    );
+                                    if !is_host {
+                                        if let Err(reason) = access_store
+                                            .check_sign_in(&username, client.connection.peer_ip())
+                                        {
+                                            info!("Sign in of {} denied: {}", username, reason);
+                                            client.connection.send(ServerMessage::SignInDenied { reason });
+                                            client.connection.disconnect();
+                                            continue;
+                                        }
+                                    }
                                     client.sign_in(username.clone());
-                                    if let Some(player) =
+                                    client.set_op(is_host || access_store.is_op(&username));
+                                    if client.spectator {
+                                        // Spectators are never stored, they start above the spawn area
+                                        client.data = PlayerData::new(
+                                            client.player_id,
+                                            &client.data.username,
+                                        );
+                                        client.data.x = REGION_SIZE_BLOCKS as f32 / 2.0;
+                                        client.data.y = REGION_SIZE_BLOCKS as f32 / 2.0;
+                                        client.data.z = world
+                                            .get_top_z(client.data.x as WorldCoord, client.data.y as WorldCoord)
+                                            as f32
+                                            + 10.0;
+                                        info!(
+                                            "Spectator {} signed in",
+                                            client.data.username
+                                        );
+                                    } else if let Some(player) =
                                         player_store.get_player(&client.data.username)
                                     {
                                         // Existing player
@@ -136,7 +267,7 @@ impl YabServer {
                                         client.data.x = REGION_SIZE_BLOCKS as f32 / 2.0 + rng.gen_range(-spawn_range, spawn_range);
                                         client.data.y = REGION_SIZE_BLOCKS as f32 / 2.0 + rng.gen_range(-spawn_range, spawn_range);
                                         client.data.z = world
-                                            .get_top_z(client.data.x as i16, client.data.y as i16)
+                                            .get_top_z(client.data.x as WorldCoord, client.data.y as WorldCoord)
                                             as f32
                                             + 3.0;
                                         player_store.new_player(&client.data);
@@ -155,17 +286,29 @@ impl YabServer {
                                         inventory: client.data.inventory.clone(),
                                         gametime: daynight.get_time(),
                                         block_registry: serde_json::to_string(&block_registry.all_blocks()).unwrap(),
+                                        op: client.op,
+                                        height_chunks: world.world_def().height_chunks as u16,
+                                        // Clients derive the biomes from the biome map, like the generator
+                                        biome_seed: match world.world_def().world_type {
+                                            GeneratorType::Biomes => Some(biome_seed(world.world_def().seed)),
+                                            _ => None,
+                                        },
                                     });
-                                    broadcast_to_all.push(ServerMessage::PlayerSpawn {
-                                        x: client.data.x,
-                                        y: client.data.y,
-                                        z: client.data.z,
-                                        yaw: client.data.yaw,
-                                        pitch: client.data.pitch,
-                                        player_id: client.player_id,
-                                        username: username,
+                                    client.connection.send(ServerMessage::Claims {
+                                        claims: claim_store.claims().clone(),
                                     });
-                                    signed_in_player_ids.push(client.player_id);
+                                    if !client.spectator {
+                                        broadcast_to_all.push(ServerMessage::PlayerSpawn {
+                                            x: client.data.x,
+                                            y: client.data.y,
+                                            z: client.data.z,
+                                            yaw: client.data.yaw,
+                                            pitch: client.data.pitch,
+                                            player_id: client.player_id,
+                                            username: username,
+                                        });
+                                    }
+                                    signed_in_client_ids.push(client.connection.client_id);
                                 }
                                 ClientMessage::SignOut {} => {
                                     info!(
@@ -174,6 +317,82 @@ impl YabServer {
                                     );
                                     client.connection.disconnect();
                                 }
+                                ClientMessage::AdminCommand { command } => {
+                                    if !client.is_signed_in() {
+                                        continue;
+                                    }
+                                    if client.op {
+                                        admin_commands.push((client.connection.client_id, command));
+                                    } else {
+                                        warn!(
+                                            "Player {} ({}) is not allowed to use admin commands",
+                                            client.data.username, client.player_id
+                                        );
+                                        client.connection.send(ServerMessage::AdminResponse {
+                                            message: "You are not an operator".to_string(),
+                                        });
+                                    }
+                                }
+                                ClientMessage::Claim { min, max } => {
+                                    if !client.is_signed_in() || client.spectator {
+                                        continue;
+                                    }
+                                    match claim_store.add(&client.data.username, min, max, client.op) {
+                                        Ok(claim_id) => {
+                                            info!("Player {} claimed {:?} to {:?} ({})", client.data.username, min, max, claim_id);
+                                            broadcast_to_all.push(ServerMessage::Claims {
+                                                claims: claim_store.claims().clone(),
+                                            });
+                                        }
+                                        Err(e) => {
+                                            warn!("Claim of player {} denied: {}", client.data.username, e);
+                                            client.connection.send(ServerMessage::ClaimDenied { reason: e });
+                                        }
+                                    }
+                                }
+                                ClientMessage::Unclaim { claim_id } => {
+                                    if !client.is_signed_in() || client.spectator {
+                                        continue;
+                                    }
+                                    match claim_store.remove(claim_id, &client.data.username, client.op) {
+                                        Ok(_) => {
+                                            info!("Player {} removed claim {}", client.data.username, claim_id);
+                                            broadcast_to_all.push(ServerMessage::Claims {
+                                                claims: claim_store.claims().clone(),
+                                            });
+                                        }
+                                        Err(e) => {
+                                            warn!("Unclaim of player {} denied: {}", client.data.username, e);
+                                            client.connection.send(ServerMessage::ClaimDenied { reason: e });
+                                        }
+                                    }
+                                }
+                                ClientMessage::SetClaimMember {
+                                    claim_id,
+                                    username,
+                                    member,
+                                } => {
+                                    if !client.is_signed_in() || client.spectator {
+                                        continue;
+                                    }
+                                    match claim_store.set_member(claim_id, &client.data.username, client.op, &username, member) {
+                                        Ok(_) => {
+                                            broadcast_to_all.push(ServerMessage::Claims {
+                                                claims: claim_store.claims().clone(),
+                                            });
+                                        }
+                                        Err(e) => {
+                                            warn!("Claim member change of player {} denied: {}", client.data.username, e);
+                                            client.connection.send(ServerMessage::ClaimDenied { reason: e });
+                                        }
+                                    }
+                                }
+                                ClientMessage::StatusRequest {} => {
+                                    debug!(
+                                        "Ignoring status request of signed in player {}",
+                                        client.player_id
+                                    );
+                                }
                                 ClientMessage::PositionUpdate {
                                     x,
                                     y,
@@ -184,7 +403,24 @@ impl YabServer {
                                     if !client.is_signed_in() {
                                         continue;
                                     }
+                                    // Only operators and spectators may fly, other players are moved back when they do
+                                    if !client.op && !client.spectator {
+                                        let moved_back = world
+                                            .is_player_supported(x, y, z)
+                                            .and_then(|supported| client.flight_check.check(x, y, z, supported, Instant::now()));
+                                        if let Some((x, y, z)) = moved_back {
+                                            warn!(
+                                                "Player {} ({}) flies without being an operator, moving it back",
+                                                client.data.username, client.player_id
+                                            );
+                                            client.connection.send(ServerMessage::MovePlayer { x, y, z });
+                                            continue;
+                                        }
+                                    }
                                     client.update_position(x, y, z, yaw, pitch);
+                                    if client.spectator {
+                                        continue;
+                                    }
                                     let player =
                                         player_store.get_mut_player(&client.data.username).unwrap();
                                     player.x = x;
@@ -236,6 +472,23 @@ impl YabServer {
                                     if !client.is_signed_in() {
                                         continue;
                                     }
+                                    if client.spectator
+                                        || (!client.op
+                                            && !claim_store.may_build(&client.data.username, wbx, wby))
+                                    {
+                                        warn!(
+                                            "User {} ({}) is not allowed to change block {},{},{}",
+                                            client.data.username, client.player_id, wbx, wby, wbz
+                                        );
+                                        // Undo the change on the client side
+                                        client.connection.send(ServerMessage::SetBlock {
+                                            wbx,
+                                            wby,
+                                            wbz,
+                                            block: world.get_block(wbx, wby, wbz).kind(),
+                                        });
+                                        continue;
+                                    }
                                     // Add or remove resources from inventory
                                     let block = block.kind();
                                     let mut allowed = true;
@@ -260,7 +513,7 @@ impl YabServer {
                                         }
                                     }
                                     if allowed {
-                                        world.set_block(wbx, wby, wbz, block);
+                                        world.set_block(wbx, wby, wbz, block, &client.data.username);
                                         broadcast_to_all.push(ServerMessage::SetBlock {
                                             wbx,
                                             wby,
@@ -272,11 +525,35 @@ impl YabServer {
                             }
                         }
                     }
-                    // Sign in new players by sending them existing spawns
-                    for player_id in signed_in_player_ids {
+                    // Execute admin commands, these can affect any client
+                    for (client_id, command) in admin_commands {
+                        let position = match clients.iter().find(|c| c.connection.client_id == client_id) {
+                            Some(client) => (client.data.x as WorldCoord, client.data.y as WorldCoord, client.data.z as WorldCoord),
+                            None => continue,
+                        };
+                        let message = execute_admin_command(
+                            &command,
+                            position,
+                            &mut access_store,
+                            &mut world,
+                            &mut backups,
+                            &mut clients,
+                            &block_registry,
+                            &mut broadcast_to_all,
+                        );
+                        if let Some(client) = clients.iter_mut().find(|c| c.connection.client_id == client_id) {
+                            client.connection.send(ServerMessage::AdminResponse { message });
+                        }
+                    }
+
+                    // Sign in new players and spectators by sending them existing spawns
+                    for client_id in signed_in_client_ids {
                         let mut spawns = Vec::new();
                         for client in &clients {
-                            if client.is_signed_in() && client.player_id != player_id {
+                            if client.is_signed_in()
+                                && !client.spectator
+                                && client.connection.client_id != client_id
+                            {
                                 spawns.push(ServerMessage::PlayerSpawn {
                                     x: client.data.x,
                                     y: client.data.y,
@@ -289,7 +566,7 @@ impl YabServer {
                             }
                         }
                         for client in &mut clients {
-                            if client.player_id == player_id {
+                            if client.connection.client_id == client_id {
                                 for spawn in &spawns {
                                     client.connection.send(spawn.clone());
                                 }
@@ -403,6 +680,30 @@ impl YabServer {
                                     );
                                 }
                             }
+                            ServerMessage::Claims { claims } => {
+                                for broadcast_target in &mut clients {
+                                    if !broadcast_target.is_signed_in() {
+                                        continue;
+                                    }
+                                    broadcast_target.connection.send(ServerMessage::Claims {
+                                        claims: claims.clone(),
+                                    });
+                                }
+                            }
+                            ServerMessage::ChunkColumn { col, block_data } => {
+                                // Columns replace the ones the clients have, e.g. after a paste
+                                for broadcast_target in &mut clients {
+                                    if !broadcast_target.is_signed_in() {
+                                        continue;
+                                    }
+                                    if broadcast_target.is_subscribed_to(*col) {
+                                        broadcast_target.connection.send(ServerMessage::ChunkColumn {
+                                            col: *col,
+                                            block_data: block_data.clone(),
+                                        });
+                                    }
+                                }
+                            }
                             _ => {}
                         }
                     }
@@ -427,7 +728,9 @@ impl YabServer {
 
                     // Filter out closed clients
                     clients.retain(|c| {
-                        if !c.connection.connected {
+                        if !c.connection.connected && c.spectator {
+                            info!("Spectator {} disconnected", c.data.username);
+                        } else if !c.connection.connected && c.authenticated {
                             info!("Player {} despawned", c.player_id);
                             broadcast_to_all.push(ServerMessage::PlayerDespawn {
                                 player_id: c.player_id,
@@ -437,8 +740,20 @@ impl YabServer {
                     });
 
                     update_profile.start();
+                    backups.update();
+                    if backups.is_due() {
+                        // Save everything first, the files are not written while the backup is made
+                        world.save(daynight.get_time());
+                        player_store.save_if_needed(true);
+                        backups.start();
+                    }
+                    world.set_saving_paused(backups.is_running());
+                    access_store.set_saving_paused(backups.is_running());
+                    claim_store.set_saving_paused(backups.is_running());
                     world.update( daynight.get_time());
-                    player_store.save_if_needed(false);
+                    if !backups.is_running() {
+                        player_store.save_if_needed(false);
+                    }
                     update_profile.end();
 
                     // Sleep for a little if there is nothing to do to reduce CPU usage
@@ -473,7 +788,14 @@ impl YabServer {
                             for client in &mut clients {
                                 client.connection.disconnect();
                             }
+                            for (connection, _) in &mut pending_connections {
+                                connection.disconnect();
+                            }
                             server_comms.shutdown();
+                            backups.wait();
+                            world.set_saving_paused(false);
+                            access_store.set_saving_paused(false);
+                            claim_store.set_saving_paused(false);
                             world.save(daynight.get_time());
                             player_store.save_if_needed(true);
                             break;
@@ -483,9 +805,14 @@ impl YabServer {
                 }
             })
             .unwrap();
-        if wait {
+        // The server thread stops right away if the world cannot be loaded
+        let started = started_rx
+            .recv()
+            .unwrap_or_else(|_| Err("the server stopped while loading the world".to_string()));
+        if started.is_err() || wait {
             handle.join().unwrap();
         }
+        started
     }
 
     pub fn shutdown(&mut self, message: String) {
@@ -500,11 +827,71 @@ impl YabServer {
     }
 }
 
+/// Load the world with the given ID, creating it first if it does not exist. Returns the block
+/// registry and folder of the world with it.
+fn load_world(
+    world_id: &str,
+    seed: u32,
+    description: &str,
+    world_type: GeneratorType,
+    height_chunks: usize,
+    preset: WorldPreset,
+) -> Result<(BlockRegistry, PathBuf, ServerWorldHandler), String> {
+    let world_list = WorldsStore::new();
+    let world_folder = world_list.get_world_path(world_id);
+    let assets = Assets::new("assets");
+    let block_registry = BlockRegistry::load_or_create(&assets.path("blocks.json"))?;
+    debug!(
+        "Server block registry contains {} blocks",
+        block_registry.all_blocks().len()
+    );
+
+    if world_folder.exists() {
+        backup::apply_requested_restore(&world_folder);
+    } else {
+        world_list.create_new_world(
+            world_id,
+            seed,
+            description,
+            world_type,
+            height_chunks,
+            preset,
+            load_ore_definitions(&assets.path(ORES_FILE)),
+        );
+    }
+    let storage = FileStorage::open(&world_folder)
+        .map_err(|e| format!("world {} cannot be loaded: {}", world_id, e))?;
+    let world = ServerWorldHandler::new(Box::new(storage), &block_registry)
+        .map_err(|e| format!("world {} cannot be loaded: {}", world_id, e))?;
+    Ok((block_registry, world_folder, world))
+}
+
+/// Describe the server to connections that have not signed in
+fn server_status(
+    clients: &Vec<Client>,
+    world_def: &WorldDef,
+    config: &ServerConfig,
+) -> ServerStatus {
+    ServerStatus {
+        protocol_version: PROTOCOL_VERSION,
+        description: world_def.description.clone(),
+        seed: world_def.seed,
+        world_type: format!("{:?}", world_def.world_type),
+        motd: config.motd.clone(),
+        max_players: MAX_PLAYERS as u8,
+        players: clients
+            .iter()
+            .filter(|c| c.is_signed_in() && !c.spectator)
+            .map(|c| c.data.username.clone())
+            .collect(),
+    }
+}
+
 /// Find the lowest unused player ID
 fn get_free_player_id(clients: &Vec<Client>) -> u8 {
     // Get a sorted list of existing player IDs
     let mut player_ids = Vec::new();
-    for client in clients {
+    for client in clients.iter().filter(|c| !c.spectator) {
         player_ids.push(client.player_id);
     }
     player_ids.sort_by(|a, b| a.partial_cmp(&b).unwrap());
@@ -518,3 +905,40 @@ fn get_free_player_id(clients: &Vec<Client>) -> u8 {
     }
     last_id + 1
 }
+
+/// Whether a client is the host: it knows the key of the server and connects from the same
+/// machine. Usernames are chosen by the clients, so they cannot identify the host.
+fn is_host(
+    server_host_key: Option<&String>,
+    client_host_key: Option<&String>,
+    peer_ip: Option<IpAddr>,
+) -> bool {
+    match (server_host_key, client_host_key, peer_ip) {
+        (Some(server_key), Some(client_key), Some(ip)) => {
+            server_key == client_key && ip.is_loopback()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod host_test {
+
+    use crate::is_host;
+    use std::net::IpAddr;
+
+    #[test]
+    fn host_needs_key_and_loopback() {
+        let key = "0123456789abcdef".to_string();
+        let other_key = "fedcba9876543210".to_string();
+        let local: IpAddr = "127.0.0.1".parse().unwrap();
+        let remote: IpAddr = "192.168.1.20".parse().unwrap();
+        assert!(is_host(Some(&key), Some(&key), Some(local)));
+        assert!(!is_host(Some(&key), Some(&key), Some(remote)));
+        assert!(!is_host(Some(&key), Some(&other_key), Some(local)));
+        assert!(!is_host(Some(&key), None, Some(local)));
+        assert!(!is_host(Some(&key), Some(&key), None));
+        // Without a host anyone claiming to be the host is refused
+        assert!(!is_host(None, Some(&key), Some(local)));
+    }
+}
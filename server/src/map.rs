@@ -0,0 +1,391 @@
+use crate::region::{region_position, Region};
+use crate::storage::FileStorage;
+use crate::world_store::WorldStore;
+use common::block::{Block, BlockRegistry, BlockTrait, AIR_BLOCK_KIND, FACE_ZP};
+use common::chunk::{Chunk, ChunkColumnPos, CHUNK_SIZE};
+use common::world_pos::WorldCoord;
+use image::{save_buffer_with_format, ColorType, ImageFormat};
+use log::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Colour of blocks without a texture that can be loaded
+const UNKNOWN_COLOR: [u8; 3] = [255, 0, 255];
+// Colour of positions without any block
+const EMPTY_COLOR: [u8; 3] = [0, 0, 0];
+// Area around the origin drawn for worlds without stored columns, in columns
+const DEFAULT_RANGE_COLUMNS: WorldCoord = 8;
+/// Largest number of pixels of a map, the image is kept in memory while it is drawn
+pub const MAX_MAP_PIXELS: u64 = 8192 * 8192;
+
+/// Part of the world to draw, in world block coordinates, with the number of blocks per pixel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapArea {
+    pub min_x: WorldCoord,
+    pub min_y: WorldCoord,
+    pub max_x: WorldCoord,
+    pub max_y: WorldCoord,
+    pub scale: WorldCoord,
+}
+
+impl MapArea {
+    /// An area between two corners, both inclusive. Fails if the corners are swapped or the
+    /// map would have more than MAX_MAP_PIXELS pixels.
+    pub fn new(
+        min_x: WorldCoord,
+        min_y: WorldCoord,
+        max_x: WorldCoord,
+        max_y: WorldCoord,
+        scale: WorldCoord,
+    ) -> Option<MapArea> {
+        if min_x > max_x || min_y > max_y || scale < 1 {
+            return None;
+        }
+        let area = MapArea {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+            scale,
+        };
+        let pixels = area
+            .pixel_size(min_x, max_x)
+            .checked_mul(area.pixel_size(min_y, max_y))?;
+        if pixels > MAX_MAP_PIXELS {
+            return None;
+        }
+        Some(area)
+    }
+
+    /// Parse an area from "min_x,min_y,max_x,max_y"
+    pub fn parse(area: &str, scale: WorldCoord) -> Option<MapArea> {
+        let coords: Vec<WorldCoord> = area
+            .split(',')
+            .map(|c| c.trim().parse().ok())
+            .collect::<Option<Vec<WorldCoord>>>()?;
+        if coords.len() != 4 {
+            return None;
+        }
+        MapArea::new(coords[0], coords[1], coords[2], coords[3], scale)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixel_size(self.min_x, self.max_x) as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixel_size(self.min_y, self.max_y) as u32
+    }
+
+    fn pixel_size(&self, min: WorldCoord, max: WorldCoord) -> u64 {
+        (max as i64 - min as i64) as u64 / self.scale as u64 + 1
+    }
+}
+
+/// Colours of blocks seen from above, the average colour of their top texture
+pub struct BlockColors {
+    colors: Vec<Option<[u8; 3]>>,
+}
+
+impl BlockColors {
+    /// Average the top texture of each block in the texture folder
+    pub fn load(registry: &BlockRegistry, texture_folder: &Path) -> BlockColors {
+        let colors = registry
+            .all_blocks()
+            .iter()
+            .map(|block_def| {
+                let texture = block_def.textures.get(FACE_ZP)?;
+                let path = texture_folder.join(format!("{}.png", texture));
+                match average_color(&path) {
+                    Ok(color) => Some(color),
+                    Err(e) => {
+                        warn!("Cannot load texture {:?}: {}", path, e);
+                        Some(UNKNOWN_COLOR)
+                    }
+                }
+            })
+            .collect();
+        BlockColors { colors }
+    }
+
+    pub fn get(&self, block: Block) -> [u8; 3] {
+        match self.colors.get(block.kind() as usize) {
+            Some(Some(color)) => *color,
+            Some(None) => EMPTY_COLOR,
+            None => UNKNOWN_COLOR,
+        }
+    }
+}
+
+/// Average colour of a texture, weighted by alpha so transparent pixels do not count
+fn average_color(path: &Path) -> Result<[u8; 3], image::ImageError> {
+    let image = image::open(path)?.to_rgba();
+    let mut sum = [0u64; 3];
+    let mut weight = 0u64;
+    for pixel in image.pixels() {
+        let alpha = pixel[3] as u64;
+        for i in 0..3 {
+            sum[i] += pixel[i] as u64 * alpha;
+        }
+        weight += alpha;
+    }
+    if weight == 0 {
+        return Ok(EMPTY_COLOR);
+    }
+    Ok([
+        (sum[0] / weight) as u8,
+        (sum[1] / weight) as u8,
+        (sum[2] / weight) as u8,
+    ])
+}
+
+/// Highest block that is not air at a position in a column, with its z
+fn top_block(chunks: &Vec<Chunk>, x: usize, y: usize) -> Option<(Block, usize)> {
+    for chunk in chunks.iter().rev() {
+        if chunk.is_solid() && chunk.get_block(0, 0, 0).kind() == AIR_BLOCK_KIND {
+            continue;
+        }
+        for z in (0..CHUNK_SIZE).rev() {
+            let block = chunk.get_block(x, y, z);
+            if block.kind() != AIR_BLOCK_KIND {
+                return Some((block, chunk.pos.z as usize * CHUNK_SIZE + z));
+            }
+        }
+    }
+    None
+}
+
+/// Draw a top-down map of an area with one pixel per `scale` blocks, north (+y) up. Each pixel
+/// has the colour of the highest block. With height shading pixels that are higher than the
+/// pixel to their north are lighter and lower ones are darker. Returns the RGB bytes of the image.
+pub fn render_map(
+    store: &mut WorldStore,
+    colors: &BlockColors,
+    area: &MapArea,
+    shading: bool,
+) -> Vec<u8> {
+    let width = area.width() as usize;
+    let height = area.height() as usize;
+    let mut pixels = vec![EMPTY_COLOR; width * height];
+    let mut heights = vec![0usize; width * height];
+    let size = CHUNK_SIZE as WorldCoord;
+    let col_min = ChunkColumnPos::from_world_block_coords(area.min_x, area.min_y);
+    let col_max = ChunkColumnPos::from_world_block_coords(area.max_x, area.max_y);
+    for cx in col_min.x..=col_max.x {
+        for cy in col_min.y..=col_max.y {
+            let mut chunks = None;
+            for rel_x in 0..size {
+                let wbx = cx * size + rel_x;
+                if wbx < area.min_x || wbx > area.max_x || (wbx - area.min_x) % area.scale != 0 {
+                    continue;
+                }
+                for rel_y in 0..size {
+                    let wby = cy * size + rel_y;
+                    if wby < area.min_y || wby > area.max_y || (wby - area.min_y) % area.scale != 0
+                    {
+                        continue;
+                    }
+                    // Columns are only loaded when a pixel needs them
+                    let chunks = chunks.get_or_insert_with(|| {
+                        store.load_or_generate_column(ChunkColumnPos::new(cx, cy))
+                    });
+                    let px = ((wbx - area.min_x) / area.scale) as usize;
+                    let py = height - 1 - ((wby - area.min_y) / area.scale) as usize;
+                    if let Some((block, z)) = top_block(chunks, rel_x as usize, rel_y as usize) {
+                        pixels[py * width + px] = colors.get(block);
+                        heights[py * width + px] = z;
+                    }
+                }
+            }
+        }
+    }
+    let mut bytes = Vec::with_capacity(width * height * 3);
+    for py in 0..height {
+        for px in 0..width {
+            let color = pixels[py * width + px];
+            let factor = if shading && py > 0 {
+                let z = heights[py * width + px];
+                let north_z = heights[(py - 1) * width + px];
+                if z > north_z {
+                    1.15
+                } else if z < north_z {
+                    0.85
+                } else {
+                    1.0
+                }
+            } else {
+                1.0
+            };
+            for c in color.iter() {
+                bytes.push((*c as f32 * factor).min(255.0) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+/// Area of the stored columns of a world, or an area around the origin if nothing is stored
+fn stored_area(world_folder: &Path, scale: WorldCoord) -> io::Result<MapArea> {
+    let mut bounds: Option<(WorldCoord, WorldCoord, WorldCoord, WorldCoord)> = None;
+    for entry in fs::read_dir(world_folder)?.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let (region_x, region_y) = match region_position(&name) {
+            Some(position) => position,
+            None => continue,
+        };
+        let region = match Region::open_read_only(&entry.path()) {
+            Ok(region) => region,
+            Err(e) => {
+                warn!("Region {} cannot be opened: {}", name, e);
+                continue;
+            }
+        };
+        for col in region.stored_columns(region_x, region_y) {
+            bounds = Some(match bounds {
+                Some((min_x, min_y, max_x, max_y)) => (
+                    min_x.min(col.x),
+                    min_y.min(col.y),
+                    max_x.max(col.x),
+                    max_y.max(col.y),
+                ),
+                None => (col.x, col.y, col.x, col.y),
+            });
+        }
+    }
+    let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((
+        -DEFAULT_RANGE_COLUMNS,
+        -DEFAULT_RANGE_COLUMNS,
+        DEFAULT_RANGE_COLUMNS - 1,
+        DEFAULT_RANGE_COLUMNS - 1,
+    ));
+    // The block coordinates of columns far from the origin do not fit
+    let size = CHUNK_SIZE as WorldCoord;
+    let to_blocks = |min: WorldCoord, max: WorldCoord| {
+        Some((
+            min.checked_mul(size)?,
+            max.checked_add(1)?.checked_mul(size)? - 1,
+        ))
+    };
+    let area = match (to_blocks(min_x, max_x), to_blocks(min_y, max_y)) {
+        (Some((min_x, max_x)), Some((min_y, max_y))) => {
+            MapArea::new(min_x, min_y, max_x, max_y, scale)
+        }
+        _ => None,
+    };
+    area.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "the stored columns do not fit in a map of {} pixels, give an area or a larger scale",
+                MAX_MAP_PIXELS
+            ),
+        )
+    })
+}
+
+/// Export a top-down map of a world to a PNG file. Without an area the map shows the columns
+/// that were changed. The world is migrated first if it is in an older format.
+pub fn export_map(
+    world_folder: &Path,
+    registry: &BlockRegistry,
+    texture_folder: &Path,
+    area: Option<MapArea>,
+    scale: WorldCoord,
+    shading: bool,
+    png_path: &Path,
+) -> io::Result<MapArea> {
+    let storage = FileStorage::open(world_folder)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut store = match WorldStore::new(Box::new(storage), registry) {
+        Some(store) => store,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "world definition cannot be loaded",
+            ))
+        }
+    };
+    let area = match area {
+        Some(area) => area,
+        None => stored_area(world_folder, scale)?,
+    };
+    let colors = BlockColors::load(registry, texture_folder);
+    info!(
+        "Drawing map of {},{} to {},{} at {} blocks per pixel",
+        area.min_x, area.min_y, area.max_x, area.max_y, area.scale
+    );
+    let bytes = render_map(&mut store, &colors, &area, shading);
+    save_buffer_with_format(
+        png_path,
+        &bytes,
+        area.width(),
+        area.height(),
+        ColorType::Rgb8,
+        ImageFormat::Png,
+    )
+    .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+    Ok(area)
+}
+
+#[cfg(test)]
+mod map_test {
+
+    use crate::map::*;
+    use crate::storage::MemoryStorage;
+    use crate::world_store::test_world_def;
+    use common::block::BEDROCK_BLOCK;
+    use common::world_type::GeneratorType;
+
+    #[test]
+    fn parse_area() {
+        let area = MapArea::parse("-32,-16,31,15", 2).unwrap();
+        assert_eq!(
+            (area.min_x, area.min_y, area.max_x, area.max_y),
+            (-32, -16, 31, 15)
+        );
+        assert_eq!((area.width(), area.height()), (32, 16));
+        assert!(MapArea::parse("1,2,3", 1).is_none());
+        assert!(MapArea::parse("5,0,0,5", 1).is_none());
+        assert!(MapArea::parse("0,0,5,5", 0).is_none());
+        // Areas that would make a huge image are refused, without overflowing
+        assert!(MapArea::parse("-2147483648,0,2147483647,0", 1).is_none());
+        let full = format!("{},{},{},{}", i32::MIN, i32::MIN, i32::MAX, i32::MAX);
+        assert!(MapArea::parse(&full, 1).is_none());
+        let area = MapArea::parse(&full, 1 << 20).unwrap();
+        assert_eq!((area.width(), area.height()), (4096, 4096));
+        assert!(MapArea::parse("0,0,8191,8191", 1).is_some());
+        assert!(MapArea::parse("0,0,8191,8192", 1).is_none());
+    }
+
+    #[test]
+    fn top_blocks_are_drawn() {
+        let registry = BlockRegistry::default();
+        let world_def = test_world_def(GeneratorType::Flat, 4);
+        let mut store =
+            WorldStore::new(Box::new(MemoryStorage::new(world_def)), &registry).unwrap();
+        let col = ChunkColumnPos::new(0, 0);
+        let mut column = store.load_or_generate_column(col);
+        let top_chunk = column.len() - 1;
+        column[top_chunk].set_block(3, 5, CHUNK_SIZE - 1, BEDROCK_BLOCK);
+        store.enqueue_chunk_save(&column[top_chunk]);
+
+        let mut colors = BlockColors {
+            colors: vec![None; 256],
+        };
+        colors.colors[BEDROCK_BLOCK.kind() as usize] = Some([10, 20, 30]);
+        let area = MapArea {
+            min_x: 0,
+            min_y: 0,
+            max_x: 15,
+            max_y: 15,
+            scale: 1,
+        };
+        let bytes = render_map(&mut store, &colors, &area, false);
+        assert_eq!(bytes.len(), 16 * 16 * 3);
+        // North is up, so y 5 is in row 10
+        let i = (10 * 16 + 3) * 3;
+        assert_eq!(&bytes[i..i + 3], &[10, 20, 30]);
+        assert_ne!(&bytes[0..3], &[10, 20, 30]);
+    }
+}
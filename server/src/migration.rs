@@ -0,0 +1,187 @@
+use crate::block_log::convert_block_log_coords;
+use crate::region::convert_superchunks;
+use common::world_definition::{WorldDef, WORLD_DEF_FILE, WORLD_FORMAT_VERSION};
+use log::*;
+use std::io;
+use std::path::Path;
+
+/// A migration step converts a world folder from one format version to the next
+type MigrationStep = fn(&Path) -> io::Result<()>;
+
+/// Migration steps, the step at index N converts format version N to version N + 1
+const MIGRATIONS: [MigrationStep; WORLD_FORMAT_VERSION as usize] = [
+    // Version 0 stored chunk columns in superchunk files, version 1 uses region files
+    convert_superchunks,
+    // Version 2 stores changed columns as diffs against the generated terrain
+    full_columns_to_diffs,
+    // Version 3 uses 32-bit world coordinates in the block log
+    convert_block_log_coords,
+];
+
+/// Chunks stored in full are diffs without unchanged blocks, so they can be kept as they are
+fn full_columns_to_diffs(_world_folder: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Bring a world folder up to date with the current format version. Each step is saved in
+/// the world definition when it completes, so an interrupted migration resumes where it stopped.
+/// Worlds written in a newer format are refused.
+pub fn migrate_world(world_folder: &Path, world_def: &mut WorldDef) -> Result<(), String> {
+    if world_def.format_version > WORLD_FORMAT_VERSION {
+        return Err(format!(
+            "world format version {} is newer than the supported version {}",
+            world_def.format_version, WORLD_FORMAT_VERSION
+        ));
+    }
+    while world_def.format_version < WORLD_FORMAT_VERSION {
+        let from = world_def.format_version;
+        info!(
+            "Migrating world {} from format version {} to {}",
+            world_def.seed,
+            from,
+            from + 1
+        );
+        MIGRATIONS[from as usize](world_folder).map_err(|e| {
+            format!(
+                "migration from format version {} to {} failed: {}",
+                from,
+                from + 1,
+                e
+            )
+        })?;
+        world_def.format_version = from + 1;
+        world_def
+            .save(&world_folder.join(WORLD_DEF_FILE))
+            .map_err(|e| format!("world definition cannot be saved: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod migration_test {
+
+    use crate::block_log::BlockLog;
+    use crate::migration::migrate_world;
+    use crate::region::{region_filename, Region};
+    use common::block::{Block, AIR_BLOCK, BEDROCK_BLOCK};
+    use common::chunk::{ChunkColumnPos, DEFAULT_WORLD_HEIGHT_CHUNKS};
+    use common::comms::RleDecode;
+    use common::world_definition::{WorldDef, WORLD_DEF_FILE, WORLD_FORMAT_VERSION};
+    use std::fs;
+    use std::io::Cursor;
+    use std::path::{Path, PathBuf};
+
+    /// Copy a fixture world to a temporary folder, so the fixture itself is never changed
+    fn fixture_world(fixture: &str, test: &str) -> PathBuf {
+        let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures")
+            .join("worlds")
+            .join(fixture);
+        let target = std::env::temp_dir().join(format!("yab_migration_test_{}", test));
+        if target.exists() {
+            fs::remove_dir_all(&target).unwrap();
+        }
+        fs::create_dir_all(&target).unwrap();
+        for entry in fs::read_dir(&source).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, target.join(path.file_name().unwrap())).unwrap();
+        }
+        target
+    }
+
+    fn load_def(world_folder: &Path) -> WorldDef {
+        WorldDef::load(&world_folder.join(WORLD_DEF_FILE)).unwrap()
+    }
+
+    fn has_extension(world_folder: &Path, extension: &str) -> bool {
+        fs::read_dir(world_folder).unwrap().any(|e| {
+            e.unwrap()
+                .path()
+                .extension()
+                .map_or(false, |ext| ext == extension)
+        })
+    }
+
+    /// The fixtures hold the columns (0,0) and (1,0), with bedrock in the lowest chunk and
+    /// air above it
+    fn check_fixture_columns(world_folder: &Path) {
+        for x in 0..2 {
+            let col = ChunkColumnPos::new(x, 0);
+            let mut region = Region::open(&world_folder.join(region_filename(col))).unwrap();
+            let column_bytes = region.read_column(col).unwrap().unwrap();
+            assert_eq!(column_bytes.len(), DEFAULT_WORLD_HEIGHT_CHUNKS);
+            for (z, chunk_bytes) in column_bytes.iter().enumerate() {
+                let blocks: Vec<Block> =
+                    Vec::rle_decode_from(&mut Cursor::new(chunk_bytes)).unwrap();
+                let expected = if z == 0 { BEDROCK_BLOCK } else { AIR_BLOCK };
+                assert!(blocks.iter().all(|b| *b == expected));
+            }
+        }
+    }
+
+    #[test]
+    fn migrate_superchunk_world() {
+        let world_folder = fixture_world("v0", "superchunks");
+        let mut world_def = load_def(&world_folder);
+        assert_eq!(world_def.format_version, 0);
+        assert!(has_extension(&world_folder, "chk"));
+
+        migrate_world(&world_folder, &mut world_def).unwrap();
+        assert_eq!(world_def.format_version, WORLD_FORMAT_VERSION);
+        assert_eq!(load_def(&world_folder).format_version, WORLD_FORMAT_VERSION);
+        assert!(!has_extension(&world_folder, "chk"));
+
+        check_fixture_columns(&world_folder);
+    }
+
+    #[test]
+    fn migrate_region_world() {
+        let world_folder = fixture_world("v1", "regions");
+        let mut world_def = load_def(&world_folder);
+        assert_eq!(world_def.format_version, 1);
+
+        migrate_world(&world_folder, &mut world_def).unwrap();
+        assert_eq!(world_def.format_version, WORLD_FORMAT_VERSION);
+        assert_eq!(load_def(&world_folder).format_version, WORLD_FORMAT_VERSION);
+        check_fixture_columns(&world_folder);
+    }
+
+    #[test]
+    fn migrate_block_log() {
+        let world_folder = fixture_world("v1", "block_log");
+        // A record with 16-bit coordinates, as written by format version 2
+        let mut record = Vec::new();
+        record.extend_from_slice(&1234u32.to_le_bytes());
+        record.extend_from_slice(&0u16.to_le_bytes());
+        record.extend_from_slice(&(-300i16).to_le_bytes());
+        record.extend_from_slice(&(-2i16).to_le_bytes());
+        record.extend_from_slice(&70i16.to_le_bytes());
+        record.extend_from_slice(&AIR_BLOCK.to_le_bytes());
+        record.extend_from_slice(&BEDROCK_BLOCK.to_le_bytes());
+        fs::write(world_folder.join("block_log.dat"), &record).unwrap();
+        fs::write(world_folder.join("block_log_users.txt"), "builder\n").unwrap();
+
+        let mut world_def = load_def(&world_folder);
+        migrate_world(&world_folder, &mut world_def).unwrap();
+        let changes = BlockLog::open(&world_folder)
+            .unwrap()
+            .changes_by("builder", 0);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].timestamp, 1234);
+        assert_eq!(
+            (changes[0].wbx, changes[0].wby, changes[0].wbz),
+            (-300, -2, 70)
+        );
+        assert_eq!(changes[0].old_block, AIR_BLOCK);
+        assert_eq!(changes[0].new_block, BEDROCK_BLOCK);
+    }
+
+    #[test]
+    fn refuse_newer_world() {
+        let world_folder = fixture_world("v1", "newer");
+        let mut world_def = load_def(&world_folder);
+        world_def.format_version = WORLD_FORMAT_VERSION + 1;
+        assert!(migrate_world(&world_folder, &mut world_def).is_err());
+        assert_eq!(world_def.format_version, WORLD_FORMAT_VERSION + 1);
+    }
+}
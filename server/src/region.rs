@@ -0,0 +1,696 @@
+use crate::superchunk::SuperChunk;
+use common::chunk::*;
+use common::comms::read_from::ReadFrom;
+use common::comms::write_to::WriteTo;
+use common::world_pos::WorldCoord;
+use crc32fast::Hasher;
+use log::*;
+use snap::raw::{Decoder, Encoder};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Width and length of a region in chunk columns
+pub const REGION_SIZE_COLUMNS: WorldCoord = 32;
+const COLUMN_COUNT: usize = (REGION_SIZE_COLUMNS * REGION_SIZE_COLUMNS) as usize;
+const MAGIC: &[u8; 4] = b"YABR";
+const FORMAT_VERSION: u16 = 2;
+// The index follows the magic and version, it has two index entries for each column
+const INDEX_OFFSET: u64 = 4 + 2;
+const ENTRY_SIZE: u64 = 12;
+const HEADER_SIZE: u64 = INDEX_OFFSET + COLUMN_COUNT as u64 * 2 * ENTRY_SIZE;
+// Regions are compacted when unused records take more space than this and than the used ones
+const MIN_COMPACT_WASTE: u64 = 1024 * 1024;
+
+/// Location and checksum of a compressed column record
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Record {
+    offset: u32,
+    length: u32,
+    checksum: u32,
+}
+
+/// A region file stores a square of chunk columns. The header holds an index with the
+/// offset, length and checksum of each column record, so single columns can be read and
+/// written without touching the rest of the file. Each record is compressed independently.
+/// Records are always appended and the previous record of a column is kept as a backup,
+/// so a write that is interrupted by a crash never damages the last saved version.
+/// Changed index entries are written by `sync`, once the records they refer to are on disk.
+pub struct Region {
+    path: PathBuf,
+    file: File,
+    // Current and backup record of each column
+    index: Vec<(Record, Record)>,
+    // Index entries that changed since the last sync
+    unsynced: Vec<usize>,
+    file_length: u64,
+    pub last_touched: Instant,
+}
+
+impl Region {
+    /// Open an existing region file
+    pub fn open(path: &Path) -> io::Result<Region> {
+        Region::open_with(path, true)
+    }
+
+    /// Open an existing region file without write access, columns cannot be written
+    pub fn open_read_only(path: &Path) -> io::Result<Region> {
+        Region::open_with(path, false)
+    }
+
+    fn open_with(path: &Path, write: bool) -> io::Result<Region> {
+        let mut file = OpenOptions::new().read(true).write(write).open(path)?;
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic).map_err(header_error)?;
+        let version = read_u16(&mut file).map_err(header_error)?;
+        if &magic != MAGIC || version != FORMAT_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("not a version {} region file", FORMAT_VERSION),
+            ));
+        }
+        let mut header = vec![0u8; (HEADER_SIZE - INDEX_OFFSET) as usize];
+        file.read_exact(&mut header).map_err(header_error)?;
+        let mut reader = Cursor::new(header);
+        let file_length = file.metadata()?.len();
+        let mut index = Vec::with_capacity(COLUMN_COUNT);
+        for i in 0..COLUMN_COUNT {
+            let mut records = Vec::new();
+            for _ in 0..2 {
+                let record = read_record(&mut reader)?;
+                if record.length == 0 {
+                    continue;
+                }
+                // The index has no checksum, records outside the file are dropped and a good
+                // backup then becomes the current record
+                if (record.offset as u64) < HEADER_SIZE
+                    || record.offset as u64 + record.length as u64 > file_length
+                {
+                    warn!(
+                        "Dropping index entry {} of region {:?} that is outside the file",
+                        i, path
+                    );
+                    continue;
+                }
+                records.push(record);
+            }
+            records.resize(2, Record::default());
+            index.push((records[0], records[1]));
+        }
+        Ok(Region {
+            path: path.to_path_buf(),
+            file,
+            index,
+            unsynced: Vec::new(),
+            file_length,
+            last_touched: Instant::now(),
+        })
+    }
+
+    /// Create a new empty region file
+    pub fn create(path: &Path) -> io::Result<Region> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let mut header = Vec::with_capacity(HEADER_SIZE as usize);
+        header.extend_from_slice(MAGIC);
+        write_to(&mut header, &FORMAT_VERSION)?;
+        header.resize(HEADER_SIZE as usize, 0);
+        file.write_all(&header)?;
+        file.sync_all()?;
+        sync_parent_dir(path)?;
+        Ok(Region {
+            path: path.to_path_buf(),
+            file,
+            index: vec![(Record::default(), Record::default()); COLUMN_COUNT],
+            unsynced: Vec::new(),
+            file_length: HEADER_SIZE,
+            last_touched: Instant::now(),
+        })
+    }
+
+    pub fn open_or_create(path: &Path) -> io::Result<Region> {
+        if path.exists() {
+            Region::open(path)
+        } else {
+            Region::create(path)
+        }
+    }
+
+    pub fn contains(&self, col: ChunkColumnPos) -> bool {
+        self.index[column_index(col)].0.length > 0
+    }
+
+    /// Columns stored in the region, given the position of the region from its filename
+    pub fn stored_columns(
+        &self,
+        region_x: WorldCoord,
+        region_y: WorldCoord,
+    ) -> Vec<ChunkColumnPos> {
+        let mut columns = Vec::new();
+        for (i, (current, _)) in self.index.iter().enumerate() {
+            if current.length > 0 {
+                let x = (i % REGION_SIZE_COLUMNS as usize) as WorldCoord;
+                let y = (i / REGION_SIZE_COLUMNS as usize) as WorldCoord;
+                columns.push(ChunkColumnPos::new(
+                    region_x * REGION_SIZE_COLUMNS + x,
+                    region_y * REGION_SIZE_COLUMNS + y,
+                ));
+            }
+        }
+        columns
+    }
+
+    /// Read the run-length encoded chunks of a column, if stored.
+    /// Falls back to the backup record if the current one is damaged.
+    pub fn read_column(&mut self, col: ChunkColumnPos) -> io::Result<Option<Vec<Vec<u8>>>> {
+        let (current, backup) = self.index[column_index(col)];
+        if current.length == 0 {
+            return Ok(None);
+        }
+        let error = match self.read_record(current) {
+            Ok(column_bytes) => return Ok(Some(column_bytes)),
+            Err(e) => e,
+        };
+        if backup.length == 0 {
+            return Err(error);
+        }
+        warn!(
+            "Column {:?} in region {:?} is damaged ({}), loading the previous version",
+            col, self.path, error
+        );
+        self.read_record(backup).map(Some)
+    }
+
+    /// Write the run-length encoded chunks of a column. The new record is only used after a
+    /// crash once the region has been synced.
+    pub fn write_column(
+        &mut self,
+        col: ChunkColumnPos,
+        column_bytes: &Vec<Vec<u8>>,
+    ) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        for chunk_bytes in column_bytes {
+            assert!(chunk_bytes.len() < std::u16::MAX as usize);
+            write_to(&mut bytes, &(chunk_bytes.len() as u16))?;
+            bytes.extend_from_slice(chunk_bytes);
+        }
+        let compressed = Encoder::new()
+            .compress_vec(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let record = self.append_raw(&compressed)?;
+
+        // Update the index entry, keeping the previous record as backup
+        let i = column_index(col);
+        let (current, _) = self.index[i];
+        self.index[i] = (record, current);
+        if !self.unsynced.contains(&i) {
+            self.unsynced.push(i);
+        }
+        Ok(())
+    }
+
+    /// Write the changed index entries, after making sure the records they refer to are on
+    /// disk, so a batch of column writes needs only two syncs
+    pub fn sync(&mut self) -> io::Result<()> {
+        if self.unsynced.is_empty() {
+            return Ok(());
+        }
+        self.file.sync_data()?;
+        for i in std::mem::take(&mut self.unsynced) {
+            self.write_index_entry(i, self.index[i])?;
+        }
+        self.file.sync_data()
+    }
+
+    /// Are there so many unused records that the region should be compacted?
+    pub fn needs_compaction(&self) -> bool {
+        let used = self.used_length();
+        let wasted = self
+            .file_length
+            .saturating_sub(HEADER_SIZE)
+            .saturating_sub(used);
+        wasted > MIN_COMPACT_WASTE && wasted > used
+    }
+
+    /// Rewrite the region without the unused records. The compacted region is written to a
+    /// temporary file that replaces the region only once it is completely on disk.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("reg.tmp");
+        let mut tmp = Region::create(&tmp_path)?;
+        for i in 0..COLUMN_COUNT {
+            let (current, backup) = self.index[i];
+            // Damaged records are dropped, a good backup then becomes the current record
+            let mut records = Vec::new();
+            for record in [current, backup].iter().filter(|r| r.length > 0) {
+                match self.read_raw(*record) {
+                    Ok(compressed) => records.push(tmp.append_raw(&compressed)?),
+                    Err(e) => warn!("Dropping damaged record from region {:?}: {}", self.path, e),
+                }
+            }
+            records.resize(2, Record::default());
+            let entry = (records[0], records[1]);
+            tmp.write_index_entry(i, entry)?;
+            tmp.index[i] = entry;
+        }
+        tmp.file.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        sync_parent_dir(&self.path)?;
+        info!(
+            "Compacted region {:?} from {} to {} bytes",
+            self.path, self.file_length, tmp.file_length
+        );
+        tmp.path = self.path.clone();
+        tmp.last_touched = self.last_touched;
+        // The index entries of the compacted region are all on disk
+        self.unsynced.clear();
+        *self = tmp;
+        Ok(())
+    }
+
+    fn used_length(&self) -> u64 {
+        self.index
+            .iter()
+            .map(|(current, backup)| current.length as u64 + backup.length as u64)
+            .sum()
+    }
+
+    /// Read a record and check it against its checksum
+    fn read_raw(&mut self, record: Record) -> io::Result<Vec<u8>> {
+        if record.offset as u64 + record.length as u64 > self.file_length {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "record extends beyond the end of the file",
+            ));
+        }
+        let mut compressed = vec![0u8; record.length as usize];
+        self.file.seek(SeekFrom::Start(record.offset as u64))?;
+        self.file.read_exact(&mut compressed)?;
+        if checksum(&compressed) != record.checksum {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "record checksum mismatch",
+            ));
+        }
+        Ok(compressed)
+    }
+
+    fn read_record(&mut self, record: Record) -> io::Result<Vec<Vec<u8>>> {
+        let compressed = self.read_raw(record)?;
+        let bytes = Decoder::new()
+            .decompress_vec(&compressed)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let length = bytes.len() as u64;
+        let mut reader = Cursor::new(bytes);
+        // The number of chunks depends on the world height, so read until the record ends
+        let mut column_bytes = Vec::new();
+        while reader.position() < length {
+            let chunk_length = read_u16(&mut reader)? as usize;
+            let mut chunk_bytes = vec![0u8; chunk_length];
+            reader.read_exact(&mut chunk_bytes)?;
+            column_bytes.push(chunk_bytes);
+        }
+        Ok(column_bytes)
+    }
+
+    /// Append an already compressed record, without syncing
+    fn append_raw(&mut self, compressed: &[u8]) -> io::Result<Record> {
+        let offset = self.file_length;
+        // Offsets and lengths are stored as u32
+        if offset + compressed.len() as u64 > std::u32::MAX as u64 {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "region file is too large",
+            ));
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        self.file.write_all(compressed)?;
+        self.file_length += compressed.len() as u64;
+        Ok(Record {
+            offset: offset as u32,
+            length: compressed.len() as u32,
+            checksum: checksum(compressed),
+        })
+    }
+
+    fn write_index_entry(&mut self, i: usize, entry: (Record, Record)) -> io::Result<()> {
+        let mut entry_bytes = Vec::new();
+        write_record(&mut entry_bytes, entry.0)?;
+        write_record(&mut entry_bytes, entry.1)?;
+        self.file
+            .seek(SeekFrom::Start(INDEX_OFFSET + i as u64 * 2 * ENTRY_SIZE))?;
+        self.file.write_all(&entry_bytes)
+    }
+}
+
+impl Drop for Region {
+    fn drop(&mut self) {
+        if let Err(e) = self.sync() {
+            error!("Error syncing region {:?}: {}", self.path, e);
+        }
+    }
+}
+
+/// Filename of the region containing a column
+pub fn region_filename(col: ChunkColumnPos) -> String {
+    format!("r{}_{}.reg", col.x >> 5, col.y >> 5)
+}
+
+/// Position of a region from its filename, the inverse of region_filename
+pub fn region_position(filename: &str) -> Option<(WorldCoord, WorldCoord)> {
+    let name = filename.strip_prefix('r')?.strip_suffix(".reg")?;
+    let mut parts = name.splitn(2, '_');
+    let x = parts.next()?.parse().ok()?;
+    let y = parts.next()?.parse().ok()?;
+    // Positions outside this range cannot hold a column, the index would overflow
+    let range = (WorldCoord::MIN >> 5)..=(WorldCoord::MAX >> 5);
+    if !range.contains(&x) || !range.contains(&y) {
+        return None;
+    }
+    Some((x, y))
+}
+
+/// Convert the superchunk files of the old world format in a folder to region files
+pub fn convert_superchunks(world_folder: &Path) -> io::Result<()> {
+    for entry in fs::read_dir(world_folder)?.flatten() {
+        let sc_path = entry.path();
+        if sc_path.extension().map_or(true, |ext| ext != "chk") {
+            continue;
+        }
+        info!("Converting superchunk {:?} to region format", sc_path);
+        let sc = match SuperChunk::load(&sc_path) {
+            Ok(sc) => sc,
+            Err(e) => {
+                // The columns in it will be generated again from the seed
+                error!(
+                    "Superchunk {:?} is damaged and cannot be converted: {}",
+                    sc_path, e
+                );
+                set_aside_damaged_file(&sc_path);
+                continue;
+            }
+        };
+        let mut regions: Vec<(String, Region)> = Vec::new();
+        for (col, column_bytes) in &sc.chunk_data {
+            let filename = region_filename(*col);
+            if !regions.iter().any(|(name, _)| *name == filename) {
+                let region = Region::open_or_create(&world_folder.join(&filename))?;
+                regions.push((filename.clone(), region));
+            }
+            let (_, region) = regions
+                .iter_mut()
+                .find(|(name, _)| *name == filename)
+                .unwrap();
+            // Columns that were saved in the new format already are more recent
+            if !region.contains(*col) {
+                region.write_column(*col, column_bytes)?;
+            }
+        }
+        for (_, region) in regions.iter_mut() {
+            region.sync()?;
+        }
+        fs::remove_file(&sc_path)?;
+    }
+    Ok(())
+}
+
+fn column_index(col: ChunkColumnPos) -> usize {
+    let x = (col.x & (REGION_SIZE_COLUMNS - 1)) as usize;
+    let y = (col.y & (REGION_SIZE_COLUMNS - 1)) as usize;
+    y * REGION_SIZE_COLUMNS as usize + x
+}
+
+/// Move a damaged file out of the way so it is not loaded again, but can still be inspected
+pub fn set_aside_damaged_file(path: &Path) {
+    let damaged_path = PathBuf::from(format!("{}.damaged", path.to_string_lossy()));
+    match fs::rename(path, &damaged_path) {
+        Ok(_) => error!("Moved damaged file {:?} to {:?}", path, damaged_path),
+        Err(e) => error!("Cannot move damaged file {:?} aside: {}", path, e),
+    }
+}
+
+/// Make sure a rename or newly created file in a folder is on disk
+#[cfg(unix)]
+pub fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => File::open(parent)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+/// Directories cannot be opened for syncing on this platform
+#[cfg(not(unix))]
+pub fn sync_parent_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// A region file that ends within its header is damaged, not unreadable
+fn header_error(e: io::Error) -> io::Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            io::Error::new(io::ErrorKind::InvalidData, "region header is truncated")
+        }
+        _ => e,
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    let mut hasher = Hasher::new();
+    hasher.update(bytes);
+    hasher.finalize()
+}
+
+fn write_record(bytes: &mut Vec<u8>, record: Record) -> io::Result<()> {
+    write_to(bytes, &record.offset)?;
+    write_to(bytes, &record.length)?;
+    write_to(bytes, &record.checksum)
+}
+
+fn read_record<R: Read>(reader: &mut R) -> io::Result<Record> {
+    Ok(Record {
+        offset: read_u32(reader)?,
+        length: read_u32(reader)?,
+        checksum: read_u32(reader)?,
+    })
+}
+
+fn write_to<T: WriteTo<Vec<u8>>>(bytes: &mut Vec<u8>, value: &T) -> io::Result<()> {
+    value
+        .write_to(bytes)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    u16::read_from(reader).map_err(|e| io::Error::new(io::ErrorKind::UnexpectedEof, e.to_string()))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    u32::read_from(reader).map_err(|e| io::Error::new(io::ErrorKind::UnexpectedEof, e.to_string()))
+}
+
+#[cfg(test)]
+mod region_test {
+
+    use crate::region::*;
+
+    fn temp_region(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    /// Chunks of bytes that do not compress, so records have a known size
+    fn noise_column(seed: u32, chunks: u32, length: usize) -> Vec<Vec<u8>> {
+        (0..chunks)
+            .map(|i| noise(seed * chunks + i, length))
+            .collect()
+    }
+
+    fn noise(seed: u32, length: usize) -> Vec<u8> {
+        let mut state = seed.wrapping_mul(2654435761).wrapping_add(1);
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn write_and_read_columns() {
+        let path = temp_region("yab_region_test.reg");
+        let mut region = Region::create(&path).unwrap();
+        let col = ChunkColumnPos::new(-3, 5);
+        let other = ChunkColumnPos::new(-4, 5);
+        let column_bytes = vec![vec![1, 2, 3], vec![], vec![4; 100]];
+        region.write_column(col, &column_bytes).unwrap();
+        assert!(region.contains(col));
+        assert!(!region.contains(other));
+        assert_eq!(region.read_column(other).unwrap(), None);
+
+        // The column is only in the index on disk after a sync
+        assert!(!Region::open(&path).unwrap().contains(col));
+        region.sync().unwrap();
+        let mut region = Region::open_read_only(&path).unwrap();
+        assert_eq!(region.read_column(col).unwrap(), Some(column_bytes));
+        assert_eq!(
+            region.stored_columns(-1, 0),
+            vec![ChunkColumnPos::new(-3, 5)]
+        );
+        assert!(region.write_column(other, &vec![vec![1]]).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn grown_column_is_relocated() {
+        let path = temp_region("yab_region_grow_test.reg");
+        let mut region = Region::create(&path).unwrap();
+        let col = ChunkColumnPos::new(1, 1);
+        let small = noise_column(1, 2, 100);
+        let large = noise_column(2, 4, 60000);
+        region.write_column(col, &small).unwrap();
+        region
+            .write_column(ChunkColumnPos::new(2, 1), &small)
+            .unwrap();
+        let (first, _) = region.index[column_index(col)];
+        region.write_column(col, &large).unwrap();
+
+        // Records are never rewritten in place, the new record is appended after the other
+        // column and the previous one becomes the backup
+        let (current, backup) = region.index[column_index(col)];
+        assert_eq!(backup, first);
+        assert!(current.length > 4 * 60000);
+        assert!(current.offset as u64 > first.offset as u64 + first.length as u64);
+        drop(region);
+        let mut region = Region::open(&path).unwrap();
+        assert_eq!(region.read_column(col).unwrap(), Some(large));
+
+        // A damaged record falls back to the previous version of the column
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(current.offset as u64 + 10))
+            .unwrap();
+        file.write_all(&[0; 16]).unwrap();
+        drop(file);
+        let mut region = Region::open(&path).unwrap();
+        assert_eq!(region.read_column(col).unwrap(), Some(small));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn compaction_frees_unused_records() {
+        let path = temp_region("yab_region_compact_test.reg");
+        let mut region = Region::create(&path).unwrap();
+        let col = ChunkColumnPos::new(0, 0);
+        let other = ChunkColumnPos::new(31, 31);
+        region.write_column(other, &vec![vec![7; 10]]).unwrap();
+        let mut last = Vec::new();
+        for version in 0..8 {
+            last = noise_column(version, 4, 60000);
+            region.write_column(col, &last).unwrap();
+        }
+        assert!(region.needs_compaction());
+        let length = fs::metadata(&path).unwrap().len();
+
+        // Only the current and backup records of each column are kept
+        region.compact().unwrap();
+        assert!(!region.needs_compaction());
+        let compacted_length = fs::metadata(&path).unwrap().len();
+        assert!(compacted_length < length / 3);
+        assert_eq!(compacted_length, region.file_length);
+        assert_eq!(region.read_column(col).unwrap(), Some(last.clone()));
+
+        // New records are appended after the compacted ones
+        region.write_column(col, &vec![vec![1]]).unwrap();
+        drop(region);
+        let mut region = Region::open(&path).unwrap();
+        assert_eq!(region.read_column(col).unwrap(), Some(vec![vec![1]]));
+        assert_eq!(region.read_column(other).unwrap(), Some(vec![vec![7; 10]]));
+        assert!(fs::metadata(&path).unwrap().len() < length / 3);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn index_outside_file_is_dropped() {
+        let path = temp_region("yab_region_index_test.reg");
+        let mut region = Region::create(&path).unwrap();
+        let col = ChunkColumnPos::new(3, 4);
+        let other = ChunkColumnPos::new(4, 4);
+        let first = noise_column(1, 2, 1000);
+        let second = noise_column(2, 2, 1000);
+        region.write_column(other, &first).unwrap();
+        region.write_column(col, &first).unwrap();
+        region.write_column(col, &second).unwrap();
+        region.sync().unwrap();
+        let (current, backup) = region.index[column_index(col)];
+        drop(region);
+
+        // The file is cut off in the middle of the current record of the column
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(current.offset as u64 + 10).unwrap();
+        drop(file);
+        let mut region = Region::open(&path).unwrap();
+        assert_eq!(region.index[column_index(col)], (backup, Record::default()));
+        assert_eq!(region.read_column(col).unwrap(), Some(first.clone()));
+        assert_eq!(region.read_column(other).unwrap(), Some(first.clone()));
+        assert!(!region.needs_compaction());
+
+        // An index entry before the end of the header is dropped as well
+        let mut record = Vec::new();
+        write_record(
+            &mut record,
+            Record {
+                offset: 1,
+                length: 1,
+                checksum: 0,
+            },
+        )
+        .unwrap();
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(
+            INDEX_OFFSET + column_index(other) as u64 * 2 * ENTRY_SIZE,
+        ))
+        .unwrap();
+        file.write_all(&record).unwrap();
+        drop(file);
+        let mut region = Region::open(&path).unwrap();
+        assert!(!region.contains(other));
+        assert_eq!(region.read_column(col).unwrap(), Some(first));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn region_positions() {
+        let col = ChunkColumnPos::new(-33, 64);
+        assert_eq!(region_position(&region_filename(col)), Some((-2, 2)));
+        let col = ChunkColumnPos::new(WorldCoord::MIN, WorldCoord::MAX);
+        let (x, y) = region_position(&region_filename(col)).unwrap();
+        let path = temp_region("yab_region_far_test.reg");
+        let mut region = Region::create(&path).unwrap();
+        region.write_column(col, &vec![vec![1]]).unwrap();
+        assert_eq!(region.stored_columns(x, y), vec![col]);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(region_position("r67108864_0.reg"), None);
+        assert_eq!(region_position("r0_0.chk"), None);
+    }
+
+    #[test]
+    fn truncated_region_is_invalid() {
+        let path = temp_region("yab_region_truncated_test.reg");
+        fs::write(&path, b"YABR").unwrap();
+        let e = Region::open(&path).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        fs::write(&path, b"not a region").unwrap();
+        let e = Region::open(&path).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        fs::remove_file(&path).unwrap();
+        let e = Region::open(&path).err().unwrap();
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+    }
+}
@@ -0,0 +1,301 @@
+use common::comms::{read_from::ReadFrom, write_to::WriteTo, RleDecode, RleEncode};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Folder where schematics are stored, they can be pasted in any world
+pub const SCHEMATIC_FOLDER: &str = "schematics";
+const SCHEMATIC_EXTENSION: &str = "yabs";
+const MAGIC: &[u8; 4] = b"YABS";
+const SCHEMATIC_FORMAT_VERSION: u16 = 1;
+/// Maximum size of a schematic along each axis, so a layer fits in a single RLE run list
+pub const MAX_SCHEMATIC_SIZE: usize = 128;
+/// Maximum number of blocks in a schematic, so copying and pasting one does not stall the server
+pub const MAX_SCHEMATIC_VOLUME: usize = 64 * 64 * 128;
+
+/// A box of blocks. Blocks refer to a palette of block codes rather than block indices, so a
+/// schematic can be pasted after the block registry has changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Schematic {
+    pub size_x: usize,
+    pub size_y: usize,
+    pub size_z: usize,
+    palette: Vec<String>,
+    // Palette index of each block, x first, then y and then z
+    blocks: Vec<u16>,
+}
+
+impl Schematic {
+    /// Create a schematic filled with the given block code
+    pub fn new(size_x: usize, size_y: usize, size_z: usize, code: &str) -> Self {
+        assert!(size_x > 0 && size_y > 0 && size_z > 0);
+        assert!(
+            size_x <= MAX_SCHEMATIC_SIZE
+                && size_y <= MAX_SCHEMATIC_SIZE
+                && size_z <= MAX_SCHEMATIC_SIZE
+        );
+        assert!(size_x * size_y * size_z <= MAX_SCHEMATIC_VOLUME);
+        Schematic {
+            size_x,
+            size_y,
+            size_z,
+            palette: vec![code.to_string()],
+            blocks: vec![0; size_x * size_y * size_z],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> &str {
+        &self.palette[self.blocks[self.index(x, y, z)] as usize]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, code: &str) {
+        let palette_index = match self.palette.iter().position(|c| c == code) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(code.to_string());
+                self.palette.len() - 1
+            }
+        };
+        let index = self.index(x, y, z);
+        self.blocks[index] = palette_index as u16;
+    }
+
+    /// The block codes used in the schematic
+    pub fn palette(&self) -> &Vec<String> {
+        &self.palette
+    }
+
+    /// The schematic mirrored along the x axis if requested and then turned counterclockwise
+    /// (seen from above) by a number of quarter turns
+    pub fn transformed(&self, quarter_turns: usize, mirror: bool) -> Schematic {
+        let mut result = self.clone();
+        if mirror {
+            for z in 0..self.size_z {
+                for y in 0..self.size_y {
+                    for x in 0..self.size_x {
+                        let index = result.index(x, y, z);
+                        result.blocks[index] = self.blocks[self.index(self.size_x - 1 - x, y, z)];
+                    }
+                }
+            }
+        }
+        for _ in 0..quarter_turns % 4 {
+            let source = result.clone();
+            result.size_x = source.size_y;
+            result.size_y = source.size_x;
+            for z in 0..source.size_z {
+                for y in 0..source.size_y {
+                    for x in 0..source.size_x {
+                        let index = result.index(source.size_y - 1 - y, x, z);
+                        result.blocks[index] = source.blocks[source.index(x, y, z)];
+                    }
+                }
+            }
+        }
+        result
+    }
+
+    /// Write the schematic: a header with the size and the palette, followed by the
+    /// run-length encoded palette indices of each layer
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        write_to(writer, &SCHEMATIC_FORMAT_VERSION)?;
+        write_to(writer, &(self.size_x as u16))?;
+        write_to(writer, &(self.size_y as u16))?;
+        write_to(writer, &(self.size_z as u16))?;
+        write_to(writer, &(self.palette.len() as u16))?;
+        for code in &self.palette {
+            write_to(writer, code)?;
+        }
+        let layer_size = self.size_x * self.size_y;
+        for layer in self.blocks.chunks(layer_size) {
+            layer
+                .to_vec()
+                .rle_encode_to(writer)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Schematic> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a schematic"));
+        }
+        let version = read_u16(reader)?;
+        if version != SCHEMATIC_FORMAT_VERSION {
+            return Err(invalid_data(&format!(
+                "schematic format version {} is not supported",
+                version
+            )));
+        }
+        let size_x = read_u16(reader)? as usize;
+        let size_y = read_u16(reader)? as usize;
+        let size_z = read_u16(reader)? as usize;
+        let sizes = [size_x, size_y, size_z];
+        if sizes
+            .iter()
+            .any(|size| *size == 0 || *size > MAX_SCHEMATIC_SIZE)
+            || size_x * size_y * size_z > MAX_SCHEMATIC_VOLUME
+        {
+            return Err(invalid_data(&format!(
+                "invalid schematic size {}x{}x{}",
+                size_x, size_y, size_z
+            )));
+        }
+        let palette_size = read_u16(reader)? as usize;
+        let mut palette = Vec::with_capacity(palette_size);
+        for _ in 0..palette_size {
+            palette.push(String::read_from(reader).map_err(|e| invalid_data(&e.to_string()))?);
+        }
+        let mut blocks = Vec::with_capacity(size_x * size_y * size_z);
+        for _ in 0..size_z {
+            let layer: Vec<u16> =
+                Vec::rle_decode_from(reader).map_err(|e| invalid_data(&e.to_string()))?;
+            if layer.len() != size_x * size_y {
+                return Err(invalid_data("schematic layer has the wrong size"));
+            }
+            if layer.iter().any(|index| *index as usize >= palette.len()) {
+                return Err(invalid_data("schematic block is not in the palette"));
+            }
+            blocks.extend(layer);
+        }
+        Ok(Schematic {
+            size_x,
+            size_y,
+            size_z,
+            palette,
+            blocks,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(folder) = path.parent() {
+            fs::create_dir_all(folder)?;
+        }
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    pub fn load(path: &Path) -> io::Result<Schematic> {
+        Schematic::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        assert!(x < self.size_x && y < self.size_y && z < self.size_z);
+        x + (y + z * self.size_y) * self.size_x
+    }
+}
+
+/// Path of the schematic with a name, names are limited to letters, digits, `-` and `_`
+pub fn schematic_path(name: &str) -> Option<PathBuf> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+    Some(
+        Path::new(SCHEMATIC_FOLDER)
+            .join(name)
+            .with_extension(SCHEMATIC_EXTENSION),
+    )
+}
+
+/// Names of the stored schematics, sorted
+pub fn list_schematics() -> Vec<String> {
+    let mut names: Vec<String> = match fs::read_dir(SCHEMATIC_FOLDER) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .map_or(false, |ext| ext == SCHEMATIC_EXTENSION)
+            })
+            .filter_map(|path| {
+                path.file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    names.sort();
+    names
+}
+
+fn write_to<W: Write, T: WriteTo<W>>(writer: &mut W, value: &T) -> io::Result<()> {
+    value
+        .write_to(writer)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    u16::read_from(reader).map_err(|e| invalid_data(&e.to_string()))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+#[cfg(test)]
+mod schematic_test {
+
+    use crate::schematic::*;
+    use std::io::Cursor;
+
+    /// A 3x2x2 schematic with a stone line along x, and a dirt block at its end
+    fn test_schematic() -> Schematic {
+        let mut schematic = Schematic::new(3, 2, 2, "air");
+        for x in 0..3 {
+            schematic.set(x, 0, 0, "stone");
+        }
+        schematic.set(2, 1, 1, "dirt");
+        schematic
+    }
+
+    #[test]
+    fn round_trip() {
+        let schematic = test_schematic();
+        let mut bytes = Vec::new();
+        schematic.write_to(&mut bytes).unwrap();
+        let read = Schematic::read_from(&mut Cursor::new(&bytes)).unwrap();
+        assert_eq!(read, schematic);
+        assert_eq!(read.palette(), &vec!["air", "stone", "dirt"]);
+        bytes.truncate(bytes.len() - 2);
+        assert!(Schematic::read_from(&mut Cursor::new(&bytes)).is_err());
+    }
+
+    #[test]
+    fn rotate_and_mirror() {
+        let schematic = test_schematic();
+        let mirrored = schematic.transformed(0, true);
+        assert_eq!(mirrored.get(0, 1, 1), "dirt");
+        assert_eq!(mirrored.get(2, 1, 1), "air");
+
+        // A quarter turn turns the line along x into a line along y
+        let turned = schematic.transformed(1, false);
+        assert_eq!((turned.size_x, turned.size_y, turned.size_z), (2, 3, 2));
+        for y in 0..3 {
+            assert_eq!(turned.get(1, y, 0), "stone");
+            assert_eq!(turned.get(0, y, 0), "air");
+        }
+        assert_eq!(turned.get(0, 2, 1), "dirt");
+
+        assert_eq!(schematic.transformed(4, false), schematic);
+        assert_eq!(schematic.transformed(2, false).get(0, 0, 1), "dirt");
+        assert_eq!(
+            schematic.transformed(2, true),
+            schematic.transformed(0, true).transformed(2, false)
+        );
+    }
+
+    #[test]
+    fn schematic_names() {
+        assert!(schematic_path("tower_2").is_some());
+        assert!(schematic_path("../tower").is_none());
+        assert!(schematic_path("").is_none());
+    }
+}
@@ -0,0 +1,105 @@
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub motd: String,
+    /// Minutes between world backups, zero disables scheduled backups
+    #[serde(default = "default_backup_interval_minutes")]
+    pub backup_interval_minutes: u64,
+    /// Number of world backups that are kept
+    #[serde(default = "default_backup_count")]
+    pub backup_count: usize,
+}
+
+impl ServerConfig {
+    pub fn defaults() -> Self {
+        ServerConfig {
+            motd: "Welcome to YAB-World".to_string(),
+            backup_interval_minutes: default_backup_interval_minutes(),
+            backup_count: default_backup_count(),
+        }
+    }
+
+    pub fn load() -> Self {
+        ServerConfig::load_from(&ServerConfig::get_config_path())
+    }
+
+    /// Load the configuration, a file that cannot be read or parsed is kept as it is so the
+    /// operator can fix it, the defaults are only saved when there is no file yet
+    fn load_from(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(config_string) => match serde_json::from_str(&config_string) {
+                Ok(config) => config,
+                Err(e) => {
+                    error!(
+                        "Error parsing server configuration file {:?}, using defaults: {}",
+                        path, e
+                    );
+                    ServerConfig::defaults()
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let config = ServerConfig::defaults();
+                config.save_to(path);
+                config
+            }
+            Err(e) => {
+                error!(
+                    "Error loading server configuration file {:?}, using defaults: {}",
+                    path, e
+                );
+                ServerConfig::defaults()
+            }
+        }
+    }
+
+    fn save_to(&self, path: &Path) {
+        let config_string = serde_json::to_string_pretty(&self).unwrap();
+        if let Err(e) = fs::write(path, config_string) {
+            warn!("Error saving server configuration file: {}", e);
+        } else {
+            info!("Server configuration file {:?} updated: {:?}", path, self);
+        }
+    }
+
+    fn get_config_path() -> PathBuf {
+        PathBuf::from("server_config.json")
+    }
+}
+
+fn default_backup_interval_minutes() -> u64 {
+    30
+}
+
+fn default_backup_count() -> usize {
+    10
+}
+
+#[cfg(test)]
+mod server_config_test {
+
+    use crate::server_config::*;
+
+    #[test]
+    fn broken_config_is_kept() {
+        let folder = std::env::temp_dir().join("yab_server_config_test");
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        let path = folder.join("server_config.json");
+        // Without a file the defaults are saved
+        let config = ServerConfig::load_from(&path);
+        assert_eq!(config.motd, ServerConfig::defaults().motd);
+        assert!(path.exists());
+        // A file with an error is not overwritten
+        let broken = r#"{ "motd": "Hello", "backup_count": "many" }"#;
+        fs::write(&path, broken).unwrap();
+        let config = ServerConfig::load_from(&path);
+        assert_eq!(config.motd, ServerConfig::defaults().motd);
+        assert_eq!(fs::read_to_string(&path).unwrap(), broken);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
@@ -1,21 +1,36 @@
 use core::time;
-use std::{collections::HashMap, thread::sleep};
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    thread::sleep,
+};
 
 use common::{
-    block::{Block, BlockRegistry},
+    block::{Block, BlockRegistry, BlockTrait, AIR_BLOCK_KIND},
     chunk::{
         chunk_buffer::ChunkBuffer, Chunk, ChunkColumn, ChunkColumnPos, ChunkPos, ColumnStatus,
-        CHUNK_SIZE, WORLD_HEIGHT_CHUNKS,
+        CHUNK_SIZE,
     },
     comms::RleEncode,
-    world_type::GeneratorType,
+    world_definition::WorldDef,
+    world_pos::WorldCoord,
 };
 use nalgebra_glm::Vec3;
 
-use crate::{generator::WorldGenerator, world_store::WorldStore};
+use crate::{
+    block_log::{BlockChange, BlockLog},
+    generator::WorldGenerator,
+    schematic::{Schematic, MAX_SCHEMATIC_SIZE, MAX_SCHEMATIC_VOLUME},
+    storage::WorldStorage,
+    world_store::WorldStore,
+};
+
+// Blocks below the position of a player, from its eyes to just below its feet, that support it
+const PLAYER_SUPPORT_DEPTH: WorldCoord = 3;
 
 pub struct ServerWorldHandler {
     store: WorldStore,
+    block_log: Option<BlockLog>,
     buffer: ChunkBuffer,
     generator: WorldGenerator,
     generate_requests: HashMap<ChunkColumnPos, usize>,
@@ -24,66 +39,90 @@ pub struct ServerWorldHandler {
 }
 
 impl ServerWorldHandler {
-    /// Create a new world
+    /// Handle the world kept in a storage backend, fails if the storage has no world definition
     pub fn new(
-        seed: u32,
-        description: &str,
-        world_type: GeneratorType,
+        storage: Box<dyn WorldStorage>,
         block_registry: &BlockRegistry,
-    ) -> Self {
-        ServerWorldHandler {
-            store: WorldStore::new(seed, description, world_type),
-            buffer: ChunkBuffer::new(),
-            generator: WorldGenerator::new(seed, world_type, block_registry),
+    ) -> Result<Self, String> {
+        let store = match WorldStore::new(storage, block_registry) {
+            Some(store) => store,
+            None => return Err("the world definition cannot be loaded".to_string()),
+        };
+        let height_chunks = store.world_def().height_chunks;
+        let generator = WorldGenerator::new(store.world_def(), block_registry);
+        // The block log is only kept for worlds on the filesystem
+        let block_log = store
+            .world_folder()
+            .map(BlockLog::open)
+            .transpose()
+            .map_err(|e| format!("the block log cannot be opened: {}", e))?;
+        Ok(ServerWorldHandler {
+            store,
+            block_log,
+            buffer: ChunkBuffer::new(height_chunks),
+            generator,
             generate_requests: HashMap::new(),
             max_outstanding_work: num_cpus::get() * 2,
             outstanding_work: 0,
-        }
+        })
     }
 
-    /// Load an existing world
-    pub fn load(seed: u32, block_registry: &BlockRegistry) -> Self {
-        let store = WorldStore::load(seed).unwrap();
-        let world_type = store.world_def().world_type;
-        ServerWorldHandler {
-            store,
-            buffer: ChunkBuffer::new(),
-            generator: WorldGenerator::new(seed, world_type, block_registry),
-            generate_requests: HashMap::new(),
-            max_outstanding_work: num_cpus::get() * 2,
-            outstanding_work: 0,
-        }
+    pub fn world_def(&self) -> &WorldDef {
+        self.store.world_def()
     }
 
     pub fn time_on_start(&self) -> f32 {
         self.store.world_def().gametime
     }
 
-    pub fn get_top_z(&self, wbx: i16, wby: i16) -> i16 {
+    pub fn get_top_z(&self, wbx: WorldCoord, wby: WorldCoord) -> WorldCoord {
         self.buffer.get_top_z(wbx, wby)
     }
 
-    pub fn get_block(&mut self, wbx: i16, wby: i16, wbz: i16) -> Block {
+    pub fn get_block(&mut self, wbx: WorldCoord, wby: WorldCoord, wbz: WorldCoord) -> Block {
         self.buffer.get_block(wbx, wby, wbz)
     }
 
-    pub fn prepare_spawn_area(&mut self, col: ChunkColumnPos, chunk_range: i16) {
-        // Load stored columns or put the generator to work
+    /// Are there blocks next to or below a player at this position? Water supports players as
+    /// well. None if the blocks around the player are not loaded.
+    pub fn is_player_supported(&self, x: f32, y: f32, z: f32) -> Option<bool> {
+        let (wbx, wby, wbz) = (
+            x.floor() as WorldCoord,
+            y.floor() as WorldCoord,
+            z.floor() as WorldCoord,
+        );
+        for (dx, dy) in [(-1, -1), (-1, 1), (1, -1), (1, 1)].iter() {
+            let col = ChunkColumnPos::from_world_block_coords(wbx + dx, wby + dy);
+            match self.buffer.get_column_pos(&col) {
+                Some(column) if column.status() == ColumnStatus::Stored => {}
+                _ => return None,
+            }
+        }
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -PLAYER_SUPPORT_DEPTH..=0 {
+                    if self.buffer.get_block(wbx + dx, wby + dy, wbz + dz).kind() != AIR_BLOCK_KIND
+                    {
+                        return Some(true);
+                    }
+                }
+            }
+        }
+        Some(false)
+    }
+
+    pub fn prepare_spawn_area(&mut self, col: ChunkColumnPos, chunk_range: WorldCoord) {
+        // Put the generator to work, the changes of stored columns are applied when they are ready
         for cy in col.y - chunk_range..col.y + chunk_range {
             for cx in col.x - chunk_range..col.x + chunk_range {
                 let new_col = ChunkColumnPos::new(cx, cy);
-                if let Some(chunks) = self.store.load_column(col) {
-                    self.buffer
-                        .store_column(ChunkColumn::new(col, ColumnStatus::Stored, chunks));
-                } else {
-                    self.buffer.store_column(ChunkColumn::new(
-                        new_col,
-                        ColumnStatus::Requested,
-                        Vec::new(),
-                    ));
-                    self.outstanding_work += 1;
-                    self.generator.generate(new_col);
-                }
+                self.buffer.store_column(ChunkColumn::new(
+                    new_col,
+                    ColumnStatus::Requested,
+                    Vec::new(),
+                ));
+                self.outstanding_work += 1;
+                self.generator.generate(new_col);
             }
         }
         // And wait until the work is done
@@ -94,17 +133,29 @@ impl ServerWorldHandler {
         }
     }
 
-    /// Change a block in the world
-    pub fn set_block(&mut self, wbx: i16, wby: i16, wbz: i16, block: Block) {
+    /// Change a block in the world, the change is recorded in the block log
+    pub fn set_block(
+        &mut self,
+        wbx: WorldCoord,
+        wby: WorldCoord,
+        wbz: WorldCoord,
+        block: Block,
+        username: &str,
+    ) {
         let cp = ChunkPos::from_world_pos(Vec3::new(wbx as f32, wby as f32, wbz as f32));
-        if cp.z >= 0 && cp.z < WORLD_HEIGHT_CHUNKS as i16 {
+        if cp.z >= 0 && cp.z < self.buffer.height_chunks() as WorldCoord {
             let col = ChunkColumnPos::from_chunk_pos(cp);
+            let old_block = self.buffer.get_block(wbx, wby, wbz);
             if let Some(column) = self.buffer.get_mut_column(col.x, col.y) {
+                self.store.keep_baseline(col, &column.chunks);
+                if let Some(block_log) = &mut self.block_log {
+                    block_log.log(username, wbx, wby, wbz, old_block.kind(), block.kind());
+                }
                 let chunk = &mut column.chunks[cp.z as usize];
                 chunk.set_block(
-                    (wbx - cp.x * CHUNK_SIZE as i16) as usize,
-                    (wby - cp.y * CHUNK_SIZE as i16) as usize,
-                    (wbz - cp.z * CHUNK_SIZE as i16) as usize,
+                    (wbx - cp.x * CHUNK_SIZE as WorldCoord) as usize,
+                    (wby - cp.y * CHUNK_SIZE as WorldCoord) as usize,
+                    (wbz - cp.z * CHUNK_SIZE as WorldCoord) as usize,
                     block,
                 );
                 self.store.enqueue_chunk_save(&chunk);
@@ -112,20 +163,209 @@ impl ServerWorldHandler {
         }
     }
 
+    pub fn block_log_mut(&mut self) -> Option<&mut BlockLog> {
+        self.block_log.as_mut()
+    }
+
+    /// Undo logged changes, newest first. Blocks that were changed again since are left alone.
+    /// Returns the restored blocks.
+    pub fn rollback(
+        &mut self,
+        changes: &Vec<BlockChange>,
+        block_registry: &BlockRegistry,
+        username: &str,
+    ) -> Vec<(WorldCoord, WorldCoord, WorldCoord, Block)> {
+        let mut restored = Vec::new();
+        for change in changes.iter().rev() {
+            let col = ChunkColumnPos::from_world_block_coords(change.wbx, change.wby);
+            self.load_column_if_needed(col);
+            let current = self.buffer.get_block(change.wbx, change.wby, change.wbz);
+            if current.kind() == change.new_block {
+                // The log only keeps the block kind
+                let block = block_registry.set_block_flags(change.old_block);
+                self.set_block(change.wbx, change.wby, change.wbz, block, username);
+                restored.push((change.wbx, change.wby, change.wbz, block));
+            }
+        }
+        restored
+    }
+
+    /// Copy the blocks of a box between two corners, both inclusive. Fails if the box is too
+    /// large or part of it is still being generated.
+    pub fn copy_schematic(
+        &mut self,
+        from: (WorldCoord, WorldCoord, WorldCoord),
+        to: (WorldCoord, WorldCoord, WorldCoord),
+        block_registry: &BlockRegistry,
+    ) -> Result<Schematic, String> {
+        let min = (from.0.min(to.0), from.1.min(to.1), from.2.min(to.2));
+        let max = (from.0.max(to.0), from.1.max(to.1), from.2.max(to.2));
+        // The corners come from a command, the size is computed in i64 to not overflow
+        let axis_size = |min: WorldCoord, max: WorldCoord| (max as i64 - min as i64 + 1) as u64;
+        let sizes = [
+            axis_size(min.0, max.0),
+            axis_size(min.1, max.1),
+            axis_size(min.2, max.2),
+        ];
+        if sizes.iter().any(|size| *size > MAX_SCHEMATIC_SIZE as u64) {
+            return Err(format!(
+                "The area is larger than {} blocks along an axis",
+                MAX_SCHEMATIC_SIZE
+            ));
+        }
+        let size = (sizes[0] as usize, sizes[1] as usize, sizes[2] as usize);
+        if size.0 * size.1 * size.2 > MAX_SCHEMATIC_VOLUME {
+            return Err(format!(
+                "The area is larger than {} blocks",
+                MAX_SCHEMATIC_VOLUME
+            ));
+        }
+        let height_blocks = (self.buffer.height_chunks() * CHUNK_SIZE) as WorldCoord;
+        if min.2 < 0 || max.2 >= height_blocks {
+            return Err(format!(
+                "The area must be between heights 0 and {}",
+                height_blocks - 1
+            ));
+        }
+        self.prepare_columns(min, max)?;
+        let mut schematic = Schematic::new(size.0, size.1, size.2, "air");
+        for z in 0..size.2 {
+            for y in 0..size.1 {
+                for x in 0..size.0 {
+                    let block = self.buffer.get_block(
+                        min.0 + x as WorldCoord,
+                        min.1 + y as WorldCoord,
+                        min.2 + z as WorldCoord,
+                    );
+                    schematic.set(x, y, z, &block_registry.get(block).code);
+                }
+            }
+        }
+        Ok(schematic)
+    }
+
+    /// Paste a schematic with its lowest corner at a position, blocks above the world are left
+    /// out. Changes are logged as with `set_block` and each changed chunk is saved once. Returns
+    /// the number of changed blocks and the changed columns.
+    pub fn paste_schematic(
+        &mut self,
+        schematic: &Schematic,
+        at: (WorldCoord, WorldCoord, WorldCoord),
+        block_registry: &BlockRegistry,
+        username: &str,
+    ) -> Result<(usize, Vec<ChunkColumnPos>), String> {
+        // Blocks are looked up by code, as the registry may have changed since the copy
+        let mut palette = Vec::new();
+        for code in schematic.palette() {
+            if !block_registry.all_blocks().iter().any(|b| b.code == *code) {
+                return Err(format!("Unknown block {} in schematic", code));
+            }
+            palette.push((code.as_str(), block_registry.block_from_code(code)));
+        }
+        let max = (
+            at.0 + schematic.size_x as WorldCoord - 1,
+            at.1 + schematic.size_y as WorldCoord - 1,
+            at.2 + schematic.size_z as WorldCoord - 1,
+        );
+        self.prepare_columns(at, max)?;
+        let mut changed = 0;
+        let mut changed_columns = Vec::new();
+        let mut changed_chunks = HashSet::new();
+        for z in 0..schematic.size_z {
+            let wbz = at.2 + z as WorldCoord;
+            if wbz < 0 || wbz >= (self.buffer.height_chunks() * CHUNK_SIZE) as WorldCoord {
+                continue;
+            }
+            for y in 0..schematic.size_y {
+                for x in 0..schematic.size_x {
+                    let wbx = at.0 + x as WorldCoord;
+                    let wby = at.1 + y as WorldCoord;
+                    let code = schematic.get(x, y, z);
+                    let block = palette.iter().find(|(c, _)| *c == code).unwrap().1;
+                    let old_block = self.buffer.get_block(wbx, wby, wbz);
+                    if old_block.kind() == block.kind() {
+                        continue;
+                    }
+                    let cp = ChunkPos::from_world_block_coords(wbx, wby, wbz);
+                    let col = ChunkColumnPos::from_chunk_pos(cp);
+                    let column = self.buffer.get_mut_column(col.x, col.y).unwrap();
+                    if !changed_columns.contains(&col) {
+                        self.store.keep_baseline(col, &column.chunks);
+                        changed_columns.push(col);
+                    }
+                    if let Some(block_log) = &mut self.block_log {
+                        block_log.log(username, wbx, wby, wbz, old_block.kind(), block.kind());
+                    }
+                    column.chunks[cp.z as usize].set_block(
+                        (wbx - cp.x * CHUNK_SIZE as WorldCoord) as usize,
+                        (wby - cp.y * CHUNK_SIZE as WorldCoord) as usize,
+                        (wbz - cp.z * CHUNK_SIZE as WorldCoord) as usize,
+                        block,
+                    );
+                    changed_chunks.insert(cp);
+                    changed += 1;
+                }
+            }
+        }
+        for cp in changed_chunks {
+            if let Some(chunk) = self.buffer.get_chunk_pos(cp) {
+                self.store.enqueue_chunk_save(chunk);
+            }
+        }
+        Ok((changed, changed_columns))
+    }
+
+    /// Make sure the columns of a box are in the buffer, loading or generating them right away.
+    /// Fails if a column is still being generated in the background or waiting for it.
+    fn prepare_columns(
+        &mut self,
+        min: (WorldCoord, WorldCoord, WorldCoord),
+        max: (WorldCoord, WorldCoord, WorldCoord),
+    ) -> Result<(), String> {
+        let min_col = ChunkColumnPos::from_world_block_coords(min.0, min.1);
+        let max_col = ChunkColumnPos::from_world_block_coords(max.0, max.1);
+        for cx in min_col.x..=max_col.x {
+            for cy in min_col.y..=max_col.y {
+                let col = ChunkColumnPos::new(cx, cy);
+                if self.generate_requests.contains_key(&col) {
+                    return Err("Part of the area is still being generated, try again".to_string());
+                }
+                match self.buffer.get_column_pos(&col) {
+                    Some(column) if column.status() != ColumnStatus::Stored => {
+                        return Err(
+                            "Part of the area is still being generated, try again".to_string()
+                        );
+                    }
+                    Some(_) => {}
+                    None => {
+                        let chunks = self.store.generate_column(col);
+                        self.buffer.store_column(ChunkColumn::new(
+                            col,
+                            ColumnStatus::Stored,
+                            chunks,
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn load_column_if_needed(&mut self, col: ChunkColumnPos) {
+        if self.buffer.get_column_pos(&col).is_none() {
+            let chunks = self.store.generate_column(col);
+            self.buffer
+                .store_column(ChunkColumn::new(col, ColumnStatus::Stored, chunks));
+        }
+    }
+
+    /// The compressed blocks of a column in the buffer. Other columns, including stored ones,
+    /// are generated by the workers.
     pub fn try_clone_existing_column(&mut self, col: ChunkColumnPos) -> Option<Vec<Vec<u8>>> {
         if let Some(column) = self.buffer.get_column_pos(&col) {
             if column.status() == ColumnStatus::Stored {
                 return Some(compress_chunk(&column.chunks));
             }
-        } else {
-            if let Some(chunks) = self.store.load_column(col) {
-                let block_data = compress_chunk(&chunks);
-                // Store in buffer
-                self.buffer
-                    .store_column(ChunkColumn::new(col, ColumnStatus::Stored, chunks));
-                // Return compressed blocks
-                return Some(block_data);
-            }
         }
         None
     }
@@ -155,20 +395,20 @@ impl ServerWorldHandler {
         &mut self,
         return_data: bool,
     ) -> Option<(ChunkColumnPos, Vec<Vec<u8>>)> {
-        if let Some((col, chunks)) = self.generator.try_receive() {
+        if let Some((col, mut chunks)) = self.generator.try_receive() {
+            // Changed columns are stored as their changes to the generated terrain
+            self.store.restore_changes(col, &mut chunks);
             if let Some(column) = self.buffer.get_mut_column(col.x, col.y) {
                 self.outstanding_work -= 1;
                 // Store the new column in the in-memory buffer
                 column.set_status(ColumnStatus::Stored);
                 column.chunks = chunks;
-                // Save the column to disk
-                self.store.enqueue_column_save(col, &column.chunks);
                 if return_data {
                     // Return the column rle-encoded for distribution to clients
                     let mut block_data = Vec::new();
                     for chunk in &column.chunks {
                         let mut bytes = Vec::new();
-                        chunk.blocks.rle_encode_to(&mut bytes).unwrap();
+                        chunk.block_vec().rle_encode_to(&mut bytes).unwrap();
                         block_data.push(bytes);
                     }
                     Some((col, block_data))
@@ -208,11 +448,29 @@ impl ServerWorldHandler {
             }
         }
         self.store.save_world_if_needed(false, gametime);
+        if let Some(block_log) = &mut self.block_log {
+            block_log.flush();
+        }
+    }
+
+    /// Pause periodical autosaves and block log writes, e.g. while a backup is made
+    pub fn set_saving_paused(&mut self, paused: bool) {
+        self.store.set_saving_paused(paused);
+        if let Some(block_log) = &mut self.block_log {
+            block_log.set_saving_paused(paused);
+        }
+    }
+
+    pub fn world_folder(&self) -> Option<&Path> {
+        self.store.world_folder()
     }
 
     /// Save the world now, e.g. before shutdown
     pub fn save(&mut self, gametime: f32) {
         self.store.save_world_if_needed(true, gametime);
+        if let Some(block_log) = &mut self.block_log {
+            block_log.flush();
+        }
     }
 }
 
@@ -220,8 +478,120 @@ fn compress_chunk(chunks: &Vec<Chunk>) -> Vec<Vec<u8>> {
     let mut block_data = Vec::new();
     for chunk in chunks {
         let mut bytes = Vec::new();
-        chunk.blocks.rle_encode_to(&mut bytes).unwrap();
+        chunk.block_vec().rle_encode_to(&mut bytes).unwrap();
         block_data.push(bytes);
     }
     block_data
 }
+
+#[cfg(test)]
+mod server_world_handler_test {
+
+    use crate::server_world_handler::*;
+    use crate::storage::FileStorage;
+    use crate::world_store::test_world_def;
+    use common::block::AIR_BLOCK;
+    use common::chunk::ChunkColumnPos;
+    use common::world_definition::WORLD_DEF_FILE;
+    use common::world_type::GeneratorType;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Handler of a flat world in a new folder, so block changes are logged
+    fn file_world(name: &str, block_registry: &BlockRegistry) -> (PathBuf, ServerWorldHandler) {
+        let world_folder = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&world_folder);
+        fs::create_dir_all(&world_folder).unwrap();
+        test_world_def(GeneratorType::Flat, 4)
+            .save(&world_folder.join(WORLD_DEF_FILE))
+            .unwrap();
+        let storage = FileStorage::open(&world_folder).unwrap();
+        let world = ServerWorldHandler::new(Box::new(storage), block_registry).unwrap();
+        (world_folder, world)
+    }
+
+    #[test]
+    fn rollback_changes_of_player() {
+        let registry = BlockRegistry::default();
+        let (world_folder, mut world) = file_world("yab_rollback_test", &registry);
+        let stone = registry.block_from_code("stone");
+        let dirt = registry.block_from_code("dirt");
+        world.prepare_columns((0, 0, 60), (0, 0, 60)).unwrap();
+        assert_eq!(world.get_block(1, 1, 60), AIR_BLOCK);
+        world.set_block(1, 1, 60, stone, "griefer");
+        world.set_block(2, 1, 60, stone, "griefer");
+        world.set_block(2, 1, 60, dirt, "builder");
+
+        let changes = world.block_log_mut().unwrap().changes_by("griefer", 0);
+        assert_eq!(changes.len(), 2);
+        let restored = world.rollback(&changes, &registry, "rollback");
+        // The block changed again by another player is left alone
+        assert_eq!(restored, vec![(1, 1, 60, AIR_BLOCK)]);
+        assert_eq!(world.get_block(1, 1, 60), AIR_BLOCK);
+        assert_eq!(world.get_block(2, 1, 60), dirt);
+        // The rollback itself is logged too
+        let rolled_back = world.block_log_mut().unwrap().changes_by("rollback", 0);
+        assert_eq!(rolled_back.len(), 1);
+        fs::remove_dir_all(&world_folder).unwrap();
+    }
+
+    #[test]
+    fn copy_and_paste() {
+        let registry = BlockRegistry::default();
+        let (world_folder, mut world) = file_world("yab_paste_test", &registry);
+        // The surface of the flat world, across chunk borders
+        let copy = world
+            .copy_schematic((10, 0, 26), (19, 3, 34), &registry)
+            .unwrap();
+        assert_eq!((copy.size_x, copy.size_y, copy.size_z), (10, 4, 9));
+        let (changed, mut columns) = world
+            .paste_schematic(&copy, (28, 5, 40), &registry, "builder")
+            .unwrap();
+        columns.sort_by_key(|col| col.x);
+        assert_eq!(
+            columns,
+            vec![ChunkColumnPos::new(1, 0), ChunkColumnPos::new(2, 0)]
+        );
+        let check = |world: &mut ServerWorldHandler| {
+            for x in 0..10 {
+                for y in 0..4 {
+                    for z in 0..9 {
+                        let block = world.get_block(28 + x, 5 + y, 40 + z);
+                        assert_eq!(block.kind(), world.get_block(10 + x, y, 26 + z).kind());
+                        // Pasted blocks have their flags, like the blocks of the players
+                        assert_eq!(block, registry.set_block_flags(block.kind()));
+                    }
+                }
+            }
+        };
+        check(&mut world);
+        // Only the terrain up to z 32 replaces the air
+        let logged = world.block_log_mut().unwrap().changes_by("builder", 0);
+        assert_eq!(logged.len(), changed);
+        assert_eq!(changed, 10 * 4 * 7);
+
+        // The changed chunks are saved
+        world.save(0.0);
+        drop(world);
+        let storage = FileStorage::open(&world_folder).unwrap();
+        let mut world = ServerWorldHandler::new(Box::new(storage), &registry).unwrap();
+        world.prepare_columns((0, 0, 0), (47, 15, 0)).unwrap();
+        check(&mut world);
+
+        // Huge, overflowing or too voluminous areas are refused before anything is generated
+        let far = std::i32::MAX;
+        assert!(world
+            .copy_schematic((-far - 1, 0, 0), (far, 0, 0), &registry)
+            .is_err());
+        assert!(world
+            .copy_schematic((far, far, 0), (-far - 1, -far - 1, 10), &registry)
+            .is_err());
+        assert!(world
+            .copy_schematic((0, 0, 0), (127, 127, 127), &registry)
+            .is_err());
+        assert!(world
+            .copy_schematic((0, 0, 0), (127, 0, 0), &registry)
+            .is_ok());
+        fs::remove_dir_all(&world_folder).unwrap();
+    }
+}
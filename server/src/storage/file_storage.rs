@@ -0,0 +1,205 @@
+use super::WorldStorage;
+use crate::generator::generator_fingerprint;
+use crate::migration::migrate_world;
+use crate::region::*;
+use common::chunk::ChunkColumnPos;
+use common::world_definition::{WorldDef, WORLD_DEF_FILE};
+use log::*;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const MAX_REGION_CACHE_DURATION: Duration = Duration::from_secs(60 * 5);
+
+/// Stores a world in a folder, with the chunk columns in region files
+pub struct FileStorage {
+    world_folder: PathBuf,
+    region_cache: HashMap<String, Region>,
+}
+
+impl FileStorage {
+    /// Open the world in a folder, migrating it to the current format if needed. Worlds of
+    /// which the generated terrain has changed are refused.
+    pub fn open(world_folder: &Path) -> Result<FileStorage, String> {
+        let mut world_def = match WorldDef::load(&world_folder.join(WORLD_DEF_FILE)) {
+            Some(world_def) => world_def,
+            None => return Err(format!("no world definition found in {:?}", world_folder)),
+        };
+        check_generator(world_folder, &mut world_def)?;
+        // Worlds saved in an older format are converted first
+        migrate_world(world_folder, &mut world_def)?;
+        Ok(FileStorage {
+            world_folder: world_folder.to_path_buf(),
+            region_cache: HashMap::new(),
+        })
+    }
+
+    /// Get the region containing a column from the cache or from disk. A region that is not
+    /// a valid region file is set aside and replaced, other errors are returned.
+    fn get_region(&mut self, col: ChunkColumnPos, create: bool) -> io::Result<Option<&mut Region>> {
+        let filename = region_filename(col);
+        if !self.region_cache.contains_key(&filename) {
+            let path = self.world_folder.join(&filename);
+            let region = if path.exists() {
+                match Region::open(&path) {
+                    Ok(region) => region,
+                    Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                        // Columns in a damaged region are generated again from the seed
+                        error!("Region {} is damaged and cannot be loaded: {}", filename, e);
+                        set_aside_damaged_file(&path);
+                        if !create {
+                            return Ok(None);
+                        }
+                        Region::create(&path)?
+                    }
+                    Err(e) => return Err(e),
+                }
+            } else if create {
+                Region::create(&path)?
+            } else {
+                // Not available for loading, needs to be generated
+                return Ok(None);
+            };
+            self.region_cache.insert(filename.clone(), region);
+        }
+        let region = self.region_cache.get_mut(&filename).unwrap();
+        region.last_touched = Instant::now();
+        Ok(Some(region))
+    }
+}
+
+/// Check that the terrain is generated as it was when the world was saved, as changed columns
+/// are stored as diffs against it. Worlds without a fingerprint get the current one.
+fn check_generator(world_folder: &Path, world_def: &mut WorldDef) -> Result<(), String> {
+    let fingerprint = generator_fingerprint(&world_def.preset);
+    match world_def.generator {
+        Some(stored) if stored == fingerprint => Ok(()),
+        Some(stored) => Err(format!(
+            "the terrain generator has changed since the world was saved: generator version {} \
+             and models {:016x}, the world needs version {} and models {:016x}",
+            fingerprint.version, fingerprint.models_hash, stored.version, stored.models_hash
+        )),
+        None => {
+            world_def.generator = Some(fingerprint);
+            world_def
+                .save(&world_folder.join(WORLD_DEF_FILE))
+                .map_err(|e| format!("world definition cannot be saved: {}", e))
+        }
+    }
+}
+
+impl WorldStorage for FileStorage {
+    fn load_world_def(&mut self) -> Option<WorldDef> {
+        WorldDef::load(&self.world_folder.join(WORLD_DEF_FILE))
+    }
+
+    fn save_world_def(&mut self, world_def: &WorldDef) -> io::Result<()> {
+        world_def.save(&self.world_folder.join(WORLD_DEF_FILE))
+    }
+
+    fn load_column(&mut self, col: ChunkColumnPos) -> io::Result<Option<Vec<Vec<u8>>>> {
+        match self.get_region(col, false)? {
+            Some(region) => region.read_column(col),
+            None => Ok(None),
+        }
+    }
+
+    fn save_column(&mut self, col: ChunkColumnPos, column_bytes: &Vec<Vec<u8>>) -> io::Result<()> {
+        match self.get_region(col, true)? {
+            Some(region) => region.write_column(col, column_bytes),
+            None => Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("no region available for column {:?}", col),
+            )),
+        }
+    }
+
+    /// Sync the saved columns, reclaim the space of replaced records and close unused region files
+    fn flush(&mut self) {
+        for (filename, region) in self.region_cache.iter_mut() {
+            if let Err(e) = region.sync() {
+                error!("Error syncing region {}: {}", filename, e);
+            }
+            if region.needs_compaction() {
+                if let Err(e) = region.compact() {
+                    error!("Error compacting region {}: {}", filename, e);
+                }
+            }
+        }
+        let now = Instant::now();
+        self.region_cache.retain(|_, region| {
+            now.duration_since(region.last_touched) < MAX_REGION_CACHE_DURATION
+        });
+    }
+
+    fn folder(&self) -> Option<&Path> {
+        Some(&self.world_folder)
+    }
+}
+
+#[cfg(test)]
+mod file_storage_test {
+
+    use crate::storage::file_storage::*;
+    use crate::world_store::test_world_def;
+    use common::world_definition::GeneratorFingerprint;
+    use common::world_type::GeneratorType;
+    use std::fs;
+
+    #[test]
+    fn only_invalid_regions_are_set_aside() {
+        let world_folder = std::env::temp_dir().join("yab_file_storage_test");
+        let _ = fs::remove_dir_all(&world_folder);
+        fs::create_dir_all(&world_folder).unwrap();
+        test_world_def(GeneratorType::Flat, 4)
+            .save(&world_folder.join(WORLD_DEF_FILE))
+            .unwrap();
+        let mut storage = FileStorage::open(&world_folder).unwrap();
+
+        // A region that is not a region file is replaced
+        let col = ChunkColumnPos::new(0, 0);
+        let path = world_folder.join(region_filename(col));
+        fs::write(&path, b"not a region").unwrap();
+        assert_eq!(storage.load_column(col).unwrap(), None);
+        assert!(world_folder.join("r0_0.reg.damaged").exists());
+        storage.save_column(col, &vec![vec![1, 2]]).unwrap();
+        storage.flush();
+        let mut storage = FileStorage::open(&world_folder).unwrap();
+        assert_eq!(storage.load_column(col).unwrap(), Some(vec![vec![1, 2]]));
+
+        // A region that cannot be read for another reason is left alone
+        let other = ChunkColumnPos::new(32, 0);
+        let other_path = world_folder.join(region_filename(other));
+        fs::create_dir(&other_path).unwrap();
+        assert!(storage.load_column(other).is_err());
+        assert!(storage.save_column(other, &vec![vec![1]]).is_err());
+        assert!(other_path.is_dir());
+        fs::remove_dir_all(&world_folder).unwrap();
+    }
+
+    #[test]
+    fn changed_generator_is_refused() {
+        let world_folder = std::env::temp_dir().join("yab_file_storage_generator_test");
+        let _ = fs::remove_dir_all(&world_folder);
+        fs::create_dir_all(&world_folder).unwrap();
+        let def_path = world_folder.join(WORLD_DEF_FILE);
+        test_world_def(GeneratorType::Flat, 4)
+            .save(&def_path)
+            .unwrap();
+        // Worlds without a fingerprint get the current one
+        FileStorage::open(&world_folder).unwrap();
+        let mut world_def = WorldDef::load(&def_path).unwrap();
+        let fingerprint = world_def.generator.unwrap();
+        assert_eq!(fingerprint, generator_fingerprint(&world_def.preset));
+        FileStorage::open(&world_folder).unwrap();
+
+        world_def.generator = Some(GeneratorFingerprint {
+            version: fingerprint.version + 1,
+            ..fingerprint
+        });
+        world_def.save(&def_path).unwrap();
+        assert!(FileStorage::open(&world_folder).is_err());
+        fs::remove_dir_all(&world_folder).unwrap();
+    }
+}
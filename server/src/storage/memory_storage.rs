@@ -0,0 +1,40 @@
+use super::WorldStorage;
+use common::chunk::ChunkColumnPos;
+use common::world_definition::WorldDef;
+use std::collections::HashMap;
+use std::io;
+
+/// Keeps a world in memory only, e.g. for tests
+pub struct MemoryStorage {
+    world_def: WorldDef,
+    columns: HashMap<ChunkColumnPos, Vec<Vec<u8>>>,
+}
+
+impl MemoryStorage {
+    pub fn new(world_def: WorldDef) -> MemoryStorage {
+        MemoryStorage {
+            world_def,
+            columns: HashMap::new(),
+        }
+    }
+}
+
+impl WorldStorage for MemoryStorage {
+    fn load_world_def(&mut self) -> Option<WorldDef> {
+        Some(self.world_def.clone())
+    }
+
+    fn save_world_def(&mut self, world_def: &WorldDef) -> io::Result<()> {
+        self.world_def = world_def.clone();
+        Ok(())
+    }
+
+    fn load_column(&mut self, col: ChunkColumnPos) -> io::Result<Option<Vec<Vec<u8>>>> {
+        Ok(self.columns.get(&col).cloned())
+    }
+
+    fn save_column(&mut self, col: ChunkColumnPos, column_bytes: &Vec<Vec<u8>>) -> io::Result<()> {
+        self.columns.insert(col, column_bytes.clone());
+        Ok(())
+    }
+}
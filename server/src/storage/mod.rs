@@ -0,0 +1,32 @@
+mod file_storage;
+mod memory_storage;
+
+pub use file_storage::FileStorage;
+pub use memory_storage::MemoryStorage;
+
+use common::chunk::ChunkColumnPos;
+use common::world_definition::WorldDef;
+use std::io;
+use std::path::Path;
+
+/// Backend that stores the chunk columns and the definition of a single world.
+/// Columns are stored as the run-length encoded bytes of their chunks, bottom chunk first.
+pub trait WorldStorage: Send {
+    fn load_world_def(&mut self) -> Option<WorldDef>;
+
+    fn save_world_def(&mut self, world_def: &WorldDef) -> io::Result<()>;
+
+    /// Load the chunks of a column, if the column is stored
+    fn load_column(&mut self, col: ChunkColumnPos) -> io::Result<Option<Vec<Vec<u8>>>>;
+
+    fn save_column(&mut self, col: ChunkColumnPos, column_bytes: &Vec<Vec<u8>>) -> io::Result<()>;
+
+    /// Called after each batch of saved columns, e.g. to release resources
+    fn flush(&mut self) {}
+
+    /// Folder for the other files of the world, such as players and the block log.
+    /// Storage that does not live on the filesystem has no folder.
+    fn folder(&self) -> Option<&Path> {
+        None
+    }
+}
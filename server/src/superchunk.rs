@@ -1,14 +1,20 @@
 use common::chunk::*;
 use common::comms::read_from::ReadFrom;
 use common::comms::write_to::WriteTo;
+use common::world_pos::WorldCoord;
 use snap::read::FrameDecoder;
 use snap::write::FrameEncoder;
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fs::{File, OpenOptions};
-use std::io::{BufReader, BufWriter, Read, Write};
+use std::io::{self, BufReader, BufWriter, Read, Write};
 use std::path::Path;
 use std::time::Instant;
 
+/// Old worlds all had the same height
+const SUPERCHUNK_HEIGHT_CHUNKS: usize = 64;
+
+/// Superchunks are the storage format of old worlds, they hold columns with 16-bit coordinates
 pub struct SuperChunk {
     pub last_touched: Instant,
     pub chunk_data: HashMap<ChunkColumnPos, Vec<Vec<u8>>>,
@@ -38,9 +44,9 @@ impl SuperChunk {
             .unwrap();
         // Write chunk data
         for (cp, column_bytes) in &self.chunk_data {
-            cp.x.write_to(&mut encoder).unwrap();
-            cp.y.write_to(&mut encoder).unwrap();
-            assert!(column_bytes.len() == WORLD_HEIGHT_CHUNKS);
+            i16::try_from(cp.x).unwrap().write_to(&mut encoder).unwrap();
+            i16::try_from(cp.y).unwrap().write_to(&mut encoder).unwrap();
+            assert!(column_bytes.len() == SUPERCHUNK_HEIGHT_CHUNKS);
             for bytes in column_bytes {
                 assert!(bytes.len() < std::u16::MAX as usize);
                 (bytes.len() as u16).write_to(&mut encoder).unwrap();
@@ -50,25 +56,37 @@ impl SuperChunk {
         encoder.flush().unwrap();
     }
 
-    /// Load a superchunk
-    pub fn load(sc_path: &Path) -> SuperChunk {
+    /// Load a superchunk, failing if it is damaged
+    pub fn load(sc_path: &Path) -> io::Result<SuperChunk> {
         let mut sc = SuperChunk::new();
-        let file = File::open(sc_path).unwrap();
+        let file = File::open(sc_path)?;
         let reader = BufReader::new(file);
+        // The frame decoder checks the checksum of every frame
         let mut decoder = FrameDecoder::new(reader);
-        let chunk_count = u16::read_from(&mut decoder).unwrap();
+        let chunk_count = read_u16(&mut decoder)?;
         for _ in 0..chunk_count {
-            let x = i16::read_from(&mut decoder).unwrap();
-            let y = i16::read_from(&mut decoder).unwrap();
+            let x = read_i16(&mut decoder)?;
+            let y = read_i16(&mut decoder)?;
             let mut column_bytes = Vec::new();
-            for _ in 0..WORLD_HEIGHT_CHUNKS {
-                let length = u16::read_from(&mut decoder).unwrap() as usize;
+            for _ in 0..SUPERCHUNK_HEIGHT_CHUNKS {
+                let length = read_u16(&mut decoder)? as usize;
                 let mut bytes = vec![0; length];
-                decoder.read_exact(&mut bytes).unwrap();
+                decoder.read_exact(&mut bytes)?;
                 column_bytes.push(bytes);
             }
-            sc.chunk_data.insert(ChunkColumnPos { x, y }, column_bytes);
+            sc.chunk_data.insert(
+                ChunkColumnPos::new(x as WorldCoord, y as WorldCoord),
+                column_bytes,
+            );
         }
-        sc
+        Ok(sc)
     }
 }
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    u16::read_from(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+fn read_i16<R: Read>(reader: &mut R) -> io::Result<i16> {
+    i16::read_from(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
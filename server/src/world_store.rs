@@ -1,214 +1,611 @@
-use crate::superchunk::SuperChunk;
+use crate::generator::ColumnGenerator;
+use crate::storage::WorldStorage;
+use common::block::{Block, BlockRegistry};
 use common::chrono::Utc;
 use common::chunk::*;
 use common::comms::*;
 use common::world_definition::*;
-use common::world_type::GeneratorType;
 use log::*;
+use std::collections::{HashMap, HashSet};
 use std::io::Cursor;
+use std::path::Path;
 use std::time::{Duration, Instant};
-use std::{collections::HashMap, path::PathBuf};
 
-const MAX_SC_CACHE_DURATION: Duration = Duration::from_secs(60 * 5);
 const MIN_SAVE_INTERVAL: Duration = Duration::from_millis(5000);
+// Marks a block in a chunk diff that is equal to the generated block
+const UNCHANGED_BLOCK: Block = std::u32::MAX;
 
+/// Keeps track of changed chunk columns and periodically writes them to a storage backend.
+/// Columns are stored as a diff against the terrain generated from the seed, so columns
+/// that were never changed are not stored at all.
 pub struct WorldStore {
     last_save: Instant,
-    sc_cache: HashMap<String, SuperChunk>,
-    save_queue: HashMap<ChunkColumnPos, Vec<Vec<u8>>>,
-    world_folder: PathBuf,
+    storage: Box<dyn WorldStorage>,
+    generator: ColumnGenerator,
+    // Changed chunks by column and chunk z
+    save_queue: HashMap<ChunkColumnPos, HashMap<usize, Chunk>>,
+    // Generated terrain of changed columns, so saving them does not generate them again.
+    // It is dropped once a column was saved and did not change until the next save.
+    baselines: HashMap<ChunkColumnPos, Vec<Chunk>>,
+    // Changed columns whose generated terrain was dropped, they are generated again to save them
+    dropped_baselines: HashSet<ChunkColumnPos>,
     world_def: WorldDef,
+    saving_paused: bool,
 }
 
 impl WorldStore {
-    pub fn new(seed: u32, description: &str, world_type: GeneratorType) -> Self {
-        let world_list = WorldsStore::new();
-        let world_folder = world_list.get_world_path(seed);
-        WorldStore {
+    /// Use a storage backend, fails if it holds no world definition
+    pub fn new(mut storage: Box<dyn WorldStorage>, block_registry: &BlockRegistry) -> Option<Self> {
+        let world_def = storage.load_world_def()?;
+        Some(WorldStore {
             last_save: Instant::now(),
-            sc_cache: HashMap::new(),
+            storage,
+            generator: ColumnGenerator::for_world(&world_def, block_registry),
             save_queue: HashMap::new(),
-            world_folder: world_folder.into(),
-            world_def: world_list.create_new_world(seed, description, world_type),
-        }
-    }
-
-    pub fn load(seed: u32) -> Option<Self> {
-        let world_list = WorldsStore::new();
-        let world_folder = world_list.get_world_path(seed);
-        if let Some(world_def) = world_list.try_load_world(seed) {
-            Some(WorldStore {
-                last_save: Instant::now(),
-                sc_cache: HashMap::new(),
-                save_queue: HashMap::new(),
-                world_folder: world_folder.into(),
-                world_def,
-            })
-        } else {
-            None
-        }
+            baselines: HashMap::new(),
+            dropped_baselines: HashSet::new(),
+            world_def,
+            saving_paused: false,
+        })
     }
 
     pub fn world_def(&self) -> &WorldDef {
         &self.world_def
     }
 
-    /// Enqueue an updated chunk for saving when save_world_if_needed is called
-    /// This assumes the chunk is already in the superchunk cache
+    /// Folder of the world, if it is stored on the filesystem
+    pub fn world_folder(&self) -> Option<&Path> {
+        self.storage.folder()
+    }
+
+    /// Keep changes in the save queue instead of writing them, e.g. while a backup is made
+    pub fn set_saving_paused(&mut self, paused: bool) {
+        self.saving_paused = paused;
+    }
+
+    /// Enqueue a changed chunk for saving when save_world_if_needed is called
     pub fn enqueue_chunk_save(&mut self, chunk: &Chunk) {
         let col = ChunkColumnPos::from_chunk_pos(chunk.pos);
-        // Use run-length encoding to save memory and disk space
-        let mut bytes = Vec::new();
-        chunk.blocks.rle_encode_to(&mut bytes).unwrap();
-        if let Some(column_bytes) = self.save_queue.get_mut(&col) {
-            // Column is already in save queue, so update it there
-            column_bytes[chunk.pos.z as usize] = bytes;
-        } else if let Some(sc) = self.sc_cache.get_mut(&col_to_sc_filename(col)) {
-            // Get the column from the superchunk cache and enqueue it for update
-            let column_bytes = sc.chunk_data.get_mut(&col).unwrap();
-            column_bytes[chunk.pos.z as usize] = bytes;
-            self.save_queue.insert(col, column_bytes.clone());
-        }
-    }
-
-    /// Enqueue a generated chunk column for saving when save_world_if_needed is called
-    pub fn enqueue_column_save(&mut self, col: ChunkColumnPos, column: &Vec<Chunk>) {
-        assert!(column.len() == WORLD_HEIGHT_CHUNKS);
-        let mut column_bytes = Vec::new();
-        for chunk in column {
-            let mut bytes = Vec::new();
-            // Use run-length encoding to save memory and disk space
-            chunk.blocks.rle_encode_to(&mut bytes).unwrap();
-            column_bytes.push(bytes);
-        }
-        self.save_queue.insert(col, column_bytes);
-    }
-
-    /// Load a column of chunks from disk (if available)
+        self.save_queue
+            .entry(col)
+            .or_insert_with(HashMap::new)
+            .insert(chunk.pos.z as usize, chunk.clone());
+    }
+
+    /// Load a column of chunks that was changed, by generating it again and applying the
+    /// stored changes. Returns None for columns that were never changed.
     pub fn load_column(&mut self, col: ChunkColumnPos) -> Option<Vec<Chunk>> {
-        let sc_filename = col_to_sc_filename(col);
-
-        // Is the superchunk in the cache?
-        let in_cache = self.sc_cache.contains_key(&sc_filename);
-        if !in_cache {
-            // Not in the cache, is it on the disk?
-            let sc_path = self.world_folder.join(format!("{}.chk", sc_filename));
-            if sc_path.exists() {
-                let sc = SuperChunk::load(&sc_path);
-                self.sc_cache.insert(sc_filename.clone(), sc);
-            } else {
-                // Not available for loading, needs to be generated
-                return None;
-            }
+        let column_bytes = self.read_column_bytes(col);
+        if column_bytes.is_none() && !self.save_queue.contains_key(&col) {
+            return None;
+        }
+        let mut chunks = self
+            .generator
+            .generate_column(self.world_def.world_type, col);
+        self.apply_changes(col, column_bytes, &mut chunks);
+        Some(chunks)
+    }
+
+    /// Generate a column right away and apply its changes, keeping the generated terrain of
+    /// changed columns for saving them
+    pub fn generate_column(&mut self, col: ChunkColumnPos) -> Vec<Chunk> {
+        let mut chunks = self
+            .generator
+            .generate_column(self.world_def.world_type, col);
+        self.restore_changes(col, &mut chunks);
+        chunks
+    }
+
+    /// Apply the stored and queued changes to a column generated elsewhere, e.g. by the
+    /// generator workers. The generated terrain of changed columns is kept for saving them.
+    pub fn restore_changes(&mut self, col: ChunkColumnPos, chunks: &mut Vec<Chunk>) {
+        let column_bytes = self.read_column_bytes(col);
+        if column_bytes.is_none() && !self.save_queue.contains_key(&col) {
+            return;
+        }
+        self.baselines.entry(col).or_insert_with(|| chunks.clone());
+        self.apply_changes(col, column_bytes, chunks);
+    }
+
+    /// Keep the generated terrain of a column that is about to be changed, for saving it. The
+    /// column must not have been changed before, or have been passed to restore_changes.
+    /// Columns whose generated terrain was dropped after saving are generated again instead.
+    pub fn keep_baseline(&mut self, col: ChunkColumnPos, chunks: &Vec<Chunk>) {
+        if !self.baselines.contains_key(&col) && !self.dropped_baselines.contains(&col) {
+            self.baselines.insert(col, chunks.clone());
         }
+    }
 
-        // The superchunk is in the cache now. See if it contains the requested chunk.
-        let sc = self.sc_cache.get_mut(&sc_filename).unwrap();
-        sc.last_touched = Instant::now();
-        if sc.chunk_data.contains_key(&col) {
-            // Decode the chunk column and return it
-            let mut chunks = Vec::new();
-            let mut z = 0;
-            for chunk_data in sc.chunk_data.get(&col).unwrap() {
-                let blocks = Vec::rle_decode_from(&mut Cursor::new(chunk_data)).unwrap();
-                let chunk = Chunk {
-                    pos: ChunkPos {
-                        x: col.x,
-                        y: col.y,
-                        z: z,
-                    },
-                    blocks,
-                };
-                chunks.push(chunk);
-                z = z + 1;
+    fn apply_changes(
+        &mut self,
+        col: ChunkColumnPos,
+        column_bytes: Option<Vec<Vec<u8>>>,
+        chunks: &mut Vec<Chunk>,
+    ) {
+        if let Some(column_bytes) = column_bytes {
+            for (chunk, diff_bytes) in chunks.iter_mut().zip(column_bytes.iter()) {
+                if let Err(e) = apply_diff(chunk, diff_bytes) {
+                    error!(
+                        "Chunk {:?} is damaged and cannot be loaded: {}",
+                        chunk.pos, e
+                    );
+                }
             }
-            return Some(chunks);
         }
+        // Chunks waiting to be saved are more recent than the stored ones
+        if let Some(queued) = self.save_queue.get(&col) {
+            for (z, chunk) in queued {
+                chunks[*z] = chunk.clone();
+            }
+        }
+    }
 
-        // Not in the superchunk, needs to be generated
-        None
+    /// Load a column of chunks, or generate it if it was never changed
+    pub fn load_or_generate_column(&mut self, col: ChunkColumnPos) -> Vec<Chunk> {
+        match self.load_column(col) {
+            Some(chunks) => chunks,
+            None => self
+                .generator
+                .generate_column(self.world_def.world_type, col),
+        }
     }
 
     /// Save enqueued chunks if needed (i.e. when there are too many or a time period has passed)
     pub fn save_world_if_needed(&mut self, force: bool, game_time: f32) {
         // Is it time to save chunks?
-        if !force && Instant::now().duration_since(self.last_save) < MIN_SAVE_INTERVAL {
+        if !force
+            && (self.saving_paused
+                || Instant::now().duration_since(self.last_save) < MIN_SAVE_INTERVAL)
+        {
             return;
         }
 
         self.world_def.gametime = game_time;
         self.world_def.timestamp = Utc::now();
-        self.world_def.save(&self.world_folder.join(WORLD_DEF_FILE));
-
-        // Group chunks by superchunk
-        let mut c_per_sc: HashMap<String, Vec<ChunkColumnPos>> = HashMap::new();
-        for col in self.save_queue.keys() {
-            c_per_sc
-                .entry(col_to_sc_filename(*col))
-                .or_insert(Vec::new())
-                .push(*col);
-        }
-
-        // Now get each superchunk in turn and save the chunk data
-        for (sc_filename, cp_list) in c_per_sc {
-            let sc_path = self.world_folder.join(format!("{}.chk", sc_filename));
-            let new_sc_path = self.world_folder.join(format!("{}.new", sc_filename));
-            if self.sc_cache.contains_key(&sc_filename) {
-                // Get from superchunk cache
-                let mut sc = self.sc_cache.get_mut(&sc_filename).unwrap();
-                sc.last_touched = Instant::now();
-                // Update chunk data
-                for cp in cp_list {
-                    if let Some(bytes) = self.save_queue.get(&cp) {
-                        sc.chunk_data.insert(cp, bytes.clone());
+        if let Err(e) = self.storage.save_world_def(&self.world_def) {
+            error!("Error saving world definition: {}", e);
+        }
+
+        // Only the changed columns are written
+        let save_queue: Vec<(ChunkColumnPos, HashMap<usize, Chunk>)> =
+            self.save_queue.drain().collect();
+        let saved_columns: HashSet<ChunkColumnPos> =
+            save_queue.iter().map(|(col, _)| *col).collect();
+        for (col, queued) in save_queue {
+            // Keep the stored diffs of the chunks that did not change since
+            let mut column_bytes = self
+                .read_column_bytes(col)
+                .unwrap_or_else(|| vec![unchanged_diff(); self.world_def.height_chunks]);
+            column_bytes.resize(self.world_def.height_chunks, unchanged_diff());
+            let generated;
+            let baseline = match self.baselines.get(&col) {
+                Some(baseline) => baseline,
+                None => {
+                    generated = self
+                        .generator
+                        .generate_column(self.world_def.world_type, col);
+                    &generated
+                }
+            };
+            for (z, chunk) in &queued {
+                column_bytes[*z] = encode_diff(&baseline[*z], chunk);
+            }
+            if let Err(e) = self.storage.save_column(col, &column_bytes) {
+                // The changes are kept, so they are saved with the next save
+                error!("Error saving column {:?}: {}", col, e);
+                self.save_queue.insert(col, queued);
+            }
+        }
+        // Columns that were saved before and did not change since no longer need their
+        // generated terrain in memory
+        let dropped_baselines = &mut self.dropped_baselines;
+        self.baselines.retain(|col, _| {
+            let keep = saved_columns.contains(col);
+            if !keep {
+                dropped_baselines.insert(*col);
+            }
+            keep
+        });
+        self.storage.flush();
+        self.last_save = Instant::now();
+    }
+
+    /// Read the chunk diffs of a stored column
+    fn read_column_bytes(&mut self, col: ChunkColumnPos) -> Option<Vec<Vec<u8>>> {
+        match self.storage.load_column(col) {
+            Ok(column_bytes) => column_bytes,
+            Err(e) => {
+                // The column is generated again from the seed
+                error!("Column {:?} is damaged and cannot be loaded: {}", col, e);
+                None
+            }
+        }
+    }
+}
+
+/// Encode the blocks of a chunk that differ from the generated chunk, with run-length
+/// encoding. A chunk diff without any unchanged blocks is simply the encoded chunk.
+fn encode_diff(generated: &Chunk, chunk: &Chunk) -> Vec<u8> {
+    let generated_blocks = generated.block_vec();
+    let diff: Vec<Block> = if chunk.is_solid() && generated.is_solid() {
+        if generated_blocks == chunk.block_vec() {
+            vec![UNCHANGED_BLOCK]
+        } else {
+            chunk.block_vec()
+        }
+    } else {
+        let mut diff = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE);
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let block = chunk.get_block(x, y, z);
+                    if block == generated.get_block(x, y, z) {
+                        diff.push(UNCHANGED_BLOCK);
+                    } else {
+                        diff.push(block);
                     }
                 }
-                sc.save(&new_sc_path);
-            } else {
-                let mut sc = if sc_path.exists() {
-                    // Load existing superchunk
-                    SuperChunk::load(&sc_path)
-                } else {
-                    // Create new superchunk
-                    SuperChunk::new()
-                };
-                sc.last_touched = Instant::now();
-                // Update chunk data
-                for cp in cp_list {
-                    if let Some(bytes) = self.save_queue.get(&cp) {
-                        sc.chunk_data.insert(cp, bytes.clone());
+            }
+        }
+        diff
+    };
+    let mut bytes = Vec::new();
+    diff.rle_encode_to(&mut bytes).unwrap();
+    bytes
+}
+
+/// Apply an encoded chunk diff to a generated chunk
+pub(crate) fn apply_diff(chunk: &mut Chunk, diff_bytes: &Vec<u8>) -> Result<(), CommsError> {
+    let diff: Vec<Block> = Vec::rle_decode_from(&mut Cursor::new(diff_bytes))?;
+    if diff.len() == 1 {
+        if diff[0] != UNCHANGED_BLOCK {
+            *chunk = Chunk::new_solid(chunk.pos, diff[0]);
+        }
+        return Ok(());
+    }
+    if diff.len() != CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE {
+        return Err(CommsError::ProtocolError);
+    }
+    let mut index = 0;
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                if diff[index] != UNCHANGED_BLOCK {
+                    chunk.set_block(x, y, z, diff[index]);
+                }
+                index += 1;
+            }
+        }
+    }
+    chunk.optimize();
+    Ok(())
+}
+
+fn unchanged_diff() -> Vec<u8> {
+    let mut bytes = Vec::new();
+    vec![UNCHANGED_BLOCK].rle_encode_to(&mut bytes).unwrap();
+    bytes
+}
+
+/// World definition for tests, with caves, ores and structures turned off
+#[cfg(test)]
+pub(crate) fn test_world_def(
+    world_type: common::world_type::GeneratorType,
+    height_chunks: usize,
+) -> WorldDef {
+    WorldDef {
+        id: "test".to_string(),
+        seed: 1,
+        world_type,
+        description: "test".to_string(),
+        version: "0.1.0".to_string(),
+        format_version: WORLD_FORMAT_VERSION,
+        height_chunks,
+        caves: None,
+        ores: Vec::new(),
+        structures: false,
+        preset: common::world_preset::WorldPreset::default(),
+        generator: None,
+        timestamp: Utc::now(),
+        gametime: 0.0,
+    }
+}
+
+#[cfg(test)]
+mod world_store_test {
+
+    use crate::generator::ColumnGenerator;
+    use crate::storage::{MemoryStorage, WorldStorage};
+    use crate::world_store::{test_world_def, WorldStore};
+    use common::block::{BlockRegistry, AIR_BLOCK, BEDROCK_BLOCK};
+    use common::chunk::{
+        Chunk, ChunkColumnPos, ChunkPos, CHUNK_SIZE, DEFAULT_WORLD_HEIGHT_CHUNKS,
+        MIN_WORLD_HEIGHT_CHUNKS,
+    };
+    use common::comms::RleEncode;
+    use common::world_definition::{CaveSettings, WorldDef};
+    use common::world_pos::WorldCoord;
+    use common::world_type::GeneratorType;
+    use std::io;
+
+    fn world_def(world_type: GeneratorType) -> WorldDef {
+        WorldDef {
+            caves: Some(CaveSettings::default()),
+            ..test_world_def(world_type, DEFAULT_WORLD_HEIGHT_CHUNKS)
+        }
+    }
+
+    fn memory_store(world_type: GeneratorType) -> WorldStore {
+        let storage = MemoryStorage::new(world_def(world_type));
+        WorldStore::new(Box::new(storage), &BlockRegistry::default()).unwrap()
+    }
+
+    fn generated_column(world_type: GeneratorType, col: ChunkColumnPos) -> Vec<Chunk> {
+        let mut generator =
+            ColumnGenerator::for_world(&world_def(world_type), &BlockRegistry::default());
+        generator.generate_column(world_type, col)
+    }
+
+    /// Storage that shares its columns with the test, so stored bytes can be inspected
+    struct SharedStorage {
+        inner: std::sync::Arc<std::sync::Mutex<MemoryStorage>>,
+    }
+
+    impl WorldStorage for SharedStorage {
+        fn load_world_def(&mut self) -> Option<WorldDef> {
+            self.inner.lock().unwrap().load_world_def()
+        }
+
+        fn save_world_def(&mut self, world_def: &WorldDef) -> io::Result<()> {
+            self.inner.lock().unwrap().save_world_def(world_def)
+        }
+
+        fn load_column(&mut self, col: ChunkColumnPos) -> io::Result<Option<Vec<Vec<u8>>>> {
+            self.inner.lock().unwrap().load_column(col)
+        }
+
+        fn save_column(
+            &mut self,
+            col: ChunkColumnPos,
+            column_bytes: &Vec<Vec<u8>>,
+        ) -> io::Result<()> {
+            self.inner.lock().unwrap().save_column(col, column_bytes)
+        }
+    }
+
+    #[test]
+    fn untouched_columns_are_not_stored() {
+        let mut store = memory_store(GeneratorType::Flat);
+        let col = ChunkColumnPos::new(2, -3);
+        assert!(store.load_column(col).is_none());
+        store.save_world_if_needed(true, 0.5);
+        assert!(store.load_column(col).is_none());
+        assert_eq!(store.world_def().gametime, 0.5);
+    }
+
+    #[test]
+    fn changed_column_round_trip() {
+        let world_type = GeneratorType::Default;
+        let shared = std::sync::Arc::new(std::sync::Mutex::new(MemoryStorage::new(world_def(
+            world_type,
+        ))));
+        let storage = SharedStorage {
+            inner: shared.clone(),
+        };
+        let mut store = WorldStore::new(Box::new(storage), &BlockRegistry::default()).unwrap();
+        let col = ChunkColumnPos::new(-1, 4);
+        let mut column = generated_column(world_type, col);
+
+        // Change a few blocks, replace a whole chunk and make another one solid
+        column[2].set_block(1, 2, 3, BEDROCK_BLOCK);
+        column[2].set_block(15, 15, 15, AIR_BLOCK);
+        column[5] = Chunk::new_normal(column[5].pos, BEDROCK_BLOCK);
+        column[5].set_block(0, 0, 0, AIR_BLOCK);
+        column[DEFAULT_WORLD_HEIGHT_CHUNKS - 1] =
+            Chunk::new_solid(column[DEFAULT_WORLD_HEIGHT_CHUNKS - 1].pos, BEDROCK_BLOCK);
+        for z in [2, 5, DEFAULT_WORLD_HEIGHT_CHUNKS - 1].iter() {
+            store.enqueue_chunk_save(&column[*z]);
+        }
+        // Enqueued chunks can be loaded before they are saved
+        assert!(store.load_column(col).unwrap() == column);
+        store.save_world_if_needed(true, 0.0);
+        assert!(store.load_column(col).unwrap() == column);
+
+        // Only the changes are stored
+        let column_bytes = shared.lock().unwrap().load_column(col).unwrap().unwrap();
+        assert_eq!(column_bytes.len(), DEFAULT_WORLD_HEIGHT_CHUNKS);
+        assert!(column_bytes[2].len() < 32);
+        assert!(column_bytes[10].len() < 16);
+
+        // Changing another chunk keeps the earlier changes
+        column[7].set_block(8, 8, 8, BEDROCK_BLOCK);
+        store.enqueue_chunk_save(&column[7]);
+        store.save_world_if_needed(true, 0.0);
+        let mut fresh_store = WorldStore::new(
+            Box::new(SharedStorage { inner: shared }),
+            &BlockRegistry::default(),
+        )
+        .unwrap();
+        let loaded = fresh_store.load_column(col).unwrap();
+        for (loaded_chunk, chunk) in loaded.iter().zip(column.iter()) {
+            for x in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for z in 0..CHUNK_SIZE {
+                        assert_eq!(loaded_chunk.get_block(x, y, z), chunk.get_block(x, y, z));
                     }
                 }
-                sc.save(&new_sc_path);
-                // Store superchunk in cache
-                self.sc_cache.insert(sc_filename, sc);
             }
-            // Rename if succesful
-            if let Err(e) = std::fs::rename(&new_sc_path, &sc_path) {
-                error!(
-                    "Error renaming superchunk from {} to {}: {}",
-                    &sc_path.to_string_lossy(),
-                    &new_sc_path.to_string_lossy(),
-                    e
-                );
+        }
+    }
+
+    #[test]
+    fn kept_terrain_is_used_for_saving() {
+        let world_type = GeneratorType::Flat;
+        let shared = std::sync::Arc::new(std::sync::Mutex::new(MemoryStorage::new(world_def(
+            world_type,
+        ))));
+        let mut store = WorldStore::new(
+            Box::new(SharedStorage {
+                inner: shared.clone(),
+            }),
+            &BlockRegistry::default(),
+        )
+        .unwrap();
+        let col = ChunkColumnPos::new(1, 1);
+        // Terrain handed to the store, e.g. by the generator workers, is not generated again
+        let terrain: Vec<Chunk> = (0..DEFAULT_WORLD_HEIGHT_CHUNKS)
+            .map(|z| Chunk::new_solid(ChunkPos::new(col.x, col.y, z as WorldCoord), BEDROCK_BLOCK))
+            .collect();
+        store.keep_baseline(col, &terrain);
+        let mut chunk = Chunk::new_normal(terrain[1].pos, BEDROCK_BLOCK);
+        chunk.set_block(0, 0, 0, AIR_BLOCK);
+        store.enqueue_chunk_save(&chunk);
+        store.save_world_if_needed(true, 0.0);
+        let column_bytes = shared.lock().unwrap().load_column(col).unwrap().unwrap();
+        assert!(column_bytes[1].len() < 32);
+
+        // The changes are applied to the terrain of the workers
+        let mut fresh_store = WorldStore::new(
+            Box::new(SharedStorage { inner: shared }),
+            &BlockRegistry::default(),
+        )
+        .unwrap();
+        let mut column = terrain.clone();
+        fresh_store.restore_changes(col, &mut column);
+        assert_eq!(column[1].get_block(0, 0, 0), AIR_BLOCK);
+        assert_eq!(column[1].get_block(1, 0, 0), BEDROCK_BLOCK);
+        assert!(column[2] == terrain[2]);
+        let mut untouched = terrain.clone();
+        fresh_store.restore_changes(ChunkColumnPos::new(5, 5), &mut untouched);
+        assert!(untouched == terrain);
+
+        // The terrain is dropped after a save in which the column did not change, later
+        // changes are saved against the terrain generated from the seed
+        assert!(store.baselines.contains_key(&col));
+        store.save_world_if_needed(true, 0.0);
+        assert!(store.baselines.is_empty());
+        store.keep_baseline(col, &terrain);
+        assert!(store.baselines.is_empty());
+        chunk.set_block(1, 0, 0, AIR_BLOCK);
+        store.enqueue_chunk_save(&chunk);
+        store.save_world_if_needed(true, 0.0);
+        let mut column = generated_column(world_type, col);
+        fresh_store.restore_changes(col, &mut column);
+        assert!(column[1] == chunk);
+    }
+
+    /// Storage that fails to save columns while asked to
+    struct FailingStorage {
+        inner: SharedStorage,
+        failing: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl WorldStorage for FailingStorage {
+        fn load_world_def(&mut self) -> Option<WorldDef> {
+            self.inner.load_world_def()
+        }
+
+        fn save_world_def(&mut self, world_def: &WorldDef) -> io::Result<()> {
+            self.inner.save_world_def(world_def)
+        }
+
+        fn load_column(&mut self, col: ChunkColumnPos) -> io::Result<Option<Vec<Vec<u8>>>> {
+            self.inner.load_column(col)
+        }
+
+        fn save_column(
+            &mut self,
+            col: ChunkColumnPos,
+            column_bytes: &Vec<Vec<u8>>,
+        ) -> io::Result<()> {
+            if self.failing.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(io::Error::new(io::ErrorKind::Other, "disk full"));
             }
+            self.inner.save_column(col, column_bytes)
         }
+    }
 
-        self.save_queue.clear();
-        self.clean_cache();
-        self.last_save = Instant::now();
+    #[test]
+    fn failed_save_is_retried() {
+        let world_type = GeneratorType::Flat;
+        let shared = std::sync::Arc::new(std::sync::Mutex::new(MemoryStorage::new(world_def(
+            world_type,
+        ))));
+        let failing = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let mut store = WorldStore::new(
+            Box::new(FailingStorage {
+                inner: SharedStorage {
+                    inner: shared.clone(),
+                },
+                failing: failing.clone(),
+            }),
+            &BlockRegistry::default(),
+        )
+        .unwrap();
+        let col = ChunkColumnPos::new(-2, 3);
+        let mut column = generated_column(world_type, col);
+        store.keep_baseline(col, &column);
+        column[0].set_block(3, 3, 3, AIR_BLOCK);
+        store.enqueue_chunk_save(&column[0]);
+
+        // The change stays queued and its terrain is kept until it is saved
+        store.save_world_if_needed(true, 0.0);
+        assert!(shared.lock().unwrap().load_column(col).unwrap().is_none());
+        assert!(store.save_queue.contains_key(&col));
+        store.save_world_if_needed(true, 0.0);
+        assert!(store.baselines.contains_key(&col));
+        failing.store(false, std::sync::atomic::Ordering::SeqCst);
+        store.save_world_if_needed(true, 0.0);
+        assert!(store.save_queue.is_empty());
+        let loaded = store.load_column(col).unwrap();
+        assert_eq!(loaded[0].get_block(3, 3, 3), AIR_BLOCK);
     }
 
-    /// Remove unused superchunks from memory
-    fn clean_cache(&mut self) {
-        let now = Instant::now();
-        self.sc_cache
-            .retain(|_, sc| now.duration_since(sc.last_touched) < MAX_SC_CACHE_DURATION);
+    #[test]
+    fn load_full_column() {
+        // Columns saved before diffs were introduced hold all blocks of each chunk
+        let world_type = GeneratorType::Flat;
+        let mut storage = MemoryStorage::new(world_def(world_type));
+        let col = ChunkColumnPos::new(0, 0);
+        let mut column: Vec<Chunk> = (0..DEFAULT_WORLD_HEIGHT_CHUNKS)
+            .map(|z| Chunk::new_solid(ChunkPos::new(col.x, col.y, z as WorldCoord), AIR_BLOCK))
+            .collect();
+        column[3] = Chunk::new_normal(ChunkPos::new(0, 0, 3), BEDROCK_BLOCK);
+        let column_bytes: Vec<Vec<u8>> = column
+            .iter()
+            .map(|chunk| {
+                let mut bytes = Vec::new();
+                chunk.block_vec().rle_encode_to(&mut bytes).unwrap();
+                bytes
+            })
+            .collect();
+        storage.save_column(col, &column_bytes).unwrap();
+
+        let mut store = WorldStore::new(Box::new(storage), &BlockRegistry::default()).unwrap();
+        assert!(store.load_column(col).unwrap() == column);
     }
-}
 
-fn col_to_sc_filename(col: ChunkColumnPos) -> String {
-    let sc_x = col.x >> 5;
-    let sc_y = col.y >> 5;
-    format!("s{}_{}", sc_x, sc_y)
+    #[test]
+    fn low_world_round_trip() {
+        // Hills with trees and other objects are cut off at the top of a low world
+        let mut def = world_def(GeneratorType::Default);
+        def.height_chunks = MIN_WORLD_HEIGHT_CHUNKS;
+        let mut store =
+            WorldStore::new(Box::new(MemoryStorage::new(def)), &BlockRegistry::default()).unwrap();
+        let col = ChunkColumnPos::new(3, 3);
+        let mut top = Chunk::new_solid(
+            ChunkPos::new(col.x, col.y, MIN_WORLD_HEIGHT_CHUNKS as WorldCoord - 1),
+            AIR_BLOCK,
+        );
+        top.set_block(0, 0, CHUNK_SIZE - 1, BEDROCK_BLOCK);
+        store.enqueue_chunk_save(&top);
+        store.save_world_if_needed(true, 0.0);
+
+        let column = store.load_column(col).unwrap();
+        assert_eq!(column.len(), MIN_WORLD_HEIGHT_CHUNKS);
+        assert_eq!(
+            column[MIN_WORLD_HEIGHT_CHUNKS - 1].get_block(0, 0, CHUNK_SIZE - 1),
+            BEDROCK_BLOCK
+        );
+    }
 }
@@ -1,9 +1,18 @@
 // #![windows_subsystem = "windows"]
 
 use client::{block_preview_generator::generate_block_previews, StartMode, YabClient};
-use common::{comms::DEFAULT_TCP_PORT, world_type::GeneratorType};
+use common::{
+    block::BlockRegistry,
+    chunk::DEFAULT_WORLD_HEIGHT_CHUNKS,
+    comms::DEFAULT_TCP_PORT,
+    world_definition::{seed_world_id, WorldsStore},
+    world_preset::{
+        find_preset, load_or_create_presets, WorldPreset, DEFAULT_PRESET_NAME, PRESETS_FOLDER,
+    },
+    world_type::GeneratorType,
+};
 use flexi_logger;
-use gamework::video::generate_texture_atlas;
+use gamework::{video::generate_texture_atlas, Assets};
 use log::*;
 use num_cpus;
 use rand::Rng;
@@ -15,6 +24,12 @@ enum RunMode {
     Server,
     TexturePack,
     BlockPreviews,
+    ListBackups,
+    RestoreBackup(String),
+    ExportWorld(String),
+    ImportWorld(String),
+    Inspect,
+    Map(String),
 }
 
 fn main() {
@@ -33,7 +48,14 @@ fn main() {
     let mut run_mode = RunMode::Client;
     let mut client_start_mode = StartMode::Normal;
     let mut world_type = GeneratorType::Default;
+    let mut height_chunks = DEFAULT_WORLD_HEIGHT_CHUNKS;
+    let mut preset_name = None;
     let mut seed = rng.gen::<u32>();
+    let mut world_id = None;
+    let mut json = false;
+    let mut map_area = None;
+    let mut map_scale = 1;
+    let mut map_shading = false;
     for arg in args {
         let mut split_arg = arg.split("=");
         let arg_key = if let Some(arg_key) = split_arg.next() {
@@ -50,6 +72,13 @@ fn main() {
                     panic!("seed argument needs a numerical value");
                 }
             }
+            "world" => {
+                if let Some(arg_value) = arg_value_opt {
+                    world_id = Some(arg_value.to_string());
+                } else {
+                    panic!("world argument needs the ID of a world");
+                }
+            }
             "type" => {
                 if let Some(world_type_str) = arg_value_opt {
                     world_type = match world_type_str {
@@ -57,12 +86,27 @@ fn main() {
                         "water" => GeneratorType::Water,
                         "alien" => GeneratorType::Alien,
                         "default" => GeneratorType::Default,
+                        "biomes" => GeneratorType::Biomes,
                         _ => {
-                            panic!("type argument needs a value: flat, water or default")
+                            panic!("type argument needs a value: flat, water, alien, default or biomes")
                         }
                     }
                 } else {
-                    panic!("type argument needs a value: flat, water or default");
+                    panic!("type argument needs a value: flat, water, alien, default or biomes");
+                }
+            }
+            "height" => {
+                if let Some(arg_value) = arg_value_opt {
+                    height_chunks = arg_value.parse::<usize>().unwrap();
+                } else {
+                    panic!("height argument needs the world height in chunks");
+                }
+            }
+            "preset" => {
+                if let Some(arg_value) = arg_value_opt {
+                    preset_name = Some(arg_value.to_string());
+                } else {
+                    panic!("preset argument needs the name of a preset");
                 }
             }
             "server" => run_mode = RunMode::Server,
@@ -70,13 +114,63 @@ fn main() {
             "continue" => client_start_mode = StartMode::Continue,
             "pack" => run_mode = RunMode::TexturePack,
             "block_previews" => run_mode = RunMode::BlockPreviews,
+            "backups" => run_mode = RunMode::ListBackups,
+            "inspect" => run_mode = RunMode::Inspect,
+            "json" => json = true,
+            "map" => {
+                if let Some(png_path) = arg_value_opt {
+                    run_mode = RunMode::Map(png_path.to_string());
+                } else {
+                    panic!("map argument needs the path of the image to create");
+                }
+            }
+            "area" => {
+                if let Some(area) = arg_value_opt {
+                    map_area = Some(area.to_string());
+                } else {
+                    panic!("area argument needs the corners of the map: min_x,min_y,max_x,max_y");
+                }
+            }
+            "scale" => {
+                if let Some(arg_value) = arg_value_opt {
+                    map_scale = arg_value.parse::<i32>().unwrap().max(1);
+                } else {
+                    panic!("scale argument needs the number of blocks per pixel");
+                }
+            }
+            "shade" => map_shading = true,
+            "export" => {
+                if let Some(archive_path) = arg_value_opt {
+                    run_mode = RunMode::ExportWorld(archive_path.to_string());
+                } else {
+                    panic!("export argument needs the path of the archive to create");
+                }
+            }
+            "import" => {
+                if let Some(archive_path) = arg_value_opt {
+                    run_mode = RunMode::ImportWorld(archive_path.to_string());
+                } else {
+                    panic!("import argument needs the path of an archive");
+                }
+            }
+            "restore" => {
+                if let Some(backup_name) = arg_value_opt {
+                    run_mode = RunMode::RestoreBackup(backup_name.to_string());
+                } else {
+                    panic!("restore argument needs the name of a backup");
+                }
+            }
             _ => {}
         }
     }
 
+    // Without a world ID the world is named after the seed, like worlds from before world IDs
+    let world_id = world_id.unwrap_or_else(|| seed_world_id(seed));
+
     match run_mode {
         RunMode::Client => {
-            let mut client = YabClient::new(client_start_mode, world_type);
+            let preset = select_preset(preset_name);
+            let mut client = YabClient::new(client_start_mode, world_type, height_chunks, preset);
             if let Err(e) = client.run() {
                 error!("{}", common::error::failure_to_string(e));
             }
@@ -85,7 +179,18 @@ fn main() {
             // In case that no remote connection is desired use 127.0.0.1 instead of 0.0.0.0
             let server_address = format!("0.0.0.0:{}", DEFAULT_TCP_PORT);
             let mut server = YabServer::new(&server_address);
-            server.run(true, seed, "Command-line server".to_string(), world_type);
+            let preset = select_preset(preset_name);
+            if let Err(e) = server.run(
+                true,
+                world_id,
+                seed,
+                "Command-line server".to_string(),
+                world_type,
+                height_chunks,
+                preset,
+            ) {
+                error!("Server cannot be started: {}", e);
+            }
         }
         RunMode::TexturePack => {
             info!("Packing texture atlas");
@@ -103,6 +208,92 @@ fn main() {
                 Path::new("assets/block_previews").to_path_buf(),
             );
         }
+        RunMode::ListBackups => {
+            let world_folder = WorldsStore::new().get_world_path(&world_id);
+            for backup_name in server::backup::list_backups(&world_folder) {
+                info!("Backup {}", backup_name);
+            }
+        }
+        RunMode::RestoreBackup(backup_name) => {
+            let world_folder = WorldsStore::new().get_world_path(&world_id);
+            if let Err(e) = server::backup::restore_backup(&world_folder, &backup_name) {
+                error!("Cannot restore backup {}: {}", backup_name, e);
+            }
+        }
+        RunMode::ExportWorld(archive_path) => {
+            let world_folder = WorldsStore::new().get_world_path(&world_id);
+            let registry = BlockRegistry::load_or_create(Path::new("assets/blocks.json")).unwrap();
+            if let Err(e) =
+                server::archive::export_world(&world_folder, &registry, Path::new(&archive_path))
+            {
+                error!("Cannot export world {}: {}", world_id, e);
+            }
+        }
+        RunMode::ImportWorld(archive_path) => {
+            let registry = BlockRegistry::load_or_create(Path::new("assets/blocks.json")).unwrap();
+            match server::archive::import_world(Path::new(&archive_path), &registry) {
+                Ok(manifest) => info!(
+                    "Imported world {} ({}) with seed {}",
+                    manifest.world.id, manifest.world.description, manifest.world.seed
+                ),
+                Err(e) => error!("Cannot import world from {}: {}", archive_path, e),
+            }
+        }
+        RunMode::Inspect => {
+            let world_folder = WorldsStore::new().get_world_path(&world_id);
+            let registry = BlockRegistry::load_or_create(Path::new("assets/blocks.json")).unwrap();
+            match server::inspect::inspect_world(&world_folder, &registry) {
+                Ok(report) => {
+                    if json {
+                        println!("{}", report.to_json());
+                    } else {
+                        print!("{}", report);
+                    }
+                }
+                Err(e) => error!("Cannot inspect world {}: {}", world_id, e),
+            }
+        }
+        RunMode::Map(png_path) => {
+            let world_folder = WorldsStore::new().get_world_path(&world_id);
+            let registry = BlockRegistry::load_or_create(Path::new("assets/blocks.json")).unwrap();
+            let area = map_area.map(|area| {
+                server::map::MapArea::parse(&area, map_scale).unwrap_or_else(|| {
+                    panic!(
+                        "area argument needs the corners of the map: min_x,min_y,max_x,max_y, \
+                        the map can have at most {} pixels",
+                        server::map::MAX_MAP_PIXELS
+                    )
+                })
+            });
+            match server::map::export_map(
+                &world_folder,
+                &registry,
+                Path::new("assets/block_textures"),
+                area,
+                map_scale,
+                map_shading,
+                Path::new(&png_path),
+            ) {
+                Ok(area) => info!(
+                    "Saved {}x{} map to {}",
+                    area.width(),
+                    area.height(),
+                    png_path
+                ),
+                Err(e) => error!("Cannot export map of world {}: {}", world_id, e),
+            }
+        }
     }
     info!("Exiting main");
 }
+
+/// The preset with the given name from the assets, or the default preset
+fn select_preset(name: Option<String>) -> WorldPreset {
+    let folder = Assets::default().path(PRESETS_FOLDER);
+    let presets = load_or_create_presets(&folder);
+    match name {
+        Some(name) => find_preset(&presets, &name)
+            .unwrap_or_else(|| panic!("preset {} is not in {:?}", name, folder)),
+        None => find_preset(&presets, DEFAULT_PRESET_NAME).unwrap_or_default(),
+    }
+}